@@ -0,0 +1,233 @@
+//! Timed benchmarks for transfer submission, compilation and deployment against a local Ganache.
+//!
+//! Run with `cargo bench --bench throughput`. Each group spawns its own instamine chain and drops
+//! it before the next group starts. Ops/sec and p50/p95 latencies are printed and written as JSON
+//! to `target/bench/throughput.json`, or to `$BENCH_OUTPUT` when it's set.
+
+use ethers::prelude::*;
+use ethers::utils::{parse_ether, Ganache, GanacheInstance};
+use ethers_solc::{Project, ProjectPathsConfig};
+use eyre::Result;
+use futures::future::join_all;
+use rust_eth_yt::chain_context::ChainContext;
+use rust_eth_yt::deploy::{deploy_contract, DeployOptions};
+use rust_eth_yt::project::{compile, contract_parts};
+use rust_eth_yt::traffic::percentile_ms;
+use rust_eth_yt::transfer::{send_signed_transfer, TransferOptions};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const MNEMONIC: &str = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+const CONTRACTS: &str = "examples/";
+const TRANSFERS: usize = 100;
+const COMPILES: usize = 5;
+const DEPLOYMENTS: usize = 20;
+
+/// One benchmarked operation: how many ran, how long they took overall and per operation.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Measurement {
+    group: &'static str,
+    name: &'static str,
+    ops: usize,
+    elapsed_ms: u64,
+    ops_per_sec: f64,
+    p50_ms: u64,
+    p95_ms: u64,
+}
+
+impl Measurement {
+    fn new(
+        group: &'static str,
+        name: &'static str,
+        mut latencies: Vec<Duration>,
+        elapsed: Duration,
+    ) -> Self {
+        latencies.sort();
+        let measurement = Self {
+            group,
+            name,
+            ops: latencies.len(),
+            elapsed_ms: elapsed.as_millis() as u64,
+            ops_per_sec: latencies.len() as f64 / elapsed.as_secs_f64().max(f64::EPSILON),
+            p50_ms: percentile_ms(&latencies, 50),
+            p95_ms: percentile_ms(&latencies, 95),
+        };
+        println!(
+            "{group}/{name}: {} ops in {} ms, {:.1} ops/s, p50 {} ms, p95 {} ms",
+            measurement.ops,
+            measurement.elapsed_ms,
+            measurement.ops_per_sec,
+            measurement.p50_ms,
+            measurement.p95_ms
+        );
+        measurement
+    }
+}
+
+/// Spawns the instamine chain a group runs against; it is torn down when dropped.
+fn spawn_chain() -> Result<(GanacheInstance, Provider<Http>)> {
+    let ganache = Ganache::new()
+        .mnemonic(MNEMONIC)
+        .args(["--miner.instamine", "eager"])
+        .spawn();
+    let provider = Provider::try_from(ganache.endpoint())?.interval(Duration::from_millis(10));
+    Ok((ganache, provider))
+}
+
+/// Sends the same number of transfers one after the other, then all at once through a nonce manager.
+async fn transfers() -> Result<Vec<Measurement>> {
+    let (ganache, provider) = spawn_chain()?;
+    let chain_id = provider.get_chainid().await?.as_u64();
+    let value = parse_ether("0.001")?;
+    let recipients: Vec<Address> = (0..TRANSFERS).map(|_| Address::random()).collect();
+    let options = TransferOptions {
+        check_funds: false, // Measure the submission, not the balance lookup
+        ..Default::default()
+    };
+
+    // Sequential: each transfer waits for the previous receipt
+    let wallet: LocalWallet = ganache.keys()[0].clone().into();
+    let ctx = ChainContext::new(provider.clone());
+    let mut latencies = Vec::with_capacity(TRANSFERS);
+    let started = Instant::now();
+    for to in &recipients {
+        let sent = Instant::now();
+        send_signed_transfer(&ctx, wallet.clone(), *to, value, &options).await?;
+        latencies.push(sent.elapsed());
+    }
+    let sequential = Measurement::new("transfers", "sequential", latencies, started.elapsed());
+
+    // Concurrent: nonces are handed out locally so every transfer is in flight together
+    let wallet: LocalWallet = ganache.keys()[1].clone().into();
+    let signer = SignerMiddleware::new(provider.clone(), wallet.with_chain_id(chain_id));
+    let from = signer.address();
+    let client = Arc::new(NonceManagerMiddleware::new(signer, from));
+    let started = Instant::now();
+    let sends = recipients.iter().map(|to| {
+        let client = client.clone();
+        let tx = TransactionRequest::new().to(*to).value(value).from(from);
+        async move {
+            let sent = Instant::now();
+            client
+                .send_transaction(tx, None)
+                .await?
+                .await?
+                .ok_or_else(|| eyre::eyre!("transfer to {to:?} was dropped"))?;
+            Ok::<_, eyre::Report>(sent.elapsed())
+        }
+    });
+    let latencies = join_all(sends)
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>>>()?;
+    let concurrent = Measurement::new("transfers", "concurrent", latencies, started.elapsed());
+
+    drop(ganache);
+    Ok(vec![sequential, concurrent])
+}
+
+/// Compiles the example contracts with a warm cache, and from scratch without one.
+fn compilation() -> Result<Vec<Measurement>> {
+    let scratch = std::env::temp_dir().join(format!("rust-eth-yt-bench-{}", std::process::id()));
+    let cached = cached_project(Path::new(CONTRACTS), &scratch)?;
+    cached.compile()?; // Warm the cache and artifacts the timed runs reuse
+    let uncached = Project::builder()
+        .paths(paths(Path::new(CONTRACTS))?)
+        .set_auto_detect(true)
+        .ephemeral()
+        .no_artifacts()
+        .build()?;
+
+    let mut measurements = Vec::new();
+    for (name, project) in [("cached", &cached), ("uncached", &uncached)] {
+        let mut latencies = Vec::with_capacity(COMPILES);
+        let started = Instant::now();
+        for _ in 0..COMPILES {
+            let run = Instant::now();
+            let output = project.compile()?;
+            eyre::ensure!(
+                !output.has_compiler_errors(),
+                "compilation failed: {output}"
+            );
+            latencies.push(run.elapsed());
+        }
+        measurements.push(Measurement::new(
+            "compile",
+            name,
+            latencies,
+            started.elapsed(),
+        ));
+    }
+
+    std::fs::remove_dir_all(&scratch)?;
+    Ok(measurements)
+}
+
+fn paths(root: &Path) -> Result<ProjectPathsConfig> {
+    Ok(ProjectPathsConfig::builder()
+        .root(root)
+        .sources(root)
+        .build()?)
+}
+
+/// Same layout as [`rust_eth_yt::project::build_project`], with the cache and artifacts kept in `scratch`.
+fn cached_project(root: &Path, scratch: &Path) -> Result<Project> {
+    let mut paths = paths(root)?;
+    paths.artifacts = scratch.join("out");
+    paths.cache = scratch.join("solidity-files-cache.json");
+    Ok(Project::builder()
+        .paths(paths)
+        .set_auto_detect(true)
+        .set_cached(true)
+        .build()?)
+}
+
+/// Deploys `Migrations` repeatedly, from sending the transaction to having its receipt and code.
+async fn deployments() -> Result<Measurement> {
+    let (ganache, provider) = spawn_chain()?;
+    let project = compile(CONTRACTS).await?.output;
+    let (abi, bytecode) = contract_parts(&project, CONTRACTS, "Migrations")?;
+    let wallet: LocalWallet = ganache.keys()[0].clone().into();
+    let ctx = ChainContext::new(provider);
+
+    let mut latencies = Vec::with_capacity(DEPLOYMENTS);
+    let started = Instant::now();
+    for _ in 0..DEPLOYMENTS {
+        let sent = Instant::now();
+        deploy_contract(
+            &ctx,
+            wallet.clone(),
+            "Migrations",
+            abi.clone(),
+            bytecode.clone(),
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        latencies.push(sent.elapsed());
+    }
+    let measurement = Measurement::new("deploy", "migrations", latencies, started.elapsed());
+
+    drop(ganache);
+    Ok(measurement)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let mut results = transfers().await?;
+    results.extend(compilation()?);
+    results.push(deployments().await?);
+
+    let path = std::env::var_os("BENCH_OUTPUT")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("target/bench/throughput.json"));
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, serde_json::to_string_pretty(&results)?)?;
+    println!("wrote {}", path.display());
+    Ok(())
+}
@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.12;
+
+/// @title Create2Deployer
+/// @notice Deploys init code with CREATE2, so the new contract's address only depends on this
+///         deployer's address, the salt and the init code.
+/// @dev The deployed contract sees this deployer as `msg.sender` in its constructor.
+contract Create2Deployer {
+    event Deployed(address indexed addr, bytes32 indexed salt);
+
+    /// @notice Deploys `initCode` at the address derived from `salt`, forwarding any value sent.
+    function deploy(bytes32 salt, bytes memory initCode) external payable returns (address addr) {
+        assembly {
+            addr := create2(callvalue(), add(initCode, 32), mload(initCode), salt)
+        }
+        require(addr != address(0), "Create2Deployer: deployment failed");
+        emit Deployed(addr, salt);
+    }
+}
@@ -0,0 +1,92 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.12;
+
+/// @title ERC1967Proxy
+/// @notice Upgradeable proxy keeping its implementation and admin in the ERC-1967 slots.
+/// @dev Unlike a transparent proxy, the admin can also call through to the implementation;
+///      only `upgradeTo` and `admin` are handled by the proxy itself, so an implementation
+///      must not define functions with those selectors.
+contract ERC1967Proxy {
+    /// @dev bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)
+    bytes32 internal constant IMPLEMENTATION_SLOT =
+        0x360894a13ba1a3210667c828492db98dca3e2076cc3735a920a3ca505d382bbc;
+    /// @dev bytes32(uint256(keccak256("eip1967.proxy.admin")) - 1)
+    bytes32 internal constant ADMIN_SLOT =
+        0xb53127684a568b3173ae13b9f8a6016e243e63b6e8ee1178d6a717850b5d6103;
+
+    event Upgraded(address indexed implementation);
+    event AdminChanged(address previousAdmin, address newAdmin);
+
+    /// @param implementation Initial implementation.
+    /// @param data Initializer calldata run against the implementation in the proxy's context, if not empty.
+    constructor(address implementation, bytes memory data) payable {
+        _setImplementation(implementation);
+        _setAdmin(msg.sender);
+        if (data.length > 0) {
+            (bool success, bytes memory returnData) = implementation.delegatecall(data);
+            if (!success) {
+                assembly {
+                    revert(add(returnData, 32), mload(returnData))
+                }
+            }
+        }
+    }
+
+    /// @notice Current admin, the only account allowed to upgrade.
+    function admin() external view returns (address account) {
+        bytes32 slot = ADMIN_SLOT;
+        assembly {
+            account := sload(slot)
+        }
+    }
+
+    /// @notice Points the proxy at `implementation`.
+    function upgradeTo(address implementation) external {
+        require(msg.sender == this.admin(), "ERC1967Proxy: caller is not the admin");
+        _setImplementation(implementation);
+    }
+
+    fallback() external payable {
+        _delegate();
+    }
+
+    receive() external payable {
+        _delegate();
+    }
+
+    function _delegate() internal {
+        bytes32 slot = IMPLEMENTATION_SLOT;
+        assembly {
+            let implementation := sload(slot)
+            calldatacopy(0, 0, calldatasize())
+            let result := delegatecall(gas(), implementation, 0, calldatasize(), 0, 0)
+            returndatacopy(0, 0, returndatasize())
+            switch result
+            case 0 {
+                revert(0, returndatasize())
+            }
+            default {
+                return(0, returndatasize())
+            }
+        }
+    }
+
+    function _setImplementation(address implementation) private {
+        require(implementation.code.length > 0, "ERC1967Proxy: implementation is not a contract");
+        bytes32 slot = IMPLEMENTATION_SLOT;
+        assembly {
+            sstore(slot, implementation)
+        }
+        emit Upgraded(implementation);
+    }
+
+    function _setAdmin(address account) private {
+        bytes32 slot = ADMIN_SLOT;
+        address previous;
+        assembly {
+            previous := sload(slot)
+            sstore(slot, account)
+        }
+        emit AdminChanged(previous, account);
+    }
+}
@@ -0,0 +1,161 @@
+//! Differences between two versions of a contract's ABI.
+
+use ethers::abi::{Abi, Function, Param, StateMutability};
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+
+/// Functions added, removed or changed between two ABIs, each list sorted by name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AbiDiff {
+    /// Signatures of the functions only the new ABI has.
+    pub added: Vec<String>,
+    /// Signatures of the functions only the old ABI has.
+    pub removed: Vec<String>,
+    /// Functions both ABIs have under different signatures.
+    pub changed: Vec<ChangedFunction>,
+}
+
+/// A function name whose signatures differ between the two ABIs.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ChangedFunction {
+    pub name: String,
+    /// Signatures in the old ABI (several for an overloaded function).
+    pub old: Vec<String>,
+    pub new: Vec<String>,
+}
+
+impl AbiDiff {
+    /// Whether the two ABIs declare the same functions.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compares the functions of `old` and `new` by name. A name present in both is changed when
+/// its parameter types, return types or mutability differ, overloads included.
+pub fn diff_abi(old: &Abi, new: &Abi) -> AbiDiff {
+    let (old, new) = (signatures(old), signatures(new));
+    let mut diff = AbiDiff::default();
+    for (name, old_signatures) in &old {
+        match new.get(name) {
+            None => diff.removed.extend(old_signatures.iter().cloned()),
+            Some(new_signatures) if new_signatures != old_signatures => {
+                diff.changed.push(ChangedFunction {
+                    name: name.clone(),
+                    old: old_signatures.clone(),
+                    new: new_signatures.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+    for (name, new_signatures) in &new {
+        if !old.contains_key(name) {
+            diff.added.extend(new_signatures.iter().cloned());
+        }
+    }
+    diff
+}
+
+/// Loads an ABI from a JSON file holding either the ABI array or an artifact with an `abi` field
+/// (Hardhat, Foundry or [`export_artifacts`](crate::artifacts::export_artifacts) output).
+pub fn load_abi(path: impl AsRef<Path>) -> Result<Abi> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum AbiFile {
+        Abi(Abi),
+        Artifact { abi: Abi },
+    }
+
+    let path = path.as_ref();
+    let json = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    let file: AbiFile = serde_json::from_str(&json)
+        .wrap_err_with(|| format!("{} holds no JSON ABI", path.display()))?;
+    Ok(match file {
+        AbiFile::Abi(abi) | AbiFile::Artifact { abi } => abi,
+    })
+}
+
+// Sorted signatures of every function, keyed by name
+fn signatures(abi: &Abi) -> BTreeMap<String, Vec<String>> {
+    abi.functions
+        .iter()
+        .map(|(name, overloads)| {
+            let mut signatures: Vec<String> = overloads.iter().map(signature).collect();
+            signatures.sort();
+            (name.clone(), signatures)
+        })
+        .collect()
+}
+
+// `name(types) [mutability] returns (types)`, the way Solidity interfaces read
+fn signature(function: &Function) -> String {
+    let types = |params: &[Param]| {
+        params
+            .iter()
+            .map(|param| param.kind.to_string())
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    let mut signature = format!("{}({})", function.name, types(&function.inputs));
+    match function.state_mutability {
+        StateMutability::NonPayable => {}
+        mutability => signature.push_str(&format!(" {mutability:?}").to_lowercase()),
+    }
+    if !function.outputs.is_empty() {
+        signature.push_str(&format!(" returns ({})", types(&function.outputs)));
+    }
+    signature
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::abi::parse_abi;
+
+    #[test]
+    fn test_added_removed_and_changed_functions() -> Result<()> {
+        let old = parse_abi(&[
+            "function balanceOf(address owner) view returns (uint256)",
+            "function transfer(address to, uint256 value) returns (bool)",
+            "function pause()",
+            "function mint(uint256 amount)",
+        ])?;
+        let new = parse_abi(&[
+            "function balanceOf(address owner) view returns (uint256)",
+            "function transfer(address to, uint256 value) returns (bool)",
+            "function burn(uint256 amount)",
+            "function mint(address to, uint256 amount)",
+        ])?;
+
+        let diff = diff_abi(&old, &new);
+        assert_eq!(diff.added, ["burn(uint256)"]);
+        assert_eq!(diff.removed, ["pause()"]);
+        assert_eq!(
+            diff.changed,
+            [ChangedFunction {
+                name: "mint".to_string(),
+                old: vec!["mint(uint256)".to_string()],
+                new: vec!["mint(address,uint256)".to_string()],
+            }]
+        );
+
+        assert!(diff_abi(&new, &new).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_mutability_and_outputs_are_part_of_the_signature() -> Result<()> {
+        let old = parse_abi(&["function total() view returns (uint256)"])?;
+        let new = parse_abi(&["function total() returns (uint128)"])?;
+
+        let diff = diff_abi(&old, &new);
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].old, ["total() view returns (uint256)"]);
+        assert_eq!(diff.changed[0].new, ["total() returns (uint128)"]);
+        Ok(())
+    }
+}
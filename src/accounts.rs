@@ -0,0 +1,154 @@
+//! Overview of the Ganache accounts: address, balance and nonce of each key.
+
+use crate::amount::format_eth_decimal;
+use ethers::{
+    core::k256::SecretKey,
+    prelude::{Address, LocalWallet, Middleware, Signer, U256},
+    utils::to_checksum,
+};
+use eyre::{eyre, Result};
+use futures::future::try_join_all;
+use serde::Serialize;
+
+/// One account of [`account_infos`].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountInfo {
+    /// Position of the key, as used by `--account-index`.
+    pub index: usize,
+    pub address: Address,
+    /// Balance in Wei.
+    pub balance: U256,
+    /// Transactions sent so far.
+    pub nonce: U256,
+}
+
+/// Derives the address of each of `keys` (the first `limit` when set) and fetches their
+/// balances and nonces concurrently.
+pub async fn account_infos<M: Middleware>(
+    provider: &M,
+    keys: &[SecretKey],
+    limit: Option<usize>,
+) -> Result<Vec<AccountInfo>>
+where
+    M::Error: 'static,
+{
+    let keys = &keys[..limit.map_or(keys.len(), |limit| limit.min(keys.len()))];
+    try_join_all(keys.iter().enumerate().map(|(index, key)| async move {
+        let address = LocalWallet::from(key.clone()).address();
+        let (balance, nonce) = futures::try_join!(
+            provider.get_balance(address, None),
+            provider.get_transaction_count(address, None)
+        )
+        .map_err(|e| eyre!(e))?;
+        eyre::Ok(AccountInfo {
+            index,
+            address,
+            balance,
+            nonce,
+        })
+    }))
+    .await
+}
+
+/// Lays `accounts` out as a table with a header row and aligned columns.
+pub fn accounts_table(accounts: &[AccountInfo]) -> String {
+    let rows: Vec<[String; 4]> = accounts
+        .iter()
+        .map(|account| {
+            [
+                account.index.to_string(),
+                to_checksum(&account.address, None),
+                format_eth_decimal(account.balance),
+                account.nonce.to_string(),
+            ]
+        })
+        .collect();
+    let header = ["#", "address", "balance (ETH)", "nonce"].map(String::from);
+    let mut widths = header.each_ref().map(String::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    // Numbers are right-aligned, the address left-aligned
+    let line = |row: &[String; 4]| {
+        format!(
+            "{:>w0$}  {:<w1$}  {:>w2$}  {:>w3$}",
+            row[0],
+            row[1],
+            row[2],
+            row[3],
+            w0 = widths[0],
+            w1 = widths[1],
+            w2 = widths[2],
+            w3 = widths[3]
+        )
+    };
+    std::iter::once(&header)
+        .chain(&rows)
+        .map(line)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::prelude::Provider;
+    use ethers::utils::{parse_ether, Ganache};
+
+    fn account(index: usize, balance: U256, nonce: u64) -> AccountInfo {
+        AccountInfo {
+            index,
+            address: Address::from_low_u64_be(index as u64 + 1),
+            balance,
+            nonce: nonce.into(),
+        }
+    }
+
+    #[test]
+    fn test_table_columns_align() -> Result<()> {
+        let accounts = [
+            account(0, parse_ether(1000)?, 3),
+            account(11, U256::from(5u64), 120),
+        ];
+        let table = accounts_table(&accounts);
+        let lines: Vec<_> = table.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.len() == lines[0].len()));
+        assert!(lines[0].starts_with(" #  address"));
+        assert!(lines[1].ends_with("1000.000000000000000000      3"));
+        assert!(lines[2]
+            .to_lowercase()
+            .starts_with("11  0x000000000000000000000000000000000000000c  "));
+        assert!(lines[2].ends_with("0.000000000000000005    120"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_lists_every_configured_account() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new()
+            .mnemonic(mnemonic)
+            .args(["--wallet.totalAccounts", "4"])
+            .spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+
+        let accounts = account_infos(&provider, ganache.keys(), None).await?;
+        assert_eq!(accounts.len(), 4);
+        for (account, address) in accounts.iter().zip(ganache.addresses()) {
+            assert_eq!(account.address, *address);
+            assert_eq!(account.balance, provider.get_balance(*address, None).await?);
+            assert!(account.nonce.is_zero());
+        }
+        assert_eq!(accounts_table(&accounts).lines().count(), 5);
+
+        let first_two = account_infos(&provider, ganache.keys(), Some(2)).await?;
+        assert_eq!(first_two, accounts[..2]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,13 @@
+//! Human-readable formatting of Wei amounts.
+
+use ethers::{types::U256, utils::format_ether};
+
+/// Formats a Wei amount as a decimal ETH string, e.g. `1.500000000000000000 ETH`.
+pub fn format_eth(wei: U256) -> String {
+    format!("{} ETH", format_ether(wei))
+}
+
+/// Formats a Wei amount as a bare decimal ETH string, e.g. `1.500000000000000000`.
+pub fn format_eth_decimal(wei: U256) -> String {
+    format_ether(wei)
+}
@@ -0,0 +1,301 @@
+//! Export of compiled ABIs/bytecode to standalone files, and loading them (or Hardhat/Foundry
+//! artifacts) back for deployment.
+
+use crate::errors::Error;
+use ethers::{abi::Abi, types::Bytes};
+use ethers_solc::{ConfigurableArtifacts, ProjectCompileOutput};
+use eyre::{eyre, Context, Result};
+use serde::Deserialize;
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+/// Writes `<ContractName>.abi.json` (standard JSON ABI array) and `<ContractName>.bin` (hex creation
+/// bytecode) into `out_dir` for every artifact that has them, returning the written paths.
+///
+/// Existing files are left untouched and reported as an error unless `overwrite` is set.
+pub fn export_artifacts(
+    project: &ProjectCompileOutput<ConfigurableArtifacts>,
+    out_dir: impl AsRef<Path>,
+    overwrite: bool,
+) -> Result<Vec<PathBuf>> {
+    let out_dir = out_dir.as_ref();
+    std::fs::create_dir_all(out_dir)
+        .wrap_err_with(|| format!("Failed to create {}", out_dir.display()))?;
+
+    // Collect the files first so nothing is written when any of them would be clobbered
+    let mut files: Vec<(PathBuf, String)> = Vec::new();
+    let mut names = HashSet::new();
+    for (id, artifact) in project.artifact_ids() {
+        let name = sanitize_name(&id.name);
+        if !names.insert(name.clone()) {
+            return Err(eyre!(
+                "Two artifacts export to the same name {name:?} (latest from {})",
+                id.source.display()
+            ));
+        }
+
+        if let Some(abi) = &artifact.abi {
+            // The lossless value is exactly what solc emitted: the standard JSON array form
+            let json = serde_json::to_string_pretty(&abi.abi_value)?;
+            files.push((out_dir.join(format!("{name}.abi.json")), json + "\n"));
+        }
+
+        let bytecode = artifact
+            .bytecode
+            .as_ref()
+            .and_then(|bytecode| bytecode.object.as_bytes())
+            .filter(|bytes| !bytes.is_empty());
+        if let Some(bytes) = bytecode {
+            files.push((
+                out_dir.join(format!("{name}.bin")),
+                hex::encode(bytes) + "\n",
+            ));
+        }
+    }
+
+    if !overwrite {
+        if let Some((path, _)) = files.iter().find(|(path, _)| path.exists()) {
+            return Err(eyre!(
+                "{} already exists, pass overwrite to replace it",
+                path.display()
+            ));
+        }
+    }
+
+    for (path, contents) in &files {
+        std::fs::write(path, contents)
+            .wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(files.into_iter().map(|(path, _)| path).collect())
+}
+
+/// Loads an ABI JSON file and a hex bytecode file (with or without `0x`) written by
+/// [`export_artifacts`] or `solc --abi --bin`, ready for a `ContractFactory`.
+pub fn load_artifact(
+    abi_path: impl AsRef<Path>,
+    bin_path: impl AsRef<Path>,
+) -> Result<(Abi, Bytes)> {
+    let abi_path = abi_path.as_ref();
+    let bin_path = bin_path.as_ref();
+
+    let abi_json = std::fs::read_to_string(abi_path)
+        .wrap_err_with(|| format!("Failed to read {}", abi_path.display()))?;
+    let abi: Abi = serde_json::from_str(&abi_json)
+        .wrap_err_with(|| format!("{} is not a JSON ABI", abi_path.display()))?;
+
+    let bin = std::fs::read_to_string(bin_path)
+        .wrap_err_with(|| format!("Failed to read {}", bin_path.display()))?;
+    let bytecode = decode_bytecode(&bin)
+        .wrap_err_with(|| format!("{} is not hex bytecode", bin_path.display()))?;
+
+    Ok((abi, bytecode))
+}
+
+/// ABI and creation bytecode of a prebuilt contract, ready for a `ContractFactory`.
+#[derive(Clone, Debug)]
+pub struct Artifact {
+    pub abi: Abi,
+    pub bytecode: Bytes,
+}
+
+// Hardhat's `artifacts/**/<Name>.json`: the bytecode is a plain hex string
+#[derive(Deserialize)]
+struct HardhatArtifact {
+    abi: Abi,
+    bytecode: String,
+}
+
+// Foundry's `out/<File>.sol/<Name>.json`: the bytecode is an object holding the hex string
+#[derive(Deserialize)]
+struct FoundryArtifact {
+    abi: Abi,
+    bytecode: FoundryBytecode,
+}
+
+#[derive(Deserialize)]
+struct FoundryBytecode {
+    object: String,
+}
+
+impl Artifact {
+    /// Parses a Hardhat artifact (`{abi, bytecode}`); `deployedBytecode` is not needed.
+    pub fn from_hardhat_json(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let artifact: HardhatArtifact = read_json(path)?;
+        Self::new(artifact.abi, &artifact.bytecode, path)
+    }
+
+    /// Parses a Foundry artifact (`{abi, bytecode: {object}}`); `deployedBytecode` is not needed.
+    pub fn from_foundry_json(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let artifact: FoundryArtifact = read_json(path)?;
+        Self::new(artifact.abi, &artifact.bytecode.object, path)
+    }
+
+    fn new(abi: Abi, bytecode: &str, path: &Path) -> Result<Self> {
+        let bytecode = decode_bytecode(bytecode)
+            .wrap_err_with(|| format!("Invalid bytecode in {}", path.display()))?;
+        if bytecode.is_empty() {
+            // Interfaces and abstract contracts have no creation code
+            return Err(eyre!("{} has no bytecode to deploy", path.display()));
+        }
+        Ok(Self { abi, bytecode })
+    }
+}
+
+fn read_json<T: for<'de> Deserialize<'de>>(path: &Path) -> Result<T> {
+    let json = std::fs::read_to_string(path)
+        .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&json)
+        .wrap_err_with(|| format!("{} is not a recognised artifact", path.display()))
+}
+
+/// Decodes hex bytecode with or without `0x`, rejecting unlinked library placeholders
+/// (`__$<hash>$__` or the older `__<Path>:<Name>__`), which are 40 characters wide.
+fn decode_bytecode(hex_code: &str) -> Result<Bytes> {
+    let hex_code = hex_code.trim();
+    let hex_code = hex_code.strip_prefix("0x").unwrap_or(hex_code);
+
+    let mut placeholders = Vec::new();
+    let mut rest = hex_code;
+    while let Some(start) = rest.find("__") {
+        let end = (start + 40).min(rest.len());
+        placeholders.push(rest[start..end].to_string());
+        rest = &rest[end..];
+    }
+    if !placeholders.is_empty() {
+        return Err(Error::UnlinkedLibraries { placeholders }.into());
+    }
+
+    Ok(hex::decode(hex_code)?.into())
+}
+
+/// Keeps ASCII alphanumerics, `_` and `-`; everything else becomes `_` so names are safe file stems.
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::deploy::{deploy_contract, DeployOptions};
+    use crate::project::compile;
+    use ethers::prelude::{LocalWallet, Middleware, Provider, U256};
+    use ethers::utils::Ganache;
+
+    #[test]
+    fn test_sanitize_name() {
+        assert_eq!(sanitize_name("BUSDImplementation"), "BUSDImplementation");
+        assert_eq!(sanitize_name("../evil name"), "___evil_name");
+    }
+
+    #[test]
+    fn test_decode_bytecode_prefixes_and_placeholders() {
+        assert_eq!(
+            decode_bytecode("0x6001").unwrap(),
+            Bytes::from(vec![0x60, 0x01])
+        );
+        assert_eq!(
+            decode_bytecode("6001\n").unwrap(),
+            Bytes::from(vec![0x60, 0x01])
+        );
+
+        let placeholder = "__$2bb01e9ed1af8b5ef44a44b29e2e45a5e2$__";
+        let err = decode_bytecode(&format!("0x6001{placeholder}6002")).unwrap_err();
+        match err.downcast_ref::<Error>() {
+            Some(Error::UnlinkedLibraries { placeholders }) => {
+                assert_eq!(placeholders, &[placeholder.to_string()])
+            }
+            other => panic!("expected UnlinkedLibraries, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_deploy_hardhat_and_foundry_artifacts() -> Result<()> {
+        let hardhat = Artifact::from_hardhat_json("tests/fixtures/artifacts/hardhat/Answer.json")?;
+        let foundry = Artifact::from_foundry_json("tests/fixtures/artifacts/foundry/Answer.json")?;
+        // Same contract, one with a `0x` prefix and one without
+        assert_eq!(hardhat.bytecode, foundry.bytecode);
+
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        for artifact in [hardhat, foundry] {
+            let wallet: LocalWallet = ganache.keys()[0].clone().into();
+            let (contract, _) = deploy_contract(
+                &ctx,
+                wallet,
+                "Answer",
+                artifact.abi,
+                artifact.bytecode,
+                (),
+                &DeployOptions::default(),
+            )
+            .await?;
+            let value: U256 = contract.method("value", ())?.call().await?;
+            assert_eq!(value, U256::from(42u64));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_export_load_and_deploy_round_trip() -> Result<()> {
+        let project = compile("examples/").await?.output;
+        let out_dir = std::env::temp_dir().join("rust-eth-yt-export-round-trip");
+        let _ = std::fs::remove_dir_all(&out_dir);
+
+        let written = export_artifacts(&project, &out_dir, false)?;
+        assert!(written.contains(&out_dir.join("BUSDImplementation.abi.json")));
+        assert!(written.contains(&out_dir.join("BUSDImplementation.bin")));
+
+        // A second export refuses to overwrite, unless asked to
+        assert!(export_artifacts(&project, &out_dir, false).is_err());
+        export_artifacts(&project, &out_dir, true)?;
+
+        // The ABI file is the standard JSON array, parseable by ethabi
+        let abi_json = std::fs::read_to_string(out_dir.join("BUSDImplementation.abi.json"))?;
+        assert!(abi_json.trim_start().starts_with('['));
+        let abi: ethers::abi::ethabi::Contract = serde_json::from_str(&abi_json)?;
+        assert!(abi.function("transfer").is_ok());
+
+        let (abi, bytecode) = load_artifact(
+            out_dir.join("BUSDImplementation.abi.json"),
+            out_dir.join("BUSDImplementation.bin"),
+        )?;
+
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let (contract, _) = deploy_contract(
+            &ctx,
+            wallet,
+            "BUSDImplementation",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        let code = provider.get_code(contract.address(), None).await?;
+        assert!(!code.is_empty(), "Deployed contract should have code");
+
+        Ok(())
+    }
+}
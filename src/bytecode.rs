@@ -0,0 +1,150 @@
+//! Comparison of on-chain runtime code with the compiled artifact.
+
+use ethers::{
+    prelude::{Address, JsonRpcClient, Middleware, Provider},
+    types::Bytes,
+};
+use eyre::Result;
+
+/// Fetches the runtime code at `address` and compares it with `expected_runtime` (the artifact's
+/// deployed bytecode).
+///
+/// With `strict` the bytes must be identical. Otherwise the trailing CBOR metadata (source hash,
+/// compiler version) is ignored on both sides, and so are 32-byte zero words of the expected
+/// code, which is where solc leaves room for `immutable` values filled in by the constructor.
+pub async fn verify_deployed_code<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    address: Address,
+    expected_runtime: &Bytes,
+    strict: bool,
+) -> Result<bool> {
+    let actual = provider.get_code(address, None).await?;
+    Ok(runtime_code_matches(&actual, expected_runtime, strict))
+}
+
+fn runtime_code_matches(actual: &[u8], expected: &[u8], strict: bool) -> bool {
+    if strict {
+        return actual == expected;
+    }
+
+    let (actual, expected) = (strip_metadata(actual), strip_metadata(expected));
+    if actual.len() != expected.len() {
+        return false;
+    }
+
+    // Mark the expected code's zero words as wildcards for immutables
+    let mut wildcard = vec![false; expected.len()];
+    let mut zeros = 0;
+    for (i, byte) in expected.iter().enumerate() {
+        zeros = if *byte == 0 { zeros + 1 } else { 0 };
+        if zeros >= 32 {
+            wildcard[i + 1 - 32..=i].fill(true);
+        }
+    }
+
+    actual
+        .iter()
+        .zip(expected)
+        .zip(wildcard)
+        .all(|((a, e), wildcard)| wildcard || a == e)
+}
+
+// solc appends `<CBOR metadata><2-byte big-endian length of it>` to the runtime code
+fn strip_metadata(code: &[u8]) -> &[u8] {
+    let Some(len_bytes) = code.len().checked_sub(2).map(|at| &code[at..]) else {
+        return code;
+    };
+    let metadata_len = u16::from_be_bytes([len_bytes[0], len_bytes[1]]) as usize;
+    match code.len().checked_sub(metadata_len + 2) {
+        // CBOR maps start with 0xa0..=0xbf; anything else is not metadata
+        Some(start) if metadata_len > 0 && (0xa0..=0xbf).contains(&code[start]) => &code[..start],
+        _ => code,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::deploy::{deploy_contract, DeployOptions};
+    use crate::project::{compile, contract_parts};
+    use ethers::prelude::LocalWallet;
+    use ethers::utils::Ganache;
+    use ethers_solc::Artifact;
+
+    // Appends a fake 4-byte CBOR metadata blob and its length, like solc does
+    fn with_metadata(body: &[u8], metadata: [u8; 4]) -> Vec<u8> {
+        let mut code = body.to_vec();
+        code.extend(metadata);
+        code.extend(4u16.to_be_bytes());
+        code
+    }
+
+    #[test]
+    fn test_metadata_is_ignored_unless_strict() {
+        let body = [0x60, 0x01, 0x60, 0x00, 0x55]; // PUSH1 1 PUSH1 0 SSTORE
+        let expected = with_metadata(&body, [0xa1, 0x01, 0x02, 0x03]);
+        let actual = with_metadata(&body, [0xa1, 0x09, 0x09, 0x09]);
+
+        assert!(runtime_code_matches(&actual, &expected, false));
+        assert!(!runtime_code_matches(&actual, &expected, true));
+        assert!(runtime_code_matches(&expected, &expected, true));
+
+        // A different instruction is never accepted
+        let other = with_metadata(&[0x60, 0x02, 0x60, 0x00, 0x55], [0xa1, 0x01, 0x02, 0x03]);
+        assert!(!runtime_code_matches(&other, &expected, false));
+    }
+
+    #[test]
+    fn test_immutable_words_are_wildcards() {
+        // PUSH32 <immutable> with the value left zeroed in the artifact
+        let mut expected = vec![0x7f];
+        expected.extend([0u8; 32]);
+        expected.push(0x50);
+        let mut actual = vec![0x7f];
+        actual.extend([0xab; 32]);
+        actual.push(0x50);
+
+        assert!(runtime_code_matches(&actual, &expected, false));
+        assert!(!runtime_code_matches(&actual, &expected, true));
+        actual[33] = 0x51;
+        assert!(!runtime_code_matches(&actual, &expected, false));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_deployed_busd_matches_artifact() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let runtime = project
+            .find_first("BUSDImplementation")
+            .and_then(|artifact| artifact.get_deployed_bytecode_bytes())
+            .map(|bytes| bytes.into_owned())
+            .expect("BUSDImplementation has deployed bytecode");
+
+        let (contract, _) = deploy_contract(
+            &ctx,
+            wallet,
+            "BUSDImplementation",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+
+        assert!(verify_deployed_code(&provider, contract.address(), &runtime, true).await?);
+        assert!(verify_deployed_code(&provider, contract.address(), &runtime, false).await?);
+
+        // An address without code doesn't match
+        assert!(!verify_deployed_code(&provider, Address::zero(), &runtime, false).await?);
+
+        Ok(())
+    }
+}
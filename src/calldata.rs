@@ -0,0 +1,391 @@
+//! Decoding of raw transaction input into the function it calls and its arguments.
+
+use crate::errors::Error;
+use ethers::{
+    abi::{
+        token::{LenientTokenizer, Tokenizer},
+        Abi, AbiParser, Param, Token,
+    },
+    prelude::{JsonRpcClient, Middleware, Provider, H256},
+    types::Bytes,
+    utils::{format_ether, id},
+};
+use eyre::{eyre, Result};
+use std::collections::HashMap;
+
+/// Signatures tried when the selector isn't in the ABI at hand.
+const COMMON_SIGNATURES: &[&str] = &[
+    "transfer(address,uint256)",
+    "transferFrom(address,address,uint256)",
+    "approve(address,uint256)",
+    "balanceOf(address)",
+    "allowance(address,address)",
+    "totalSupply()",
+    "mint(address,uint256)",
+    "burn(uint256)",
+    "increaseAllowance(address,uint256)",
+    "decreaseAllowance(address,uint256)",
+    "safeTransferFrom(address,address,uint256)",
+    "setApprovalForAll(address,bool)",
+    "transferOwnership(address)",
+    "renounceOwnership()",
+    "multicall(bytes[])",
+];
+
+/// The 4-byte selector of a canonical signature such as `transfer(address,uint256)`: the first
+/// bytes of its keccak-256 hash.
+pub fn function_selector(signature: &str) -> [u8; 4] {
+    id(signature)
+}
+
+/// Maps the selector of each of `abi`'s functions to the function's name.
+///
+/// Overloads share a name but not a selector, so each gets its own entry.
+pub fn selectors_of(abi: &Abi) -> HashMap<[u8; 4], String> {
+    abi.functions()
+        .map(|function| (function.short_signature(), function.name.clone()))
+        .collect()
+}
+
+/// Parses command-line style arguments into the tokens of `inputs`, one string per parameter.
+///
+/// Integers are decimal, and arrays and tuples take `[a,b]` and `(a,b)`; see
+/// ethabi's `LenientTokenizer` for the full syntax.
+pub fn encode_args(inputs: &[Param], args: &[&str]) -> Result<Vec<Token>> {
+    if inputs.len() != args.len() {
+        return Err(eyre!(
+            "expected {} arguments ({}), got {}",
+            inputs.len(),
+            inputs
+                .iter()
+                .map(|input| format!("{} {}", input.kind, input.name))
+                .collect::<Vec<_>>()
+                .join(", "),
+            args.len()
+        ));
+    }
+    inputs
+        .iter()
+        .zip(args)
+        .map(|(input, arg)| {
+            LenientTokenizer::tokenize(&input.kind, arg)
+                .map_err(|err| eyre!("invalid {} for {}: {arg:?} ({err})", input.kind, input.name))
+        })
+        .collect()
+}
+
+/// A function call recovered from calldata.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedCall {
+    /// Name of the called function.
+    pub function: String,
+    /// Arguments in declaration order, with their parameter names (`arg<i>` for unnamed ones).
+    pub args: Vec<(String, Token)>,
+}
+
+/// Finds the function of `abi` whose selector starts `data` and decodes its arguments.
+///
+/// A selector that matches none of the ABI's functions yields [`Error::UnknownSelector`].
+pub fn decode_calldata(abi: &Abi, data: &Bytes) -> Result<DecodedCall> {
+    let selector: [u8; 4] = data
+        .get(..4)
+        .and_then(|selector| selector.try_into().ok())
+        .ok_or_else(|| eyre!("calldata is {} bytes, shorter than a selector", data.len()))?;
+    let function = abi
+        .functions()
+        .find(|function| function.short_signature() == selector)
+        .ok_or(Error::UnknownSelector { selector })?;
+
+    let tokens = function.decode_input(&data[4..])?;
+    let args = function
+        .inputs
+        .iter()
+        .zip(tokens)
+        .enumerate()
+        .map(|(i, (param, token))| {
+            let name = if param.name.is_empty() {
+                format!("arg{i}")
+            } else {
+                param.name.clone()
+            };
+            (name, token)
+        })
+        .collect();
+    Ok(DecodedCall {
+        function: function.name.clone(),
+        args,
+    })
+}
+
+/// Fetches transaction `tx_hash` and decodes its input as a call of `abi`, returning the
+/// function name and its arguments in declaration order.
+///
+/// A selector that matches none of the ABI's functions yields [`Error::UnknownSelector`].
+pub async fn decode_tx_input<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    abi: &Abi,
+    tx_hash: H256,
+) -> Result<(String, Vec<Token>)> {
+    let tx = provider
+        .get_transaction(tx_hash)
+        .await?
+        .ok_or_else(|| eyre!("transaction {tx_hash:?} not found"))?;
+    let call = decode_calldata(abi, &tx.input)?;
+    let tokens = call.args.into_iter().map(|(_, token)| token).collect();
+    Ok((call.function, tokens))
+}
+
+/// Fetches transaction `tx_hash` and describes its sender, recipient, value and decoded call.
+pub async fn explain_tx<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    tx_hash: H256,
+    abi: &Abi,
+) -> Result<String> {
+    let tx = provider
+        .get_transaction(tx_hash)
+        .await?
+        .ok_or_else(|| eyre!("transaction {tx_hash:?} not found"))?;
+
+    let to = match tx.to {
+        Some(to) => format!("{to:?}"),
+        None => "contract creation".to_string(),
+    };
+    let mut out = format!(
+        "From:  {:?}\nTo:    {}\nValue: {} ETH\n",
+        tx.from,
+        to,
+        format_ether(tx.value)
+    );
+    out.push_str(&explain_calldata(abi, &tx.input));
+    Ok(out)
+}
+
+/// Describes `data` as a call of `abi`, falling back to the common signatures for unknown selectors.
+pub fn explain_calldata(abi: &Abi, data: &Bytes) -> String {
+    if data.is_empty() {
+        return "Call:  none (plain transfer)".to_string();
+    }
+    match decode_calldata(abi, data) {
+        Ok(call) => describe(&call),
+        Err(_) => {
+            let selector = &data[..data.len().min(4)];
+            let mut out = format!("Call:  unknown selector 0x{}", hex::encode(selector));
+            for signature in COMMON_SIGNATURES {
+                if function_selector(signature)[..] != *selector {
+                    continue;
+                }
+                out.push_str(&format!("\n       matches {signature}"));
+                // Decode with the matching signature when the arguments fit it
+                let decoded = AbiParser::default()
+                    .parse_function(&format!("function {signature}"))
+                    .ok()
+                    .and_then(|function| function.decode_input(&data[4..]).ok());
+                if let Some(tokens) = decoded {
+                    for (i, token) in tokens.iter().enumerate() {
+                        out.push_str(&format!("\n         arg{i} = {}", format_token(token)));
+                    }
+                }
+            }
+            out
+        }
+    }
+}
+
+fn describe(call: &DecodedCall) -> String {
+    let mut out = format!("Call:  {}", call.function);
+    for (name, token) in &call.args {
+        out.push_str(&format!("\n         {name} = {}", format_token(token)));
+    }
+    out
+}
+
+// Integers in decimal and addresses with 0x, unlike `Token`'s own hex Display
+pub(crate) fn format_token(token: &Token) -> String {
+    match token {
+        Token::Uint(value) => value.to_string(),
+        Token::Int(value) => ethers::types::I256::from_raw(*value).to_string(),
+        Token::Address(address) => format!("{address:?}"),
+        Token::Bytes(bytes) | Token::FixedBytes(bytes) => format!("0x{}", hex::encode(bytes)),
+        Token::Array(tokens) | Token::FixedArray(tokens) => format!(
+            "[{}]",
+            tokens
+                .iter()
+                .map(format_token)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Token::Tuple(tokens) => format!(
+            "({})",
+            tokens
+                .iter()
+                .map(format_token)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        token => token.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::deploy::{deploy_contract, DeployOptions};
+    use crate::project::{compile, contract_parts};
+    use ethers::abi::{parse_abi, Address};
+    use ethers::prelude::{LocalWallet, Signer, TransactionRequest, U256};
+    use ethers::utils::Ganache;
+
+    #[test]
+    fn test_function_selectors() -> Result<()> {
+        assert_eq!(
+            function_selector("transfer(address,uint256)"),
+            [0xa9, 0x05, 0x9c, 0xbb]
+        );
+
+        let abi = parse_abi(&[
+            "function transfer(address to, uint256 amount) returns (bool)",
+            "function balanceOf(address owner) view returns (uint256)",
+            "function safeTransferFrom(address from, address to, uint256 id)",
+            "function safeTransferFrom(address from, address to, uint256 id, bytes data)",
+        ])?;
+        let selectors = selectors_of(&abi);
+        assert_eq!(selectors.len(), 4);
+        assert_eq!(selectors[&[0xa9, 0x05, 0x9c, 0xbb]], "transfer");
+        assert_eq!(
+            selectors[&function_selector("balanceOf(address)")],
+            "balanceOf"
+        );
+        for signature in [
+            "safeTransferFrom(address,address,uint256)",
+            "safeTransferFrom(address,address,uint256,bytes)",
+        ] {
+            assert_eq!(selectors[&function_selector(signature)], "safeTransferFrom");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_args_from_strings() -> Result<()> {
+        let abi = parse_abi(&["function batch(address to, uint256[] amounts, bool strict)"])?;
+        let inputs = &abi.function("batch")?.inputs;
+        let to = "0xB794F5eA0ba39494cE839613fffBA74279579268";
+        let tokens = encode_args(inputs, &[to, "[1,16]", "true"])?;
+        assert_eq!(
+            tokens,
+            vec![
+                Token::Address(to.parse()?),
+                Token::Array(vec![Token::Uint(1u64.into()), Token::Uint(16u64.into())]),
+                Token::Bool(true),
+            ]
+        );
+
+        let err = encode_args(inputs, &[to]).unwrap_err().to_string();
+        assert!(err.contains("expected 3 arguments"), "{err}");
+        let err = encode_args(inputs, &["nope", "[]", "true"])
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("invalid address for to"), "{err}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_selector_offers_common_signatures() -> Result<()> {
+        let erc20 = parse_abi(&["function transfer(address to, uint256 amount) returns (bool)"])?;
+        let data: Bytes = erc20
+            .function("transfer")?
+            .encode_input(&[
+                Token::Address(Address::from_low_u64_be(0xb0b)),
+                Token::Uint(U256::from(500u64)),
+            ])?
+            .into();
+
+        // Nothing in this ABI has the selector
+        let other = parse_abi(&["function value() view returns (uint256)"])?;
+        let err = decode_calldata(&other, &data).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::UnknownSelector { selector }) if selector[..] == data[..4]
+        ));
+
+        let explanation = explain_calldata(&other, &data);
+        assert!(
+            explanation.contains("unknown selector 0xa9059cbb"),
+            "{explanation}"
+        );
+        assert!(
+            explanation.contains("matches transfer(address,uint256)"),
+            "{explanation}"
+        );
+        assert!(explanation.contains("arg1 = 500"), "{explanation}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_explain_busd_transfer() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let owner = wallet.address();
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let (busd, _) = deploy_contract(
+            &ctx,
+            wallet,
+            "BUSDImplementation",
+            abi.clone(),
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        busd.method::<_, ()>("unpause", ())?.send().await?.await?;
+        busd.method::<_, bool>("increaseSupply", U256::from(1_000_000u64))?
+            .send()
+            .await?
+            .await?;
+
+        // Send the encoded transfer as raw calldata
+        let holder = Address::from_low_u64_be(0xb0b);
+        let data = abi
+            .function("transfer")?
+            .encode_input(&[Token::Address(holder), Token::Uint(U256::from(1234u64))])?;
+        let tx = TransactionRequest::new()
+            .from(owner)
+            .to(busd.address())
+            .data(data);
+        let receipt = busd
+            .client()
+            .send_transaction(tx, None)
+            .await?
+            .await?
+            .ok_or_else(|| eyre!("no receipt"))?;
+
+        let (function, tokens) = decode_tx_input(&provider, &abi, receipt.transaction_hash).await?;
+        assert_eq!(function, "transfer");
+        assert_eq!(
+            tokens,
+            vec![Token::Address(holder), Token::Uint(U256::from(1234u64))]
+        );
+
+        let explanation = explain_tx(&provider, receipt.transaction_hash, &abi).await?;
+        assert!(explanation.contains("Call:  transfer"), "{explanation}");
+        assert!(explanation.contains("= 1234"), "{explanation}");
+        assert!(
+            explanation.contains(&format!("{holder:?}")),
+            "{explanation}"
+        );
+        assert!(
+            explanation.contains(&format!("From:  {owner:?}")),
+            "{explanation}"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+//! Per-provider cache of the chain id, the latest block and the suggested EIP-1559 fees.
+
+use crate::timeouts::{average_block_time, BLOCK_TIME_SAMPLE};
+use async_trait::async_trait;
+use ethers::prelude::{
+    Block, BlockId, BlockNumber, Middleware, MiddlewareError, Provider, TxHash, Ws, U256,
+};
+use ethers::utils::{
+    eip1559_default_estimator, EIP1559_FEE_ESTIMATION_PAST_BLOCKS,
+    EIP1559_FEE_ESTIMATION_REWARD_PERCENTILE,
+};
+use futures::StreamExt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tokio::sync::{watch, OnceCell};
+use tracing::warn;
+
+/// Wraps a provider and answers the lookups the helpers keep repeating from a cache: the chain
+/// id for good, the latest block and the suggested fees for one block time, refreshed lazily.
+///
+/// Create one per provider and hand it to the deploy and transfer helpers, which take it in place
+/// of the provider. It is a [`Middleware`] itself, so the fee and funds helpers accept it too.
+/// Clones share the cache.
+#[derive(Clone, Debug)]
+pub struct ChainContext<M> {
+    inner: M,
+    ttl: Option<Duration>,
+    cache: Arc<Cache>,
+    heads: Option<watch::Receiver<u64>>,
+}
+
+#[derive(Debug, Default)]
+struct Cache {
+    chain_id: OnceCell<U256>,
+    block_time: OnceCell<Duration>,
+    latest_block: Mutex<Option<(Instant, Block<TxHash>)>>,
+    suggested_fees: Mutex<Option<(Instant, (U256, U256))>>,
+}
+
+#[derive(Error, Debug)]
+/// Thrown when a lookup through a [`ChainContext`] fails
+pub enum ChainContextError<M: Middleware> {
+    /// Thrown when the internal middleware errors
+    #[error("{0}")]
+    MiddlewareError(M::Error),
+    /// The node returned no latest block
+    #[error("Latest block not found")]
+    MissingBlock,
+    /// The latest block has no base fee, so EIP-1559 fees can't be suggested
+    #[error("EIP-1559 not activated")]
+    NoBaseFee,
+}
+
+impl<M: Middleware> MiddlewareError for ChainContextError<M> {
+    type Inner = M::Error;
+
+    fn from_err(src: M::Error) -> Self {
+        ChainContextError::MiddlewareError(src)
+    }
+
+    fn as_inner(&self) -> Option<&Self::Inner> {
+        match self {
+            ChainContextError::MiddlewareError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl<M: Middleware> ChainContext<M> {
+    /// Caches lookups on `inner` for the chain's measured [`ChainContext::block_time`], so an
+    /// instamine chain, whose block time is zero, is always asked afresh.
+    pub fn new(inner: M) -> Self {
+        Self {
+            inner,
+            ttl: None,
+            cache: Arc::default(),
+            heads: None,
+        }
+    }
+
+    /// Reuses the latest block and the fees for `ttl` instead of the measured block time.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    /// The chain id, fetched once.
+    pub async fn chain_id(&self) -> Result<u64, ChainContextError<M>> {
+        Ok(self.get_chainid().await?.as_u64())
+    }
+
+    /// The base fee of the latest block, or `None` on a chain without EIP-1559.
+    pub async fn base_fee(&self) -> Result<Option<U256>, ChainContextError<M>> {
+        Ok(self.latest_block().await?.base_fee_per_gas)
+    }
+
+    /// `(max_fee_per_gas, max_priority_fee_per_gas)` as the provider's default estimator
+    /// suggests them from the latest block and the recent fee history.
+    pub async fn suggested_fees(&self) -> Result<(U256, U256), ChainContextError<M>> {
+        if let Some(fees) = fresh(&self.cache.suggested_fees, self.current_ttl().await?) {
+            return Ok(fees);
+        }
+        let base_fee = self.base_fee().await?.ok_or(ChainContextError::NoBaseFee)?;
+        let history = self
+            .inner
+            .fee_history(
+                EIP1559_FEE_ESTIMATION_PAST_BLOCKS,
+                BlockNumber::Latest,
+                &[EIP1559_FEE_ESTIMATION_REWARD_PERCENTILE],
+            )
+            .await
+            .map_err(MiddlewareError::from_err)?;
+        let fees = eip1559_default_estimator(base_fee, history.reward);
+        store(&self.cache.suggested_fees, fees);
+        Ok(fees)
+    }
+
+    /// [`average_block_time`] over the last [`BLOCK_TIME_SAMPLE`] blocks, measured once. Zero on
+    /// a chain that mines on demand, or that has no blocks yet.
+    pub async fn block_time(&self) -> Result<Duration, ChainContextError<M>> {
+        // Straight from the node: the cached block's lifetime depends on this measure
+        let measure = || async {
+            average_block_time(&self.inner, BLOCK_TIME_SAMPLE)
+                .await
+                .map_err(MiddlewareError::from_err)
+        };
+        self.cache
+            .block_time
+            .get_or_try_init(measure)
+            .await
+            .copied()
+    }
+
+    /// Numbers of the new heads, when the context follows them (see [`ChainContext::watch_heads`]).
+    pub fn heads(&self) -> Option<watch::Receiver<u64>> {
+        self.heads.clone()
+    }
+
+    // The set ttl, or else one block time
+    async fn current_ttl(&self) -> Result<Duration, ChainContextError<M>> {
+        match self.ttl {
+            Some(ttl) => Ok(ttl),
+            None => self.block_time().await,
+        }
+    }
+
+    async fn latest_block(&self) -> Result<Block<TxHash>, ChainContextError<M>> {
+        if let Some(block) = fresh(&self.cache.latest_block, self.current_ttl().await?) {
+            return Ok(block);
+        }
+        let block = self
+            .inner
+            .get_block(BlockNumber::Latest)
+            .await
+            .map_err(MiddlewareError::from_err)?
+            .ok_or(ChainContextError::MissingBlock)?;
+        store(&self.cache.latest_block, block.clone());
+        Ok(block)
+    }
+}
+
+impl ChainContext<Provider<Ws>> {
+    /// Follows the chain's new heads over the websocket, so receipts are checked when a block
+    /// arrives instead of on a timer. If the subscription fails or ends, waits fall back to
+    /// polling.
+    pub fn watch_heads(mut self) -> Self {
+        let (sender, receiver) = watch::channel(0);
+        let provider = self.inner.clone();
+        tokio::spawn(async move {
+            let mut heads = match provider.subscribe_blocks().await {
+                Ok(heads) => heads,
+                Err(err) => return warn!(%err, "can't follow new heads, polling instead"),
+            };
+            while let Some(block) = heads.next().await {
+                let number = block.number.unwrap_or_default().as_u64();
+                if sender.send(number).is_err() {
+                    return; // Every context is gone
+                }
+            }
+        });
+        self.heads = Some(receiver);
+        self
+    }
+}
+
+// The cached value, unless it is older than `ttl`
+fn fresh<T: Clone>(slot: &Mutex<Option<(Instant, T)>>, ttl: Duration) -> Option<T> {
+    let slot = slot.lock().unwrap();
+    slot.as_ref()
+        .filter(|(fetched, _)| fetched.elapsed() < ttl)
+        .map(|(_, value)| value.clone())
+}
+
+fn store<T>(slot: &Mutex<Option<(Instant, T)>>, value: T) {
+    *slot.lock().unwrap() = Some((Instant::now(), value));
+}
+
+#[cfg_attr(target_arch = "wasm32", async_trait(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_trait)]
+impl<M: Middleware> Middleware for ChainContext<M> {
+    type Error = ChainContextError<M>;
+    type Provider = M::Provider;
+    type Inner = M;
+
+    fn inner(&self) -> &M {
+        &self.inner
+    }
+
+    async fn get_chainid(&self) -> Result<U256, Self::Error> {
+        self.cache
+            .chain_id
+            .get_or_try_init(|| self.inner.get_chainid())
+            .await
+            .copied()
+            .map_err(MiddlewareError::from_err)
+    }
+
+    async fn get_block<T: Into<BlockId> + Send + Sync>(
+        &self,
+        block_hash_or_number: T,
+    ) -> Result<Option<Block<TxHash>>, Self::Error> {
+        let block = block_hash_or_number.into();
+        if block == BlockId::Number(BlockNumber::Latest) {
+            return self.latest_block().await.map(Some);
+        }
+        self.inner
+            .get_block(block)
+            .await
+            .map_err(MiddlewareError::from_err)
+    }
+
+    async fn estimate_eip1559_fees(
+        &self,
+        estimator: Option<fn(U256, Vec<Vec<U256>>) -> (U256, U256)>,
+    ) -> Result<(U256, U256), Self::Error> {
+        match estimator {
+            None => self.suggested_fees().await,
+            // A custom estimator gets fresh data
+            Some(_) => self
+                .inner
+                .estimate_eip1559_fees(estimator)
+                .await
+                .map_err(MiddlewareError::from_err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{block_reply, counting_provider, fee_history_reply, reply};
+    use ethers::providers::MockResponse;
+    use ethers::utils::parse_units;
+    use eyre::Result;
+
+    #[tokio::test]
+    async fn test_lookups_within_the_ttl_hit_the_node_once() -> Result<()> {
+        let base_fee = U256::from(parse_units(10, "gwei")?);
+        let (provider, calls) = counting_provider([
+            reply(U256::from(1337u64)),
+            block_reply(5, Some(base_fee)),
+            fee_history_reply(base_fee, parse_units(2, "gwei")?.into()),
+        ]);
+        let context = ChainContext::new(provider).ttl(Duration::from_secs(12));
+
+        for _ in 0..10 {
+            assert_eq!(context.chain_id().await?, 1337);
+        }
+        for _ in 0..10 {
+            assert_eq!(context.base_fee().await?, Some(base_fee));
+        }
+        let mut suggested = Vec::new();
+        for _ in 0..10 {
+            suggested.push(context.suggested_fees().await?);
+        }
+        assert!(suggested.windows(2).all(|pair| pair[0] == pair[1]));
+        // The helpers see the cache through the middleware methods too
+        assert_eq!(context.get_chainid().await?, U256::from(1337u64));
+        assert!(context.get_block(BlockNumber::Latest).await?.is_some());
+        assert_eq!(context.estimate_eip1559_fees(None).await?, suggested[0]);
+
+        assert_eq!(
+            calls.methods(),
+            ["eth_chainId", "eth_getBlockByNumber", "eth_feeHistory"]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stale_entries_are_refetched() -> Result<()> {
+        let (provider, calls) = counting_provider([
+            block_reply(5, Some(1u64.into())),
+            block_reply(6, Some(2u64.into())),
+        ]);
+        let context = ChainContext::new(provider).ttl(Duration::ZERO);
+
+        assert_eq!(context.base_fee().await?, Some(1u64.into()));
+        assert_eq!(context.base_fee().await?, Some(2u64.into()));
+        assert_eq!(calls.methods().len(), 2);
+
+        // Legacy chains have no fees to suggest
+        let (provider, _) = counting_provider([block_reply(5, None)]);
+        let context = ChainContext::new(provider).ttl(Duration::ZERO);
+        assert!(matches!(
+            context.suggested_fees().await,
+            Err(ChainContextError::NoBaseFee)
+        ));
+        Ok(())
+    }
+
+    fn timed_block_reply(number: u64, timestamp: u64, base_fee: u64) -> MockResponse {
+        reply(Block::<TxHash> {
+            number: Some(number.into()),
+            timestamp: timestamp.into(),
+            base_fee_per_gas: Some(base_fee.into()),
+            ..Default::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_default_ttl_is_one_block_time() -> Result<()> {
+        // Nine intervals in 90 seconds: once measured, the latest block is reused for 10 seconds
+        let (provider, calls) = counting_provider([
+            timed_block_reply(20, 200, 1),
+            timed_block_reply(11, 110, 1),
+            timed_block_reply(20, 200, 1),
+        ]);
+        let context = ChainContext::new(provider);
+        assert_eq!(context.base_fee().await?, Some(1u64.into()));
+        assert_eq!(context.base_fee().await?, Some(1u64.into()));
+        assert_eq!(context.block_time().await?, Duration::from_secs(10));
+        assert_eq!(calls.methods().len(), 3);
+
+        // Instamine blocks share their timestamp, so nothing is reused
+        let (provider, calls) = counting_provider([
+            timed_block_reply(20, 100, 1),
+            timed_block_reply(11, 100, 1),
+            timed_block_reply(20, 100, 1),
+            timed_block_reply(21, 100, 2),
+        ]);
+        let context = ChainContext::new(provider);
+        assert_eq!(context.base_fee().await?, Some(1u64.into()));
+        assert_eq!(context.base_fee().await?, Some(2u64.into()));
+        assert_eq!(context.block_time().await?, Duration::ZERO);
+        assert_eq!(calls.methods().len(), 4);
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+//! Checks that an endpoint serves the expected chain before anything is sent to it.
+
+use crate::errors::Error;
+use ethers::prelude::{BlockNumber, Middleware, H256};
+use eyre::{ContextCompat, Result};
+use tracing::info;
+
+/// What the endpoint must serve; [`ChainGuard::check`] refuses anything else.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainGuard {
+    chain_id: u64,
+    genesis_hash: Option<H256>,
+    client: Option<String>,
+}
+
+/// What the endpoint reported to [`ChainGuard::check`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainInfo {
+    pub chain_id: u64,
+    /// `web3_clientVersion`, e.g. `Ganache/v7.9.2/EthereumJS TestRPC/v7.9.2/ethereum-js`.
+    pub client_version: String,
+    /// Hash of block 0.
+    pub genesis_hash: H256,
+}
+
+impl ChainGuard {
+    pub fn new(chain_id: u64) -> Self {
+        Self {
+            chain_id,
+            genesis_hash: None,
+            client: None,
+        }
+    }
+
+    /// Also require block 0 to have this hash, which tells apart chains sharing a chain id.
+    pub fn genesis_hash(mut self, hash: H256) -> Self {
+        self.genesis_hash = Some(hash);
+        self
+    }
+
+    /// Also require the client version to contain `name` (case-insensitive), e.g. `ganache`.
+    pub fn client(mut self, name: impl Into<String>) -> Self {
+        self.client = Some(name.into());
+        self
+    }
+
+    /// Queries `eth_chainId`, `web3_clientVersion` and block 0, failing on the first mismatch
+    /// with [`Error::ChainIdMismatch`], [`Error::GenesisHashMismatch`] or
+    /// [`Error::UnexpectedClient`].
+    pub async fn check<M: Middleware>(&self, provider: &M) -> Result<ChainInfo>
+    where
+        M::Error: 'static,
+    {
+        let info = ChainInfo {
+            chain_id: provider.get_chainid().await?.as_u64(),
+            client_version: provider.client_version().await?,
+            genesis_hash: provider
+                .get_block(BlockNumber::Number(0u64.into()))
+                .await?
+                .and_then(|block| block.hash)
+                .context("The node has no genesis block")?,
+        };
+
+        if info.chain_id != self.chain_id {
+            return Err(Error::ChainIdMismatch {
+                endpoint: None,
+                expected: self.chain_id,
+                actual: info.chain_id,
+            }
+            .into());
+        }
+        if let Some(genesis_hash) = self.genesis_hash {
+            if info.genesis_hash != genesis_hash {
+                return Err(Error::GenesisHashMismatch {
+                    expected: genesis_hash,
+                    actual: info.genesis_hash,
+                }
+                .into());
+            }
+        }
+        if let Some(client) = &self.client {
+            if !info
+                .client_version
+                .to_lowercase()
+                .contains(&client.to_lowercase())
+            {
+                return Err(Error::UnexpectedClient {
+                    expected: client.clone(),
+                    actual: info.client_version.clone(),
+                }
+                .into());
+            }
+        }
+        info!(chain_id = info.chain_id, client = %info.client_version, "chain checked");
+        Ok(info)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::transfer::{send_transfer, TransferOptions};
+    use ethers::prelude::{Provider, U256};
+    use ethers::utils::Ganache;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_guard_checks_chain_id_genesis_and_client() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+
+        let info = ChainGuard::new(chain_id)
+            .client("ganache")
+            .check(&provider)
+            .await?;
+        assert_eq!(info.chain_id, chain_id);
+        ChainGuard::new(chain_id)
+            .genesis_hash(info.genesis_hash)
+            .check(&provider)
+            .await?;
+
+        let err = ChainGuard::new(1).check(&provider).await.unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<Error>(),
+                Some(Error::ChainIdMismatch { expected: 1, actual, .. }) if *actual == chain_id
+            ),
+            "{err:?}"
+        );
+        assert!(err.to_string().contains("expected chain 1"), "{err}");
+
+        let err = ChainGuard::new(chain_id)
+            .genesis_hash(H256::repeat_byte(1))
+            .check(&provider)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::GenesisHashMismatch { actual, .. }) if *actual == info.genesis_hash
+        ));
+
+        let err = ChainGuard::new(chain_id)
+            .client("geth")
+            .check(&provider)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::UnexpectedClient { expected, .. }) if expected == "geth"
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_transfer_to_the_wrong_chain_is_refused() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let (from, to) = (ganache.addresses()[0], ganache.addresses()[1]);
+
+        let options = TransferOptions {
+            expected_chain: Some(1),
+            ..Default::default()
+        };
+        let err = send_transfer(&ctx, from, to, U256::from(1000u64), &options)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ChainIdMismatch { expected: 1, .. })
+        ));
+
+        // Nothing was sent
+        assert_eq!(
+            provider.get_transaction_count(from, None).await?,
+            U256::zero()
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,226 @@
+//! Connection settings loaded from a TOML file, with command line flags taking precedence.
+
+use crate::node::GanacheConfig;
+use crate::notify::NotifyConfig;
+#[cfg(feature = "sqlite")]
+use crate::sqlite::Ledger;
+use clap::Args;
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+#[cfg(feature = "sqlite")]
+use std::sync::Arc;
+
+/// Settings of a binary run, as found in the `--config` file.
+///
+/// ```toml
+/// [ganache]
+/// port = 8545
+/// block_time = 2
+/// auto_mine = true
+///
+/// [wallet]
+/// mnemonic = "brisk usual burst upper buddy female library dial rifle mercy globe nurse"
+/// account_index = 0
+///
+/// [database]
+/// path = "runs.sqlite"
+///
+/// [notify]
+/// webhook = "https://hooks.slack.com/services/…"
+/// min_value_eth = 10.0
+/// ```
+///
+/// Every section and field is optional.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub ganache: GanacheConfig,
+    pub wallet: WalletConfig,
+    pub database: DatabaseConfig,
+    pub notify: NotifyConfig,
+}
+
+/// Which account signs the transactions.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WalletConfig {
+    /// Mnemonic the Ganache accounts and the wallet are derived from.
+    pub mnemonic: Option<String>,
+    /// Derivation index of the signing account (0 when unset).
+    pub account_index: Option<u32>,
+}
+
+/// Where runs are recorded; only used by builds with the `sqlite` feature.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    /// SQLite file the deployments, transactions and balances are recorded in.
+    pub path: Option<PathBuf>,
+}
+
+impl DatabaseConfig {
+    /// Opens the configured database, migrating it if needed.
+    #[cfg(feature = "sqlite")]
+    pub fn open(&self) -> Result<Option<Arc<Ledger>>> {
+        self.path
+            .as_ref()
+            .map(|path| Ledger::open(path).map(Arc::new))
+            .transpose()
+    }
+}
+
+impl Config {
+    /// Loads a TOML config file; unknown keys are rejected so typos don't go unnoticed.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&text).wrap_err_with(|| format!("Invalid config file {}", path.display()))
+    }
+}
+
+/// Command line flags shared by the binaries; each one overrides the config file's value.
+#[derive(Args, Clone, Debug, Default)]
+pub struct ConnectionArgs {
+    /// TOML file with `[ganache]` and `[wallet]` settings
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+    /// Port for the Ganache node
+    #[arg(long, global = true)]
+    pub port: Option<u16>,
+    /// Seconds between Ganache blocks (mines on every transaction when unset)
+    #[arg(long, global = true)]
+    pub block_time: Option<u64>,
+    /// RPC URL of a chain for Ganache to fork
+    #[arg(long, global = true)]
+    pub fork: Option<String>,
+    /// Leave transactions pending until a block is mined explicitly
+    #[arg(long, global = true)]
+    pub no_auto_mine: bool,
+    /// Mnemonic for the Ganache accounts and the wallet
+    #[arg(long, global = true)]
+    pub mnemonic: Option<String>,
+    /// Derivation index of the signing account
+    #[arg(long, global = true)]
+    pub account_index: Option<u32>,
+    /// SQLite file recording the run (requires the `sqlite` feature)
+    #[arg(long, global = true)]
+    pub database: Option<PathBuf>,
+    /// URL notified when a deployment or transfer completes
+    #[arg(long, global = true)]
+    pub webhook: Option<String>,
+}
+
+impl ConnectionArgs {
+    /// The config file's settings (defaults without `--config`) with the flags applied on top.
+    pub fn resolve(&self) -> Result<Config> {
+        let mut config = match &self.config {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+        let ganache = &mut config.ganache;
+        ganache.port = self.port.or(ganache.port);
+        ganache.block_time = self.block_time.or(ganache.block_time);
+        ganache.fork = self.fork.clone().or(ganache.fork.take());
+        ganache.auto_mine &= !self.no_auto_mine;
+        let wallet = &mut config.wallet;
+        wallet.mnemonic = self.mnemonic.clone().or(wallet.mnemonic.take());
+        wallet.account_index = self.account_index.or(wallet.account_index);
+        config.database.path = self.database.clone().or(config.database.path.take());
+        config.notify.webhook = self.webhook.clone().or(config.notify.webhook.take());
+        if cfg!(not(feature = "sqlite")) {
+            if let Some(path) = &config.database.path {
+                eyre::bail!(
+                    "can't record runs in {}: built without the `sqlite` feature",
+                    path.display()
+                );
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_config(name: &str, contents: &str) -> Result<PathBuf> {
+        let path = std::env::temp_dir().join(format!("{name}-{}.toml", std::process::id()));
+        std::fs::write(&path, contents)?;
+        Ok(path)
+    }
+
+    #[test]
+    fn test_config_file_round_trip() -> Result<()> {
+        let config = Config {
+            ganache: GanacheConfig {
+                port: Some(8545),
+                block_time: None,
+                fork: Some("https://rpc.example.org".to_string()),
+                auto_mine: false,
+            },
+            wallet: WalletConfig {
+                mnemonic: Some(
+                    "brisk usual burst upper buddy female library dial rifle mercy globe nurse"
+                        .to_string(),
+                ),
+                account_index: None,
+            },
+            database: DatabaseConfig {
+                path: Some(PathBuf::from("runs.sqlite")),
+            },
+            notify: NotifyConfig {
+                webhook: Some("https://hooks.example.org/deploys".to_string()),
+                template: Some("{event} of {subject} {status}".to_string()),
+                min_value_eth: Some(2.5),
+            },
+        };
+        let path = temp_config("config-round-trip", &toml::to_string(&config)?)?;
+        let loaded = Config::from_file(&path);
+        std::fs::remove_file(&path)?;
+        assert_eq!(loaded?, config);
+
+        // Missing sections fall back to their defaults
+        let path = temp_config("config-partial", "[ganache]\nblock_time = 3\n")?;
+        let loaded = Config::from_file(&path);
+        std::fs::remove_file(&path)?;
+        let loaded = loaded?;
+        assert_eq!(loaded.ganache.block_time, Some(3));
+        assert_eq!(loaded.ganache.port, None);
+        assert!(loaded.ganache.auto_mine);
+        assert_eq!(loaded.wallet, WalletConfig::default());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_flags_override_file_values() -> Result<()> {
+        let path = temp_config(
+            "config-overrides",
+            "[ganache]\nport = 8545\nblock_time = 2\n\n[wallet]\naccount_index = 1\n",
+        )?;
+        let args = ConnectionArgs {
+            config: Some(path.clone()),
+            port: Some(9545),
+            account_index: Some(4),
+            ..Default::default()
+        };
+        let config = args.resolve();
+        std::fs::remove_file(&path)?;
+        let config = config?;
+
+        assert_eq!(config.ganache.port, Some(9545));
+        assert_eq!(config.ganache.block_time, Some(2));
+        assert_eq!(config.wallet.account_index, Some(4));
+        assert_eq!(config.wallet.mnemonic, None);
+
+        // Typos are reported rather than ignored
+        let path = temp_config("config-typo", "[ganache]\nprot = 8545\n")?;
+        let err = Config::from_file(&path).unwrap_err();
+        std::fs::remove_file(&path)?;
+        assert!(format!("{err:?}").contains("prot"), "{err:?}");
+
+        Ok(())
+    }
+}
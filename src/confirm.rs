@@ -0,0 +1,226 @@
+//! Confirmation gate shown before a deployment or a contract call is sent.
+
+use crate::amount::format_eth;
+use crate::calldata::format_token;
+use crate::chain_context::ChainContext;
+use crate::deploy::{deploy_contract, DeployOptions, DeploymentResult, SignerDeployedContract};
+use crate::dry_run::dry_run_deploy;
+use crate::errors::Error;
+use ethers::{
+    abi::{Abi, Token, Tokenize},
+    prelude::{Address, Http, LocalWallet, Middleware, Provider, Signer, U256},
+    types::Bytes,
+};
+use eyre::{ContextCompat, Result};
+use std::{
+    fmt,
+    io::{BufRead, IsTerminal, Write},
+};
+
+/// Everything a deployment is about to do, shown before asking for confirmation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TxPreview {
+    pub chain_id: u64,
+    pub endpoint: String,
+    pub sender: Address,
+    pub contract: String,
+    /// The function called on an existing contract, or `None` for a deployment.
+    pub function: Option<String>,
+    /// Arguments of the constructor, or of `function`.
+    pub constructor_args: Vec<Token>,
+    pub gas_estimate: U256,
+    /// Maximum cost of the deployment, in wei.
+    pub cost: U256,
+}
+
+impl fmt::Display for TxPreview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let args: Vec<String> = self.constructor_args.iter().map(format_token).collect();
+        match &self.function {
+            Some(function) => {
+                writeln!(f, "Call {}.{function}({})", self.contract, args.join(", "))?
+            }
+            None => writeln!(f, "Deploy {}({})", self.contract, args.join(", "))?,
+        }
+        writeln!(f, "  chain id:  {}", self.chain_id)?;
+        writeln!(f, "  endpoint:  {}", self.endpoint)?;
+        writeln!(f, "  sender:    {:?}", self.sender)?;
+        writeln!(f, "  gas:       {}", self.gas_estimate)?;
+        write!(f, "  max cost:  {}", format_eth(self.cost))
+    }
+}
+
+/// Decides whether a previewed transaction may be sent.
+pub trait Confirmer {
+    /// Returns `true` to send the transaction described by `preview`.
+    fn confirm(&mut self, preview: &TxPreview) -> Result<bool>;
+}
+
+/// Prints the preview on stderr and sends only after `yes` is typed on stdin.
+///
+/// Fails with [`Error::ConfirmationNeedsTerminal`] when stdin isn't a terminal, instead of
+/// waiting for an answer that will never come.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StdinConfirmer;
+
+impl Confirmer for StdinConfirmer {
+    fn confirm(&mut self, preview: &TxPreview) -> Result<bool> {
+        let stdin = std::io::stdin();
+        if !stdin.is_terminal() {
+            return Err(Error::ConfirmationNeedsTerminal.into());
+        }
+        eprint!("{preview}\nType `yes` to send: ");
+        std::io::stderr().flush()?;
+        let mut answer = String::new();
+        stdin.lock().read_line(&mut answer)?;
+        Ok(answer.trim() == "yes")
+    }
+}
+
+/// Gives the same answer to every preview, for `--yes` and for tests.
+#[derive(Clone, Copy, Debug)]
+pub struct AutoConfirmer(pub bool);
+
+impl Confirmer for AutoConfirmer {
+    fn confirm(&mut self, _preview: &TxPreview) -> Result<bool> {
+        Ok(self.0)
+    }
+}
+
+/// Dry-runs the deployment of `name`, asks `confirmer` about its preview and, only if it agrees,
+/// deploys it like [`deploy_contract`]. A refusal fails with [`Error::ConfirmationDeclined`]
+/// before anything is sent.
+#[allow(clippy::too_many_arguments)]
+pub async fn confirmed_deploy<A: Tokenize>(
+    ctx: &ChainContext<Provider<Http>>,
+    wallet: LocalWallet,
+    name: &str,
+    abi: Abi,
+    bytecode: Bytes,
+    constructor_args: A,
+    options: &DeployOptions,
+    confirmer: &mut dyn Confirmer,
+) -> Result<(
+    SignerDeployedContract<ChainContext<Provider<Http>>>,
+    DeploymentResult,
+)> {
+    let tokens = constructor_args.into_tokens();
+    let sender = wallet.address();
+    let dry_run = dry_run_deploy(
+        ctx.inner(),
+        sender,
+        &abi,
+        bytecode.clone(),
+        tokens.clone(),
+        options,
+    )
+    .await?;
+    let preview = TxPreview {
+        chain_id: options.timeouts.rpc("chain id", ctx.chain_id()).await?,
+        endpoint: ctx.inner().as_ref().url().to_string(),
+        sender,
+        contract: name.to_string(),
+        function: None,
+        constructor_args: tokens.clone(),
+        gas_estimate: dry_run
+            .gas_estimate
+            .context("The deployment reverts in simulation")?,
+        cost: dry_run.estimated_cost.unwrap_or_default(),
+    };
+
+    if !confirmer.confirm(&preview)? {
+        return Err(Error::ConfirmationDeclined {
+            contract: name.to_string(),
+        }
+        .into());
+    }
+    deploy_contract(ctx, wallet, name, abi, bytecode, tokens, options).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::{compile, contract_parts};
+    use ethers::utils::Ganache;
+
+    /// Answers like a user would, remembering what it was shown.
+    struct Scripted {
+        answer: bool,
+        shown: Vec<TxPreview>,
+    }
+
+    impl Confirmer for Scripted {
+        fn confirm(&mut self, preview: &TxPreview) -> Result<bool> {
+            self.shown.push(preview.clone());
+            Ok(self.answer)
+        }
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_declined_deployment_sends_nothing() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let sender = wallet.address();
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "Migrations")?;
+        let options = DeployOptions::default();
+
+        let mut confirmer = Scripted {
+            answer: false,
+            shown: Vec::new(),
+        };
+        let err = confirmed_deploy(
+            &ctx,
+            wallet.clone(),
+            "Migrations",
+            abi.clone(),
+            bytecode.clone(),
+            (),
+            &options,
+            &mut confirmer,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ConfirmationDeclined { contract }) if contract == "Migrations"
+        ));
+        assert_eq!(
+            provider.get_transaction_count(sender, None).await?,
+            U256::zero()
+        );
+
+        // The preview described the deployment that would have been sent
+        let preview = &confirmer.shown[0];
+        assert_eq!(preview.chain_id, 1337);
+        assert_eq!(preview.sender, sender);
+        assert_eq!(preview.endpoint, ganache.endpoint() + "/");
+        assert!(preview.gas_estimate > U256::zero());
+        assert!(preview.to_string().contains("Deploy Migrations()"));
+
+        // Agreeing sends it
+        let (_, result) = confirmed_deploy(
+            &ctx,
+            wallet,
+            "Migrations",
+            abi,
+            bytecode,
+            (),
+            &options,
+            &mut AutoConfirmer(true),
+        )
+        .await?;
+        assert!(!provider.get_code(result.address, None).await?.is_empty());
+        assert_eq!(
+            provider.get_transaction_count(sender, None).await?,
+            U256::one()
+        );
+
+        Ok(())
+    }
+}
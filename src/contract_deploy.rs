@@ -1,183 +1,526 @@
-use ethers::{
-    contract::{Contract, ContractFactory}, // Import for interacting with and deploying Ethereum smart contracts
-    middleware::SignerMiddleware,          // Middleware to sign transactions using a wallet
-    prelude::{LocalWallet, Middleware, Provider, Signer, U256}, // Types for wallet, Ethereum provider, and other utilities
-    types::BlockNumber, // Used for referencing Ethereum block numbers
-    utils::Ganache,     // Utility to spin up a local Ethereum testnet (Ganache)
-};
-
-use ethers_solc::{Artifact, Project, ProjectPathsConfig}; // Import for Solidity project and artifact management
-use ethers_solc::{ConfigurableArtifacts, ProjectCompileOutput}; // Solidity compilation outputs and configuration
-use eyre::{eyre, ContextCompat, Ok, Result}; // For error handling and contextual errors
-use hex::ToHex; // Utility to convert addresses and other data to hexadecimal
-use std::{
-    path::{Path, PathBuf}, // Used for file system path management
-    time::Duration,        // Duration utility used to set intervals
+use clap::{Parser, Subcommand}; // Command line argument parsing
+use ethers::prelude::{Address, BlockNumber, Middleware, Provider, Signer}; // Types for Ethereum provider, and other utilities
+
+#[cfg(feature = "sqlite")]
+use eyre::ContextCompat; // Reading the history requires a database
+use eyre::Result; // For error handling and contextual errors
+use futures::StreamExt; // Reading the block stream
+#[cfg(feature = "sqlite")]
+use rust_eth_yt::sqlite::RunHistory; // Recent rows of the run database
+use rust_eth_yt::{
+    abi_diff::{diff_abi, load_abi}, // ABI comparison between two versions
+    accounts::{account_infos, accounts_table}, // Startup table of the Ganache accounts
+    chain_context::ChainContext,    // Cached chain id, latest block and fees
+    config::ConnectionArgs,         // Config file and connection flags
+    confirm::{confirmed_deploy, AutoConfirmer, Confirmer, StdinConfirmer}, // Preview and confirmation before sending
+    contract_repl::ContractSession, // Interactive session on a deployed contract
+    create2::create2_init_code,     // Bytecode followed by the constructor arguments
+    deploy::{deploy_contract, DeployEvent, DeployOptions}, // Deploys a contract through a signing client
+    dry_run::dry_run_deploy, // Estimates and simulates without sending
+    explorer::{inspect_block, KnownContracts}, // Block and transaction report
+    flatten::write_flattened, // Single-file source for manual verification
+    logging::{init_tracing, LogFormat}, // Subscriber setup for the binary
+    mnemonic::validate_mnemonic, // Mnemonic validation
+    monitor::watch_blocks,   // Stream of mined blocks
+    output::render,          // Human or JSON rendering of the final result
+    project::{compile, contract_names, contract_parts, print_project, ContractList}, // Solidity compilation and artifact lookup
+    repl::{run, ReplSession}, // Interactive command session
+    report::{abi_to_markdown, detect_selector_collisions, project_report}, // ABI reference and selector checks of a compiled folder
+    safe::{SafeTx, SafeTxArgs}, // Safe transaction export instead of sending
+    state::{dump_state, ChainState}, // Balances, nonces and code of the accounts
+    timeouts::OpTimeouts,       // Upper bounds for network awaits
+    traffic::{generate, TrafficOptions}, // Load generation between Ganache accounts
+    wallet::{load_ganache_wallet, parse_private_key, PRIVATE_KEY_VAR}, // PRIVATE_KEY or a Ganache key
 };
+use std::{path::PathBuf, time::Duration}; // Paths of ABI files and the traffic and monitor durations
+use tokio::io::BufReader; // Line reading for the REPL
+use tokio::sync::mpsc::unbounded_channel; // Progress events from the deployment
+use tracing::info; // Structured progress output
+
+/// Compiles the example contracts and deploys `BUSDImplementation` to a local Ganache chain.
+#[derive(Parser)]
+struct Cli {
+    /// Format of the log output (filter it with `RUST_LOG`)
+    #[arg(long, value_enum, default_value_t = LogFormat::Pretty)]
+    log_format: LogFormat,
+    /// Print the result as a single JSON object on stdout
+    #[arg(long, global = true)]
+    json: bool,
+    /// Also write the deployed contract's flattened source next to it, as `<Name>.flat.sol`
+    #[arg(long)]
+    flatten: bool,
+    /// Estimate and simulate the deployment, printing what would be sent, without sending it
+    #[arg(long)]
+    dry_run: bool,
+    /// Print a line on stderr at each deployment stage
+    #[arg(long)]
+    progress: bool,
+    /// Show a preview of the deployment and send it only after typing `yes`
+    #[arg(long)]
+    confirm: bool,
+    /// Answer `yes` to the --confirm prompt, for scripts
+    #[arg(long, requires = "confirm")]
+    yes: bool,
+    /// Refuse to deploy unless the node serves this chain id
+    #[arg(long)]
+    expected_chain: Option<u64>,
+    /// Rows of the account table printed at startup (every Ganache account by default)
+    #[arg(long)]
+    limit: Option<usize>,
+    #[command(flatten)]
+    connection: ConnectionArgs,
+    #[command(flatten)]
+    safe: SafeTxArgs,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-// Type alias for a contract deployed using a wallet and signing middleware
-pub type SignerDeployedContract<T> = Contract<SignerMiddleware<Provider<T>, LocalWallet>>;
+#[derive(Subcommand)]
+enum Command {
+    /// Compile the examples and deploy `BUSDImplementation` (the default)
+    Deploy,
+    /// Read `balance`, `transfer` and `deploy` commands from stdin against a persistent Ganache session,
+    /// or `ls`, `call`, `send` and `events` against the contract at --address
+    Repl {
+        /// Deployed contract to explore
+        #[arg(long, requires = "abi")]
+        address: Option<Address>,
+        /// ABI of the contract: a plain ABI array or an artifact with an `abi` field
+        #[arg(long)]
+        abi: Option<PathBuf>,
+        /// Node the contract lives on, sending with PRIVATE_KEY; the spawned Ganache by default
+        #[arg(long, requires = "address")]
+        endpoint: Option<String>,
+        /// Send transactions without showing their preview first
+        #[arg(long)]
+        no_confirm: bool,
+    },
+    /// Compile a folder and list the contracts it contains
+    List {
+        /// Folder containing the Solidity sources
+        #[arg(default_value = "examples/")]
+        dir: String,
+        /// Also write the ABI reference of every contract as Markdown to this file
+        #[arg(long)]
+        docs: Option<PathBuf>,
+    },
+    /// Compare two ABI files (plain ABI arrays or artifacts with an `abi` field)
+    AbiDiff {
+        /// ABI of the current version
+        #[arg(long)]
+        old: PathBuf,
+        /// ABI of the upgraded version
+        #[arg(long)]
+        new: PathBuf,
+    },
+    /// Print one line per mined block until ctrl-c
+    Monitor {
+        /// Node to watch (a `ws://` endpoint subscribes, others are polled); the spawned Ganache by default
+        #[arg(long)]
+        endpoint: Option<String>,
+        /// Mark the blocks containing transactions sent by this address
+        #[arg(long)]
+        watch: Option<Address>,
+    },
+    /// Print a block's transactions with their status and gas, like a block explorer
+    Inspect {
+        /// Node to read the block from
+        #[arg(long)]
+        endpoint: String,
+        /// Block number (the latest by default)
+        #[arg(long)]
+        block: Option<u64>,
+    },
+    /// Send small transfers round-robin between Ganache accounts and summarize the throughput
+    Traffic {
+        /// Transfers per second
+        #[arg(long, default_value_t = 20.0)]
+        rate: f64,
+        /// How long to send for, in seconds
+        #[arg(long, default_value_t = 10)]
+        duration: u64,
+        /// Number of Ganache accounts taking part
+        #[arg(long, default_value_t = 5)]
+        accounts: usize,
+        /// Seed of the recipient and amount sequence, to replay a run
+        #[arg(long)]
+        seed: Option<u64>,
+    },
+    /// Print the balance, nonce and code presence of every Ganache account
+    State {
+        /// More addresses to include after the Ganache accounts
+        #[arg(long = "address")]
+        addresses: Vec<Address>,
+    },
+    /// Print the latest deployments and transactions recorded in the --database file
+    #[cfg(feature = "sqlite")]
+    History {
+        /// Chain whose rows are shown
+        #[arg(long, default_value_t = 1337)]
+        chain_id: u64,
+        /// Rows shown of each kind
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Define a mnemonic (12-word seed) to generate private keys for the wallet
-    let mnemonic = "brisk usual burst upper buddy female library dial rifle mercy globe nurse";
+    let cli = Cli::parse(); // Parse the command line arguments
+    init_tracing(cli.log_format); // Install the log subscriber before anything is reported
+
+    // Listing only needs the compiler, not a chain
+    if let Some(Command::List { dir, docs }) = &cli.command {
+        let output = compile(dir).await?.output; // Compiled once for the names, the collisions and the docs
+        let contracts = contract_names(&output);
+        let report = project_report(&output);
+        let collisions = detect_selector_collisions(&report);
+        if !collisions.is_empty() {
+            // On stderr, so `--json` output stays parseable
+            eprintln!(
+                "warning: {} function selector collision(s):",
+                collisions.len()
+            );
+            for collision in &collisions {
+                eprintln!("  {collision}");
+            }
+        }
+        if let Some(path) = docs {
+            std::fs::write(path, abi_to_markdown(&report))?;
+            info!(path = %path.display(), "Wrote the ABI reference");
+        }
+        println!("{}", render(&ContractList { contracts }, cli.json)?);
+        return Ok(());
+    }
+
+    // Neither does comparing two ABI files
+    if let Some(Command::AbiDiff { old, new }) = &cli.command {
+        let diff = diff_abi(&load_abi(old)?, &load_abi(new)?);
+        println!("{}", render(&diff, cli.json)?);
+        return Ok(());
+    }
+
+    // Inspecting a block only reads from the given node
+    if let Some(Command::Inspect { endpoint, block }) = &cli.command {
+        let provider = Provider::try_from(endpoint.as_str())?;
+        let block = block.map_or(BlockNumber::Latest, BlockNumber::from);
+        let report = inspect_block(&provider, block, &KnownContracts::new()).await?;
+        println!("{}", render(&report, cli.json)?);
+        return Ok(());
+    }
+
+    // Neither does exploring a contract on an existing node
+    if let Some(Command::Repl {
+        address: Some(address),
+        abi: Some(abi),
+        endpoint: Some(endpoint),
+        no_confirm,
+    }) = &cli.command
+    {
+        let provider = Provider::try_from(endpoint.as_str())?;
+        let wallet = std::env::var(PRIVATE_KEY_VAR)
+            .ok()
+            .map(|key| parse_private_key(&key))
+            .transpose()?; // Read-only without a key
+        let mut session = ContractSession::new(
+            ChainContext::new(provider),
+            &contract_name(abi),
+            *address,
+            load_abi(abi)?,
+            wallet,
+        )
+        .confirm(!no_confirm);
+        return run(
+            &mut session,
+            BufReader::new(tokio::io::stdin()),
+            &mut std::io::stdout(),
+        )
+        .await;
+    }
+
+    // Watching an existing node doesn't need a Ganache of our own
+    if let Some(Command::Monitor {
+        endpoint: Some(endpoint),
+        watch,
+    }) = &cli.command
+    {
+        return monitor(endpoint, *watch, cli.json).await;
+    }
+
+    // Load the config file, if any, with the command line flags taking precedence
+    let config = cli.connection.resolve()?;
+
+    // The history only reads the run database
+    #[cfg(feature = "sqlite")]
+    if let Some(Command::History { chain_id, limit }) = &cli.command {
+        let ledger = config
+            .database
+            .open()?
+            .context("history needs --database or a [database] path in the config file")?;
+        let mut deployments = ledger.query_deployments(*chain_id)?;
+        deployments.truncate(*limit);
+        let history = RunHistory {
+            deployments,
+            transactions: ledger.query_transactions(*chain_id, *limit)?,
+        };
+        println!("{}", render(&history, cli.json)?);
+        return Ok(());
+    }
+
+    // Define a mnemonic (12-word seed) to generate private keys for the wallet, failing fast if it is invalid
+    let mnemonic =
+        validate_mnemonic(config.wallet.mnemonic.as_deref().unwrap_or(
+            "brisk usual burst upper buddy female library dial rifle mercy globe nurse",
+        ))?;
 
-    // Launch a local Ganache Ethereum testnet instance using the mnemonic
-    let ganache = Ganache::new().mnemonic(mnemonic).spawn();
-    println!("HTTP Endpoint: {}", ganache.endpoint()); // Print the Ganache instance's HTTP endpoint
+    // Launch a local Ganache Ethereum testnet instance using the mnemonic and the configured options
+    let ganache = config.ganache.spawn(&mnemonic);
+    info!(endpoint = %ganache.endpoint(), "ganache started"); // Report the Ganache instance's HTTP endpoint
 
-    // Generate a local wallet using the first private key derived from the mnemonic
-    let wallet: LocalWallet = ganache.keys()[0].clone().into();
+    // Load the wallet from PRIVATE_KEY if set, otherwise select the configured Ganache account (the first by default)
+    let account_index = config.wallet.account_index.unwrap_or(0) as usize;
+    let wallet = load_ganache_wallet(&ganache, account_index)?; // Fails past Ganache's last account
     let first_address = wallet.address(); // Get the wallet's address (derived from the private key)
-    println!(
-        "wallet first address: {}",
-        first_address.encode_hex::<String>() // Convert the address to hexadecimal and print it
-    );
+    info!(address = ?first_address, "wallet first address");
 
     // Create a provider to interact with the Ethereum network (Ganache in this case)
-    let provider = Provider::try_from(ganache.endpoint())?.interval(Duration::from_millis(10)); // Set polling interval
-    let chain_id = provider.get_chainid().await?; // Get the chain ID for the Ethereum network
-    println!("Ganache started with chain id {}", chain_id); // Print the chain ID
+    let provider = Provider::try_from(ganache.endpoint())?;
+    config.ganache.apply_mining(&provider).await?; // Stop the miner when auto-mining is disabled
+    let accounts = account_infos(&provider, ganache.keys(), cli.limit).await?;
+    eprintln!("{}", accounts_table(&accounts)); // Table of every account on stderr, so stdout keeps only the result
+    let timeouts = OpTimeouts::default(); // Bound every network await so a dead node can't hang the run
+    let context = ChainContext::new(provider.clone()); // Caches the chain id, latest block and fees for the deployment helpers
+    let chain_id = timeouts.rpc("chain id", context.chain_id()).await?; // Get the chain ID for the Ethereum network
+    info!(%chain_id, "connected"); // Report the chain ID
+
+    // Without an endpoint, watch the Ganache started above
+    if let Some(Command::Monitor { watch, .. }) = cli.command {
+        return monitor(&ganache.ws_endpoint(), watch, cli.json).await;
+    }
+
+    // Traffic runs between the Ganache accounts instead of deploying anything
+    if let Some(Command::Traffic {
+        rate,
+        duration,
+        accounts,
+        seed,
+    }) = cli.command
+    {
+        let wallets = ganache
+            .keys()
+            .iter()
+            .take(accounts)
+            .map(|key| key.clone().into())
+            .collect();
+        let options = TrafficOptions {
+            seed,
+            ..Default::default()
+        };
+        let summary = generate(
+            &context,
+            wallets,
+            rate,
+            Duration::from_secs(duration),
+            &options,
+        )
+        .await?;
+        println!("{}", render(&summary, cli.json)?);
+        return Ok(());
+    }
+
+    // Dump the accounts of the Ganache started above, plus any extra addresses
+    if let Some(Command::State { addresses }) = &cli.command {
+        let mut all = ganache.addresses().to_vec();
+        all.extend(addresses);
+        let accounts = timeouts
+            .rpc("state dump", dump_state(&provider, &all))
+            .await?;
+        println!("{}", render(&ChainState { accounts }, cli.json)?);
+        return Ok(());
+    }
+
+    // In REPL mode the same Ganache, provider and wallet serve every command until `exit`
+    if let Some(Command::Repl {
+        address,
+        abi,
+        no_confirm,
+        ..
+    }) = cli.command
+    {
+        let input = BufReader::new(tokio::io::stdin());
+        return match (address, abi) {
+            (Some(address), Some(abi)) => {
+                let mut session = ContractSession::new(
+                    context,
+                    &contract_name(&abi),
+                    address,
+                    load_abi(&abi)?,
+                    Some(wallet),
+                )
+                .confirm(!no_confirm);
+                run(&mut session, input, &mut std::io::stdout()).await
+            }
+            _ => {
+                let mut session = ReplSession::new(provider, wallet, "examples/");
+                run(&mut session, input, &mut std::io::stdout()).await
+            }
+        };
+    }
 
     // Define the folder containing Solidity contract files
     let contracts_folder = "examples/";
 
-    // Compile the Solidity contracts located in the folder
-    let project = compile(contracts_folder).await?;
-
-    // Print the details of the compiled project, including ABI and functions
-    print_project(project.clone()).await?;
+    // With --progress, a task prints each deployment stage as it is reached
+    let mut options = DeployOptions {
+        expected_chain: cli.expected_chain, // Checked against the node before sending
+        notifier: config.notify.notifier()?, // Posts the deployment to the configured webhook
+        #[cfg(feature = "sqlite")]
+        ledger: config.database.open()?, // Records the deployment when a database is configured
+        ..Default::default()
+    };
+    let renderer = cli.progress.then(|| {
+        let (progress, mut events) = unbounded_channel::<DeployEvent>();
+        options.progress = Some(progress);
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                eprintln!("[deploy] {event}");
+            }
+        })
+    });
+    // Report a failed stage before returning its error
+    let failed = |err: eyre::Report| {
+        options.emit(DeployEvent::Failed {
+            error: format!("{err:#}"),
+        });
+        err
+    };
+
+    // Compile the Solidity contracts located in the folder (warnings are reported as they are collected)
+    options.emit(DeployEvent::CompileStarted);
+    let report = compile(contracts_folder).await.map_err(failed)?;
+    options.emit(DeployEvent::CompileFinished {
+        warnings: report.warnings.len(),
+    });
+    let project = report.output;
+
+    // Report the details of the compiled project, including ABI and functions
+    print_project(&project).await?;
 
     // Get the wallet's balance from the Ganache provider
-    let balance = provider.get_balance(wallet.address(), None).await?;
-    println!(
-        "Wallet first address {} balance: {}",
-        wallet.address().encode_hex::<String>(), // Encode the address to hexadecimal for printing
-        balance
-    );
+    let balance = timeouts
+        .rpc("balance", provider.get_balance(first_address, None))
+        .await?;
+    info!(address = ?first_address, %balance, "wallet balance");
 
     // Contract deployment begins here
     let contract_name = "BUSDImplementation"; // The name of the contract to deploy
-    let extension = ".sol"; // File extension for Solidity contracts
-    let binding = contracts_folder.to_string() + contract_name + extension; // Construct the full contract path
-    let contract_path = binding.as_str(); // Convert to string reference
-    let contract_relative_path = Path::new(contract_path); // Create a path object for the contract
-
-    // Convert the relative contract path to an absolute path on the filesystem
-    let contract_absolute_path = std::fs::canonicalize(contract_relative_path)?;
-
-    // Convert the absolute path to a string for further use (required for the `find` function)
-    let contract_absolute_str = contract_absolute_path
-        .to_str()
-        .context("Failed to convert path to string")?; // Error handling for failed conversion
-
-    // Locate the compiled contract using the project object
-    let contract = project
-        .find(contract_absolute_str, contract_name) // Find the contract by its name and path
-        .context("Contract not found")? // Handle the case where the contract is not found
-        .clone(); // Clone the contract (ownership handling)
-
-    // Extract ABI (Application Binary Interface) and bytecode from the compiled contract
-    let (abi, bytecode, _) = contract.into_parts();
-    let abi = abi.context("Missing abi from contract")?; // Ensure that ABI is available
-    let bytecode = bytecode.context("Missing bytecode from contract")?; // Ensure that bytecode is available
-
-    // Rebuild the wallet with the correct chain ID (required to sign transactions on the correct chain)
-    let wallet = wallet.with_chain_id(chain_id.as_u64());
-    // Create a client to interact with the blockchain (includes the signing wallet)
-    let client = SignerMiddleware::new(provider.clone(), wallet).into();
-
-    // Create a factory for deploying the contract using the ABI and bytecode
-    let factory = ContractFactory::new(abi.clone(), bytecode, client);
-
-    // Initialize the deployment process (passing constructor arguments if any, here it is empty `()`)
-    let mut deployer = factory.deploy(())?;
-
-    // Get the latest block information to determine gas pricing
-    let block = provider
-        .clone()
-        .get_block(BlockNumber::Latest)
-        .await?
-        .context("Failed to get block");
-
-    // Get the base fee for the next block and set the gas price for the transaction
-    let gas_price = block?
-        .next_block_base_fee()
-        .context("Failed to get the base fee for the next block")?;
-    deployer.tx.set_gas_price::<U256>(gas_price); // Set gas price for the transaction
-
-    // Send the transaction to deploy the contract and await its completion
-    let contract = deployer.clone().legacy().send().await?;
-    println!(
-        "BUSDImpl contract address {}",
-        contract.address().encode_hex::<String>() // Print the deployed contract's address
-    );
 
-    Ok(()) // Indicate that the process completed successfully
-}
+    // Locate the compiled contract and extract its ABI and bytecode
+    let (abi, bytecode) =
+        contract_parts(&project, contracts_folder, contract_name).map_err(failed)?;
+    options.emit(DeployEvent::ArtifactResolved {
+        name: contract_name.to_string(),
+    });
+
+    // Write the single-file source some explorers require for verification, if asked
+    if cli.flatten {
+        write_flattened(
+            contracts_folder,
+            &format!("{contracts_folder}{contract_name}.sol"),
+        )?;
+    }
 
-// Function to compile a Solidity project from the given root folder path
-pub async fn compile(root: &str) -> Result<ProjectCompileOutput<ConfigurableArtifacts>> {
-    let root = PathBuf::from(root); // Convert the root folder path to a PathBuf object
-    if !root.exists() {
-        return Err(eyre!("Project root {root:?} does not exist!")); // Error handling for non-existent project root
+    // In dry-run mode, report what would be sent and stop before anything reaches the chain
+    if cli.dry_run {
+        let report = dry_run_deploy(&provider, first_address, &abi, bytecode, (), &options).await?;
+        println!("{}", render(&report, cli.json)?);
+        return Ok(());
     }
 
-    // Define the paths to be used for the Solidity project
-    let paths = ProjectPathsConfig::builder()
-        .root(&root)
-        .sources(&root)
-        .build()?; // Build the project path configuration
-
-    // Build the project object, enabling auto-detection of the Solidity compiler
-    let project = Project::builder()
-        .paths(paths)
-        .set_auto_detect(true) // Automatically detect Solidity compiler
-        .no_artifacts() // Avoid writing artifacts to disk
-        .build()?;
-
-    // Compile the Solidity project
-    let output = project.compile()?;
-
-    // Check if there were any compiler errors
-    if output.has_compiler_errors() {
-        Err(eyre!(
-            "Compiling solidity project failed: {:?}",
-            output.output().errors // Print compilation errors
-        ))
+    // With --as-safe-tx, write the deployment for the Safe's owners to sign instead of sending it
+    // (a Safe can't CREATE itself, so it calls the deterministic deployment proxy)
+    if let Some(safe) = cli.safe.as_safe_tx {
+        let init_code = create2_init_code(&abi, &bytecode, ())?;
+        let (tx, address) = SafeTx::create(&init_code, [0; 32])?;
+        let export = cli.safe.write(&provider, safe, tx, Some(address)).await?;
+        info!(path = %cli.safe.safe_tx_out.display(), "safe transaction written");
+        println!("{}", render(&export, cli.json)?);
+        return Ok(());
+    }
+
+    // Send the transaction to deploy the contract (no constructor arguments) and await its completion
+    let deployed = if cli.confirm {
+        // Preview the deployment and wait for the go-ahead (or take it from --yes)
+        let mut confirmer: Box<dyn Confirmer> = if cli.yes {
+            Box::new(AutoConfirmer(true))
+        } else {
+            Box::new(StdinConfirmer)
+        };
+        confirmed_deploy(
+            &context,
+            wallet,
+            contract_name,
+            abi,
+            bytecode,
+            (),
+            &options,
+            confirmer.as_mut(),
+        )
+        .await
+        .map(|(_, result)| result) // Only the metadata is reported
     } else {
-        Ok(output.clone()) // Return the compiled output if successful
+        deploy_contract(&context, wallet, contract_name, abi, bytecode, (), &options)
+            .await
+            .map(|(_, result)| result)
+    };
+
+    // Close the progress channel and let the renderer print the last stage, failed or not
+    drop(options);
+    if let Some(renderer) = renderer {
+        renderer.await?;
     }
+    let result = deployed?;
+
+    // Print the deployment metadata as the command's only stdout output
+    println!("{}", render(&result, cli.json)?);
+
+    Ok(()) // Indicate that the process completed successfully
 }
 
-// Function to print the details of the compiled contracts, including ABI and functions
-pub async fn print_project(project: ProjectCompileOutput<ConfigurableArtifacts>) -> Result<()> {
-    let artifacts = project.into_artifacts(); // Extract the compiled artifacts (contracts)
-    for (id, artifact) in artifacts {
-        let name = id.name; // Get the contract's name
-        let abi = artifact.abi.context("No ABI found for artifact {name}")?; // Get the ABI and ensure it exists
-
-        println!("{}", "=".repeat(80)); // Print a separator
-        println!("CONTRACT: {:?}", name); // Print the contract name
-
-        let contract = &abi.abi;
-        let functions = contract.functions(); // Get the list of functions from the contract
-        let functions = functions.cloned(); // Clone the function list for iteration
-        let constructor = contract.constructor(); // Get the constructor if available
-
-        // If the contract has a constructor, print its arguments
-        if let Some(constructor) = constructor {
-            let args = &constructor.inputs;
-            println!("CONSTRUCTOR args: {:?}", args); // Print the constructor arguments
-        }
+// Name of the contract an ABI file describes, from the file name (`Token.abi.json` is `Token`)
+fn contract_name(abi: &std::path::Path) -> String {
+    let file = abi.file_name().unwrap_or_default().to_string_lossy();
+    file.split('.').next().unwrap_or_default().to_string()
+}
 
-        // Print each function's name and parameters
-        for func in functions {
-            let name = &func.name; // Get the function name
-            let params = &func.inputs; // Get the function parameters
-            println!("FUNCTION {name} {params:?}"); // Print function details
+// Prints every block mined on `endpoint` until ctrl-c or until the node goes away
+async fn monitor(endpoint: &str, watch: Option<Address>, json: bool) -> Result<()> {
+    let mut blocks = watch_blocks(endpoint, Duration::from_millis(500), None).await?;
+    info!(endpoint, "watching blocks, press ctrl-c to stop");
+    loop {
+        let block = tokio::select! {
+            block = blocks.next() => block,
+            _ = tokio::signal::ctrl_c() => return Ok(()), // A clean stop, not an error
+        };
+        let Some(block) = block else { return Ok(()) };
+        let block = block?; // A lost node ends the command with the last block it reported
+        let watched = watch.is_some_and(|address| block.has_sender(address));
+        if json {
+            println!("{}", serde_json::to_string(&block)?); // One JSON object per line
+        } else {
+            let base_fee = block
+                .base_fee
+                .map_or("-".to_string(), |fee| fee.to_string());
+            println!(
+                "{}block {} {:?} time={} txs={} gas_used={} base_fee={}",
+                if watched { "* " } else { "  " },
+                block.number,
+                block.hash,
+                block.timestamp,
+                block.tx_count,
+                block.gas_used,
+                base_fee
+            );
         }
     }
-    Ok(())
 }
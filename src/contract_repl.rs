@@ -0,0 +1,480 @@
+//! Interactive session against one deployed contract, used by `repl --address`.
+
+use crate::amount::format_eth;
+use crate::calldata::{encode_args, format_token};
+use crate::chain_context::ChainContext;
+use crate::confirm::TxPreview;
+use crate::events::decode_log;
+use crate::polling::PendingTxExt;
+use crate::receipt::ReceiptSummary;
+use crate::repl::{parse_address, LineHandler, ReplOutcome};
+use crate::report::contract_report;
+use crate::timeouts::OpTimeouts;
+use crate::wallet::bind_to_chain;
+use async_trait::async_trait;
+use ethers::{
+    abi::{Abi, Function, StateMutability, Token},
+    prelude::{Address, Filter, Http, LocalWallet, Middleware, Provider, Signer, SignerMiddleware},
+    types::{transaction::eip2718::TypedTransaction, BlockNumber, TransactionRequest},
+};
+use eyre::{eyre, ContextCompat, Result, WrapErr};
+use std::path::Path;
+
+const HELP: &str = "\
+ls                          functions of the contract with their signatures
+call <fn> <args...>         call a view function
+send <fn> <args...>         send a transaction calling a function, after a preview
+events <name> [from..to]    decoded events, from every block by default
+balance [address]           ETH balance of an account, the contract's by default
+help                        this message
+exit                        leave the session";
+
+/// A parsed line of a [`ContractSession`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ContractCommand {
+    List,
+    Call {
+        function: String,
+        args: Vec<String>,
+    },
+    Send {
+        function: String,
+        args: Vec<String>,
+    },
+    Events {
+        name: String,
+        from: Option<u64>,
+        to: Option<u64>,
+    },
+    Balance(Option<Address>),
+    /// `yes` after a `send` preview.
+    Confirm,
+    Help,
+    Exit,
+    /// A blank line.
+    Nothing,
+}
+
+impl ContractCommand {
+    /// Parses one input line; unknown commands and malformed arguments are errors.
+    pub fn parse(line: &str) -> Result<Self> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let owned = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect();
+        let command = match words.as_slice() {
+            [] => Self::Nothing,
+            ["ls"] => Self::List,
+            ["call", function, args @ ..] => Self::Call {
+                function: function.to_string(),
+                args: owned(args),
+            },
+            ["send", function, args @ ..] => Self::Send {
+                function: function.to_string(),
+                args: owned(args),
+            },
+            ["events", name] => Self::Events {
+                name: name.to_string(),
+                from: None,
+                to: None,
+            },
+            ["events", name, range] => {
+                let (from, to) = parse_range(range)?;
+                Self::Events {
+                    name: name.to_string(),
+                    from,
+                    to,
+                }
+            }
+            ["balance"] => Self::Balance(None),
+            ["balance", address] => Self::Balance(Some(parse_address(address)?)),
+            ["yes"] => Self::Confirm,
+            ["help"] => Self::Help,
+            ["exit"] | ["quit"] => Self::Exit,
+            [command, ..] => {
+                return Err(eyre!(
+                    "Unknown or malformed command {command:?}, type `help` for the list"
+                ))
+            }
+        };
+        Ok(command)
+    }
+}
+
+// `from..to` with either end optional
+fn parse_range(range: &str) -> Result<(Option<u64>, Option<u64>)> {
+    let (from, to) = range
+        .split_once("..")
+        .with_context(|| format!("Invalid block range {range:?}, expected from..to"))?;
+    let block = |end: &str| -> Result<Option<u64>> {
+        if end.is_empty() {
+            return Ok(None);
+        }
+        end.parse()
+            .map(Some)
+            .wrap_err_with(|| format!("Invalid block number {end:?}"))
+    };
+    Ok((block(from)?, block(to)?))
+}
+
+/// A transaction previewed by `send`, waiting for `yes`.
+struct PendingSend {
+    function: String,
+    tx: TypedTransaction,
+}
+
+/// Calls, sends and event queries against the contract at `address`, described by `abi`.
+///
+/// `send` shows a [`TxPreview`] and only sends once the next line is `yes`; any other line
+/// drops it. Without a wallet the session is read-only.
+pub struct ContractSession {
+    ctx: ChainContext<Provider<Http>>,
+    name: String,
+    address: Address,
+    abi: Abi,
+    wallet: Option<LocalWallet>,
+    confirm: bool,
+    pending: Option<PendingSend>,
+    timeouts: OpTimeouts,
+}
+
+impl ContractSession {
+    /// Starts a session on the contract `name` at `address` through `ctx`, sending from `wallet`
+    /// if given.
+    pub fn new(
+        ctx: ChainContext<Provider<Http>>,
+        name: &str,
+        address: Address,
+        abi: Abi,
+        wallet: Option<LocalWallet>,
+    ) -> Self {
+        Self {
+            ctx,
+            name: name.to_string(),
+            address,
+            abi,
+            wallet,
+            confirm: true,
+            pending: None,
+            timeouts: OpTimeouts::default(),
+        }
+    }
+
+    /// Sends without a preview when `confirm` is false, as `--yes` asks.
+    pub fn confirm(mut self, confirm: bool) -> Self {
+        self.confirm = confirm;
+        self
+    }
+
+    // The function called `name` taking `args`, picking the overload by argument count
+    fn function(&self, name: &str, args: &[String]) -> Result<(&Function, Vec<Token>)> {
+        let overloads = self
+            .abi
+            .functions_by_name(name)
+            .map_err(|_| eyre!("{} has no function {name:?}", self.name))?;
+        let function = overloads
+            .iter()
+            .find(|function| function.inputs.len() == args.len())
+            .unwrap_or(&overloads[0]);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let tokens = encode_args(&function.inputs, &args)
+            .wrap_err_with(|| format!("{}: {}", name, function.signature()))?;
+        Ok((function, tokens))
+    }
+
+    fn list(&self) -> String {
+        let report = contract_report(&self.name, Path::new(""), &self.abi, true);
+        report
+            .functions
+            .iter()
+            .map(|function| {
+                let outputs: Vec<&str> = function
+                    .outputs
+                    .iter()
+                    .map(|output| output.kind.as_str())
+                    .collect();
+                format!(
+                    "{} [{}] 0x{} -> ({})",
+                    function.signature,
+                    function.state_mutability,
+                    function.selector,
+                    outputs.join(",")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    async fn call(&self, name: &str, args: &[String]) -> Result<String> {
+        let (function, tokens) = self.function(name, args)?;
+        let tx: TypedTransaction = TransactionRequest::new()
+            .to(self.address)
+            .data(function.encode_input(&tokens)?)
+            .into();
+        let output = self.timeouts.rpc("call", self.ctx.call(&tx, None)).await?;
+        let values = function.decode_output(&output)?;
+        Ok(values
+            .iter()
+            .map(format_token)
+            .collect::<Vec<_>>()
+            .join(", "))
+    }
+
+    async fn prepare_send(&mut self, name: &str, args: &[String]) -> Result<String> {
+        let from = self
+            .wallet
+            .as_ref()
+            .context("send needs a wallet; set PRIVATE_KEY")?
+            .address();
+        let (function, tokens) = self.function(name, args)?;
+        if matches!(
+            function.state_mutability,
+            StateMutability::View | StateMutability::Pure
+        ) {
+            return Err(eyre!("{name} is a view function, use `call`"));
+        }
+        let function_name = function.name.clone();
+        let mut tx: TypedTransaction = TransactionRequest::new()
+            .from(from)
+            .to(self.address)
+            .data(function.encode_input(&tokens)?)
+            .into();
+        // A revert shows up here, before anything is sent
+        let gas = self
+            .timeouts
+            .rpc("gas estimation", self.ctx.estimate_gas(&tx, None))
+            .await?;
+        tx.set_gas(gas);
+        let gas_price = self
+            .timeouts
+            .rpc("gas price", self.ctx.get_gas_price())
+            .await?;
+        let preview = TxPreview {
+            chain_id: self
+                .timeouts
+                .rpc("chain id", self.ctx.get_chainid())
+                .await?
+                .as_u64(),
+            endpoint: self.ctx.provider().as_ref().url().to_string(),
+            sender: from,
+            contract: self.name.clone(),
+            function: Some(function_name.clone()),
+            constructor_args: tokens,
+            gas_estimate: gas,
+            cost: gas * gas_price,
+        };
+        self.pending = Some(PendingSend {
+            function: function_name,
+            tx,
+        });
+        if !self.confirm {
+            return self.send_pending().await;
+        }
+        Ok(format!("{preview}\nType `yes` to send"))
+    }
+
+    async fn send_pending(&mut self) -> Result<String> {
+        let PendingSend { function, tx } = self.pending.take().context("Nothing to send")?;
+        let wallet = self.wallet.clone().context("send needs a wallet")?;
+        let wallet = self
+            .timeouts
+            .rpc("chain id", bind_to_chain(&self.ctx, wallet))
+            .await?;
+        let client = SignerMiddleware::new(self.ctx.clone(), wallet);
+        let pending = self
+            .timeouts
+            .rpc("submission", client.send_transaction(tx, None))
+            .await?;
+        let tx_hash = *pending;
+        let receipt = self
+            .timeouts
+            .receipt("receipt", pending.await_smart(&self.ctx))
+            .await?;
+        let summary = ReceiptSummary::from(&receipt);
+        if !summary.success {
+            return Err(eyre!("{function} reverted in {tx_hash:?}"));
+        }
+        Ok(format!(
+            "Sent {function} in {tx_hash:?}: block {}, gas used {}, {} logs",
+            summary.block_number, summary.gas_used, summary.logs_count
+        ))
+    }
+
+    async fn events(&self, name: &str, from: Option<u64>, to: Option<u64>) -> Result<String> {
+        let event = self
+            .abi
+            .event(name)
+            .map_err(|_| eyre!("{} has no event {name:?}", self.name))?;
+        let filter = Filter::new()
+            .address(self.address)
+            .topic0(event.signature())
+            .from_block(from.map_or(BlockNumber::Earliest, Into::into))
+            .to_block(to.map_or(BlockNumber::Latest, Into::into));
+        let logs = self
+            .timeouts
+            .rpc("logs", self.ctx.get_logs(&filter))
+            .await?;
+        let lines = logs
+            .into_iter()
+            .map(|log| {
+                let decoded = decode_log(event, log)?;
+                let args: Vec<String> = decoded
+                    .args
+                    .iter()
+                    .map(|(name, value)| format!("{name}={}", format_token(value)))
+                    .collect();
+                Ok(format!(
+                    "block {} {:?} {}({})",
+                    decoded.block,
+                    decoded.tx_hash,
+                    decoded.name,
+                    args.join(", ")
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        if lines.is_empty() {
+            return Ok(format!("No {name} events"));
+        }
+        Ok(lines.join("\n"))
+    }
+}
+
+#[async_trait]
+impl LineHandler for ContractSession {
+    async fn handle_line(&mut self, line: &str) -> Result<ReplOutcome> {
+        let command = ContractCommand::parse(line)?;
+        // Anything but `yes` drops a previewed transaction
+        let dropped = command != ContractCommand::Confirm && self.pending.take().is_some();
+        let output = match command {
+            ContractCommand::Nothing => String::new(),
+            ContractCommand::Help => HELP.to_string(),
+            ContractCommand::Exit => return Ok(ReplOutcome::Exit),
+            ContractCommand::List => self.list(),
+            ContractCommand::Call { function, args } => self.call(&function, &args).await?,
+            ContractCommand::Send { function, args } => self.prepare_send(&function, &args).await?,
+            ContractCommand::Confirm => self.send_pending().await?,
+            ContractCommand::Events { name, from, to } => self.events(&name, from, to).await?,
+            ContractCommand::Balance(address) => {
+                let address = address.unwrap_or(self.address);
+                let balance = self
+                    .timeouts
+                    .rpc("balance", self.ctx.get_balance(address, None))
+                    .await?;
+                format_eth(balance)
+            }
+        };
+        if dropped {
+            return Ok(ReplOutcome::Output(
+                format!("Transaction not sent\n{output}")
+                    .trim_end()
+                    .to_string(),
+            ));
+        }
+        Ok(ReplOutcome::Output(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deploy::{deploy_contract, DeployOptions};
+    use crate::project::{compile, contract_parts};
+    use crate::repl::run;
+    use ethers::utils::Ganache;
+
+    #[test]
+    fn test_parse_contract_commands() {
+        assert_eq!(
+            ContractCommand::parse(" call balanceOf 0xB794F5eA0ba39494cE839613fffBA74279579268")
+                .unwrap(),
+            ContractCommand::Call {
+                function: "balanceOf".to_string(),
+                args: vec!["0xB794F5eA0ba39494cE839613fffBA74279579268".to_string()],
+            }
+        );
+        assert_eq!(
+            ContractCommand::parse("events Transfer 5..").unwrap(),
+            ContractCommand::Events {
+                name: "Transfer".to_string(),
+                from: Some(5),
+                to: None,
+            }
+        );
+        assert_eq!(
+            ContractCommand::parse("balance").unwrap(),
+            ContractCommand::Balance(None)
+        );
+        assert!(ContractCommand::parse("events Transfer 5").is_err());
+        assert!(ContractCommand::parse("events Transfer a..b").is_err());
+        assert!(ContractCommand::parse("balance nope").is_err());
+        assert!(ContractCommand::parse("mint 5").is_err());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_scripted_session_on_busd() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let owner = wallet.address();
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let (busd, _) = deploy_contract(
+            &ctx,
+            wallet.clone(),
+            "BUSDImplementation",
+            abi.clone(),
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        let mut session =
+            ContractSession::new(ctx, "BUSDImplementation", busd.address(), abi, Some(wallet));
+
+        let bob = "0xB794F5eA0ba39494cE839613fffBA74279579268";
+        let script = [
+            "ls".to_string(),
+            "call totalSupply".to_string(),
+            "send increaseSupply 1000".to_string(),
+            "yes".to_string(),
+            format!("call balanceOf {owner:?}"),
+            // The constructor paused the token, so this reverts in the estimate
+            format!("send transfer {bob} 10"),
+            "call balanceOf nope".to_string(),
+            "call mint 5".to_string(),
+            // A preview followed by anything but `yes` is dropped
+            "send increaseSupply 1".to_string(),
+            "call totalSupply".to_string(),
+            "events SupplyIncreased 0..".to_string(),
+            format!("balance {bob}"),
+            "exit".to_string(),
+            "call totalSupply".to_string(),
+        ]
+        .join("\n");
+        let mut out = Vec::new();
+        run(&mut session, script.as_bytes(), &mut out).await?;
+        let out = String::from_utf8(out)?;
+
+        assert!(out.contains("transfer(address,uint256) [nonpayable] 0xa9059cbb -> (bool)"));
+        assert!(out.contains("Call BUSDImplementation.increaseSupply(1000)"));
+        assert!(out.contains("Sent increaseSupply in 0x"));
+        // totalSupply, balanceOf and totalSupply again: the second increase was never sent, and
+        // nothing ran after `exit`
+        let values: Vec<&str> = out
+            .lines()
+            .filter(|line| line.chars().all(|c| c.is_ascii_digit()))
+            .collect();
+        assert_eq!(values, ["0", "1000", "1000"], "{out}");
+        assert!(out.contains("Transaction not sent\n1000\n"), "{out}");
+        let events: Vec<&str> = out
+            .lines()
+            .filter(|line| line.starts_with("block "))
+            .collect();
+        assert_eq!(events.len(), 1, "{out}");
+        assert!(events[0].ends_with(&format!("SupplyIncreased(to={owner:?}, value=1000)")));
+        assert!(out.ends_with("0.000000000000000000 ETH\n"), "{out}");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+//! Deterministic deployments with CREATE2 through a deployer contract.
+
+use crate::deploy::DeploymentResult;
+use crate::errors::Error;
+use crate::project::{compile, contract_parts};
+use ethers::{
+    abi::{parse_abi, Abi, Tokenize},
+    contract::{Contract, ContractFactory},
+    prelude::{Address, Middleware, H256},
+    types::Bytes,
+    utils::get_create2_address,
+};
+use eyre::{eyre, ContextCompat, Result};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+};
+use tracing::info;
+
+// Folder holding the Create2Deployer source deployed once per chain
+const DEPLOYER_SOURCES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/contracts/");
+
+// Addresses of the deployers this process deployed, keyed like the Multicall3 cache
+static DEPLOYED: OnceLock<Mutex<HashMap<(u64, H256), Address>>> = OnceLock::new();
+
+/// Init code deploying `bytecode` with `constructor_args`: the bytecode followed by the encoded arguments.
+pub fn create2_init_code<A: Tokenize>(
+    abi: &Abi,
+    bytecode: &Bytes,
+    constructor_args: A,
+) -> Result<Bytes> {
+    let init_code = match abi.constructor() {
+        Some(constructor) => {
+            constructor.encode_input(bytecode.to_vec(), &constructor_args.into_tokens())?
+        }
+        None => bytecode.to_vec(),
+    };
+    Ok(init_code.into())
+}
+
+/// Address `deployer` creates `init_code` at with `salt`, per EIP-1014.
+pub fn compute_create2_address(deployer: Address, salt: [u8; 32], init_code: &Bytes) -> Address {
+    get_create2_address(deployer, salt, init_code)
+}
+
+/// Finds the `Create2Deployer` this process deployed on `client`'s chain, deploying it on first use.
+pub async fn create2_deployer_address<M: Middleware + 'static>(client: Arc<M>) -> Result<Address> {
+    let chain_id = client.get_chainid().await.map_err(|e| eyre!(e))?.as_u64();
+    let genesis = client
+        .get_block(0u64)
+        .await
+        .map_err(|e| eyre!(e))?
+        .and_then(|block| block.hash)
+        .context("Failed to get the genesis block hash")?;
+    let key = (chain_id, genesis);
+    let cache = DEPLOYED.get_or_init(Default::default);
+    if let Some(address) = cache.lock().unwrap().get(&key) {
+        return Ok(*address);
+    }
+
+    let project = compile(DEPLOYER_SOURCES).await?.output;
+    let (abi, bytecode) = contract_parts(&project, DEPLOYER_SOURCES, "Create2Deployer")?;
+    let contract = ContractFactory::new(abi, bytecode, client)
+        .deploy(())?
+        .send()
+        .await?;
+    let address = contract.address();
+    info!(address = ?address, chain_id, "create2 deployer deployed");
+
+    cache.lock().unwrap().insert(key, address);
+    Ok(address)
+}
+
+/// Deploys one instance of `bytecode` with `constructor_args` per salt, in order, through the
+/// chain's `Create2Deployer`, and returns each instance's deployment.
+///
+/// Every address is checked before sending: a salt whose address already holds code (a repeated
+/// salt included) fails with [`Error::Create2Collision`]. The constructor sees the deployer
+/// contract, not `client`'s sender, as `msg.sender`.
+pub async fn deploy_many_deterministic<M, A>(
+    client: Arc<M>,
+    abi: &Abi,
+    bytecode: &Bytes,
+    salts: &[[u8; 32]],
+    constructor_args: A,
+) -> Result<Vec<DeploymentResult>>
+where
+    M: Middleware + 'static,
+    A: Tokenize,
+{
+    let init_code = create2_init_code(abi, bytecode, constructor_args)?;
+    let deployer_address = create2_deployer_address(client.clone()).await?;
+    let deployer_abi =
+        parse_abi(&["function deploy(bytes32 salt, bytes initCode) payable returns (address)"])?;
+    let deployer = Contract::new(deployer_address, deployer_abi, client.clone());
+
+    let mut results = Vec::with_capacity(salts.len());
+    for salt in salts {
+        let address = compute_create2_address(deployer_address, *salt, &init_code);
+        let code = client.get_code(address, None).await.map_err(|e| eyre!(e))?;
+        if !code.is_empty() {
+            return Err(Error::Create2Collision {
+                salt: H256(*salt),
+                address,
+            }
+            .into());
+        }
+
+        let receipt = deployer
+            .method::<_, Address>("deploy", (*salt, init_code.clone()))?
+            .send()
+            .await?
+            .await?
+            .context("Missing deployment receipt")?;
+        if receipt.status != Some(1u64.into()) {
+            return Err(eyre!(
+                "CREATE2 deployment with salt {:?} reverted in tx {:?}",
+                H256(*salt),
+                receipt.transaction_hash
+            ));
+        }
+        info!(address = ?address, salt = ?H256(*salt), "deployed with create2");
+
+        results.push(DeploymentResult {
+            address,
+            tx_hash: receipt.transaction_hash,
+            block_number: receipt
+                .block_number
+                .context("Receipt has no block number")?
+                .as_u64(),
+            gas_used: receipt.gas_used.context("Receipt has no gas used")?,
+            init_tx_hash: None,
+            init_gas_used: None,
+        });
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::prelude::{LocalWallet, Provider, Signer, SignerMiddleware};
+    use ethers::utils::{hex, Ganache};
+
+    #[test]
+    fn test_create2_address_matches_eip1014_example() -> Result<()> {
+        // Example 1 of EIP-1014: zero deployer, zero salt, init code 0x00
+        let address = compute_create2_address(Address::zero(), [0; 32], &Bytes::from(vec![0u8]));
+        assert_eq!(
+            address,
+            "0x4D1A2e2bB4F88F0250f26Ffff098B0b30B26BF38".parse::<Address>()?
+        );
+
+        // Example 2: deployer 0xdeadbeef..., zero salt, init code 0x00
+        let deployer = "0xdeadbeef00000000000000000000000000000000".parse::<Address>()?;
+        let address = compute_create2_address(deployer, [0; 32], &Bytes::from(vec![0u8]));
+        assert_eq!(
+            hex::encode(address),
+            "b928f69bb1d91cd65274e3c79d8986362984fda3"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_three_instances_at_precomputed_addresses() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let client = Arc::new(SignerMiddleware::new(
+            provider.clone(),
+            wallet.with_chain_id(chain_id),
+        ));
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "Migrations")?;
+        let salts = [[1u8; 32], [2u8; 32], [3u8; 32]];
+        let results =
+            deploy_many_deterministic(client.clone(), &abi, &bytecode, &salts, ()).await?;
+
+        let deployer = create2_deployer_address(client.clone()).await?;
+        let init_code = create2_init_code(&abi, &bytecode, ())?;
+        assert_eq!(results.len(), 3);
+        for (result, salt) in results.iter().zip(salts) {
+            assert_eq!(
+                result.address,
+                compute_create2_address(deployer, salt, &init_code)
+            );
+            assert!(!provider.get_code(result.address, None).await?.is_empty());
+        }
+        assert_ne!(results[0].address, results[1].address);
+        assert_ne!(results[1].address, results[2].address);
+        assert_ne!(results[0].address, results[2].address);
+
+        // The same salt can't be used twice
+        let err = deploy_many_deterministic(client, &abi, &bytecode, &salts[..1], ())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::Create2Collision { address, .. }) if *address == results[0].address
+        ));
+
+        Ok(())
+    }
+}
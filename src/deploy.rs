@@ -0,0 +1,1854 @@
+//! Contract deployment helpers shared by the binaries and tests.
+
+use crate::amount::format_eth; // Human-readable costs in progress lines
+use crate::chain_context::ChainContext; // Cached chain data shared by one deployment
+use crate::chain_guard::ChainGuard; // Endpoint check before sending
+use crate::errors::Error; // Typed errors callers can match on
+use crate::fees::{resolve_fee_mode, FeeMode}; // Legacy vs EIP-1559 selection
+use crate::funds::ensure_funds; // Pre-flight balance check
+use crate::notify::{deliver, Notification, NotificationEvent, Notifier}; // Webhook on completion
+use crate::polling::PendingTxExt; // Adaptive receipt wait
+use crate::project::{artifact_parts, compile, contract_parts}; // Compilation for compile_and_deploy
+#[cfg(feature = "sqlite")]
+use crate::sqlite::Ledger; // Optional record of the deployments
+use crate::timeouts::OpTimeouts; // Upper bounds for network awaits
+use crate::wallet::bind_to_chain; // Wallet chain id taken from the provider
+use ethers::{
+    abi::{Abi, Token, Tokenize}, // ABI definition and constructor argument encoding
+    contract::{Contract, ContractFactory}, // Import for interacting with and deploying Ethereum smart contracts
+    middleware::SignerMiddleware,          // Middleware to sign transactions using a wallet
+    prelude::{Address, Http, LocalWallet, Middleware, Provider, Signer, U256}, // Types for wallet, Ethereum provider, and other utilities
+    types::{
+        transaction::eip2718::TypedTransaction, BlockNumber, Bytes, TransactionReceipt,
+        TransactionRequest, TxHash,
+    }, // Block numbers, raw bytecode and receipts
+    utils::{keccak256, rlp::RlpStream}, // Hashing and RLP encoding for address derivation
+};
+use ethers_solc::ProjectCompileOutput; // Compiled artifacts shared by multi-chain deployments
+use eyre::{eyre, ContextCompat, Result, WrapErr}; // For error handling and contextual errors
+use futures::future::{join_all, try_join_all}; // Runs the per-chain deployments concurrently
+use serde::Serialize; // Deployment metadata is reported as JSON
+use std::{fmt, sync::Arc};
+use tokio::sync::mpsc::UnboundedSender; // Progress events channel
+use tracing::{debug, info, instrument, warn, Span}; // Structured progress output
+
+// Type alias for a contract deployed using a wallet and signing middleware over `M`
+pub type SignerDeployedContract<M> = Contract<SignerMiddleware<M, LocalWallet>>;
+
+/// Computes the address a contract created with `CREATE` by `deployer` at `nonce` will get.
+///
+/// Per the yellow paper this is the last 20 bytes of `keccak256(rlp([deployer, nonce]))`.
+pub fn compute_create_address(deployer: Address, nonce: u64) -> Address {
+    let mut stream = RlpStream::new_list(2); // The sender and nonce are encoded as a two-item list
+    stream.append(&deployer);
+    stream.append(&nonce);
+
+    let hash = keccak256(stream.out()); // Hash the RLP encoding
+    Address::from_slice(&hash[12..]) // Keep the rightmost 20 bytes
+}
+
+/// Metadata of a mined deployment.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentResult {
+    pub address: Address,
+    pub tx_hash: TxHash,
+    pub block_number: u64,
+    pub gas_used: U256,
+    /// Hash of the initializer call sent right after the deployment, if any.
+    pub init_tx_hash: Option<TxHash>,
+    /// Gas used by the initializer call, if any.
+    pub init_gas_used: Option<U256>,
+}
+
+impl DeploymentResult {
+    /// Builds the result from the deployment transaction's receipt, failing if the creation
+    /// reverted.
+    pub fn from_receipt(receipt: &TransactionReceipt) -> Result<Self> {
+        if receipt.status != Some(1u64.into()) {
+            return Err(eyre!(
+                "Deployment reverted in tx {:?}",
+                receipt.transaction_hash
+            ));
+        }
+        Ok(Self {
+            address: receipt
+                .contract_address
+                .context("Receipt has no contract address")?,
+            tx_hash: receipt.transaction_hash,
+            block_number: receipt
+                .block_number
+                .context("Receipt has no block number")?
+                .as_u64(),
+            gas_used: receipt.gas_used.context("Receipt has no gas used")?,
+            init_tx_hash: None,
+            init_gas_used: None,
+        })
+    }
+}
+
+/// Knobs for [`deploy_contract`].
+#[derive(Clone, Debug)]
+pub struct DeployOptions {
+    /// Run [`ensure_funds`] before sending. Disable when the balance is expected to
+    /// change on-chain between the check and the send.
+    pub check_funds: bool,
+    /// How the deployment transaction is priced.
+    pub fee_mode: FeeMode,
+    /// Limits for the RPC calls and the receipt wait.
+    pub timeouts: OpTimeouts,
+    /// Factor applied to the gas estimate to get the gas limit; raise it for constructors whose
+    /// storage writes make the estimate tight. Must be at least 1.0.
+    pub gas_limit_multiplier: f64,
+    /// How many times a deployment rejected as underpriced is resubmitted with higher fees.
+    pub underpriced_retries: u32,
+    /// Receives a [`DeployEvent`] at each stage, and [`DeployEvent::Failed`] if a stage fails.
+    pub progress: Option<UnboundedSender<DeployEvent>>,
+    /// Chain id the provider must serve; checked with a [`ChainGuard`] before anything is sent.
+    pub expected_chain: Option<u64>,
+    /// Told about the deployment once it succeeded or failed; its failures are only logged.
+    pub notifier: Option<Arc<dyn Notifier>>,
+    /// Database recording the deployment and the deployer's balance after it; its failures are
+    /// only logged.
+    #[cfg(feature = "sqlite")]
+    pub ledger: Option<Arc<Ledger>>,
+}
+
+impl Default for DeployOptions {
+    fn default() -> Self {
+        Self {
+            check_funds: true,
+            fee_mode: FeeMode::Legacy,
+            timeouts: OpTimeouts::default(),
+            gas_limit_multiplier: 1.0,
+            underpriced_retries: 3,
+            progress: None,
+            expected_chain: None,
+            notifier: None,
+            #[cfg(feature = "sqlite")]
+            ledger: None,
+        }
+    }
+}
+
+impl DeployOptions {
+    /// Sends `event` to the progress channel, if any; a dropped receiver is ignored.
+    pub fn emit(&self, event: DeployEvent) {
+        if let Some(progress) = &self.progress {
+            let _ = progress.send(event);
+        }
+    }
+}
+
+/// A stage of a deployment, reported through [`DeployOptions::progress`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeployEvent {
+    CompileStarted,
+    CompileFinished {
+        warnings: usize,
+    },
+    ArtifactResolved {
+        name: String,
+    },
+    /// Gas limit set on the creation and its maximum cost in wei.
+    GasEstimated {
+        limit: U256,
+        cost: U256,
+    },
+    TxSubmitted {
+        hash: TxHash,
+    },
+    Confirmed {
+        block: u64,
+        confirmations: usize,
+    },
+    /// The stage after the last event failed with this error.
+    Failed {
+        error: String,
+    },
+}
+
+impl fmt::Display for DeployEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CompileStarted => write!(f, "compiling"),
+            Self::CompileFinished { warnings } => write!(f, "compiled ({warnings} warnings)"),
+            Self::ArtifactResolved { name } => write!(f, "found {name}"),
+            Self::GasEstimated { limit, cost } => {
+                write!(
+                    f,
+                    "gas limit {limit}, costing at most {}",
+                    format_eth(*cost)
+                )
+            }
+            Self::TxSubmitted { hash } => write!(f, "submitted {hash:?}"),
+            Self::Confirmed {
+                block,
+                confirmations,
+            } => write!(
+                f,
+                "confirmed in block {block} ({confirmations} confirmations)"
+            ),
+            Self::Failed { error } => write!(f, "failed: {error}"),
+        }
+    }
+}
+
+/// Scales a gas estimate by `multiplier`, rounding up to the next unit of gas.
+fn scale_gas(estimate: U256, multiplier: f64) -> U256 {
+    // Work in thousandths so the multiplication stays in integer arithmetic
+    let per_mille = U256::from((multiplier * 1000.0).round() as u64);
+    (estimate * per_mille + 999) / 1000
+}
+
+/// Deploys the contract `name` signed by `wallet`, pricing the legacy transaction at the next block's base fee.
+/// Returns the contract handle along with the deployment metadata.
+///
+/// Every lookup and the receipt wait go through `ctx`, so its cached chain id, block and fees
+/// are shared with the caller's other sends.
+///
+/// Runs inside a `deploy` span carrying the contract name and, once known, the deployed address.
+#[instrument(
+    name = "deploy",
+    skip_all,
+    fields(contract = %name, address = tracing::field::Empty)
+)]
+pub async fn deploy_contract<M, A>(
+    ctx: &ChainContext<M>,
+    wallet: LocalWallet,
+    name: &str,
+    abi: Abi,
+    bytecode: Bytes,
+    constructor_args: A,
+    options: &DeployOptions,
+) -> Result<(SignerDeployedContract<ChainContext<M>>, DeploymentResult)>
+where
+    M: Middleware + Clone + 'static,
+    M::Error: 'static,
+    A: Tokenize,
+{
+    let deployed = send_deployment(ctx, wallet, abi, bytecode, constructor_args, options).await;
+    if let Err(err) = &deployed {
+        // Tell progress consumers which stage died
+        options.emit(DeployEvent::Failed {
+            error: format!("{err:#}"),
+        });
+    }
+    #[cfg(feature = "sqlite")]
+    if let (Ok((contract, result)), Some(ledger)) = (&deployed, &options.ledger) {
+        let client = contract.client();
+        // The contract is on-chain whatever the database says, so a failed write is only logged
+        let recorded = async {
+            ledger.record_deployment(client.signer().chain_id(), name, result)?;
+            ledger
+                .snapshot_balances(client.as_ref(), &[client.address()])
+                .await
+        }
+        .await;
+        if let Err(err) = recorded {
+            warn!(contract = %name, error = %format!("{err:#}"), "deployment not recorded");
+        }
+    }
+    if let Some(notifier) = &options.notifier {
+        let notification = deployment_notification(ctx, name, &deployed, options).await;
+        deliver(Some(notifier), notification).await;
+    }
+    deployed
+}
+
+// The outcome of a deployment for the notifier; lookups that fail only leave fields empty
+async fn deployment_notification<M, C>(
+    provider: &M,
+    name: &str,
+    deployed: &Result<(C, DeploymentResult)>,
+    options: &DeployOptions,
+) -> Notification
+where
+    M: Middleware,
+    M::Error: 'static,
+{
+    let timeouts = &options.timeouts;
+    let mut notification = Notification {
+        chain_id: timeouts
+            .rpc("chain id", provider.get_chainid())
+            .await
+            .ok()
+            .map(|id| id.as_u64()),
+        contract: Some(name.to_string()),
+        ..Notification::new(NotificationEvent::Deployment, deployed.is_ok())
+    };
+    match deployed {
+        Ok((_, result)) => {
+            let receipt = timeouts
+                .rpc("receipt", provider.get_transaction_receipt(result.tx_hash))
+                .await
+                .ok()
+                .flatten();
+            notification.address = Some(result.address);
+            notification.tx_hash = Some(result.tx_hash);
+            notification = notification.gas_cost(
+                Some(result.gas_used),
+                receipt.and_then(|receipt| receipt.effective_gas_price),
+            );
+        }
+        Err(err) => notification.error = Some(format!("{err:#}")),
+    }
+    notification
+}
+
+async fn send_deployment<M, A>(
+    ctx: &ChainContext<M>,
+    wallet: LocalWallet,
+    abi: Abi,
+    bytecode: Bytes,
+    constructor_args: A,
+    options: &DeployOptions,
+) -> Result<(SignerDeployedContract<ChainContext<M>>, DeploymentResult)>
+where
+    M: Middleware + Clone + 'static,
+    M::Error: 'static,
+    A: Tokenize,
+{
+    // Reject multipliers that would cut the limit below the estimate (NaN included)
+    let multiplier = options.gas_limit_multiplier;
+    if multiplier.is_nan() || multiplier < 1.0 {
+        return Err(Error::InvalidGasLimitMultiplier { multiplier }.into());
+    }
+
+    let timeouts = &options.timeouts;
+    // Refuse to go on if the endpoint isn't the chain the caller meant
+    if let Some(expected) = options.expected_chain {
+        timeouts
+            .rpc("chain check", ChainGuard::new(expected).check(ctx))
+            .await?;
+    }
+
+    // Rebuild the wallet with the provider's chain ID (required to sign transactions on the correct chain)
+    let wallet = timeouts.rpc("chain id", bind_to_chain(ctx, wallet)).await?;
+    let from = wallet.address(); // Address paying for the deployment
+
+    // Create a client to interact with the blockchain (includes the signing wallet); it shares the context's cache
+    let client = Arc::new(SignerMiddleware::new(ctx.clone(), wallet));
+
+    // Create a factory for deploying the contract using the ABI and bytecode
+    let factory = ContractFactory::new(abi.clone(), bytecode, client.clone());
+
+    // Initialize the deployment process with the encoded constructor arguments
+    let deployer = factory.deploy(constructor_args)?;
+
+    let fee_mode = timeouts
+        .rpc(
+            "fee mode detection",
+            resolve_fee_mode(ctx, options.fee_mode),
+        )
+        .await?;
+    let mut deployer = match fee_mode {
+        FeeMode::Eip1559 => {
+            // Fill in the fees the signer middleware would, so the cost can be reported up front
+            let (max_fee, priority_fee) = timeouts
+                .rpc("fee estimation", ctx.estimate_eip1559_fees(None))
+                .await?;
+            let mut deployer = deployer;
+            if let TypedTransaction::Eip1559(tx) = &mut deployer.tx {
+                tx.max_fee_per_gas = Some(max_fee);
+                tx.max_priority_fee_per_gas = Some(priority_fee);
+            }
+            deployer
+        }
+        _ => {
+            // Get the latest block information to determine gas pricing
+            let block = timeouts
+                .rpc("latest block", ctx.get_block(BlockNumber::Latest))
+                .await?
+                .context("Failed to get block")?;
+
+            // Get the base fee for the next block and set the gas price for the transaction
+            let gas_price = block
+                .next_block_base_fee()
+                .context("Failed to get the base fee for the next block")?;
+            let mut deployer = deployer.legacy();
+            deployer.tx.set_gas_price::<U256>(gas_price); // Set gas price for the transaction
+            deployer
+        }
+    };
+
+    // Set the gas limit from the estimate, with the configured headroom
+    let estimate = timeouts
+        .rpc("gas estimation", ctx.estimate_gas(&deployer.tx, None))
+        .await?;
+    let gas_limit = scale_gas(estimate, multiplier);
+    deployer.tx.set_gas(gas_limit);
+    debug!(%estimate, %gas_limit, "gas limit set");
+    let fee_per_gas = match &deployer.tx {
+        TypedTransaction::Eip1559(tx) => tx.max_fee_per_gas,
+        tx => tx.gas_price(),
+    }
+    .unwrap_or_default();
+    options.emit(DeployEvent::GasEstimated {
+        limit: gas_limit,
+        cost: gas_limit * fee_per_gas,
+    });
+
+    if options.check_funds {
+        // Make sure the deployer can pay for the creation before the node rejects it
+        timeouts
+            .rpc(
+                "funds check",
+                ensure_funds(ctx, from, U256::zero(), gas_limit),
+            )
+            .await?;
+    }
+
+    // Send the transaction to deploy the contract, repricing it if the base fee rose since it was priced
+    let mut retries = 0;
+    let pending = loop {
+        let sent = timeouts
+            .rpc("deployment submission", async {
+                eyre::Ok(client.send_transaction(deployer.tx.clone(), None).await)
+            })
+            .await?;
+        match sent {
+            Ok(pending) => break pending,
+            Err(err) if retries < options.underpriced_retries && is_underpriced(&err) => {
+                retries += 1;
+                // Ask for the block by number so a cached latest block can't hide the rise
+                let number = timeouts.rpc("block number", ctx.get_block_number()).await?;
+                let block = timeouts
+                    .rpc("latest block", ctx.get_block(number))
+                    .await?
+                    .context("Failed to get block")?;
+                let base_fee = block
+                    .next_block_base_fee()
+                    .context("Failed to get the base fee for the next block")?;
+                bump_fees(&mut deployer.tx, base_fee);
+                warn!(retry = retries, %base_fee, "deployment underpriced, resubmitting with higher fees");
+            }
+            Err(err) => return Err(err.into()),
+        }
+    };
+    options.emit(DeployEvent::TxSubmitted { hash: *pending });
+
+    // Await its completion, backing off to the block time (or following new heads)
+    let receipt = timeouts
+        .receipt("deployment", pending.await_smart(ctx))
+        .await?;
+    let result = DeploymentResult::from_receipt(&receipt)?;
+    options.emit(DeployEvent::Confirmed {
+        block: result.block_number,
+        confirmations: 1,
+    });
+    let contract = Contract::new(result.address, abi, client);
+
+    let address = contract.address();
+    Span::current().record("address", tracing::field::debug(address));
+    info!(address = ?address, gas_used = %result.gas_used, "deployed");
+    Ok((contract, result))
+}
+
+// Nodes word a fee below the base fee or the pool minimum differently
+fn is_underpriced(err: &impl std::error::Error) -> bool {
+    let message = err.to_string().to_lowercase();
+    message.contains("underpriced")
+        || message.contains("fee cap less than block base fee")
+        || message.contains("max fee per gas less than block base fee")
+}
+
+/// Raises `tx`'s fees by 12.5%, the usual minimum for a resubmission, and at least to what
+/// `base_fee` requires.
+fn bump_fees(tx: &mut TypedTransaction, base_fee: U256) {
+    let bump = |fee: U256| fee + (fee + 7) / 8;
+    match tx {
+        TypedTransaction::Eip1559(tx) => {
+            let priority_fee = bump(tx.max_priority_fee_per_gas.unwrap_or_default());
+            let max_fee = bump(tx.max_fee_per_gas.unwrap_or_default());
+            tx.max_priority_fee_per_gas = Some(priority_fee);
+            tx.max_fee_per_gas = Some(max_fee.max(base_fee + priority_fee));
+        }
+        tx => {
+            let gas_price = bump(tx.gas_price().unwrap_or_default());
+            tx.set_gas_price(gas_price.max(base_fee));
+        }
+    }
+}
+
+/// Compiles `contracts_folder` and deploys `<contracts_folder><name>.sol`'s `name` contract like
+/// [`deploy_contract`], reporting the compilation and artifact lookup through the progress
+/// channel as well.
+pub async fn compile_and_deploy<M, A>(
+    ctx: &ChainContext<M>,
+    wallet: LocalWallet,
+    contracts_folder: &str,
+    name: &str,
+    constructor_args: A,
+    options: &DeployOptions,
+) -> Result<(SignerDeployedContract<ChainContext<M>>, DeploymentResult)>
+where
+    M: Middleware + Clone + 'static,
+    M::Error: 'static,
+    A: Tokenize,
+{
+    let fail = |err: eyre::Report| {
+        options.emit(DeployEvent::Failed {
+            error: format!("{err:#}"),
+        });
+        err
+    };
+
+    options.emit(DeployEvent::CompileStarted);
+    let report = compile(contracts_folder).await.map_err(fail)?;
+    options.emit(DeployEvent::CompileFinished {
+        warnings: report.warnings.len(),
+    });
+    let (abi, bytecode) = contract_parts(&report.output, contracts_folder, name).map_err(fail)?;
+    options.emit(DeployEvent::ArtifactResolved {
+        name: name.to_string(),
+    });
+
+    deploy_contract(ctx, wallet, name, abi, bytecode, constructor_args, options).await
+}
+
+/// Deploys a contract and sends its `init_method` call as soon as the creation is mined.
+///
+/// Upgradeable-style contracts are left uninitialized by their constructor; doing both steps in one
+/// helper keeps the window in which anyone else could call the initializer as short as possible.
+/// Fails if either transaction reverts.
+pub async fn deploy_and_initialize<M, A, I>(
+    client: Arc<M>,
+    abi: Abi,
+    bytecode: Bytes,
+    constructor_args: A,
+    init_method: &str,
+    init_args: I,
+) -> Result<DeploymentResult>
+where
+    M: Middleware + 'static,
+    A: Tokenize,
+    I: Tokenize,
+{
+    // Deploy and wait for the creation receipt
+    let factory = ContractFactory::new(abi, bytecode, client);
+    let (contract, receipt) = factory
+        .deploy(constructor_args)?
+        .send_with_receipt()
+        .await?;
+    let mut result = DeploymentResult::from_receipt(&receipt)?;
+
+    // Immediately call the initializer on the fresh contract
+    let call = contract.method::<_, ()>(init_method, init_args)?;
+    let init_receipt = call
+        .send()
+        .await?
+        .await?
+        .context("Missing initializer receipt")?;
+    if init_receipt.status != Some(1u64.into()) {
+        return Err(eyre!(
+            "{init_method} reverted on {:?} (tx {:?})",
+            result.address,
+            init_receipt.transaction_hash
+        ));
+    }
+
+    result.init_tx_hash = Some(init_receipt.transaction_hash);
+    result.init_gas_used = init_receipt.gas_used;
+    info!(address = ?result.address, init = init_method, "deployed and initialized");
+    Ok(result)
+}
+
+/// Deploys an `Ownable` contract and hands its ownership to `new_owner` as soon as the creation is
+/// mined, e.g. to a multisig; a zero `new_owner` calls `renounceOwnership()` instead.
+///
+/// The ABI is checked first, so a contract without `owner()` and the needed ownership function
+/// fails with [`Error::NotOwnable`] before anything is sent. Fails if the call reverts or if
+/// `owner()` doesn't report `new_owner` afterwards, as with two-step ownership schemes.
+pub async fn deploy_and_transfer_ownership<M, A>(
+    client: Arc<M>,
+    abi: Abi,
+    bytecode: Bytes,
+    constructor_args: A,
+    new_owner: Address,
+) -> Result<DeploymentResult>
+where
+    M: Middleware + 'static,
+    A: Tokenize,
+{
+    let (method, args) = if new_owner.is_zero() {
+        ("renounceOwnership()", vec![])
+    } else {
+        (
+            "transferOwnership(address)",
+            vec![Token::Address(new_owner)],
+        )
+    };
+    for signature in ["owner()", method] {
+        let name = &signature[..signature.find('(').unwrap_or(signature.len())];
+        let present = abi
+            .functions_by_name(name)
+            .is_ok_and(|functions| functions.iter().any(|f| f.signature() == signature));
+        if !present {
+            return Err(Error::NotOwnable {
+                function: signature.to_string(),
+            }
+            .into());
+        }
+    }
+
+    let factory = ContractFactory::new(abi, bytecode, client);
+    let (contract, receipt) = factory
+        .deploy(constructor_args)?
+        .send_with_receipt()
+        .await?;
+    let result = DeploymentResult::from_receipt(&receipt)?;
+
+    let name = &method[..method.find('(').unwrap_or(method.len())];
+    let call = contract.method::<_, ()>(name, args)?;
+    let handover = call
+        .send()
+        .await?
+        .await?
+        .context("Missing ownership transfer receipt")?;
+    if handover.status != Some(1u64.into()) {
+        return Err(eyre!(
+            "{name} reverted on {:?} (tx {:?})",
+            result.address,
+            handover.transaction_hash
+        ));
+    }
+
+    let owner: Address = contract.method("owner", ())?.call().await?;
+    if owner != new_owner {
+        return Err(eyre!(
+            "owner() of {:?} is {owner:?} after {name}, expected {new_owner:?}; the contract may \
+             need the new owner to accept",
+            result.address
+        ));
+    }
+    info!(address = ?result.address, owner = ?new_owner, "deployed and handed over");
+    Ok(result)
+}
+
+/// A node to deploy to, along with the chain it is expected to serve.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChainTarget {
+    /// HTTP JSON-RPC endpoint of the node.
+    pub endpoint: String,
+    /// Chain id the node must report; deployments to any other chain are refused.
+    pub chain_id: u64,
+}
+
+/// Deploys `contract_name` from `project` to every target concurrently.
+///
+/// Each chain gets its own provider and the wallet returned by `per_chain_wallet`, stamped with
+/// the chain's id, so nonces are tracked independently. A failing chain doesn't stop the others:
+/// the results are returned per chain id, sorted by chain id. Only a contract missing from
+/// `project` fails the whole call.
+pub async fn deploy_to_many<F>(
+    targets: Vec<ChainTarget>,
+    project: &ProjectCompileOutput,
+    contract_name: &str,
+    per_chain_wallet: F,
+    options: &DeployOptions,
+) -> Result<Vec<(u64, Result<DeploymentResult>)>>
+where
+    F: Fn(&ChainTarget) -> LocalWallet,
+{
+    // The artifact is shared by every chain, so look it up once
+    let artifact = project
+        .find_first(contract_name)
+        .with_context(|| format!("{contract_name} not found in the compiled project"))?;
+    let (abi, bytecode) = artifact_parts(contract_name, artifact)?;
+
+    let deployments = targets.iter().map(|target| {
+        let wallet = per_chain_wallet(target);
+        let (abi, bytecode) = (abi.clone(), bytecode.clone());
+        async move {
+            let result =
+                deploy_to_target(target, wallet, contract_name, abi, bytecode, options).await;
+            (target.chain_id, result)
+        }
+    });
+    let mut results = join_all(deployments).await;
+
+    // Group the output by chain; the sort is stable so duplicate targets keep their order
+    results.sort_by_key(|(chain_id, _)| *chain_id);
+    Ok(results)
+}
+
+/// Deploys to a single target of [`deploy_to_many`] after checking its chain id.
+async fn deploy_to_target(
+    target: &ChainTarget,
+    wallet: LocalWallet,
+    name: &str,
+    abi: Abi,
+    bytecode: Bytes,
+    options: &DeployOptions,
+) -> Result<DeploymentResult> {
+    let ctx = ChainContext::new(Provider::<Http>::try_from(target.endpoint.as_str())?);
+    let actual = options.timeouts.rpc("chain id", ctx.chain_id()).await?;
+    if actual != target.chain_id {
+        return Err(Error::ChainIdMismatch {
+            endpoint: Some(target.endpoint.clone()),
+            expected: target.chain_id,
+            actual,
+        }
+        .into());
+    }
+
+    let (_, result) = deploy_contract(&ctx, wallet, name, abi, bytecode, (), options).await?;
+    Ok(result)
+}
+
+/// One contract of a [`deploy_concurrent`] batch.
+#[derive(Clone, Debug)]
+pub struct DeploySpec {
+    /// Name used in logs and errors.
+    pub name: String,
+    pub abi: Abi,
+    pub bytecode: Bytes,
+    /// Encoded constructor arguments, in order.
+    pub args: Vec<Token>,
+}
+
+/// A stage of one contract of a [`deploy_batch`], passed to its `on_progress` observer.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DeployProgress {
+    Compiling { name: String },
+    Estimating { name: String },
+    Submitted { name: String, tx_hash: TxHash },
+    Confirmed { name: String, address: Address },
+}
+
+/// Compiles `contracts_folder` and deploys each `(name, constructor args)` in `contracts` with
+/// [`deploy_concurrent`], calling `on_progress`, when given, as each contract reaches a stage.
+///
+/// The folder is compiled once for the whole batch, after every contract's `Compiling` event.
+pub async fn deploy_batch<M: Middleware + Clone + 'static>(
+    ctx: &ChainContext<M>,
+    contracts_folder: &str,
+    contracts: Vec<(String, Vec<Token>)>,
+    on_progress: Option<&dyn Fn(DeployProgress)>,
+) -> Result<Vec<DeploymentResult>> {
+    let on_progress = on_progress.unwrap_or(&|_| {});
+    for (name, _) in &contracts {
+        on_progress(DeployProgress::Compiling { name: name.clone() });
+    }
+    let project = compile(contracts_folder).await?.output;
+    let specs = contracts
+        .into_iter()
+        .map(|(name, args)| {
+            let (abi, bytecode) = contract_parts(&project, contracts_folder, &name)?;
+            Ok(DeploySpec {
+                name,
+                abi,
+                bytecode,
+                args,
+            })
+        })
+        .collect::<Result<_>>()?;
+    deploy_specs(ctx, specs, on_progress, &OpTimeouts::default()).await
+}
+
+/// Deploys independent contracts at the same time from the sender of `ctx`'s client, returning
+/// their results in the order of `specs`.
+///
+/// Each deployment gets its own nonce up front, counted from the sender's pending nonce, so the
+/// node can receive them in any order and still mine them all. Gas is estimated before the
+/// nonces are set, since an estimate with a future nonce is rejected by some nodes. The
+/// contracts must not depend on each other: none of them exists before the batch is sent.
+pub async fn deploy_concurrent<M: Middleware + Clone + 'static>(
+    ctx: &ChainContext<M>,
+    specs: Vec<DeploySpec>,
+) -> Result<Vec<DeploymentResult>> {
+    deploy_specs(ctx, specs, &|_| {}, &OpTimeouts::default()).await
+}
+
+async fn deploy_specs<M: Middleware + Clone + 'static>(
+    ctx: &ChainContext<M>,
+    specs: Vec<DeploySpec>,
+    on_progress: &dyn Fn(DeployProgress),
+    timeouts: &OpTimeouts,
+) -> Result<Vec<DeploymentResult>> {
+    let sender = ctx
+        .default_sender()
+        .context("The client has no sender to deploy from")?;
+    let first_nonce = timeouts
+        .rpc(
+            "nonce",
+            ctx.get_transaction_count(sender, Some(BlockNumber::Pending.into())),
+        )
+        .await?;
+
+    // Build and price every creation before any is sent
+    let client = Arc::new(ctx.clone());
+    let mut txs = Vec::with_capacity(specs.len());
+    for (index, spec) in specs.iter().enumerate() {
+        let factory = ContractFactory::new(spec.abi.clone(), spec.bytecode.clone(), client.clone());
+        let mut tx = factory.deploy_tokens(spec.args.clone())?.tx;
+        tx.set_from(sender);
+        on_progress(DeployProgress::Estimating {
+            name: spec.name.clone(),
+        });
+        let gas = timeouts
+            .rpc("gas estimation", ctx.estimate_gas(&tx, None))
+            .await
+            .wrap_err_with(|| format!("Failed to estimate the deployment of {}", spec.name))?;
+        tx.set_gas(gas);
+        tx.set_nonce(first_nonce + index);
+        txs.push(tx);
+    }
+
+    let deployments = specs.iter().zip(txs).map(|(spec, tx)| async move {
+        let pending = timeouts
+            .rpc("deployment submission", ctx.send_transaction(tx, None))
+            .await?;
+        on_progress(DeployProgress::Submitted {
+            name: spec.name.clone(),
+            tx_hash: *pending,
+        });
+        let receipt = timeouts
+            .receipt("deployment", pending.await_smart(ctx))
+            .await?;
+        let result = DeploymentResult::from_receipt(&receipt)
+            .wrap_err_with(|| format!("Deployment of {} failed", spec.name))?;
+        info!(contract = %spec.name, address = ?result.address, "deployed");
+        on_progress(DeployProgress::Confirmed {
+            name: spec.name.clone(),
+            address: result.address,
+        });
+        Ok(result)
+    });
+    join_all(deployments).await.into_iter().collect()
+}
+
+/// Addresses the next `count` contracts created by `client`'s sender will get, counted from its
+/// pending nonce: the addresses a [`deploy_concurrent`] batch of `count` specs deploys to, so later
+/// specs can take earlier ones as constructor arguments.
+pub async fn planned_addresses<M: Middleware>(client: &M, count: usize) -> Result<Vec<Address>>
+where
+    M::Error: 'static,
+{
+    let sender = client
+        .default_sender()
+        .context("The client has no sender to deploy from")?;
+    let first_nonce = client
+        .get_transaction_count(sender, Some(BlockNumber::Pending.into()))
+        .await?
+        .as_u64();
+    Ok((0..count as u64)
+        .map(|index| compute_create_address(sender, first_nonce + index))
+        .collect())
+}
+
+/// Estimates what deploying `specs` in order from the sender of `ctx`'s client would cost in Wei,
+/// without sending anything: the gas estimate of every creation, summed and priced at the next
+/// block's base fee, as [`deploy_contract`] prices a legacy deployment.
+///
+/// Build the arguments of dependent specs with [`planned_addresses`], which follows the same
+/// running nonce. A constructor that calls into an earlier contract of the plan can't be
+/// estimated before that contract exists, and fails naming the spec.
+pub async fn estimate_plan_cost<M: Middleware + 'static>(
+    ctx: &ChainContext<M>,
+    specs: &[DeploySpec],
+) -> Result<U256>
+where
+    M::Error: 'static,
+{
+    let sender = ctx
+        .default_sender()
+        .context("The client has no sender to deploy from")?;
+    let gas_price = ctx
+        .get_block(BlockNumber::Latest)
+        .await?
+        .context("Failed to get block")?
+        .next_block_base_fee()
+        .context("Failed to get the base fee for the next block")?;
+
+    let mut total_gas = U256::zero();
+    for spec in specs {
+        // Init code is the bytecode followed by the encoded constructor arguments
+        let init_code = match spec.abi.constructor() {
+            Some(constructor) => constructor.encode_input(spec.bytecode.to_vec(), &spec.args)?,
+            None => spec.bytecode.to_vec(),
+        };
+        let tx: TypedTransaction = TransactionRequest::new()
+            .from(sender)
+            .data(init_code)
+            .into();
+        let gas = ctx
+            .estimate_gas(&tx, None)
+            .await
+            .map_err(|e| eyre!(e))
+            .wrap_err_with(|| format!("Failed to estimate the deployment of {}", spec.name))?;
+        debug!(contract = %spec.name, %gas, "deployment estimated");
+        total_gas += gas;
+    }
+    Ok(total_gas * gas_price)
+}
+
+/// Checks that every one of `addresses` holds code, such as the dependencies a deployment is
+/// about to be wired to, failing with [`Error::MissingContracts`] listing those that don't.
+pub async fn assert_contracts<M: Middleware>(provider: &M, addresses: &[Address]) -> Result<()>
+where
+    M::Error: 'static,
+{
+    let codes = try_join_all(
+        addresses
+            .iter()
+            .map(|&address| provider.get_code(address, None)),
+    )
+    .await
+    .map_err(|e| eyre!(e))?;
+    let missing: Vec<Address> = addresses
+        .iter()
+        .zip(codes)
+        .filter(|(_, code)| code.is_empty())
+        .map(|(&address, _)| address)
+        .collect();
+    if !missing.is_empty() {
+        return Err(Error::MissingContracts { addresses: missing }.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mined_receipt, mock_provider, receipt_reply, spawn_mock_node};
+    use crate::wallet::select_account;
+    use ethers::types::{Eip1559TransactionRequest, TransactionRequest, H256};
+    use ethers::utils::{get_contract_address, rlp::Rlp, Ganache};
+    use serde_json::json;
+    use std::{
+        collections::HashMap,
+        fmt::Debug,
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Id, Record},
+        Subscriber,
+    };
+    use tracing_subscriber::{
+        layer::{Context, SubscriberExt},
+        registry::LookupSpan,
+        Layer, Registry,
+    };
+
+    /// Collects the fields recorded on the `deploy` span.
+    #[derive(Clone, Default)]
+    struct DeploySpanFields(Arc<Mutex<HashMap<String, String>>>);
+
+    impl Visit for DeploySpanFields {
+        fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+            let mut fields = self.0.lock().unwrap();
+            fields.insert(field.name().to_string(), format!("{value:?}"));
+        }
+    }
+
+    impl<S> Layer<S> for DeploySpanFields
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+    {
+        fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+            if attrs.metadata().name() == "deploy" {
+                attrs.record(&mut self.clone());
+            }
+        }
+
+        fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+            if ctx.metadata(id).is_some_and(|meta| meta.name() == "deploy") {
+                values.record(&mut self.clone());
+            }
+        }
+    }
+
+    #[test]
+    fn test_compute_create_address_matches_reference() {
+        let deployer = "0x6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0"
+            .parse::<Address>()
+            .unwrap();
+
+        // Known vectors for the first nonces of this deployer
+        assert_eq!(
+            compute_create_address(deployer, 0),
+            "0xcd234a471b72ba2f1ccf0a70fcaba648a5eecd8d"
+                .parse::<Address>()
+                .unwrap()
+        );
+        assert_eq!(
+            compute_create_address(deployer, 1),
+            "0x343c43a37d37dff08ae8c4a11544c718abb4fcf8"
+                .parse::<Address>()
+                .unwrap()
+        );
+        assert_eq!(
+            compute_create_address(deployer, 300),
+            get_contract_address(deployer, 300u64)
+        );
+    }
+
+    #[test]
+    fn test_scale_gas_rounds_up() {
+        assert_eq!(
+            scale_gas(U256::from(100_000u64), 1.0),
+            U256::from(100_000u64)
+        );
+        assert_eq!(
+            scale_gas(U256::from(100_000u64), 1.5),
+            U256::from(150_000u64)
+        );
+        assert_eq!(scale_gas(U256::from(21_001u64), 1.2), U256::from(25_202u64));
+    }
+
+    #[tokio::test]
+    async fn test_multiplier_below_one_is_rejected() -> Result<()> {
+        // Nothing listens here: the guard must fire before any request is made
+        let provider = Provider::try_from("http://127.0.0.1:1")?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let options = DeployOptions {
+            gas_limit_multiplier: 0.9,
+            ..DeployOptions::default()
+        };
+
+        let err = deploy_contract(
+            &ctx,
+            wallet,
+            "Empty",
+            Abi::default(),
+            Bytes::default(),
+            (),
+            &options,
+        )
+        .await
+        .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::InvalidGasLimitMultiplier { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_bump_fees_covers_the_base_fee() {
+        let mut legacy: TypedTransaction = TransactionRequest::new().gas_price(800u64).into();
+        bump_fees(&mut legacy, U256::from(100u64));
+        assert_eq!(legacy.gas_price(), Some(U256::from(900u64)));
+        bump_fees(&mut legacy, U256::from(5_000u64));
+        assert_eq!(legacy.gas_price(), Some(U256::from(5_000u64)));
+
+        let mut eip1559: TypedTransaction = Eip1559TransactionRequest::new()
+            .max_fee_per_gas(1_000u64)
+            .max_priority_fee_per_gas(8u64)
+            .into();
+        bump_fees(&mut eip1559, U256::from(2_000u64));
+        match eip1559 {
+            TypedTransaction::Eip1559(tx) => {
+                assert_eq!(tx.max_priority_fee_per_gas, Some(U256::from(9u64)));
+                assert_eq!(tx.max_fee_per_gas, Some(U256::from(2_009u64)));
+            }
+            other => panic!("expected an EIP-1559 transaction, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_underpriced_deployment_is_resubmitted() -> Result<()> {
+        let contract = Address::repeat_byte(0xcc);
+        let base_fee = U256::from(1_000_000_000u64);
+        let risen_base_fee = base_fee * 2;
+        let raw_txs = Arc::new(Mutex::new(Vec::<Bytes>::new()));
+        let seen = raw_txs.clone();
+        let url = spawn_mock_node(move |method, params| {
+            let hash = format!("{:?}", H256::repeat_byte(0xab));
+            // The base fee doubles once the first submission has been seen
+            let block_base_fee = if seen.lock().unwrap().is_empty() {
+                base_fee
+            } else {
+                risen_base_fee
+            };
+            match method {
+                "eth_chainId" => json!("0x539"),
+                "eth_getBlockByNumber" => json!({
+                    "number": "0x1",
+                    "hash": format!("{:?}", H256::repeat_byte(0x01)),
+                    "parentHash": format!("{:?}", H256::zero()),
+                    "baseFeePerGas": format!("{block_base_fee:#x}"),
+                    // Half full, so the next block keeps the same base fee
+                    "gasUsed": "0xe4e1c0",
+                    "gasLimit": "0x1c9c380",
+                    "timestamp": "0x1",
+                    "transactions": [],
+                    "uncles": [],
+                }),
+                "eth_estimateGas" => json!("0x5208"),
+                "eth_gasPrice" => json!(format!("{base_fee:#x}")),
+                "eth_getBalance" => json!("0xde0b6b3a7640000"),
+                "eth_getTransactionCount" => json!("0x0"),
+                "eth_blockNumber" => json!("0x2"),
+                "eth_sendRawTransaction" => {
+                    let mut raw_txs = seen.lock().unwrap();
+                    raw_txs.push(params[0].as_str().unwrap_or_default().parse().unwrap());
+                    if raw_txs.len() == 1 {
+                        json!({ "error": { "code": -32000, "message": "transaction underpriced" } })
+                    } else {
+                        json!(hash)
+                    }
+                }
+                "eth_getTransactionByHash" => json!({
+                    "hash": hash,
+                    "nonce": "0x0",
+                    "blockHash": format!("{:?}", H256::repeat_byte(0x02)),
+                    "blockNumber": "0x2",
+                    "transactionIndex": "0x0",
+                    "from": format!("{:?}", Address::repeat_byte(0x11)),
+                    "to": null,
+                    "value": "0x0",
+                    "gasPrice": format!("{base_fee:#x}"),
+                    "gas": "0x5208",
+                    "input": "0x",
+                    "v": "0x0",
+                    "r": "0x1",
+                    "s": "0x1",
+                }),
+                "eth_getTransactionReceipt" => json!({
+                    "transactionHash": hash,
+                    "transactionIndex": "0x0",
+                    "blockHash": format!("{:?}", H256::repeat_byte(0x02)),
+                    "blockNumber": "0x2",
+                    "from": format!("{:?}", Address::repeat_byte(0x11)),
+                    "to": null,
+                    "cumulativeGasUsed": "0x5208",
+                    "gasUsed": "0x5208",
+                    "contractAddress": format!("{contract:?}"),
+                    "logs": [],
+                    "logsBloom": format!("0x{}", "00".repeat(256)),
+                    "status": "0x1",
+                }),
+                other => {
+                    json!({ "error": { "code": -32601, "message": format!("{other} not mocked") } })
+                }
+            }
+        })
+        .await?;
+
+        let provider = Provider::try_from(url)?.interval(Duration::from_millis(10));
+        let ctx = ChainContext::new(provider.clone());
+        let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let (_, result) = deploy_contract(
+            &ctx,
+            wallet,
+            "Empty",
+            Abi::default(),
+            Bytes::from(vec![0x00]),
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        assert_eq!(result.address, contract);
+
+        // Rejected once, then accepted at a higher gas price
+        let raw_txs = raw_txs.lock().unwrap();
+        assert_eq!(raw_txs.len(), 2);
+        let gas_price = |raw: &Bytes| -> Result<U256> {
+            let (tx, _) = TypedTransaction::decode_signed(&Rlp::new(raw))?;
+            tx.gas_price().context("Missing gas price")
+        };
+        assert_eq!(gas_price(&raw_txs[0])?, base_fee);
+        assert_eq!(gas_price(&raw_txs[1])?, risen_base_fee);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_node_reports_failed_event() -> Result<()> {
+        let provider = Provider::try_from("http://127.0.0.1:1")?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet = LocalWallet::new(&mut ethers::core::rand::thread_rng());
+        let (progress, mut events) = tokio::sync::mpsc::unbounded_channel();
+        let options = DeployOptions {
+            progress: Some(progress),
+            ..DeployOptions::default()
+        };
+
+        let err = deploy_contract(
+            &ctx,
+            wallet,
+            "Empty",
+            Abi::default(),
+            Bytes::default(),
+            (),
+            &options,
+        )
+        .await
+        .unwrap_err();
+        drop(options);
+
+        // The chain id request fails before any stage completes
+        let event = events.recv().await.expect("a failure event");
+        assert!(
+            matches!(&event, DeployEvent::Failed { error } if *error == format!("{err:#}")),
+            "{event:?}"
+        );
+        assert_eq!(events.recv().await, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_progress_events_in_order() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let (progress, mut events) = tokio::sync::mpsc::unbounded_channel();
+        let options = DeployOptions {
+            progress: Some(progress),
+            ..DeployOptions::default()
+        };
+
+        let (_, result) =
+            compile_and_deploy(&ctx, wallet, "examples/", "Migrations", (), &options).await?;
+        drop(options);
+        let mut received = Vec::new();
+        while let Some(event) = events.recv().await {
+            received.push(event);
+        }
+
+        assert_eq!(received.len(), 6, "{received:?}");
+        assert_eq!(received[0], DeployEvent::CompileStarted);
+        assert!(matches!(received[1], DeployEvent::CompileFinished { .. }));
+        assert_eq!(
+            received[2],
+            DeployEvent::ArtifactResolved {
+                name: "Migrations".to_string()
+            }
+        );
+        match received[3] {
+            DeployEvent::GasEstimated { limit, cost } => {
+                assert!(limit >= result.gas_used);
+                assert!(cost > U256::zero());
+            }
+            ref other => panic!("expected GasEstimated, got {other:?}"),
+        }
+        assert_eq!(
+            received[4],
+            DeployEvent::TxSubmitted {
+                hash: result.tx_hash
+            }
+        );
+        assert_eq!(
+            received[5],
+            DeployEvent::Confirmed {
+                block: result.block_number,
+                confirmations: 1
+            }
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_gas_limit_multiplier_is_applied() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+
+        // Estimate the plain creation the same way the deployment does
+        let creation = TransactionRequest::new()
+            .from(wallet.address())
+            .data(bytecode.clone());
+        let estimate = provider.estimate_gas(&creation.into(), None).await?;
+
+        let options = DeployOptions {
+            gas_limit_multiplier: 1.5,
+            ..DeployOptions::default()
+        };
+        let (_, result) = deploy_contract(
+            &ctx,
+            wallet,
+            "BUSDImplementation",
+            abi,
+            bytecode,
+            (),
+            &options,
+        )
+        .await?;
+
+        let tx = provider
+            .get_transaction(result.tx_hash)
+            .await?
+            .context("Deployment transaction not found")?;
+        let expected = estimate * 3 / 2;
+        assert!(
+            tx.gas >= expected && tx.gas <= expected + 1,
+            "Gas limit {} should be 1.5x the estimate {estimate}",
+            tx.gas
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_predicted_address_matches_deployment() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+
+        // Predict the address from the deployer's current nonce
+        let nonce = provider
+            .get_transaction_count(wallet.address(), None)
+            .await?;
+        let expected = compute_create_address(wallet.address(), nonce.as_u64());
+
+        let (contract, _) = deploy_contract(
+            &ctx,
+            wallet,
+            "BUSDImplementation",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        assert_eq!(
+            contract.address(),
+            expected,
+            "Deployed address should match prediction"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_deploy_span_records_name_and_address() -> Result<()> {
+        let fields = DeploySpanFields::default();
+        let _guard = tracing::subscriber::set_default(Registry::default().with(fields.clone()));
+
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "Migrations")?;
+        let (contract, _) = deploy_contract(
+            &ctx,
+            wallet,
+            "Migrations",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+
+        let recorded = fields.0.lock().unwrap();
+        assert_eq!(
+            recorded.get("contract").map(String::as_str),
+            Some("Migrations")
+        );
+        assert_eq!(
+            recorded.get("address"),
+            Some(&format!("{:?}", contract.address())),
+            "Span should record the final address"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_deploy_and_initialize_busd() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let owner = wallet.address();
+        let client = Arc::new(SignerMiddleware::new(
+            provider.clone(),
+            wallet.with_chain_id(chain_id),
+        ));
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+
+        // BUSD's constructor already runs `initialize()`; the proxy-era initializer left is
+        // `initializeDomainSeparator()`
+        let result = deploy_and_initialize(
+            client.clone(),
+            abi.clone(),
+            bytecode,
+            (),
+            "initializeDomainSeparator",
+            (),
+        )
+        .await?;
+        assert!(result.init_tx_hash.is_some());
+        assert!(result.gas_used > U256::zero());
+
+        let contract = Contract::new(result.address, abi, client);
+        let actual_owner: Address = contract.method("owner", ())?.call().await?;
+        assert_eq!(actual_owner, owner, "Deployer should own the contract");
+
+        // Calling the spent `initialize()` as the init step reports the revert
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let client = contract.client();
+        assert!(
+            deploy_and_initialize(client, abi, bytecode, (), "initialize", ())
+                .await
+                .is_err()
+        );
+
+        Ok(())
+    }
+
+    const OWNABLE_SOURCE: &str = r#"pragma solidity 0.4.24;
+
+contract Ownable {
+    address public owner;
+
+    constructor() public {
+        owner = msg.sender;
+    }
+
+    function transferOwnership(address newOwner) public {
+        require(msg.sender == owner, "not the owner");
+        require(newOwner != address(0), "zero owner");
+        owner = newOwner;
+    }
+
+    function renounceOwnership() public {
+        require(msg.sender == owner, "not the owner");
+        owner = address(0);
+    }
+}
+"#;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_deploy_and_transfer_ownership() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let client = Arc::new(SignerMiddleware::new(
+            provider.clone(),
+            wallet.with_chain_id(chain_id),
+        ));
+        let multisig = Address::from_low_u64_be(0x5afe);
+
+        let dir = std::env::temp_dir().join(format!("ownable-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("Ownable.sol"), OWNABLE_SOURCE)?;
+        let folder = format!("{}/", dir.display());
+        let project = compile(&folder).await;
+        std::fs::remove_dir_all(&dir)?;
+        let (abi, bytecode) = contract_parts(&project?.output, &folder, "Ownable")?;
+
+        let result = deploy_and_transfer_ownership(
+            client.clone(),
+            abi.clone(),
+            bytecode.clone(),
+            (),
+            multisig,
+        )
+        .await?;
+        let contract = Contract::new(result.address, abi.clone(), client.clone());
+        let owner: Address = contract.method("owner", ())?.call().await?;
+        assert_eq!(owner, multisig);
+
+        // The zero address renounces instead
+        let result =
+            deploy_and_transfer_ownership(client.clone(), abi, bytecode, (), Address::zero())
+                .await?;
+        let contract = Contract::new(result.address, contract.abi().clone(), client.clone());
+        let owner: Address = contract.method("owner", ())?.call().await?;
+        assert_eq!(owner, Address::zero());
+
+        // Migrations has no owner, so nothing is deployed
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "Migrations")?;
+        let nonce = provider
+            .get_transaction_count(client.address(), None)
+            .await?;
+        let err = deploy_and_transfer_ownership(client.clone(), abi, bytecode, (), multisig)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::NotOwnable { function }) if function == "owner()"
+        ));
+        assert_eq!(
+            provider
+                .get_transaction_count(client.address(), None)
+                .await?,
+            nonce
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_deploy_four_contracts_concurrently() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let sender = wallet.address();
+        let client = Arc::new(SignerMiddleware::new(
+            provider.clone(),
+            wallet.with_chain_id(chain_id),
+        ));
+
+        let project = compile("examples/").await?.output;
+        let specs: Vec<DeploySpec> = ["Migrations", "BUSDImplementation"]
+            .iter()
+            .cycle()
+            .take(4)
+            .map(|name| {
+                let (abi, bytecode) = contract_parts(&project, "examples/", name)?;
+                Ok(DeploySpec {
+                    name: name.to_string(),
+                    abi,
+                    bytecode,
+                    args: vec![],
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        let results = deploy_concurrent(&ChainContext::new(client), specs).await?;
+        assert_eq!(results.len(), 4);
+        // Nonces 0 to 3 went to the specs in order, whatever order they were mined in
+        for (nonce, result) in results.iter().enumerate() {
+            assert_eq!(result.address, compute_create_address(sender, nonce as u64));
+            assert!(!provider.get_code(result.address, None).await?.is_empty());
+        }
+        assert_eq!(
+            provider.get_transaction_count(sender, None).await?,
+            U256::from(4)
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_plan_cost_sums_each_deployment() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let client = Arc::new(SignerMiddleware::new(
+            provider.clone(),
+            wallet.with_chain_id(chain_id),
+        ));
+
+        // The vault takes the address the token will be deployed at
+        let folder = "tests/fixtures/plan/";
+        let project = compile(folder).await?.output;
+        let planned = planned_addresses(client.as_ref(), 2).await?;
+        let spec = |name: &str, args: Vec<Token>| -> Result<DeploySpec> {
+            let (abi, bytecode) = contract_parts(&project, folder, name)?;
+            Ok(DeploySpec {
+                name: name.to_string(),
+                abi,
+                bytecode,
+                args,
+            })
+        };
+        let specs = vec![
+            spec("TokenA", vec![Token::String("TKA".into())])?,
+            spec(
+                "Vault",
+                vec![
+                    Token::Address(planned[0]),
+                    Token::Array(vec![Token::Address(planned[0])]),
+                ],
+            )?,
+        ];
+
+        let ctx = ChainContext::new(client.clone());
+        let total = estimate_plan_cost(&ctx, &specs).await?;
+        let token = estimate_plan_cost(&ctx, &specs[..1]).await?;
+        let vault = estimate_plan_cost(&ctx, &specs[1..]).await?;
+        assert!(!token.is_zero() && !vault.is_zero());
+        assert_eq!(total, token + vault);
+        // Estimating sent nothing
+        assert!(provider
+            .get_transaction_count(client.address(), None)
+            .await?
+            .is_zero());
+
+        // The plan lands where it was planned, for no more than the estimate
+        let base_fee = ctx
+            .get_block(BlockNumber::Latest)
+            .await?
+            .and_then(|block| block.next_block_base_fee())
+            .context("Missing base fee")?;
+        let results = deploy_concurrent(&ctx, specs).await?;
+        assert_eq!(
+            results
+                .iter()
+                .map(|result| result.address)
+                .collect::<Vec<_>>(),
+            planned
+        );
+        // Priced like the estimate, the gas used stays within it
+        let spent = results.iter().fold(U256::zero(), |spent, result| {
+            spent + result.gas_used * base_fee
+        });
+        assert!(spent <= total, "spent {spent}, estimated {total}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_deploy_from_selected_account() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+
+        let err = select_account(ganache.keys(), ganache.keys().len()).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::AccountIndexOutOfRange {
+                index: 10,
+                accounts: 10
+            })
+        ));
+
+        let wallet = select_account(ganache.keys(), 2)?;
+        let (_, result) = compile_and_deploy(
+            &ctx,
+            wallet,
+            "examples/",
+            "Migrations",
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        let tx = provider
+            .get_transaction(result.tx_hash)
+            .await?
+            .context("Missing deployment transaction")?;
+        assert_eq!(tx.from, ganache.addresses()[2]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_deploy_batch_reports_progress() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let client = Arc::new(SignerMiddleware::new(
+            provider,
+            wallet.with_chain_id(chain_id),
+        ));
+
+        let names = ["Migrations", "BUSDImplementation"];
+        let events = Mutex::new(Vec::new());
+        let results = deploy_batch(
+            &ChainContext::new(client),
+            "examples/",
+            names
+                .iter()
+                .map(|name| (name.to_string(), vec![]))
+                .collect(),
+            Some(&|event| events.lock().unwrap().push(event)),
+        )
+        .await?;
+        let events = events.into_inner().unwrap();
+        assert_eq!(events.len(), 8, "{events:?}");
+
+        // Every contract is compiled, then estimated, before anything is sent
+        let named = |stage: fn(String) -> DeployProgress| -> Vec<DeployProgress> {
+            names.iter().map(|name| stage(name.to_string())).collect()
+        };
+        assert_eq!(
+            events[..2],
+            named(|name| DeployProgress::Compiling { name })
+        );
+        assert_eq!(
+            events[2..4],
+            named(|name| DeployProgress::Estimating { name })
+        );
+
+        // Each contract is then submitted and confirmed, the two possibly interleaved
+        for (name, result) in names.iter().zip(&results) {
+            let position = |expected: DeployProgress| {
+                events
+                    .iter()
+                    .position(|event| *event == expected)
+                    .unwrap_or_else(|| panic!("missing {expected:?} in {events:?}"))
+            };
+            let submitted = position(DeployProgress::Submitted {
+                name: name.to_string(),
+                tx_hash: result.tx_hash,
+            });
+            let confirmed = position(DeployProgress::Confirmed {
+                name: name.to_string(),
+                address: result.address,
+            });
+            assert!(submitted > 3 && submitted < confirmed);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_deploy_to_two_chains() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let spawn = |chain_id: u64| {
+            Ganache::new()
+                .mnemonic(mnemonic)
+                .args(["--chain.chainId", &chain_id.to_string()])
+                .spawn()
+        };
+        let (first, second) = (spawn(1338), spawn(1339));
+        let targets = vec![
+            ChainTarget {
+                endpoint: second.endpoint(),
+                chain_id: 1339,
+            },
+            ChainTarget {
+                endpoint: first.endpoint(),
+                chain_id: 1338,
+            },
+            // Listed under the wrong chain id, so it must fail on its own
+            ChainTarget {
+                endpoint: first.endpoint(),
+                chain_id: 1,
+            },
+        ];
+        let key = first.keys()[0].clone();
+
+        let project = compile("examples/").await?.output;
+        let results = deploy_to_many(
+            targets,
+            &project,
+            "Migrations",
+            |_| key.clone().into(),
+            &DeployOptions::default(),
+        )
+        .await?;
+
+        let chain_ids: Vec<u64> = results.iter().map(|(chain_id, _)| *chain_id).collect();
+        assert_eq!(chain_ids, [1, 1338, 1339]);
+        let err = results[0].1.as_ref().unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ChainIdMismatch {
+                expected: 1,
+                actual: 1338,
+                ..
+            })
+        ));
+
+        for ((chain_id, result), ganache) in results[1..].iter().zip([&first, &second]) {
+            let deployment = result
+                .as_ref()
+                .unwrap_or_else(|e| panic!("chain {chain_id}: {e:?}"));
+            let provider = Provider::try_from(ganache.endpoint())?;
+            let code = provider.get_code(deployment.address, None).await?;
+            assert!(!code.is_empty(), "chain {chain_id} has no code");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_result_from_a_mocked_receipt() -> Result<()> {
+        let hash = H256::repeat_byte(0x44);
+        let mut receipt = mined_receipt(hash);
+        receipt.contract_address = Some(Address::repeat_byte(0xcc));
+        let pending = mined_receipt(hash);
+        let (provider, mock) = mock_provider([receipt_reply(&receipt), receipt_reply(&pending)]);
+
+        let fetched = provider
+            .get_transaction_receipt(hash)
+            .await?
+            .context("The mock has the receipt")?;
+        mock.assert_request("eth_getTransactionReceipt", [hash])?;
+        let result = DeploymentResult::from_receipt(&fetched)?;
+        assert_eq!(result.address, Address::repeat_byte(0xcc));
+        assert_eq!(result.tx_hash, hash);
+        assert_eq!(result.block_number, 7);
+        assert_eq!(result.gas_used, U256::from(21_000u64));
+
+        // Without a created address the receipt isn't a deployment's
+        let fetched = provider
+            .get_transaction_receipt(hash)
+            .await?
+            .context("The mock has the receipt")?;
+        assert!(DeploymentResult::from_receipt(&fetched).is_err());
+
+        // A reverted creation is no deployment either, even with an address in the receipt
+        receipt.status = Some(0u64.into());
+        let err = DeploymentResult::from_receipt(&receipt).unwrap_err();
+        assert!(err.to_string().contains("reverted"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_assert_contracts_names_accounts_without_code() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let (_, deployed) = compile_and_deploy(
+            &ctx,
+            wallet,
+            "examples/",
+            "Migrations",
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+
+        assert_contracts(&provider, &[deployed.address]).await?;
+
+        let eoa = ganache.addresses()[1];
+        let err = assert_contracts(&provider, &[deployed.address, eoa])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::MissingContracts { addresses }) if addresses == &[eoa]
+        ));
+        assert!(err.to_string().contains(&format!("{eoa:?}")));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,308 @@
+//! Dry runs: every step of a deployment or transfer up to, but not including, sending it.
+
+use crate::deploy::DeployOptions;
+use crate::fees::{resolve_fee_mode, FeeMode};
+use crate::funds::ensure_funds;
+use crate::simulate::revert_from_response;
+use crate::timeouts::OpTimeouts;
+use crate::transfer::TransferOptions;
+use crate::tx_builder::TxBuilder;
+use ethers::{
+    abi::{Abi, Tokenize},
+    prelude::{Address, BlockNumber, Bytes, JsonRpcClient, Middleware, Provider, U256},
+    providers::{ProviderError, RpcError},
+    types::transaction::eip2718::TypedTransaction,
+};
+use eyre::{ContextCompat, Result};
+use serde::Serialize;
+use tracing::info;
+
+/// What a deployment or transfer would send, and what it would cost.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DryRunReport {
+    pub from: Address,
+    /// Recipient, `None` for a contract creation.
+    pub to: Option<Address>,
+    pub value: U256,
+    /// Size of the calldata (the init code for a creation), in bytes.
+    pub calldata_size: usize,
+    /// Nonce the transaction would use.
+    pub nonce: U256,
+    /// Concrete fee mode, [`FeeMode::Auto`] resolved against the chain.
+    pub fee_mode: FeeMode,
+    /// Gas price, or the max fee per gas for EIP-1559.
+    pub fee_per_gas: U256,
+    /// Max priority fee per gas for EIP-1559.
+    pub priority_fee_per_gas: Option<U256>,
+    /// `estimate_gas` result; `None` when the simulation reverted.
+    pub gas_estimate: Option<U256>,
+    /// Upper bound of what the sender would pay, `value` included.
+    pub estimated_cost: Option<U256>,
+    /// Outcome of the `eth_call` simulation.
+    pub simulation: DryRunSimulation,
+    /// Steps that were skipped because they need a real state change.
+    pub notes: Vec<String>,
+}
+
+/// Outcome of simulating the transaction with `eth_call` against the latest block.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum DryRunSimulation {
+    /// The call succeeded; for a creation the output is the runtime code.
+    Succeeded { output_size: usize },
+    /// The call reverted, so the transaction would too.
+    Reverted { reason: Option<String> },
+}
+
+/// Dry-runs the deployment of `bytecode` with `constructor_args` from `from`: encodes the
+/// arguments, prices the transaction like [`deploy_contract`](crate::deploy::deploy_contract)
+/// does, simulates the creation and estimates its gas, without sending anything.
+pub async fn dry_run_deploy<T, A>(
+    provider: &Provider<T>,
+    from: Address,
+    abi: &Abi,
+    bytecode: Bytes,
+    constructor_args: A,
+    options: &DeployOptions,
+) -> Result<DryRunReport>
+where
+    T: JsonRpcClient,
+    A: Tokenize,
+{
+    // Init code is the bytecode followed by the encoded constructor arguments
+    let init_code = match abi.constructor() {
+        Some(constructor) => {
+            constructor.encode_input(bytecode.to_vec(), &constructor_args.into_tokens())?
+        }
+        None => bytecode.to_vec(),
+    };
+
+    let timeouts = &options.timeouts;
+    let fee_mode = timeouts
+        .rpc(
+            "fee mode detection",
+            resolve_fee_mode(provider, options.fee_mode),
+        )
+        .await?;
+    // Legacy deployments are priced at the next block's base fee
+    let gas_price = if fee_mode == FeeMode::Legacy {
+        let block = timeouts
+            .rpc("latest block", provider.get_block(BlockNumber::Latest))
+            .await?
+            .context("Failed to get block")?;
+        block
+            .next_block_base_fee()
+            .context("Failed to get the base fee for the next block")?
+    } else {
+        U256::zero()
+    };
+
+    let builder = TxBuilder::new()
+        .from(from)
+        .data(init_code)
+        .fee_mode(fee_mode);
+    let report = dry_run(provider, &builder, fee_mode, gas_price, timeouts).await?;
+    if let (true, Some(gas)) = (options.check_funds, report.gas_estimate) {
+        timeouts
+            .rpc(
+                "funds check",
+                ensure_funds(provider, from, U256::zero(), gas),
+            )
+            .await?;
+    }
+    Ok(report)
+}
+
+/// Dry-runs the transfer of `value` Wei from `from` to `to` like
+/// [`send_transfer`](crate::transfer::send_transfer) would send it, without sending anything.
+pub async fn dry_run_transfer<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    from: Address,
+    to: Address,
+    value: U256,
+    options: &TransferOptions,
+) -> Result<DryRunReport> {
+    let timeouts = &options.timeouts;
+    // Transfers are legacy transactions priced by the node
+    let gas_price = timeouts.rpc("gas price", provider.get_gas_price()).await?;
+
+    let mut builder = TxBuilder::new().from(from).to(to).value(value);
+    if let Some(gas_limit) = options.gas_limit {
+        builder = builder.gas_limit(gas_limit);
+    }
+    let report = dry_run(provider, &builder, FeeMode::Legacy, gas_price, timeouts).await?;
+    if let (true, Some(gas)) = (options.check_funds, report.gas_estimate) {
+        timeouts
+            .rpc("funds check", ensure_funds(provider, from, value, gas))
+            .await?;
+    }
+    Ok(report)
+}
+
+/// Builds the transaction, simulates it and estimates it; never calls `send_transaction`.
+async fn dry_run<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    builder: &TxBuilder,
+    fee_mode: FeeMode,
+    gas_price: U256,
+    timeouts: &OpTimeouts,
+) -> Result<DryRunReport> {
+    let mut tx = builder.build(provider).await?;
+    let from = *tx.from().context("A dry run needs a sender")?;
+    let nonce = timeouts
+        .rpc(
+            "nonce",
+            provider.get_transaction_count(from, Some(BlockNumber::Pending.into())),
+        )
+        .await?;
+    tx.set_nonce(nonce);
+
+    let (fee_per_gas, priority_fee_per_gas) = match &mut tx {
+        TypedTransaction::Eip1559(inner) => {
+            let (max_fee, priority_fee) = timeouts
+                .rpc("fee estimation", provider.estimate_eip1559_fees(None))
+                .await?;
+            inner.max_fee_per_gas = Some(max_fee);
+            inner.max_priority_fee_per_gas = Some(priority_fee);
+            (max_fee, Some(priority_fee))
+        }
+        _ => {
+            tx.set_gas_price(gas_price);
+            (gas_price, None)
+        }
+    };
+
+    let mut notes = Vec::new();
+    // The simulation's own error is the revert, so only the timeout is surfaced as a failure
+    let simulation = timeouts
+        .rpc("simulation", async {
+            Ok::<_, ProviderError>(provider.call(&tx, None).await)
+        })
+        .await?;
+    let simulation = match simulation {
+        Ok(output) => DryRunSimulation::Succeeded {
+            output_size: output.len(),
+        },
+        Err(err) => match err.as_error_response() {
+            Some(response) => DryRunSimulation::Reverted {
+                reason: revert_from_response(response).reason,
+            },
+            None => return Err(err.into()),
+        },
+    };
+
+    // A reverting transaction can't be estimated either
+    let gas_estimate = match simulation {
+        DryRunSimulation::Succeeded { .. } => Some(
+            timeouts
+                .rpc("gas estimation", provider.estimate_gas(&tx, None))
+                .await?,
+        ),
+        DryRunSimulation::Reverted { .. } => {
+            notes.push("gas estimation skipped: the simulation reverted".to_string());
+            None
+        }
+    };
+    let value = tx.value().copied().unwrap_or_default();
+    let estimated_cost = gas_estimate.map(|gas| gas * fee_per_gas + value);
+    notes.push("send_transaction skipped: dry run".to_string());
+    notes.push("receipt wait skipped: nothing was sent".to_string());
+
+    let report = DryRunReport {
+        from,
+        to: tx.to_addr().copied(),
+        value,
+        calldata_size: tx.data().map_or(0, |data| data.len()),
+        nonce,
+        fee_mode,
+        fee_per_gas,
+        priority_fee_per_gas,
+        gas_estimate,
+        estimated_cost,
+        simulation,
+        notes,
+    };
+    info!(from = ?report.from, nonce = %report.nonce, gas = ?report.gas_estimate, "dry run");
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::{compile, contract_parts};
+    use ethers::utils::Ganache;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_dry_run_busd_deployment_sends_nothing() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let from = ganache.addresses()[0];
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let nonce_before = provider.get_transaction_count(from, None).await?;
+        let block_before = provider.get_block_number().await?;
+
+        let report = dry_run_deploy(
+            &provider,
+            from,
+            &abi,
+            bytecode.clone(),
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+
+        // Nothing reached the chain
+        assert_eq!(
+            provider.get_transaction_count(from, None).await?,
+            nonce_before
+        );
+        assert_eq!(provider.get_block_number().await?, block_before);
+
+        assert_eq!(report.from, from);
+        assert_eq!(report.to, None);
+        assert_eq!(report.nonce, nonce_before);
+        assert_eq!(report.calldata_size, bytecode.len());
+        assert!(matches!(
+            report.simulation,
+            DryRunSimulation::Succeeded { output_size } if output_size > 0
+        ));
+        let gas = report
+            .gas_estimate
+            .context("BUSD deployment was estimated")?;
+        assert!(gas > U256::from(21_000u64));
+        assert_eq!(report.estimated_cost, Some(gas * report.fee_per_gas));
+        assert!(report.notes.iter().any(|note| note.contains("receipt")));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_dry_run_transfer_leaves_balances() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let (from, to) = (ganache.addresses()[0], ganache.addresses()[1]);
+        let balance_before = provider.get_balance(to, None).await?;
+
+        let value = U256::from(1000u64);
+        let report =
+            dry_run_transfer(&provider, from, to, value, &TransferOptions::default()).await?;
+
+        assert_eq!(provider.get_balance(to, None).await?, balance_before);
+        assert_eq!(report.to, Some(to));
+        assert_eq!(report.calldata_size, 0);
+        assert_eq!(report.gas_estimate, Some(U256::from(21_000u64)));
+        assert_eq!(
+            report.estimated_cost,
+            Some(U256::from(21_000u64) * report.fee_per_gas + value)
+        );
+
+        Ok(())
+    }
+}
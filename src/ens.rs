@@ -0,0 +1,291 @@
+//! Recipient parsing: checksummed hex addresses or ENS names resolved through the provider.
+
+use crate::errors::Error;
+use ethers::{
+    prelude::{Address, Middleware},
+    providers::ENS_ADDRESS,
+    utils::to_checksum,
+};
+use eyre::Result;
+use std::{fmt, str::FromStr};
+use tracing::warn;
+
+/// An address as typed by a user: hex, or an ENS name still to be resolved.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Recipient {
+    Address(Address),
+    Name(String),
+}
+
+impl FromStr for Recipient {
+    type Err = eyre::Report;
+
+    /// Dotted inputs without a `0x` prefix are names (`.eth`, or a DNS name imported into ENS);
+    /// anything else must be a hex address, whose EIP-55 checksum is checked when it mixes upper
+    /// and lower case.
+    fn from_str(input: &str) -> Result<Self> {
+        let input = input.trim();
+        if !input.starts_with("0x") && input.contains('.') {
+            return Ok(Self::Name(input.to_lowercase()));
+        }
+        parse_checksummed(input).map(Self::Address)
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address(address) => write!(f, "{}", to_checksum(address, None)),
+            Self::Name(name) => f.write_str(name),
+        }
+    }
+}
+
+impl Recipient {
+    /// The address to send to, looking names up in the chain's ENS registry.
+    ///
+    /// Fails with [`Error::EnsUnavailable`] on chains without a registry, such as a plain Ganache.
+    /// A reverse record that doesn't point back at the name is only warned about.
+    pub async fn resolve<M: Middleware>(&self, provider: &M) -> Result<Address>
+    where
+        M::Error: 'static,
+    {
+        let name = match self {
+            Self::Address(address) => return Ok(*address),
+            Self::Name(name) => name,
+        };
+        if provider.get_code(ENS_ADDRESS, None).await?.is_empty() {
+            let chain_id = provider.get_chainid().await?.as_u64();
+            return Err(Error::EnsUnavailable { chain_id }.into());
+        }
+        let address = provider
+            .resolve_name(name)
+            .await
+            .map_err(|err| Error::EnsNameNotFound {
+                name: name.clone(),
+                message: err.to_string(),
+            })?;
+        match provider.lookup_address(address).await {
+            Ok(reverse) if reverse.eq_ignore_ascii_case(name) => {}
+            Ok(reverse) => {
+                warn!(%name, ?address, %reverse, "reverse record points at another name")
+            }
+            Err(err) => warn!(%name, ?address, %err, "no reverse record for the resolved address"),
+        }
+        Ok(address)
+    }
+}
+
+/// Returns `name_or_addr` parsed as a hex address, or resolved through ENS when it is a name.
+pub async fn resolve_address<M: Middleware>(provider: &M, name_or_addr: &str) -> Result<Address>
+where
+    M::Error: 'static,
+{
+    name_or_addr.parse::<Recipient>()?.resolve(provider).await
+}
+
+/// Parses `input` (a hex address or an ENS name) and resolves it to an address.
+///
+/// Kept for existing callers; same as [`resolve_address`].
+pub async fn resolve_recipient<M: Middleware>(provider: &M, input: &str) -> Result<Address>
+where
+    M::Error: 'static,
+{
+    resolve_address(provider, input).await
+}
+
+/// Parses a hex address, rejecting mixed-case input whose EIP-55 checksum is wrong.
+pub fn parse_checksummed(input: &str) -> Result<Address> {
+    let invalid = |reason: &str| Error::InvalidAddress {
+        input: input.to_string(),
+        reason: reason.to_string(),
+    };
+    let address: Address = input
+        .parse()
+        .map_err(|_| invalid("expected 0x followed by 40 hex characters, or an ENS name"))?;
+    let digits = input.trim_start_matches("0x");
+    let mixed_case = digits.chars().any(|c| c.is_ascii_lowercase())
+        && digits.chars().any(|c| c.is_ascii_uppercase());
+    if mixed_case && to_checksum(&address, None) != input {
+        return Err(invalid("bad EIP-55 checksum").into());
+    }
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::spawn_mock_node;
+    use ethers::abi::{encode, Token};
+    use ethers::prelude::Provider;
+    use ethers::utils::Ganache;
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_hex_and_names() -> Result<()> {
+        // EIP-55 reference vector
+        let checksummed = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        let address = parse_checksummed(checksummed)?;
+        assert_eq!(parse_checksummed(&checksummed.to_lowercase())?, address);
+        assert_eq!(
+            checksummed.parse::<Recipient>()?,
+            Recipient::Address(address)
+        );
+        assert_eq!(
+            "Vitalik.eth".parse::<Recipient>()?,
+            Recipient::Name("vitalik.eth".to_string())
+        );
+        assert_eq!(
+            "nick.xyz".parse::<Recipient>()?,
+            Recipient::Name("nick.xyz".to_string())
+        );
+
+        // One letter's case flipped breaks the checksum
+        let err = parse_checksummed("0x5aaeb6053F3E94C9b9A09f33669435E7Ef1BeAed").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::InvalidAddress { reason, .. }) if reason.contains("checksum")
+        ));
+        assert!("0x1234".parse::<Recipient>().is_err());
+
+        Ok(())
+    }
+
+    fn is_mixed_case(digits: &str) -> bool {
+        digits.chars().any(|c| c.is_ascii_lowercase())
+            && digits.chars().any(|c| c.is_ascii_uppercase())
+    }
+
+    // A checksummed address, and the position of one of its letters when it has any
+    fn checksummed_with_letter() -> impl Strategy<Value = (Address, String, Option<usize>)> {
+        any::<[u8; 20]>().prop_flat_map(|bytes| {
+            let address = Address::from(bytes);
+            let checksummed = to_checksum(&address, None);
+            let letters: Vec<usize> = checksummed
+                .char_indices()
+                .skip(2)
+                .filter(|(_, c)| c.is_ascii_alphabetic())
+                .map(|(index, _)| index)
+                .collect();
+            let letter = if letters.is_empty() {
+                Just(None).boxed()
+            } else {
+                prop::sample::select(letters).prop_map(Some).boxed()
+            };
+            (Just(address), Just(checksummed), letter)
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn test_checksummed_and_single_case_forms_parse(
+            (address, checksummed, _) in checksummed_with_letter()
+        ) {
+            prop_assert_eq!(parse_checksummed(&checksummed).unwrap(), address);
+            prop_assert_eq!(parse_checksummed(&checksummed.to_lowercase()).unwrap(), address);
+            let upper = format!("0x{}", checksummed[2..].to_uppercase());
+            prop_assert_eq!(parse_checksummed(&upper).unwrap(), address);
+        }
+
+        #[test]
+        fn test_single_case_flips_are_rejected(
+            (_, checksummed, letter) in checksummed_with_letter()
+        ) {
+            let Some(index) = letter else { return Ok(()) };
+            let mut flipped = checksummed.into_bytes();
+            flipped[index] ^= 0x20;
+            let flipped = String::from_utf8(flipped).unwrap();
+            // A flip that leaves one case only has no checksum left to check
+            if is_mixed_case(&flipped[2..]) {
+                prop_assert!(parse_checksummed(&flipped).is_err(), "{} was accepted", flipped);
+            } else {
+                prop_assert!(parse_checksummed(&flipped).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn test_flip_regressions() {
+        // A single letter is never mixed case, so either case of it is accepted
+        let single = "0x000000000000000000000000000000000000000A";
+        assert!(parse_checksummed(single).is_ok());
+        assert!(parse_checksummed(&single.to_lowercase()).is_ok());
+        // This EIP-55 vector checksums to all uppercase; lowering one letter breaks it
+        let vector = "0x52908400098527886E0F7030069857D2E4169EE7";
+        assert!(parse_checksummed(vector).is_ok());
+        assert!(parse_checksummed("0x52908400098527886e0F7030069857D2E4169EE7").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_names_resolve_through_the_registry() -> Result<()> {
+        let resolver = Address::repeat_byte(0x11);
+        let owner = Address::repeat_byte(0x22);
+        let word = |token: Token| format!("0x{}", hex::encode(encode(&[token])));
+        let url = spawn_mock_node(move |method, params| match method {
+            "eth_chainId" => json!("0x1"),
+            "eth_getCode" => json!("0x6080"),
+            "eth_call" => {
+                let data = params[0]["data"]
+                    .as_str()
+                    .or(params[0]["input"].as_str())
+                    .unwrap_or_default();
+                match &data[..10] {
+                    "0x0178b8bf" => json!(word(Token::Address(resolver))), // resolver(bytes32)
+                    "0x01ffc9a7" => json!(word(Token::Bool(true))), // supportsInterface(bytes4)
+                    "0x3b3b57de" => json!(word(Token::Address(owner))), // addr(bytes32)
+                    "0x691f3431" => json!(word(Token::String("alice.eth".to_string()))), // name(bytes32)
+                    _ => json!({ "error": { "code": -32000, "message": "unexpected call" } }),
+                }
+            }
+            _ => json!({ "error": { "code": -32601, "message": "method not found" } }),
+        })
+        .await?;
+        let provider = Provider::try_from(url)?;
+
+        assert_eq!(resolve_address(&provider, "alice.eth").await?, owner);
+        let hex = "0xb794f5ea0ba39494ce839613fffba74279579268";
+        assert_eq!(resolve_address(&provider, hex).await?, hex.parse()?);
+        assert_eq!(resolve_recipient(&provider, "alice.eth").await?, owner);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_names_need_an_ens_registry() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+
+        let err = resolve_address(&provider, "vitalik.eth").await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::EnsUnavailable { chain_id: 1337 })
+        ));
+        assert_eq!(err.to_string(), "ENS unavailable on chain 1337");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_names_resolve_on_a_mainnet_fork() -> Result<()> {
+        // Forking needs an archive-capable mainnet endpoint, which CI doesn't always have
+        let Ok(fork_url) = std::env::var("MAINNET_RPC_URL") else {
+            eprintln!("MAINNET_RPC_URL is not set, skipping the forked ENS test");
+            return Ok(());
+        };
+        let ganache = Ganache::new().fork(fork_url).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+
+        let vitalik: Address = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045".parse()?;
+        assert_eq!(resolve_address(&provider, "vitalik.eth").await?, vitalik);
+        assert_eq!(
+            resolve_address(&provider, "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045").await?,
+            vitalik
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+//! ERC-20 token helpers.
+
+use ethers::{
+    abi::{decode, parse_abi, Function, ParamType, Token},
+    contract::Contract,
+    prelude::{Address, Bytes, Middleware, TransactionRequest, U256},
+    utils::format_units,
+};
+use eyre::{eyre, ContextCompat, Result};
+use serde::Serialize;
+use tracing::info;
+
+/// What a token says about itself, as [`token_metadata`] reads it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TokenMetadata {
+    pub address: Address,
+    /// `None` when the token has no `name` getter; the getter is optional in ERC-20.
+    pub name: Option<String>,
+    /// `None` when the token has no `symbol` getter.
+    pub symbol: Option<String>,
+    pub decimals: u8,
+    /// Total supply in the token's smallest unit.
+    pub total_supply: U256,
+    /// Total supply scaled by `decimals`, with every decimal, e.g. `1000.500000` for
+    /// 1000.5 tokens of 6 decimals.
+    pub total_supply_formatted: String,
+}
+
+/// Has `owner` approve `spender` for `amount` more tokens, then has `spender` move `amount` from
+/// `owner` to `recipient` with `transferFrom`.
+///
+/// Both transactions are sent with an explicit `from`, so `owner` and `spender` must be accounts
+/// the node unlocks (as Ganache's are) or the signer of `token`'s client. The allowance is raised
+/// by `amount` rather than set to it, so any allowance `spender` already had is left afterwards.
+pub async fn approve_and_transfer_from<M: Middleware + 'static>(
+    token: &Contract<M>,
+    owner: Address,
+    spender: Address,
+    recipient: Address,
+    amount: U256,
+) -> Result<()> {
+    let current: U256 = token.method("allowance", (owner, spender))?.call().await?;
+    let allowance = current
+        .checked_add(amount)
+        .context("Allowance overflows uint256")?;
+
+    let approve = token
+        .method::<_, bool>("approve", (spender, allowance))?
+        .from(owner);
+    let receipt = approve
+        .send()
+        .await?
+        .await?
+        .context("Missing approve receipt")?;
+    if receipt.status != Some(1u64.into()) {
+        return Err(eyre!(
+            "approve by {owner:?} reverted in tx {:?}",
+            receipt.transaction_hash
+        ));
+    }
+
+    let transfer = token
+        .method::<_, bool>("transferFrom", (owner, recipient, amount))?
+        .from(spender);
+    let receipt = transfer
+        .send()
+        .await?
+        .await?
+        .context("Missing transferFrom receipt")?;
+    if receipt.status != Some(1u64.into()) {
+        return Err(eyre!(
+            "transferFrom by {spender:?} reverted in tx {:?}",
+            receipt.transaction_hash
+        ));
+    }
+    info!(token = ?token.address(), from = ?owner, to = ?recipient, %amount, "tokens transferred by spender");
+    Ok(())
+}
+
+/// Reads the name, symbol, decimals and total supply of `token` concurrently.
+///
+/// Tokens that lack `name` or `symbol`, or revert on them, get `None` for those fields, and the
+/// `bytes32` variants some early tokens (such as MKR) return are decoded too. `decimals` and
+/// `totalSupply` are required.
+pub async fn token_metadata<M: Middleware>(provider: &M, token: Address) -> Result<TokenMetadata>
+where
+    M::Error: 'static,
+{
+    let abi = parse_abi(&[
+        "function name() view returns (string)",
+        "function symbol() view returns (string)",
+        "function decimals() view returns (uint8)",
+        "function totalSupply() view returns (uint256)",
+    ])?;
+    let call = |function: &Function| {
+        let tx = TransactionRequest::new()
+            .to(token)
+            .data(function.encode_input(&[]).unwrap_or_default());
+        async move { provider.call(&tx.into(), None).await }
+    };
+    let (name, symbol, decimals, total_supply) = futures::join!(
+        call(abi.function("name")?),
+        call(abi.function("symbol")?),
+        call(abi.function("decimals")?),
+        call(abi.function("totalSupply")?)
+    );
+    let decimals = decimals.map_err(|e| eyre!("decimals() of {token:?} failed: {e}"))?;
+    let total_supply = total_supply.map_err(|e| eyre!("totalSupply() of {token:?} failed: {e}"))?;
+
+    let decimals = match abi.function("decimals")?.decode_output(&decimals)?.pop() {
+        Some(Token::Uint(decimals)) if decimals <= U256::from(u8::MAX) => decimals.as_u32() as u8,
+        other => return Err(eyre!("decimals() of {token:?} returned {other:?}")),
+    };
+    let total_supply = match abi
+        .function("totalSupply")?
+        .decode_output(&total_supply)?
+        .pop()
+    {
+        Some(Token::Uint(total_supply)) => total_supply,
+        other => return Err(eyre!("totalSupply() of {token:?} returned {other:?}")),
+    };
+    Ok(TokenMetadata {
+        address: token,
+        name: name.ok().and_then(|output| decode_text(&output)),
+        symbol: symbol.ok().and_then(|output| decode_text(&output)),
+        decimals,
+        total_supply,
+        total_supply_formatted: format_units(total_supply, u32::from(decimals))?,
+    })
+}
+
+/// Decodes the output of a `name` or `symbol` getter, either an ABI string or a zero-padded
+/// `bytes32`; an empty text counts as missing.
+fn decode_text(output: &Bytes) -> Option<String> {
+    if let Ok(mut tokens) = decode(&[ParamType::String], output) {
+        if let Some(Token::String(text)) = tokens.pop() {
+            return Some(text).filter(|text| !text.is_empty());
+        }
+    }
+    if output.len() == 32 {
+        let text = output.split(|&b| b == 0).next().unwrap_or_default();
+        return String::from_utf8(text.to_vec())
+            .ok()
+            .filter(|text| !text.is_empty());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::deploy::{deploy_contract, DeployOptions};
+    use crate::project::{compile, contract_parts};
+    use ethers::prelude::{LocalWallet, Provider};
+    use ethers::utils::Ganache;
+
+    #[test]
+    fn test_decode_string_and_bytes32_text() -> Result<()> {
+        let string = Bytes::from(ethers::abi::encode(&[Token::String("Binance USD".into())]));
+        assert_eq!(decode_text(&string).as_deref(), Some("Binance USD"));
+        let mut word = [0u8; 32];
+        word[..3].copy_from_slice(b"MKR");
+        assert_eq!(decode_text(&Bytes::from(word)).as_deref(), Some("MKR"));
+        assert_eq!(decode_text(&Bytes::new()), None);
+        assert_eq!(decode_text(&Bytes::from([0u8; 32])), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_stablecoin_metadata() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let (busd, deployed) = deploy_contract(
+            &ctx,
+            wallet,
+            "BUSDImplementation",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        busd.method::<_, bool>("increaseSupply", ethers::utils::parse_ether("1000.5")?)?
+            .send()
+            .await?
+            .await?;
+
+        let metadata = token_metadata(&provider, deployed.address).await?;
+        assert_eq!(metadata.address, deployed.address);
+        assert_eq!(metadata.name.as_deref(), Some("Binance USD"));
+        assert_eq!(metadata.symbol.as_deref(), Some("BUSD"));
+        assert_eq!(metadata.decimals, 18);
+        assert_eq!(metadata.total_supply_formatted, "1000.500000000000000000");
+
+        // An account without code has no getters at all
+        assert!(token_metadata(&provider, Address::from_low_u64_be(0xdead))
+            .await
+            .is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_spender_moves_tokens_between_accounts() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let (owner, spender) = (ganache.addresses()[0], ganache.addresses()[1]);
+        let recipient = Address::from_low_u64_be(0xbeef);
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let (busd, _) = deploy_contract(
+            &ctx,
+            wallet,
+            "BUSDImplementation",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        busd.method::<_, ()>("unpause", ())?.send().await?.await?;
+        busd.method::<_, bool>("increaseSupply", U256::from(1_000u64))?
+            .send()
+            .await?
+            .await?;
+
+        // An allowance granted earlier survives the flow
+        busd.method::<_, bool>("approve", (spender, U256::from(100u64)))?
+            .send()
+            .await?
+            .await?;
+
+        approve_and_transfer_from(&busd, owner, spender, recipient, U256::from(40u64)).await?;
+
+        let received: U256 = busd.method("balanceOf", recipient)?.call().await?;
+        assert_eq!(received, U256::from(40u64));
+        let left: U256 = busd.method("balanceOf", owner)?.call().await?;
+        assert_eq!(left, U256::from(960u64));
+        let allowance: U256 = busd.method("allowance", (owner, spender))?.call().await?;
+        assert_eq!(allowance, U256::from(100u64));
+
+        Ok(())
+    }
+}
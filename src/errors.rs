@@ -0,0 +1,222 @@
+//! Typed errors surfaced by the helpers so callers can match on them.
+
+use crate::amount::format_eth;
+use ethers::providers::JsonRpcError;
+use ethers::types::{Address, Bytes, H256, U256};
+use std::{path::PathBuf, time::Duration};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The sender cannot cover the value plus the estimated gas cost.
+    #[error(
+        "insufficient funds: have {}, need {}, short by {}",
+        format_eth(*have),
+        format_eth(*need),
+        format_eth(*shortfall)
+    )]
+    InsufficientFunds {
+        have: U256,
+        need: U256,
+        shortfall: U256,
+    },
+
+    /// A BIP-39 phrase must have 12, 15, 18, 21 or 24 words.
+    #[error("invalid mnemonic word count {count}, expected 12, 15, 18, 21 or 24")]
+    InvalidMnemonicWordCount { count: usize },
+
+    /// A mnemonic word is not part of the English BIP-39 wordlist.
+    #[error("mnemonic word {index} ({word:?}) is not in the BIP-39 English wordlist")]
+    UnknownMnemonicWord { index: usize, word: String },
+
+    /// The mnemonic words are valid but their checksum bits don't match.
+    #[error("invalid mnemonic checksum")]
+    InvalidMnemonicChecksum,
+
+    /// A network operation didn't complete within its configured timeout.
+    #[error("{operation} timed out after {after:?}")]
+    OperationTimedOut { operation: String, after: Duration },
+
+    /// The artifact's bytecode still contains library placeholders that must be linked first.
+    #[error("bytecode has unlinked library placeholders: {}", placeholders.join(", "))]
+    UnlinkedLibraries { placeholders: Vec<String> },
+
+    /// A gas limit multiplier below 1.0 would set a limit under the estimate.
+    #[error("gas limit multiplier {multiplier} is below 1.0")]
+    InvalidGasLimitMultiplier { multiplier: f64 },
+
+    /// Source files import each other in a loop, listed from the first file back to itself.
+    #[error("circular import: {}", cycle.iter().map(|path| path.display().to_string()).collect::<Vec<_>>().join(" -> "))]
+    CircularImport { cycle: Vec<PathBuf> },
+
+    /// The private key is not 32 bytes of hex or not a valid secp256k1 key.
+    #[error("invalid private key: {reason}")]
+    InvalidPrivateKey { reason: String },
+
+    /// The node doesn't accept the `eth_call` state override parameter.
+    #[error("state overrides are not supported by this node: {message}")]
+    StateOverridesUnsupported { message: String },
+
+    /// A simulated call reverted.
+    #[error("call reverted: {}", reason.as_deref().unwrap_or("no reason given"))]
+    CallReverted { reason: Option<String>, data: Bytes },
+
+    /// The calldata's 4-byte selector matches no function of the ABI.
+    #[error("unknown function selector 0x{}", hex::encode(selector))]
+    UnknownSelector { selector: [u8; 4] },
+
+    /// The node doesn't expose the `debug_*` RPC namespace.
+    #[error("{method} is not supported by this node: {message}")]
+    DebugRpcUnsupported { method: String, message: String },
+
+    /// There is no pending transaction from the account at this nonce to replace.
+    #[error("no pending transaction from {from:?} at nonce {nonce}")]
+    NoPendingTransaction { from: Address, nonce: u64 },
+
+    /// A deployment plan step refers to a step that isn't deployed before it (itself included).
+    #[error("step {step} references {reference}, which is not an earlier step of the plan")]
+    UnresolvedPlanReference { step: String, reference: String },
+
+    /// Two deployment plan steps share a name.
+    #[error("the plan already has a step named {step}")]
+    DuplicatePlanStep { step: String },
+
+    /// An endpoint serves a different chain than the one it was listed for or checked against,
+    /// so nothing was sent. `endpoint` is the URL when the caller knows it.
+    #[error(
+        "{} serves chain {actual}, expected chain {expected}",
+        endpoint.as_deref().unwrap_or("the endpoint")
+    )]
+    ChainIdMismatch {
+        endpoint: Option<String>,
+        expected: u64,
+        actual: u64,
+    },
+
+    /// A CREATE2 deployment's address already holds code.
+    #[error("{address:?} (salt {salt:?}) already holds a contract")]
+    Create2Collision { salt: H256, address: Address },
+
+    /// The deployment was not confirmed, so nothing was sent.
+    #[error("deployment of {contract} was not confirmed")]
+    ConfirmationDeclined { contract: String },
+
+    /// Confirmation was requested but stdin isn't a terminal to answer from.
+    #[error("confirmation needs an interactive terminal; pass --yes to skip it")]
+    ConfirmationNeedsTerminal,
+
+    /// A block watcher lost its node; watching again after `last_block` resumes without gaps.
+    #[error("lost the node after block {last_block}: {message}")]
+    MonitorDisconnected { last_block: u64, message: String },
+
+    /// The address was still below the awaited balance when the wait timed out.
+    #[error(
+        "{address:?} held {} after {after:?}, waiting for at least {}",
+        format_eth(*balance),
+        format_eth(*at_least)
+    )]
+    BalanceNotReached {
+        address: Address,
+        balance: U256,
+        at_least: U256,
+        after: Duration,
+    },
+
+    /// A mined transaction left the canonical chain and wasn't mined again.
+    #[error("{tx_hash:?} was reorganized out of block {block_hash:?} and not mined again in {blocks} blocks")]
+    DroppedByReorg {
+        tx_hash: H256,
+        block_hash: H256,
+        blocks: u64,
+    },
+
+    /// The endpoint's genesis block isn't the expected one, so nothing was sent.
+    #[error(
+        "refusing to send: expected genesis hash {expected:?}, the endpoint reports {actual:?}"
+    )]
+    GenesisHashMismatch { expected: H256, actual: H256 },
+
+    /// The endpoint runs another client than the one expected, so nothing was sent.
+    #[error("refusing to send: expected client {expected}, the endpoint reports {actual}")]
+    UnexpectedClient { expected: String, actual: String },
+
+    /// Input that is neither a valid hex address nor an ENS name.
+    #[error("invalid address {input:?}: {reason}")]
+    InvalidAddress { input: String, reason: String },
+
+    /// The chain has no ENS registry to resolve names with.
+    #[error("ENS unavailable on chain {chain_id}")]
+    EnsUnavailable { chain_id: u64 },
+
+    /// The registry has no address for the name.
+    #[error("ENS name {name} could not be resolved: {message}")]
+    EnsNameNotFound { name: String, message: String },
+
+    /// A payment URI that doesn't follow EIP-681.
+    #[error("invalid payment URI {uri:?}: {reason}")]
+    InvalidPaymentUri { uri: String, reason: String },
+
+    /// A URI for another scheme than `ethereum:`.
+    #[error("unsupported URI scheme {scheme:?}, expected ethereum:")]
+    UnsupportedUriScheme { scheme: String },
+
+    /// An amount that isn't a whole, non-negative number of Wei.
+    #[error("invalid amount {input:?}: {reason}")]
+    InvalidAmount { input: String, reason: String },
+
+    /// Two command line options that each say the same thing.
+    #[error("{flag} can't be combined with {with}")]
+    ConflictingFlags { flag: String, with: String },
+
+    /// No Safe contract at the address, so its nonce can't be read.
+    #[error("no Safe deployed at {safe:?} on chain {chain_id}; pass its nonce with --safe-nonce")]
+    SafeNotDeployed { safe: Address, chain_id: u64 },
+
+    /// The requested account isn't among the node's accounts.
+    #[error("account index {index} is out of range: there are {accounts} accounts")]
+    AccountIndexOutOfRange { index: usize, accounts: usize },
+
+    /// Funding the recipients would take the funder below its reserve.
+    #[error(
+        "{funder:?} can't send {} without going below its reserve of {}: it has {}",
+        format_eth(*need),
+        format_eth(*reserve),
+        format_eth(*balance)
+    )]
+    ReserveExceeded {
+        funder: Address,
+        balance: U256,
+        need: U256,
+        reserve: U256,
+    },
+
+    /// The contract lacks a function needed to hand its ownership over.
+    #[error("the contract is not ownable: its ABI has no {function}")]
+    NotOwnable { function: String },
+
+    /// Addresses expected to hold contracts have no code.
+    #[error("no contract deployed at {}", .addresses.iter().map(|a| format!("{a:?}")).collect::<Vec<_>>().join(", "))]
+    MissingContracts { addresses: Vec<Address> },
+
+    /// The transaction used all the gas it was given and reverted.
+    #[error("transaction {tx_hash:?} ran out of gas at its limit of {gas_limit}")]
+    OutOfGas { tx_hash: H256, gas_limit: U256 },
+
+    /// The transaction was mined but reverted.
+    #[error("transaction {tx_hash:?} reverted")]
+    TransactionReverted { tx_hash: H256 },
+
+    /// A transaction signed offline lacks a field the node would otherwise fill in.
+    #[error("can't sign the transaction offline without its {}", .fields.join(", "))]
+    IncompleteTransaction { fields: Vec<String> },
+}
+
+/// Whether a node's error reply means it doesn't serve the method at all. Hosted endpoints and
+/// old Ganache versions answer "method not found" (-32601) or a variant of it for namespaces
+/// such as `debug_` and `txpool_`.
+pub fn is_method_unavailable(response: &JsonRpcError) -> bool {
+    let message = response.message.to_lowercase();
+    response.code == -32601
+        || message.contains("not supported")
+        || message.contains("does not exist")
+        || message.contains("not found")
+}
@@ -0,0 +1,219 @@
+//! Resumable event polling: fetch a contract's new events and remember where to start next time.
+
+use ethers::{
+    abi::{Event, RawLog, Token},
+    contract::Contract,
+    prelude::{Filter, Log, Middleware, H256},
+};
+use eyre::{eyre, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// The last block whose events were processed, kept in a JSON file across runs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EventCursor {
+    path: PathBuf,
+    /// `None` until a first poll, which then starts at block 0.
+    pub last_block: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CursorFile {
+    last_block: u64,
+}
+
+impl EventCursor {
+    /// Loads the cursor saved at `path`, or a fresh one when the file doesn't exist yet.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let last_block = match std::fs::read_to_string(&path) {
+            Ok(text) => {
+                let file: CursorFile = serde_json::from_str(&text)
+                    .wrap_err_with(|| format!("Invalid event cursor {}", path.display()))?;
+                Some(file.last_block)
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => {
+                return Err(err)
+                    .wrap_err_with(|| format!("Failed to read event cursor {}", path.display()))
+            }
+        };
+        Ok(Self { path, last_block })
+    }
+
+    /// Writes the cursor to its file, replacing it in one step so a crash can't leave half of it.
+    pub fn save(&self) -> Result<()> {
+        let Some(last_block) = self.last_block else {
+            return Ok(());
+        };
+        let json = serde_json::to_string(&CursorFile { last_block })?;
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, json)
+            .and_then(|()| std::fs::rename(&tmp, &self.path))
+            .wrap_err_with(|| format!("Failed to save event cursor {}", self.path.display()))
+    }
+
+    /// First block the next poll looks at.
+    pub fn next_block(&self) -> u64 {
+        self.last_block.map_or(0, |last| last + 1)
+    }
+}
+
+/// One event log decoded with the contract's ABI.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedEvent {
+    /// Name of the event.
+    pub name: String,
+    pub block: u64,
+    pub tx_hash: H256,
+    pub log_index: u64,
+    /// Parameters in declaration order, with their names.
+    pub args: Vec<(String, Token)>,
+}
+
+/// Fetches the `event` logs `contract` emitted after `cursor`'s block, up to the current head,
+/// and advances the cursor to that head.
+///
+/// The cursor is only moved in memory: save it once the events are processed, so a crash in
+/// between replays them rather than losing them.
+pub async fn poll_events<M: Middleware>(
+    contract: &Contract<M>,
+    event: &str,
+    cursor: &mut EventCursor,
+) -> Result<Vec<DecodedEvent>>
+where
+    M::Error: 'static,
+{
+    let event = contract.abi().event(event)?;
+    let client = contract.client_ref();
+    let head = client
+        .get_block_number()
+        .await
+        .map_err(|e| eyre!(e))?
+        .as_u64();
+    let from = cursor.next_block();
+    if from > head {
+        return Ok(Vec::new());
+    }
+
+    let filter = Filter::new()
+        .address(contract.address())
+        .topic0(event.signature())
+        .from_block(from)
+        .to_block(head);
+    let logs = client.get_logs(&filter).await.map_err(|e| eyre!(e))?;
+    let events = logs
+        .into_iter()
+        .map(|log| decode_log(event, log))
+        .collect::<Result<Vec<_>>>()?;
+    cursor.last_block = Some(head);
+    Ok(events)
+}
+
+/// Decodes one `event` log, as fetched with a filter on the event's topic.
+pub fn decode_log(event: &Event, log: Log) -> Result<DecodedEvent> {
+    let parsed = event.parse_log(RawLog {
+        topics: log.topics,
+        data: log.data.to_vec(),
+    })?;
+    Ok(DecodedEvent {
+        name: event.name.clone(),
+        block: log
+            .block_number
+            .ok_or_else(|| eyre!("Log without a block number"))?
+            .as_u64(),
+        tx_hash: log
+            .transaction_hash
+            .ok_or_else(|| eyre!("Log without a transaction hash"))?,
+        log_index: log.log_index.unwrap_or_default().as_u64(),
+        args: parsed
+            .params
+            .into_iter()
+            .map(|param| (param.name, param.value))
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::deploy::{deploy_contract, DeployOptions};
+    use crate::project::{compile, contract_parts};
+    use ethers::prelude::{Address, LocalWallet, Provider, U256};
+    use ethers::utils::Ganache;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_second_poll_returns_only_new_events() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let (busd, _) = deploy_contract(
+            &ctx,
+            wallet,
+            "BUSDImplementation",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        busd.method::<_, ()>("unpause", ())?.send().await?.await?;
+        busd.method::<_, bool>("increaseSupply", U256::from(1_000u64))?
+            .send()
+            .await?
+            .await?;
+        let transfer = |to: u64, amount: u64| {
+            let call = busd.method::<_, bool>(
+                "transfer",
+                (Address::from_low_u64_be(to), U256::from(amount)),
+            );
+            async move { call?.send().await?.await.map_err(eyre::Report::from) }
+        };
+
+        let path = std::env::temp_dir().join(format!("event-cursor-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        transfer(0xb0b, 10).await?;
+        transfer(0xca401, 20).await?;
+        let mut cursor = EventCursor::load(&path)?;
+        let first = poll_events(&busd, "Transfer", &mut cursor).await?;
+        cursor.save()?;
+
+        transfer(0xda7e, 30).await?;
+        let mut cursor = EventCursor::load(&path)?;
+        let second = poll_events(&busd, "Transfer", &mut cursor).await;
+        std::fs::remove_file(&path)?;
+        let second = second?;
+
+        // `increaseSupply` mints with a `Transfer` from the zero address
+        let values = |events: &[DecodedEvent]| -> Vec<Token> {
+            events.iter().map(|event| event.args[2].1.clone()).collect()
+        };
+        assert_eq!(
+            values(&first),
+            vec![
+                Token::Uint(1_000.into()),
+                Token::Uint(10.into()),
+                Token::Uint(20.into())
+            ]
+        );
+        assert_eq!(values(&second), vec![Token::Uint(30.into())]);
+        assert_eq!(second[0].name, "Transfer");
+        assert_eq!(second[0].args[0].0, "from");
+        assert!(second[0].block > first[2].block);
+
+        // Nothing new since the last poll
+        assert!(poll_events(&busd, "Transfer", &mut cursor)
+            .await?
+            .is_empty());
+
+        Ok(())
+    }
+}
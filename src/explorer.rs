@@ -0,0 +1,273 @@
+//! Block explorer-style view of a single block and its transactions.
+
+use crate::calldata::{decode_calldata, format_token};
+use ethers::{
+    abi::Abi,
+    prelude::{Address, BlockNumber, JsonRpcClient, Middleware, Provider, H256, U256},
+};
+use eyre::{eyre, ContextCompat, Result};
+use futures::future::try_join_all;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Contracts the inspector can name and decode calls for, by address.
+pub type KnownContracts = HashMap<Address, (String, Abi)>;
+
+/// A block with its transactions, as [`inspect_block`] reports it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockReport {
+    pub number: u64,
+    pub hash: H256,
+    /// Unix timestamp, in seconds.
+    pub timestamp: u64,
+    pub miner: Address,
+    pub gas_used: U256,
+    pub gas_limit: U256,
+    /// `None` on chains without EIP-1559.
+    pub base_fee: Option<U256>,
+    pub transactions: Vec<TxReport>,
+}
+
+impl BlockReport {
+    /// Share of the gas limit the block used, in percent.
+    pub fn gas_used_percent(&self) -> f64 {
+        if self.gas_limit.is_zero() {
+            return 0.0;
+        }
+        self.gas_used.as_u128() as f64 * 100.0 / self.gas_limit.as_u128() as f64
+    }
+}
+
+/// One transaction of a [`BlockReport`], with what its receipt and the known contracts tell.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TxReport {
+    pub hash: H256,
+    pub from: Address,
+    /// `None` for contract creations.
+    pub to: Option<Address>,
+    pub value: U256,
+    pub success: bool,
+    pub gas_used: U256,
+    /// Address of the contract the transaction created, if any.
+    pub created: Option<Address>,
+    /// Name of the known contract called or created.
+    pub contract: Option<String>,
+    /// Function called on a known contract, when its calldata decodes.
+    pub function: Option<String>,
+    /// Arguments of `function`, with their names and rendered values.
+    pub args: Vec<(String, String)>,
+}
+
+/// Fetches `block` with its transactions and their receipts, naming the contracts in `known`
+/// and decoding the calls made to them.
+pub async fn inspect_block<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    block: BlockNumber,
+    known: &KnownContracts,
+) -> Result<BlockReport> {
+    let block = provider
+        .get_block_with_txs(block)
+        .await?
+        .with_context(|| format!("Block {block:?} not found"))?;
+    let number = block
+        .number
+        .ok_or_else(|| eyre!("Block {:?} is still pending", block.hash))?
+        .as_u64();
+
+    let transactions = try_join_all(block.transactions.iter().map(|tx| async move {
+        let receipt = provider
+            .get_transaction_receipt(tx.hash)
+            .await?
+            .with_context(|| format!("Missing receipt for {:?}", tx.hash))?;
+        let target = tx.to.or(receipt.contract_address);
+        let contract = target.and_then(|address| known.get(&address));
+        let call = match (tx.to, contract) {
+            (Some(_), Some((_, abi))) if !tx.input.is_empty() => {
+                decode_calldata(abi, &tx.input).ok()
+            }
+            _ => None,
+        };
+        eyre::Ok(TxReport {
+            hash: tx.hash,
+            from: tx.from,
+            to: tx.to,
+            value: tx.value,
+            success: receipt.status == Some(1u64.into()),
+            gas_used: receipt.gas_used.unwrap_or_default(),
+            created: receipt.contract_address,
+            contract: contract.map(|(name, _)| name.clone()),
+            function: call.as_ref().map(|call| call.function.clone()),
+            args: call
+                .map(|call| {
+                    call.args
+                        .iter()
+                        .map(|(name, token)| (name.clone(), format_token(token)))
+                        .collect()
+                })
+                .unwrap_or_default(),
+        })
+    }))
+    .await?;
+
+    Ok(BlockReport {
+        number,
+        hash: block
+            .hash
+            .ok_or_else(|| eyre!("Block {number} has no hash"))?,
+        timestamp: block.timestamp.as_u64(),
+        miner: block.author.unwrap_or_default(),
+        gas_used: block.gas_used,
+        gas_limit: block.gas_limit,
+        base_fee: block.base_fee_per_gas,
+        transactions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::deploy::{deploy_contract, DeployOptions};
+    use crate::project::{compile, contract_parts};
+    use crate::test_support::TestActors;
+    use ethers::prelude::LocalWallet;
+    use ethers::utils::Ganache;
+
+    const MNEMONIC: &str =
+        "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_creation_and_transfer_are_named() -> Result<()> {
+        let ganache = Ganache::new().mnemonic(MNEMONIC).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let recipient = Address::from_low_u64_be(0xb0b);
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let (busd, deployed) = deploy_contract(
+            &ctx,
+            wallet,
+            "BUSDImplementation",
+            abi.clone(),
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        // The token starts paused, so the transfer reverts; with its gas set it is still mined
+        busd.method::<_, bool>("transfer", (recipient, U256::from(5u64)))?
+            .gas(100_000u64)
+            .send()
+            .await?
+            .await?;
+
+        let known =
+            KnownContracts::from([(deployed.address, ("BUSDImplementation".to_string(), abi))]);
+        let latest = provider.get_block_number().await?.as_u64();
+        let mut reports = Vec::new();
+        for number in latest - 1..=latest {
+            reports.push(inspect_block(&provider, number.into(), &known).await?);
+        }
+        let transactions: Vec<_> = reports.iter().flat_map(|r| &r.transactions).collect();
+
+        let creation = transactions
+            .iter()
+            .find(|tx| tx.created == Some(deployed.address))
+            .context("The creation is missing")?;
+        assert_eq!(creation.contract.as_deref(), Some("BUSDImplementation"));
+        assert_eq!(creation.function, None);
+
+        let call = transactions
+            .iter()
+            .find(|tx| tx.function.as_deref() == Some("transfer"))
+            .context("The transfer call is missing")?;
+        assert_eq!(call.to, Some(deployed.address));
+        assert_eq!(call.args[0].1, format!("{recipient:?}"));
+        assert_eq!(call.args[1].1, "5");
+        assert!(!call.success);
+        assert!(creation.success);
+
+        let last = &reports[1];
+        assert!(last.gas_used <= last.gas_limit);
+        assert_eq!(last.number, latest);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_transfer_after_owner_pauses_is_reported_failed() -> Result<()> {
+        let ganache = Ganache::new().mnemonic(MNEMONIC).spawn();
+        let actors = TestActors::new(&ganache).await?;
+        let provider = actors.provider().clone();
+        let ctx = ChainContext::new(provider.clone());
+        let (alice, bob) = (actors.alice(), actors.bob());
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let (busd, deployed) = deploy_contract(
+            &ctx,
+            actors.owner().signer().clone(),
+            "BUSDImplementation",
+            abi.clone(),
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        busd.method::<_, ()>("unpause", ())?.send().await?.await?;
+        busd.method::<_, bool>("increaseSupply", U256::from(100u64))?
+            .send()
+            .await?
+            .await?;
+        busd.method::<_, bool>("transfer", (alice.address(), U256::from(50u64)))?
+            .send()
+            .await?
+            .await?;
+        busd.method::<_, ()>("pause", ())?.send().await?.await?;
+
+        // The owner paused the token, so alice's transfer reverts; with its gas set it is still
+        // mined
+        busd.connect(alice.clone())
+            .method::<_, bool>("transfer", (bob.address(), U256::from(5u64)))?
+            .gas(100_000u64)
+            .send()
+            .await?
+            .await?;
+        let held: U256 = busd.method("balanceOf", alice.address())?.call().await?;
+        assert_eq!(held, U256::from(50u64));
+
+        let known =
+            KnownContracts::from([(deployed.address, ("BUSDImplementation".to_string(), abi))]);
+        let latest = provider.get_block_number().await?.as_u64();
+        let mut transactions = Vec::new();
+        for number in 1..=latest {
+            transactions.extend(
+                inspect_block(&provider, number.into(), &known)
+                    .await?
+                    .transactions,
+            );
+        }
+
+        let transfers: Vec<_> = transactions
+            .iter()
+            .filter(|tx| tx.function.as_deref() == Some("transfer"))
+            .collect();
+        assert_eq!(transfers.len(), 2);
+        assert!(transfers[0].success);
+        assert_eq!(transfers[0].from, actors.owner().address());
+        let call = transfers[1];
+        assert_eq!(call.to, Some(deployed.address));
+        assert_eq!(call.from, alice.address());
+        assert_eq!(call.args[0].1, format!("{:?}", bob.address()));
+        assert_eq!(call.args[1].1, "5");
+        assert!(!call.success);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,197 @@
+//! Spreadsheet-friendly exports of transaction summaries.
+
+use crate::{amount::format_eth_decimal, history::TxSummary};
+use eyre::{Context, Result};
+use std::{fs::OpenOptions, io::Write, path::Path};
+
+/// Columns of [`export_csv`], in order.
+pub const CSV_HEADER: [&str; 10] = [
+    "timestamp",
+    "chain_id",
+    "hash",
+    "from",
+    "to",
+    "value_eth",
+    "fee_eth",
+    "status",
+    "block",
+    "nonce",
+];
+
+/// Appends `records` to the CSV file at `path`, creating it with a header row if it doesn't exist
+/// or is empty.
+///
+/// Fields are quoted as RFC 4180 requires and lines end with CRLF. Addresses and hashes are
+/// written in full lowercase hex and amounts as decimal ETH strings, so nothing is shortened or
+/// put in scientific notation. The timestamp is in seconds since the Unix epoch, and the `to`
+/// column is empty for contract creations.
+pub fn export_csv(records: &[TxSummary], path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .wrap_err_with(|| format!("Failed to open {}", path.display()))?;
+    let mut csv = String::new();
+    if file.metadata()?.len() == 0 {
+        push_row(&mut csv, CSV_HEADER.map(String::from));
+    }
+    for record in records {
+        push_row(&mut csv, csv_row(record));
+    }
+    file.write_all(csv.as_bytes())
+        .wrap_err_with(|| format!("Failed to write {}", path.display()))
+}
+
+/// The fields [`export_csv`] writes for `record`, unquoted.
+pub fn csv_row(record: &TxSummary) -> [String; 10] {
+    [
+        record.timestamp.to_string(),
+        record.chain_id.to_string(),
+        format!("{:?}", record.hash),
+        format!("{:?}", record.from),
+        record.to.map(|to| format!("{to:?}")).unwrap_or_default(),
+        format_eth_decimal(record.value),
+        format_eth_decimal(record.fee),
+        if record.success {
+            "success"
+        } else {
+            "reverted"
+        }
+        .to_string(),
+        record.block.to_string(),
+        record.nonce.to_string(),
+    ]
+}
+
+fn push_row(csv: &mut String, fields: [String; 10]) {
+    let quoted: Vec<_> = fields.iter().map(|field| quote(field)).collect();
+    csv.push_str(&quoted.join(","));
+    csv.push_str("\r\n");
+}
+
+// RFC 4180: fields with a comma, quote or line break are quoted, with inner quotes doubled
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::TxDirection;
+    use ethers::prelude::{Address, H256, I256, U256};
+    use ethers::utils::parse_ether;
+    use std::path::PathBuf;
+
+    fn temp_csv(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}-{}.csv", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    // A minimal RFC 4180 reader, enough to check what the writer produced
+    fn parse_csv(text: &str) -> Vec<Vec<String>> {
+        let (mut rows, mut row, mut field) = (Vec::new(), Vec::new(), String::new());
+        let (mut chars, mut quoted) = (text.chars().peekable(), false);
+        while let Some(c) = chars.next() {
+            match (c, quoted) {
+                ('"', true) if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                ('"', _) => quoted = !quoted,
+                (',', false) => row.push(std::mem::take(&mut field)),
+                ('\r', false) => {}
+                ('\n', false) => {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                (c, _) => field.push(c),
+            }
+        }
+        rows
+    }
+
+    fn summary(nonce: u64, to: Option<Address>, success: bool) -> TxSummary {
+        TxSummary {
+            chain_id: 1337,
+            block: 10 + nonce,
+            timestamp: 1_700_000_000 + nonce,
+            hash: H256::from_low_u64_be(0xabc + nonce),
+            nonce: nonce.into(),
+            direction: TxDirection::Sent,
+            from: Address::from_low_u64_be(0xa11ce),
+            to,
+            created: None,
+            value: parse_ether("1.5").unwrap() + U256::from(nonce),
+            fee: U256::from(21_000u64) * U256::exp10(9),
+            success,
+            balance_change: I256::zero(),
+            running_change: I256::zero(),
+        }
+    }
+
+    #[test]
+    fn test_csv_round_trip() -> Result<()> {
+        let records = vec![
+            summary(0, Some(Address::from_low_u64_be(0xb0b)), true),
+            summary(1, None, false),
+        ];
+        let path = temp_csv("export-round-trip");
+        export_csv(&records[..1], &path)?;
+        // Appending to the existing file doesn't repeat the header
+        export_csv(&records[1..], &path)?;
+        let text = std::fs::read_to_string(&path);
+        std::fs::remove_file(&path)?;
+        let rows = parse_csv(&text?);
+
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0], CSV_HEADER);
+        for (row, record) in rows[1..].iter().zip(&records) {
+            assert_eq!(row[0].parse::<u64>()?, record.timestamp);
+            assert_eq!(row[1].parse::<u64>()?, record.chain_id);
+            assert_eq!(row[2].parse::<H256>()?, record.hash);
+            assert_eq!(row[3].parse::<Address>()?, record.from);
+            assert_eq!(
+                (!row[4].is_empty())
+                    .then(|| row[4].parse::<Address>())
+                    .transpose()?,
+                record.to
+            );
+            assert_eq!(parse_ether(&row[5])?, record.value);
+            assert_eq!(parse_ether(&row[6])?, record.fee);
+            assert_eq!(row[7] == "success", record.success);
+            assert_eq!(row[8].parse::<u64>()?, record.block);
+            assert_eq!(U256::from_dec_str(&row[9])?, record.nonce);
+        }
+        assert_eq!(rows[1][5], "1.500000000000000000");
+        assert_eq!(rows[1][6], "0.000021000000000000");
+        Ok(())
+    }
+
+    #[test]
+    fn test_leading_zeros_and_quoting_survive() -> Result<()> {
+        // Shortened or numeric renderings of this address would lose its zeros
+        let to: Address = "0x0000000000000000000000000000000000000001".parse()?;
+        let record = summary(7, Some(to), true);
+        let row = csv_row(&record);
+        assert_eq!(row[4], "0x0000000000000000000000000000000000000001");
+        assert_eq!(
+            row[2],
+            "0x0000000000000000000000000000000000000000000000000000000000000ac3"
+        );
+
+        assert_eq!(quote("plain"), "plain");
+        assert_eq!(quote("a,b"), "\"a,b\"");
+        assert_eq!(quote("say \"hi\"\r\n"), "\"say \"\"hi\"\"\r\n\"");
+        assert_eq!(
+            parse_csv("\"a,b\",\"say \"\"hi\"\"\"\r\n"),
+            vec![vec!["a,b".to_string(), "say \"hi\"".to_string()]]
+        );
+        Ok(())
+    }
+}
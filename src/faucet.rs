@@ -0,0 +1,208 @@
+//! Funding fresh addresses from a well-funded account, such as one of Ganache's.
+
+use crate::{errors::Error, timeouts::OpTimeouts, wallet::bind_to_chain};
+use ethers::{
+    middleware::{NonceManagerMiddleware, SignerMiddleware},
+    prelude::{
+        Address, JsonRpcClient, LocalWallet, Middleware, Provider, Signer, TransactionReceipt,
+        TransactionRequest, U256,
+    },
+    utils::parse_ether,
+};
+use eyre::{eyre, ContextCompat, Result};
+use futures::future::join_all;
+use tracing::info;
+
+/// Gas of a plain ETH transfer to an account without code.
+const TRANSFER_GAS: u64 = 21_000;
+
+/// Knobs for [`faucet`] and [`ensure_min_balance`].
+#[derive(Clone, Debug)]
+pub struct FaucetOptions {
+    /// Balance the funder must keep after paying every transfer and its gas.
+    pub reserve: U256,
+    /// Limits for the RPC calls and the receipt waits.
+    pub timeouts: OpTimeouts,
+}
+
+impl Default for FaucetOptions {
+    fn default() -> Self {
+        Self {
+            reserve: parse_ether(1).expect("1 ETH is a valid amount"),
+            timeouts: OpTimeouts::default(),
+        }
+    }
+}
+
+/// Sends `amount_each` Wei from `funder` to every recipient, all in flight at once, and waits for
+/// the receipts.
+///
+/// A nonce manager hands out consecutive nonces, so the node can mine the transfers in any
+/// order. Nothing is sent when the transfers and their gas would leave the funder with less than
+/// `options.reserve`: that fails with [`Error::ReserveExceeded`]. Otherwise each recipient gets
+/// its own result, in the order of `recipients`, and a failed transfer doesn't stop the others.
+pub async fn faucet<T: JsonRpcClient + Clone + 'static>(
+    provider: &Provider<T>,
+    funder: LocalWallet,
+    recipients: &[Address],
+    amount_each: U256,
+    options: &FaucetOptions,
+) -> Result<Vec<(Address, Result<TransactionReceipt>)>> {
+    let timeouts = &options.timeouts;
+    let funder = timeouts
+        .rpc("chain id", bind_to_chain(provider, funder))
+        .await?;
+    let from = funder.address();
+    let count = U256::from(recipients.len());
+    let gas_price = timeouts.rpc("gas price", provider.get_gas_price()).await?;
+    let need = (amount_each + gas_price * TRANSFER_GAS) * count;
+    ensure_reserve(provider, from, need, options).await?;
+
+    let client = NonceManagerMiddleware::new(SignerMiddleware::new(provider.clone(), funder), from);
+    // Fetch the first nonce before the fan-out so concurrent sends don't each fetch it
+    timeouts.rpc("nonce", client.initialize_nonce(None)).await?;
+    let transfers = recipients.iter().map(|&to| {
+        let client = &client;
+        async move {
+            let tx = TransactionRequest::pay(to, amount_each)
+                .from(from)
+                .gas(TRANSFER_GAS)
+                .gas_price(gas_price);
+            let result = async {
+                let pending = timeouts
+                    .rpc("send", client.send_transaction(tx, None))
+                    .await?;
+                let receipt = timeouts
+                    .receipt("receipt", pending)
+                    .await?
+                    .with_context(|| format!("Missing receipt for the transfer to {to:?}"))?;
+                if receipt.status != Some(1u64.into()) {
+                    return Err(eyre!(
+                        "Transfer to {to:?} reverted in tx {:?}",
+                        receipt.transaction_hash
+                    ));
+                }
+                info!(recipient = ?to, value = %amount_each, "funded");
+                Ok(receipt)
+            }
+            .await;
+            (to, result)
+        }
+    });
+    Ok(join_all(transfers).await)
+}
+
+/// Tops `address` up to `min` Wei from `funder` when its balance is below that, returning the
+/// receipt of the top-up, or `None` when the balance was already enough.
+///
+/// Like [`faucet`], refuses to leave the funder with less than `options.reserve`.
+pub async fn ensure_min_balance<T: JsonRpcClient + Clone + 'static>(
+    provider: &Provider<T>,
+    funder: LocalWallet,
+    address: Address,
+    min: U256,
+    options: &FaucetOptions,
+) -> Result<Option<TransactionReceipt>> {
+    let balance = options
+        .timeouts
+        .rpc("balance", provider.get_balance(address, None))
+        .await?;
+    if balance >= min {
+        return Ok(None);
+    }
+    let mut funded = faucet(provider, funder, &[address], min - balance, options).await?;
+    let (_, result) = funded.pop().context("No result for the top-up")?;
+    result.map(Some)
+}
+
+async fn ensure_reserve<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    funder: Address,
+    need: U256,
+    options: &FaucetOptions,
+) -> Result<()> {
+    let balance = options
+        .timeouts
+        .rpc("balance", provider.get_balance(funder, None))
+        .await?;
+    if balance < need + options.reserve {
+        return Err(Error::ReserveExceeded {
+            funder,
+            balance,
+            need,
+            reserve: options.reserve,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::utils::Ganache;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_fund_fresh_addresses() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let funder: LocalWallet = ganache.keys()[0].clone().into();
+
+        let recipients: Vec<Address> = (0..5).map(|_| Address::random()).collect();
+        let amount = parse_ether("0.25")?;
+        let funded = faucet(
+            &provider,
+            funder.clone(),
+            &recipients,
+            amount,
+            &FaucetOptions::default(),
+        )
+        .await?;
+
+        assert_eq!(funded.len(), 5);
+        for ((recipient, result), expected) in funded.into_iter().zip(&recipients) {
+            assert_eq!(recipient, *expected);
+            result?;
+            assert_eq!(provider.get_balance(recipient, None).await?, amount);
+        }
+
+        // Already at the minimum, so nothing is sent; below it, the difference is
+        assert!(ensure_min_balance(
+            &provider,
+            funder.clone(),
+            recipients[0],
+            amount,
+            &FaucetOptions::default()
+        )
+        .await?
+        .is_none());
+        let min = parse_ether(1)?;
+        ensure_min_balance(
+            &provider,
+            funder.clone(),
+            recipients[0],
+            min,
+            &FaucetOptions::default(),
+        )
+        .await?
+        .context("Expected a top-up")?;
+        assert_eq!(provider.get_balance(recipients[0], None).await?, min);
+
+        // A reserve above the funder's balance stops the faucet before anything is sent
+        let greedy = FaucetOptions {
+            reserve: provider.get_balance(funder.address(), None).await?,
+            ..Default::default()
+        };
+        let err = faucet(&provider, funder, &recipients[..1], amount, &greedy)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ReserveExceeded { .. })
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,355 @@
+//! Fee mode selection between legacy gas pricing and EIP-1559.
+
+use ethers::{
+    abi::{Abi, Tokenize},
+    contract::ContractFactory,
+    prelude::{BlockNumber, Middleware, U256},
+    types::{transaction::eip2718::TypedTransaction, Bytes},
+};
+use eyre::{eyre, ContextCompat, Result};
+use serde::Serialize;
+use std::sync::Arc;
+
+/// How transaction fees are priced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeeMode {
+    /// A single `gasPrice` (type 0 transactions).
+    #[default]
+    Legacy,
+    /// `maxFeePerGas` / `maxPriorityFeePerGas` (type 2 transactions).
+    Eip1559,
+    /// Pick [`FeeMode::Eip1559`] when the chain supports it, [`FeeMode::Legacy`] otherwise.
+    Auto,
+}
+
+/// Transaction type a chain is known to accept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TxType {
+    /// Only type 0 transactions; typed ones are rejected.
+    Legacy,
+    /// Type 2 transactions.
+    Eip1559,
+    /// Not in the table: decided by looking at the chain.
+    Unknown,
+}
+
+// Chains that reject typed transactions, by chain id
+const LEGACY_CHAINS: &[u64] = &[
+    25,    // Cronos
+    30,    // RSK
+    31,    // RSK testnet
+    56,    // BNB Smart Chain
+    97,    // BNB Smart Chain testnet
+    250,   // Fantom Opera
+    338,   // Cronos testnet
+    4002,  // Fantom testnet
+    42262, // Oasis Emerald
+];
+
+// Chains known to accept EIP-1559 transactions, by chain id
+const EIP1559_CHAINS: &[u64] = &[
+    1,        // Mainnet
+    17000,    // Holesky
+    11155111, // Sepolia
+];
+
+/// Looks `chain_id` up in the built-in table of chains with a known transaction type.
+pub fn recommended_tx_type(chain_id: u64) -> TxType {
+    if LEGACY_CHAINS.contains(&chain_id) {
+        TxType::Legacy
+    } else if EIP1559_CHAINS.contains(&chain_id) {
+        TxType::Eip1559
+    } else {
+        TxType::Unknown
+    }
+}
+
+/// Returns whether the chain's latest block carries a `baseFeePerGas`.
+pub async fn supports_eip1559<M: Middleware>(provider: &M) -> Result<bool>
+where
+    M::Error: 'static,
+{
+    let block = provider
+        .get_block(BlockNumber::Latest)
+        .await?
+        .context("Failed to get block")?;
+    Ok(block.base_fee_per_gas.is_some())
+}
+
+/// Resolves [`FeeMode::Auto`] into a concrete mode for the connected chain; other modes are returned as-is.
+///
+/// Chains listed as legacy-only by [`recommended_tx_type`] get [`FeeMode::Legacy`] even if their
+/// blocks carry a base fee; the others get [`FeeMode::Eip1559`] when their latest block has one.
+pub async fn resolve_fee_mode<M: Middleware>(provider: &M, mode: FeeMode) -> Result<FeeMode>
+where
+    M::Error: 'static,
+{
+    if mode != FeeMode::Auto {
+        return Ok(mode);
+    }
+    let chain_id = provider.get_chainid().await?.as_u64();
+    if recommended_tx_type(chain_id) == TxType::Legacy {
+        return Ok(FeeMode::Legacy);
+    }
+    if supports_eip1559(provider).await? {
+        Ok(FeeMode::Eip1559)
+    } else {
+        Ok(FeeMode::Legacy)
+    }
+}
+
+/// Estimated cost of one transaction under a fee mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeEstimate {
+    /// Estimated gas used.
+    pub gas: U256,
+    /// Expected price per gas unit, in wei.
+    pub fee_per_gas: U256,
+    /// `gas * fee_per_gas`, in wei.
+    pub total_cost: U256,
+}
+
+/// Side-by-side cost of the same deployment in both fee modes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FeeComparison {
+    /// Cost as a type 0 transaction.
+    pub legacy: FeeEstimate,
+    /// Cost as a type 2 transaction.
+    pub eip1559: FeeEstimate,
+    /// [`FeeMode::Legacy`] or [`FeeMode::Eip1559`], whichever costs less (legacy on a tie).
+    pub cheaper: FeeMode,
+}
+
+/// Estimates the deployment of `bytecode` with `args` as a legacy and as an EIP-1559
+/// transaction, without sending either.
+///
+/// Legacy is priced at the next block's base fee, like [`deploy_contract`](crate::deploy::deploy_contract)
+/// does; EIP-1559 at that base fee plus the suggested priority fee, capped by the suggested max fee.
+pub async fn profile_fee_modes<M, A>(
+    client: Arc<M>,
+    abi: Abi,
+    bytecode: Bytes,
+    args: A,
+) -> Result<FeeComparison>
+where
+    M: Middleware + 'static,
+    A: Tokenize,
+{
+    let block = client
+        .get_block(BlockNumber::Latest)
+        .await
+        .map_err(|e| eyre!(e))?
+        .context("Failed to get block")?;
+    let base_fee = block
+        .next_block_base_fee()
+        .context("The chain doesn't support EIP-1559, there is nothing to compare")?;
+    let (max_fee, priority_fee) = client
+        .estimate_eip1559_fees(None)
+        .await
+        .map_err(|e| eyre!(e))?;
+
+    // Build the same creation transaction in both shapes
+    let factory = ContractFactory::new(abi, bytecode, client.clone());
+    let tokens = args.into_tokens();
+    let mut legacy = factory.clone().deploy_tokens(tokens.clone())?.legacy().tx;
+    legacy.set_gas_price(base_fee);
+    let mut eip1559 = factory.deploy_tokens(tokens)?.tx;
+    if let TypedTransaction::Eip1559(tx) = &mut eip1559 {
+        tx.max_fee_per_gas = Some(max_fee);
+        tx.max_priority_fee_per_gas = Some(priority_fee);
+    }
+    if let Some(from) = client.default_sender() {
+        legacy.set_from(from);
+        eip1559.set_from(from);
+    }
+
+    let legacy_gas = client
+        .estimate_gas(&legacy, None)
+        .await
+        .map_err(|e| eyre!(e))?;
+    let eip1559_gas = client
+        .estimate_gas(&eip1559, None)
+        .await
+        .map_err(|e| eyre!(e))?;
+
+    let legacy = fee_estimate(legacy_gas, base_fee);
+    let eip1559 = fee_estimate(eip1559_gas, max_fee.min(base_fee + priority_fee));
+    let cheaper = if eip1559.total_cost < legacy.total_cost {
+        FeeMode::Eip1559
+    } else {
+        FeeMode::Legacy
+    };
+    Ok(FeeComparison {
+        legacy,
+        eip1559,
+        cheaper,
+    })
+}
+
+fn fee_estimate(gas: U256, fee_per_gas: U256) -> FeeEstimate {
+    FeeEstimate {
+        gas,
+        fee_per_gas,
+        total_cost: gas * fee_per_gas,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::deploy::{deploy_contract, DeployOptions};
+    use crate::project::{compile, contract_parts};
+    use crate::test_support::{block_reply, fee_history_reply, mock_provider, reply};
+    use ethers::prelude::{LocalWallet, Provider, Signer, SignerMiddleware};
+    use ethers::utils::{eip1559_default_estimator, parse_units, Ganache};
+
+    #[test]
+    fn test_tx_type_table() {
+        assert_eq!(recommended_tx_type(1), TxType::Eip1559);
+        assert_eq!(recommended_tx_type(11155111), TxType::Eip1559);
+        assert_eq!(recommended_tx_type(56), TxType::Legacy);
+        assert_eq!(recommended_tx_type(30), TxType::Legacy);
+        // Ganache's default chain id is left to the runtime detection
+        assert_eq!(recommended_tx_type(1337), TxType::Unknown);
+    }
+
+    #[tokio::test]
+    async fn test_auto_fee_mode_on_mocked_chains() -> Result<()> {
+        let base_fee: U256 = parse_units(10, "gwei")?.into();
+
+        // Concrete modes are kept without asking the node anything
+        let (provider, _) = mock_provider([]);
+        assert_eq!(
+            resolve_fee_mode(&provider, FeeMode::Eip1559).await?,
+            FeeMode::Eip1559
+        );
+
+        // A legacy-only chain stays legacy whatever its blocks say
+        let (provider, _) = mock_provider([reply(U256::from(56))]);
+        assert_eq!(
+            resolve_fee_mode(&provider, FeeMode::Auto).await?,
+            FeeMode::Legacy
+        );
+
+        let (provider, _) =
+            mock_provider([reply(U256::from(1337)), block_reply(5, Some(base_fee))]);
+        assert_eq!(
+            resolve_fee_mode(&provider, FeeMode::Auto).await?,
+            FeeMode::Eip1559
+        );
+
+        let (provider, _) = mock_provider([reply(U256::from(1337)), block_reply(5, None)]);
+        assert_eq!(
+            resolve_fee_mode(&provider, FeeMode::Auto).await?,
+            FeeMode::Legacy
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fee_mode_profile_on_a_mock() -> Result<()> {
+        let base_fee: U256 = parse_units(10, "gwei")?.into();
+        let tip: U256 = parse_units(2, "gwei")?.into();
+        let gas = U256::from(120_000u64);
+        let (provider, _) = mock_provider([
+            block_reply(5, Some(base_fee)),
+            // `estimate_eip1559_fees` reads the block again, then the fee history
+            block_reply(5, Some(base_fee)),
+            fee_history_reply(base_fee, tip),
+            reply(gas),
+            reply(gas),
+        ]);
+
+        let comparison = profile_fee_modes(
+            Arc::new(provider),
+            Abi::default(),
+            Bytes::from_static(&[0x60, 0x80]),
+            (),
+        )
+        .await?;
+
+        // A half-full block keeps the next base fee where it was
+        let (max_fee, priority_fee) = eip1559_default_estimator(base_fee, vec![vec![tip]; 10]);
+        assert_eq!(comparison.legacy.gas, gas);
+        assert_eq!(comparison.legacy.fee_per_gas, base_fee);
+        assert_eq!(comparison.legacy.total_cost, gas * base_fee);
+        assert_eq!(
+            comparison.eip1559.fee_per_gas,
+            max_fee.min(base_fee + priority_fee)
+        );
+        assert_eq!(comparison.cheaper, FeeMode::Legacy);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_auto_fee_mode_picks_a_concrete_path() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+
+        let supported = supports_eip1559(&provider).await?;
+        let resolved = resolve_fee_mode(&provider, FeeMode::Auto).await?;
+        let expected = if supported {
+            FeeMode::Eip1559
+        } else {
+            FeeMode::Legacy
+        };
+        assert_eq!(resolved, expected);
+
+        // Deploying with the auto mode goes through whichever path was selected
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "Migrations")?;
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let options = DeployOptions {
+            fee_mode: FeeMode::Auto,
+            ..Default::default()
+        };
+        let (contract, _) =
+            deploy_contract(&ctx, wallet, "Migrations", abi, bytecode, (), &options).await?;
+        assert!(!contract.address().is_zero());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_fee_mode_profile_on_ganache() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let client = Arc::new(SignerMiddleware::new(
+            provider,
+            wallet.with_chain_id(chain_id),
+        ));
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "Migrations")?;
+        let comparison = profile_fee_modes(client.clone(), abi, bytecode, ()).await?;
+
+        for estimate in [comparison.legacy, comparison.eip1559] {
+            assert!(!estimate.gas.is_zero());
+            assert!(!estimate.fee_per_gas.is_zero());
+            assert_eq!(estimate.total_cost, estimate.gas * estimate.fee_per_gas);
+        }
+        let cheaper = if comparison.eip1559.total_cost < comparison.legacy.total_cost {
+            FeeMode::Eip1559
+        } else {
+            FeeMode::Legacy
+        };
+        assert_eq!(comparison.cheaper, cheaper);
+
+        // Nothing was sent
+        let nonce = client.get_transaction_count(client.address(), None).await?;
+        assert!(nonce.is_zero());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+//! Single-file Solidity output for explorers that don't accept multi-file verification.
+
+use crate::errors::Error;
+use crate::project::build_project;
+use ethers_solc::Graph;
+use eyre::{Context, Result};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+use tracing::info;
+
+/// Flattens `contract_path` of the project at `project_root` into one source: imports are inlined
+/// once each, and only the target keeps its SPDX identifier and pragma lines.
+///
+/// Circular imports are rejected with [`Error::CircularImport`].
+pub fn flatten(project_root: &str, contract_path: &str) -> Result<String> {
+    let project = build_project(project_root)?;
+    let target = std::fs::canonicalize(contract_path)
+        .wrap_err_with(|| format!("Contract {contract_path} does not exist"))?;
+
+    let graph = Graph::resolve(&project.paths)?;
+    if let Some(&start) = graph.files().get(&target) {
+        if let Some(cycle) = find_cycle(&graph, start) {
+            return Err(Error::CircularImport { cycle }.into());
+        }
+    }
+
+    Ok(project.paths.flatten(&target)?)
+}
+
+/// Flattens `contract_path` and writes it next to the original as `<Name>.flat.sol`,
+/// returning the written path.
+pub fn write_flattened(project_root: &str, contract_path: &str) -> Result<PathBuf> {
+    let flat = flatten(project_root, contract_path)?;
+    let path = Path::new(contract_path).with_extension("flat.sol");
+    std::fs::write(&path, flat).wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+    info!(path = %path.display(), "flattened source written");
+    Ok(path)
+}
+
+// Depth-first walk of the imports from `start`, returning the first cycle as a path list
+// that starts and ends with the same file
+fn find_cycle(graph: &Graph, start: usize) -> Option<Vec<PathBuf>> {
+    fn visit(
+        graph: &Graph,
+        node: usize,
+        stack: &mut Vec<usize>,
+        done: &mut HashSet<usize>,
+    ) -> Option<Vec<usize>> {
+        if let Some(pos) = stack.iter().position(|&n| n == node) {
+            let mut cycle = stack[pos..].to_vec();
+            cycle.push(node);
+            return Some(cycle);
+        }
+        if done.contains(&node) {
+            return None;
+        }
+
+        stack.push(node);
+        for &next in graph.imported_nodes(node) {
+            if let Some(cycle) = visit(graph, next, stack, done) {
+                return Some(cycle);
+            }
+        }
+        stack.pop();
+        done.insert(node);
+        None
+    }
+
+    let cycle = visit(graph, start, &mut Vec::new(), &mut HashSet::new())?;
+    Some(
+        cycle
+            .into_iter()
+            .map(|index| graph.node(index).unpack().0.clone())
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_dependency_is_inlined_once() -> Result<()> {
+        let flat = flatten("tests/fixtures/flatten/", "tests/fixtures/flatten/Main.sol")?;
+
+        assert_eq!(flat.matches("contract Common").count(), 1, "{flat}");
+        assert_eq!(flat.matches("SPDX-License-Identifier").count(), 1);
+        assert_eq!(flat.matches("pragma solidity").count(), 1);
+        assert!(
+            !flat.contains("import "),
+            "Imports should be inlined: {flat}"
+        );
+
+        // Dependencies come before the contracts using them
+        let common = flat.find("contract Common").unwrap();
+        assert!(common < flat.find("contract Left").unwrap());
+        assert!(common < flat.find("contract Right").unwrap());
+        assert!(flat.find("contract Right").unwrap() < flat.find("contract Main").unwrap());
+        Ok(())
+    }
+
+    #[test]
+    fn test_circular_import_reports_cycle() {
+        let err = flatten(
+            "tests/fixtures/flatten_cycle/",
+            "tests/fixtures/flatten_cycle/Ping.sol",
+        )
+        .unwrap_err();
+
+        match err.downcast_ref::<Error>() {
+            Some(Error::CircularImport { cycle }) => {
+                let names: Vec<_> = cycle
+                    .iter()
+                    .map(|path| path.file_name().unwrap().to_string_lossy())
+                    .collect();
+                assert_eq!(names, ["Ping.sol", "Pong.sol", "Ping.sol"]);
+            }
+            other => panic!("expected CircularImport, got {other:?}"),
+        }
+        assert!(err.to_string().contains("Ping.sol -> "), "{err}");
+    }
+}
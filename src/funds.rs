@@ -0,0 +1,299 @@
+//! Pre-flight balance checks run before sending transactions, balance changes measured
+//! around them, and waits for incoming funds.
+
+use crate::errors::Error;
+use ethers::prelude::{Address, Middleware, I256, U256};
+use eyre::{eyre, Result};
+use std::{collections::HashMap, future::Future, time::Duration};
+use tokio::time::{sleep, Instant};
+
+/// Checks that `from` can pay `value` plus `gas_estimate` units of gas at the current gas price.
+///
+/// Returns [`Error::InsufficientFunds`] with the exact shortfall instead of letting the node
+/// reject the transaction with its raw JSON-RPC error.
+pub async fn ensure_funds<M: Middleware>(
+    provider: &M,
+    from: Address,
+    value: U256,
+    gas_estimate: U256,
+) -> Result<()>
+where
+    M::Error: 'static,
+{
+    let have = provider.get_balance(from, None).await?; // Current sender balance
+    let gas_price = provider.get_gas_price().await?; // Price the node will quote for the transaction
+    let need = value + gas_estimate * gas_price; // Total cost of the transaction
+
+    if have < need {
+        return Err(Error::InsufficientFunds {
+            have,
+            need,
+            shortfall: need - have,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Snapshots the balances of a set of addresses around an operation.
+///
+/// ```ignore
+/// let (receipt, deltas) = BalanceTracker::new(&provider, from)
+///     .track(to)
+///     .measure(|| send_transfer(&ctx, from, to, value, &options))
+///     .await?;
+/// assert!(deltas.delta(to) > Some(I256::zero()));
+/// ```
+pub struct BalanceTracker<'a, M> {
+    provider: &'a M,
+    addresses: Vec<Address>,
+}
+
+impl<'a, M: Middleware> BalanceTracker<'a, M>
+where
+    M::Error: 'static,
+{
+    /// Tracks the balance of `address`.
+    pub fn new(provider: &'a M, address: Address) -> Self {
+        Self {
+            provider,
+            addresses: vec![address],
+        }
+    }
+
+    /// Also tracks the balance of `address`.
+    pub fn track(mut self, address: Address) -> Self {
+        if !self.addresses.contains(&address) {
+            self.addresses.push(address);
+        }
+        self
+    }
+
+    /// Runs `op` between two balance snapshots and returns its output with the changes.
+    ///
+    /// The snapshots are taken at the latest block, so `op` should wait for its transactions
+    /// to be mined.
+    pub async fn measure<F, Fut, T>(&self, op: F) -> Result<(T, BalanceDeltas)>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let before = self.balances().await?;
+        let output = op().await?;
+        let after = self.balances().await?;
+
+        let mut deltas = HashMap::new();
+        for (address, (before, after)) in self.addresses.iter().zip(before.into_iter().zip(after)) {
+            let delta = I256::try_from(after)?
+                .checked_sub(I256::try_from(before)?)
+                .ok_or_else(|| eyre!("balance change of {address:?} overflows"))?;
+            deltas.insert(*address, delta);
+        }
+        Ok((output, BalanceDeltas(deltas)))
+    }
+
+    async fn balances(&self) -> Result<Vec<U256>> {
+        let mut balances = Vec::with_capacity(self.addresses.len());
+        for address in &self.addresses {
+            balances.push(self.provider.get_balance(*address, None).await?);
+        }
+        Ok(balances)
+    }
+}
+
+/// How often [`wait_for_balance`] reads the balance.
+const BALANCE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Polls `address`'s balance until it holds `at_least` wei, returning that balance.
+///
+/// Fails with [`Error::BalanceNotReached`] if it is still short after `timeout`.
+pub async fn wait_for_balance<M: Middleware>(
+    provider: &M,
+    address: Address,
+    at_least: U256,
+    timeout: Duration,
+) -> Result<U256>
+where
+    M::Error: 'static,
+{
+    let deadline = Instant::now() + timeout;
+    loop {
+        let balance = provider.get_balance(address, None).await?;
+        if balance >= at_least {
+            return Ok(balance);
+        }
+        if Instant::now() >= deadline {
+            return Err(Error::BalanceNotReached {
+                address,
+                balance,
+                at_least,
+                after: timeout,
+            }
+            .into());
+        }
+        sleep(BALANCE_POLL_INTERVAL.min(deadline.saturating_duration_since(Instant::now()))).await;
+    }
+}
+
+/// Balance changes measured by a [`BalanceTracker`], in wei.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BalanceDeltas(HashMap<Address, I256>);
+
+impl BalanceDeltas {
+    /// Change of `address`'s balance (negative when it dropped), if it was tracked.
+    pub fn delta(&self, address: Address) -> Option<I256> {
+        self.0.get(&address).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::test_support::{mock_provider, reply};
+    use crate::transfer::{send_transfer, TransferOptions};
+    use ethers::{prelude::Provider, utils::parse_ether, utils::parse_units, utils::Ganache};
+    use eyre::ContextCompat;
+
+    #[tokio::test]
+    async fn test_cost_check_on_a_mock() -> Result<()> {
+        let from = Address::from_low_u64_be(0xa11ce);
+        let balance = parse_ether(1)?;
+        let gas_price: U256 = parse_units(20, "gwei")?.into();
+        let gas = U256::from(21_000u64);
+
+        // Exactly enough for the value and the gas
+        let value = balance - gas * gas_price;
+        let (provider, _) = mock_provider([reply(balance), reply(gas_price)]);
+        ensure_funds(&provider, from, value, gas).await?;
+
+        let (provider, _) = mock_provider([reply(balance), reply(gas_price)]);
+        let err = ensure_funds(&provider, from, value + 1, gas)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::InsufficientFunds { have, shortfall, .. })
+                if *have == balance && *shortfall == U256::one()
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_transfer_beyond_balance_reports_shortfall() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+
+        let from = ganache.addresses()[0];
+        let to = ganache.addresses()[1];
+        let balance = provider.get_balance(from, None).await?;
+        let value = balance + parse_ether(1000)?;
+
+        // Send 1000 ETH more than the account holds
+        let err = send_transfer(&ctx, from, to, value, &TransferOptions::default())
+            .await
+            .expect_err("Transfer should be rejected before sending");
+
+        match err.downcast_ref::<Error>() {
+            Some(Error::InsufficientFunds {
+                have,
+                need,
+                shortfall,
+            }) => {
+                assert_eq!(*have, balance, "Reported balance should match the chain");
+                assert!(*need > value, "Needed amount should include gas");
+                assert_eq!(*shortfall, *need - *have);
+                assert!(*shortfall > parse_ether(1000)?);
+            }
+            other => panic!("Expected InsufficientFunds, got {other:?}"),
+        }
+        assert!(
+            err.to_string().contains("ETH"),
+            "Amounts should be formatted in ETH"
+        );
+
+        // Nothing was sent, so the sender nonce is untouched
+        assert_eq!(
+            provider.get_transaction_count(from, None).await?,
+            U256::zero()
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_tracker_reports_sender_and_recipient_deltas() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+
+        let from = ganache.addresses()[0];
+        let to = ganache.addresses()[1];
+        let value = parse_ether(1)?;
+        let options = TransferOptions::default();
+        let (receipt, deltas) = BalanceTracker::new(&provider, from)
+            .track(to)
+            .measure(|| send_transfer(&ctx, from, to, value, &options))
+            .await?;
+
+        // The sender pays the amount plus the gas, the recipient gets the amount
+        let gas_used = receipt.gas_used.context("Missing gas used")?;
+        let gas_price = receipt
+            .effective_gas_price
+            .context("Missing effective gas price")?;
+        let cost = I256::try_from(value + gas_used * gas_price)?;
+        assert_eq!(deltas.delta(from), Some(-cost));
+        assert_eq!(deltas.delta(to), Some(I256::try_from(value)?));
+        assert!(deltas.delta(from) < Some(I256::zero()));
+        assert_eq!(deltas.delta(Address::zero()), None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_wait_for_balance_returns_once_funded() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+
+        let from = ganache.addresses()[0];
+        let to = Address::from_low_u64_be(0xf00d); // Starts empty
+        let value = parse_ether(1)?;
+
+        // The "faucet" pays out after a short delay
+        let faucet = {
+            let ctx = ChainContext::new(provider.clone());
+            tokio::spawn(async move {
+                sleep(Duration::from_millis(500)).await;
+                send_transfer(&ctx, from, to, value, &TransferOptions::default()).await
+            })
+        };
+
+        let balance = wait_for_balance(&provider, to, value, Duration::from_secs(10)).await?;
+        assert_eq!(balance, value);
+        faucet.await??;
+
+        // Nothing more is coming, so waiting for twice as much times out
+        let err = wait_for_balance(&provider, to, value * 2, Duration::from_millis(300))
+            .await
+            .expect_err("The balance should stay short");
+        match err.downcast_ref::<Error>() {
+            Some(Error::BalanceNotReached {
+                balance, at_least, ..
+            }) => {
+                assert_eq!(*balance, value);
+                assert_eq!(*at_least, value * 2);
+            }
+            other => panic!("Expected BalanceNotReached, got {other:?}"),
+        }
+
+        Ok(())
+    }
+}
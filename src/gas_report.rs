@@ -0,0 +1,363 @@
+//! Per-function gas usage collected over a session of transactions.
+
+use crate::deploy::DeploymentResult;
+use ethers::{
+    abi::Detokenize,
+    contract::ContractCall,
+    prelude::{Middleware, TransactionReceipt, U256},
+};
+use eyre::{eyre, ContextCompat, Result};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
+
+#[derive(Default)]
+struct Records {
+    calls: Vec<CallRecord>,
+    deployments: Vec<(String, U256)>,
+}
+
+struct CallRecord {
+    contract: String,
+    function: String,
+    gas_used: U256,
+    success: bool,
+}
+
+/// Collects the gas used by every transaction sent through it.
+///
+/// Clones share the same records, so one reporter can be handed to concurrent tasks. A disabled
+/// reporter still sends transactions but records nothing and never takes the lock.
+#[derive(Clone)]
+pub struct GasReporter {
+    records: Option<Arc<Mutex<Records>>>,
+}
+
+impl Default for GasReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GasReporter {
+    /// A reporter that records.
+    pub fn new() -> Self {
+        Self {
+            records: Some(Default::default()),
+        }
+    }
+
+    /// A reporter that only passes transactions through.
+    pub fn disabled() -> Self {
+        Self { records: None }
+    }
+
+    /// Whether transactions are being recorded.
+    pub fn is_enabled(&self) -> bool {
+        self.records.is_some()
+    }
+
+    /// Sends `call`, waits for it to be mined and records its gas under `contract`.
+    ///
+    /// A transaction mined with a failed status is recorded as a failure and returned as an error.
+    pub async fn send<M, D>(
+        &self,
+        contract: &str,
+        call: &ContractCall<M, D>,
+    ) -> Result<TransactionReceipt>
+    where
+        M: Middleware + 'static,
+        D: Detokenize,
+    {
+        let receipt = call
+            .send()
+            .await?
+            .await?
+            .context("Transaction dropped from the mempool")?;
+        let gas_used = receipt.gas_used.context("Receipt has no gas used")?;
+        let success = receipt.status == Some(1.into());
+        self.record_call(contract, &call.function.name, gas_used, success);
+
+        if !success {
+            return Err(eyre!(
+                "{contract}.{} reverted in tx {:?}",
+                call.function.name,
+                receipt.transaction_hash
+            ));
+        }
+        Ok(receipt)
+    }
+
+    /// Records one mined call of `contract.function`.
+    pub fn record_call(&self, contract: &str, function: &str, gas_used: U256, success: bool) {
+        if let Some(records) = &self.records {
+            records.lock().unwrap().calls.push(CallRecord {
+                contract: contract.to_string(),
+                function: function.to_string(),
+                gas_used,
+                success,
+            });
+        }
+    }
+
+    /// Records the deployment of `contract`, kept apart from its function calls.
+    pub fn record_deployment(&self, contract: &str, result: &DeploymentResult) {
+        if let Some(records) = &self.records {
+            records
+                .lock()
+                .unwrap()
+                .deployments
+                .push((contract.to_string(), result.gas_used));
+        }
+    }
+
+    /// Aggregates what was recorded so far; empty for a disabled reporter.
+    pub fn report(&self) -> GasReport {
+        let Some(records) = &self.records else {
+            return GasReport::default();
+        };
+        let records = records.lock().unwrap();
+
+        // Keyed by (contract, function) so the rows come out sorted
+        let mut functions: BTreeMap<(&str, &str), FunctionGas> = BTreeMap::new();
+        for record in &records.calls {
+            let row = functions
+                .entry((&record.contract, &record.function))
+                .or_insert_with(|| FunctionGas {
+                    contract: record.contract.clone(),
+                    function: record.function.clone(),
+                    calls: 0,
+                    failures: 0,
+                    min: record.gas_used,
+                    avg: U256::zero(),
+                    max: record.gas_used,
+                    total: U256::zero(),
+                });
+            row.calls += 1;
+            row.failures += u64::from(!record.success);
+            row.min = row.min.min(record.gas_used);
+            row.max = row.max.max(record.gas_used);
+            row.total += record.gas_used;
+        }
+        let functions = functions
+            .into_values()
+            .map(|mut row| {
+                row.avg = row.total / row.calls;
+                row
+            })
+            .collect();
+
+        let deployments = records
+            .deployments
+            .iter()
+            .map(|(contract, gas_used)| DeploymentGas {
+                contract: contract.clone(),
+                gas_used: *gas_used,
+            })
+            .collect();
+
+        GasReport {
+            functions,
+            deployments,
+        }
+    }
+}
+
+/// Gas statistics of the recorded transactions.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GasReport {
+    /// One row per contract function, sorted by contract then function.
+    pub functions: Vec<FunctionGas>,
+    /// Deployments in the order they were recorded.
+    pub deployments: Vec<DeploymentGas>,
+}
+
+/// Gas used by the calls of one function.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FunctionGas {
+    /// Contract name given when the call was recorded.
+    pub contract: String,
+    pub function: String,
+    /// Number of mined calls.
+    pub calls: u64,
+    /// Calls mined with a failed status (their gas is included in the statistics).
+    pub failures: u64,
+    pub min: U256,
+    /// Mean gas used, rounded down.
+    pub avg: U256,
+    pub max: U256,
+    /// Gas used by all the calls together.
+    pub total: U256,
+}
+
+/// Gas used by one deployment.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentGas {
+    pub contract: String,
+    pub gas_used: U256,
+}
+
+impl GasReport {
+    /// The report as CSV: a `kind,contract,function,calls,failures,min,avg,max,total` header,
+    /// then one `function` row per function and one `deployment` row per deployment.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("kind,contract,function,calls,failures,min,avg,max,total\n");
+        for row in &self.functions {
+            csv.push_str(&format!(
+                "function,{},{},{},{},{},{},{},{}\n",
+                row.contract,
+                row.function,
+                row.calls,
+                row.failures,
+                row.min,
+                row.avg,
+                row.max,
+                row.total
+            ));
+        }
+        for deployment in &self.deployments {
+            let gas = deployment.gas_used;
+            csv.push_str(&format!(
+                "deployment,{},,1,0,{gas},{gas},{gas},{gas}\n",
+                deployment.contract
+            ));
+        }
+        csv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::deploy::{deploy_contract, DeployOptions};
+    use crate::output::render;
+    use crate::project::{compile, contract_parts};
+    use ethers::prelude::{Address, LocalWallet, Provider};
+    use ethers::utils::Ganache;
+
+    #[test]
+    fn test_disabled_reporter_records_nothing() {
+        let reporter = GasReporter::disabled();
+        reporter.record_call("BUSD", "transfer", U256::from(50_000u64), true);
+        assert!(!reporter.is_enabled());
+        assert_eq!(reporter.report(), GasReport::default());
+    }
+
+    #[test]
+    fn test_statistics_from_concurrent_records() {
+        let reporter = GasReporter::new();
+        let handles: Vec<_> = (1..=4u64)
+            .map(|i| {
+                let reporter = reporter.clone();
+                std::thread::spawn(move || {
+                    reporter.record_call("BUSD", "transfer", U256::from(i * 10_000), i != 4);
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let report = reporter.report();
+        assert_eq!(report.functions.len(), 1);
+        let row = &report.functions[0];
+        assert_eq!((row.calls, row.failures), (4, 1));
+        assert_eq!(row.min, U256::from(10_000u64));
+        assert_eq!(row.avg, U256::from(25_000u64));
+        assert_eq!(row.max, U256::from(40_000u64));
+        assert!(report
+            .to_csv()
+            .contains("function,BUSD,transfer,4,1,10000,25000,40000,100000\n"));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_busd_session_report() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let (busd, deployment) = deploy_contract(
+            &ctx,
+            wallet,
+            "BUSDImplementation",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+
+        let reporter = GasReporter::new();
+        reporter.record_deployment("BUSD", &deployment);
+        reporter
+            .send("BUSD", &busd.method::<_, ()>("unpause", ())?)
+            .await?;
+        reporter
+            .send(
+                "BUSD",
+                &busd.method::<_, bool>("increaseSupply", U256::from(1_000_000u64))?,
+            )
+            .await?;
+        for i in 1..=3u64 {
+            let to = Address::from_low_u64_be(i);
+            reporter
+                .send(
+                    "BUSD",
+                    &busd.method::<_, bool>("transfer", (to, U256::from(i)))?,
+                )
+                .await?;
+            reporter
+                .send(
+                    "BUSD",
+                    &busd.method::<_, bool>("approve", (to, U256::from(i)))?,
+                )
+                .await?;
+        }
+        // Pause and unpause alternate, ending paused
+        for _ in 0..2 {
+            reporter
+                .send("BUSD", &busd.method::<_, ()>("pause", ())?)
+                .await?;
+            reporter
+                .send("BUSD", &busd.method::<_, ()>("unpause", ())?)
+                .await?;
+        }
+        reporter
+            .send("BUSD", &busd.method::<_, ()>("pause", ())?)
+            .await?;
+
+        let report = reporter.report();
+        for (function, calls) in [("transfer", 3), ("approve", 3), ("pause", 3)] {
+            let row = report
+                .functions
+                .iter()
+                .find(|row| row.function == function)
+                .unwrap_or_else(|| panic!("{function} is missing from the report"));
+            assert_eq!(row.calls, calls);
+            assert_eq!(row.failures, 0);
+            // Above the 21000 base cost, well under a block
+            assert!(row.min > U256::from(21_000u64), "{row:?}");
+            assert!(row.min <= row.avg && row.avg <= row.max, "{row:?}");
+            assert!(row.max < U256::from(200_000u64), "{row:?}");
+        }
+        assert_eq!(report.deployments.len(), 1);
+        assert_eq!(report.deployments[0].gas_used, deployment.gas_used);
+
+        let table = render(&report, false)?;
+        assert!(table.contains("transfer") && table.contains("Deployments"));
+        assert!(render(&report, true)?.contains("\"gasUsed\""));
+
+        Ok(())
+    }
+}
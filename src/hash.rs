@@ -0,0 +1,42 @@
+//! Keccak-256 hashing, as used for selectors, CREATE and CREATE2 addresses and salts.
+
+use eyre::{Context, Result};
+
+/// Keccak-256 of `data`.
+pub fn keccak256(data: &[u8]) -> [u8; 32] {
+    ethers::utils::keccak256(data)
+}
+
+/// Keccak-256 of the bytes a hex string encodes, with or without `0x`, as `0x`-prefixed
+/// lowercase hex.
+pub fn keccak256_hex(hex_str: &str) -> Result<String> {
+    let hex_str = hex_str.trim();
+    let digits = hex_str.strip_prefix("0x").unwrap_or(hex_str);
+    let data = hex::decode(digits).wrap_err_with(|| format!("Invalid hex input {hex_str:?}"))?;
+    Ok(format!("0x{}", hex::encode(keccak256(&data))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMPTY_HASH: &str = "0xc5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470";
+
+    #[test]
+    fn test_known_hashes() -> Result<()> {
+        assert_eq!(format!("0x{}", hex::encode(keccak256(&[]))), EMPTY_HASH);
+        assert_eq!(keccak256_hex("")?, EMPTY_HASH);
+        assert_eq!(keccak256_hex("0x")?, EMPTY_HASH);
+        assert_eq!(
+            keccak256(b"transfer(address,uint256)")[..4],
+            [0xa9, 0x05, 0x9c, 0xbb]
+        );
+        assert_eq!(
+            keccak256_hex("0x7472616e7366657228616464726573732c75696e7432353629")?,
+            keccak256_hex("7472616E7366657228616464726573732C75696E7432353629")?
+        );
+        assert!(keccak256_hex("0xabc").is_err());
+        assert!(keccak256_hex("0xzz").is_err());
+        Ok(())
+    }
+}
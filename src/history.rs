@@ -0,0 +1,307 @@
+//! What an address did on a chain, reconstructed by scanning blocks.
+
+use ethers::prelude::{Address, BlockNumber, Middleware, Transaction, H256, I256, U256};
+use eyre::{ContextCompat, Result};
+use futures::{stream, StreamExt};
+use serde::Serialize;
+use std::sync::Arc;
+
+/// How a transaction relates to the scanned address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TxDirection {
+    /// Sent by the address.
+    Sent,
+    /// Sent to the address by someone else.
+    Received,
+    /// Sent by the address to itself.
+    SelfTransfer,
+}
+
+/// One transaction sent or received by the scanned address.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TxSummary {
+    pub chain_id: u64,
+    pub block: u64,
+    /// Timestamp of the block, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    pub hash: H256,
+    pub nonce: U256,
+    pub direction: TxDirection,
+    pub from: Address,
+    /// `None` for contract creations.
+    pub to: Option<Address>,
+    /// Address of the contract the transaction created, if any.
+    pub created: Option<Address>,
+    pub value: U256,
+    /// Gas fee paid by the address: zero for received transactions.
+    pub fee: U256,
+    /// Whether the transaction succeeded; reverted ones still cost their fee.
+    pub success: bool,
+    /// Change of the address's balance caused by this transaction, in wei.
+    pub balance_change: I256,
+    /// Sum of the balance changes of the scan so far, this transaction included.
+    pub running_change: I256,
+}
+
+/// Called with `(blocks scanned, blocks in the range)` after each block.
+pub type HistoryProgress = Arc<dyn Fn(u64, u64) + Send + Sync>;
+
+/// Options for [`history`].
+#[derive(Clone)]
+pub struct HistoryOptions {
+    /// Blocks fetched at the same time.
+    pub concurrency: usize,
+    /// Stop once this many transactions were found.
+    pub limit: Option<usize>,
+    pub progress: Option<HistoryProgress>,
+}
+
+impl Default for HistoryOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 8,
+            limit: None,
+            progress: None,
+        }
+    }
+}
+
+/// Lists the transactions of blocks `from_block..=to_block` sent or received by `address`,
+/// oldest first.
+///
+/// Only top-level transactions are seen: value moved by contract internals isn't, so the
+/// running change can differ from the real balance change of addresses that receive such
+/// transfers.
+pub async fn history<M: Middleware>(
+    provider: &M,
+    address: Address,
+    from_block: u64,
+    to_block: u64,
+    options: &HistoryOptions,
+) -> Result<Vec<TxSummary>>
+where
+    M::Error: 'static,
+{
+    let chain_id = provider.get_chainid().await?.as_u64();
+    let total = to_block.saturating_sub(from_block) + 1;
+    // `buffered` keeps block order while fetching several blocks at a time
+    let mut blocks = stream::iter(from_block..=to_block)
+        .map(|number| async move {
+            let block = provider
+                .get_block_with_txs(BlockNumber::Number(number.into()))
+                .await?
+                .with_context(|| format!("Block {number} not found"))?;
+            eyre::Ok((number, block.timestamp.as_u64(), block.transactions))
+        })
+        .buffered(options.concurrency.max(1));
+
+    let mut summaries = Vec::new();
+    let mut running_change = I256::zero();
+    let mut scanned = 0;
+    while let Some(block) = blocks.next().await {
+        let (number, timestamp, transactions) = block?;
+        for tx in transactions.iter().filter(|tx| involves(tx, address)) {
+            let mined = Mined {
+                chain_id,
+                block: number,
+                timestamp,
+            };
+            let summary = summarize(provider, address, mined, tx, running_change).await?;
+            running_change = summary.running_change;
+            summaries.push(summary);
+            if options.limit.is_some_and(|limit| summaries.len() >= limit) {
+                return Ok(summaries);
+            }
+        }
+        scanned += 1;
+        if let Some(progress) = &options.progress {
+            progress(scanned, total);
+        }
+    }
+    Ok(summaries)
+}
+
+/// Summarizes the mined transaction `hash` from the point of view of `address`, e.g. to export
+/// a transfer that was just sent. Its running change is its own balance change.
+pub async fn summarize_tx<M: Middleware>(
+    provider: &M,
+    address: Address,
+    hash: H256,
+) -> Result<TxSummary>
+where
+    M::Error: 'static,
+{
+    let tx = provider
+        .get_transaction(hash)
+        .await?
+        .with_context(|| format!("Transaction {hash:?} not found"))?;
+    let block = tx
+        .block_number
+        .with_context(|| format!("Transaction {hash:?} is not mined"))?;
+    let timestamp = provider
+        .get_block(block)
+        .await?
+        .with_context(|| format!("Block {block} not found"))?
+        .timestamp
+        .as_u64();
+    let mined = Mined {
+        chain_id: provider.get_chainid().await?.as_u64(),
+        block: block.as_u64(),
+        timestamp,
+    };
+    summarize(provider, address, mined, &tx, I256::zero()).await
+}
+
+// Where and when a transaction was mined
+#[derive(Clone, Copy)]
+struct Mined {
+    chain_id: u64,
+    block: u64,
+    timestamp: u64,
+}
+
+fn involves(tx: &Transaction, address: Address) -> bool {
+    tx.from == address || tx.to == Some(address)
+}
+
+async fn summarize<M: Middleware>(
+    provider: &M,
+    address: Address,
+    mined: Mined,
+    tx: &Transaction,
+    running_change: I256,
+) -> Result<TxSummary>
+where
+    M::Error: 'static,
+{
+    let direction = match (tx.from == address, tx.to == Some(address)) {
+        (true, true) => TxDirection::SelfTransfer,
+        (true, false) => TxDirection::Sent,
+        _ => TxDirection::Received,
+    };
+    // The receipt holds the gas actually paid and the address of a created contract
+    let receipt = provider
+        .get_transaction_receipt(tx.hash)
+        .await?
+        .with_context(|| format!("Missing receipt for {:?}", tx.hash))?;
+    let fee = match direction {
+        TxDirection::Received => U256::zero(),
+        _ => {
+            let gas_used = receipt.gas_used.context("Missing gas used")?;
+            let gas_price = receipt
+                .effective_gas_price
+                .or(tx.gas_price)
+                .context("Missing gas price")?;
+            gas_used * gas_price
+        }
+    };
+    // A reverted transaction still costs its fee but moves no value
+    let success = receipt.status != Some(0u64.into());
+    let value = if !success { U256::zero() } else { tx.value };
+    let balance_change = match direction {
+        TxDirection::Sent => -I256::try_from(value + fee)?,
+        TxDirection::Received => I256::try_from(value)?,
+        TxDirection::SelfTransfer => -I256::try_from(fee)?,
+    };
+    Ok(TxSummary {
+        chain_id: mined.chain_id,
+        block: mined.block,
+        timestamp: mined.timestamp,
+        hash: tx.hash,
+        nonce: tx.nonce,
+        direction,
+        from: tx.from,
+        to: tx.to,
+        created: receipt.contract_address,
+        value: tx.value,
+        fee,
+        success,
+        balance_change,
+        running_change: running_change + balance_change,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::transfer::{send_transfer, TransferOptions};
+    use ethers::{prelude::Provider, utils::Ganache};
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_history_reconstructs_transfers() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let (alice, bob, carol) = (
+            ganache.addresses()[0],
+            ganache.addresses()[1],
+            ganache.addresses()[2],
+        );
+        let start_balance = provider.get_balance(alice, None).await?;
+
+        // Five transfers touching alice, and one she has nothing to do with
+        let transfers = [
+            (alice, bob, 1_000u64, TxDirection::Sent),
+            (bob, alice, 2_000, TxDirection::Received),
+            (alice, carol, 3_000, TxDirection::Sent),
+            (carol, alice, 4_000, TxDirection::Received),
+            (alice, alice, 5_000, TxDirection::SelfTransfer),
+        ];
+        let options = TransferOptions::default();
+        for (from, to, value, _) in transfers {
+            send_transfer(&ctx, from, to, value.into(), &options).await?;
+        }
+        send_transfer(&ctx, bob, carol, 6_000u64.into(), &options).await?;
+        let latest = provider.get_block_number().await?.as_u64();
+
+        let scanned = Arc::new(AtomicU64::new(0));
+        let options = HistoryOptions {
+            concurrency: 2,
+            progress: Some(Arc::new({
+                let scanned = scanned.clone();
+                move |done, _total| scanned.store(done, Ordering::SeqCst)
+            })),
+            ..Default::default()
+        };
+        let summaries = history(&provider, alice, 0, latest, &options).await?;
+
+        assert_eq!(summaries.len(), 5);
+        for (summary, (from, to, value, direction)) in summaries.iter().zip(transfers) {
+            assert_eq!(summary.from, from);
+            assert_eq!(summary.to, Some(to));
+            assert_eq!(summary.value, U256::from(value));
+            assert_eq!(summary.direction, direction);
+            assert_eq!(summary.fee.is_zero(), direction == TxDirection::Received);
+            assert_eq!(summary.chain_id, 1337);
+            assert!(summary.success);
+            assert!(summary.timestamp > 0);
+        }
+        assert!(summaries
+            .windows(2)
+            .all(|pair| pair[0].block <= pair[1].block));
+        assert_eq!(scanned.load(Ordering::SeqCst), latest + 1);
+
+        // Only plain transfers happened, so the running change matches the real one
+        let end_balance = provider.get_balance(alice, None).await?;
+        let running = summaries.last().context("No transactions")?.running_change;
+        assert_eq!(
+            running,
+            I256::try_from(end_balance)? - I256::try_from(start_balance)?
+        );
+
+        let limited = HistoryOptions {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let first_two = history(&provider, alice, 0, latest, &limited).await?;
+        assert_eq!(first_two, summaries[..2]);
+
+        Ok(())
+    }
+}
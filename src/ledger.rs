@@ -0,0 +1,345 @@
+//! Idempotent transfers: a payout keyed by a client-side identifier is paid at most once, even
+//! when the script sending it is retried after a crash.
+
+use crate::chain_context::ChainContext;
+use crate::polling::PendingTxExt;
+use crate::timeouts::OpTimeouts;
+use crate::tx_builder::TxBuilder;
+use crate::wallet::bind_to_chain;
+use ethers::{
+    prelude::{
+        Address, LocalWallet, Middleware, PendingTransaction, Signer, SignerMiddleware,
+        TransactionReceipt, H256, U256,
+    },
+    types::Bytes,
+    utils::keccak256,
+};
+use eyre::{eyre, ContextCompat, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+use tracing::{info, warn};
+
+/// A transfer recorded under an idempotency key, written before it is broadcast.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerEntry {
+    pub tx_hash: H256,
+    pub from: Address,
+    pub nonce: U256,
+    pub to: Address,
+    pub value: U256,
+    /// The signed transaction, rebroadcast as is if the node never saw it.
+    pub raw: Bytes,
+}
+
+/// JSON file mapping idempotency keys to the transfers sent for them.
+///
+/// Every change rewrites the file through a temporary file and a rename, so a crash leaves
+/// either the old or the new ledger, never a torn one. One process should use a ledger at a time.
+#[derive(Clone, Debug)]
+pub struct TransferLedger {
+    path: PathBuf,
+}
+
+impl TransferLedger {
+    /// Opens the ledger at `path`; the file is created on the first record.
+    pub fn open(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The transfer recorded under `key`, if any.
+    pub fn get(&self, key: &str) -> Result<Option<LedgerEntry>> {
+        Ok(self.load()?.remove(key))
+    }
+
+    /// Records `entry` under `key`, replacing any previous one, and flushes it to disk.
+    pub fn record(&self, key: &str, entry: LedgerEntry) -> Result<()> {
+        let mut entries = self.load()?;
+        entries.insert(key.to_string(), entry);
+        let tmp = self.path.with_extension("tmp");
+        let file = std::fs::File::create(&tmp)
+            .wrap_err_with(|| format!("Failed to write {}", tmp.display()))?;
+        serde_json::to_writer_pretty(&file, &entries)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, &self.path)
+            .wrap_err_with(|| format!("Failed to replace {}", self.path.display()))?;
+        Ok(())
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, LedgerEntry>> {
+        match std::fs::read(&self.path) {
+            Ok(bytes) => serde_json::from_slice(&bytes)
+                .wrap_err_with(|| format!("Corrupt ledger {}", self.path.display())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(err) => Err(eyre!(err).wrap_err(format!("Failed to read {}", self.path.display()))),
+        }
+    }
+}
+
+/// Pays `amount` Wei from `wallet` to `to` once per `key`, however many times it is called.
+///
+/// - If the transfer recorded under `key` was mined successfully, its receipt is returned.
+/// - If it is pending, it is awaited.
+/// - If the node doesn't know it (a crash between recording and broadcasting, or a node
+///   restart), the recorded signed transaction is broadcast again, with the same nonce, so it
+///   still can't be paid twice.
+/// - Only if it provably can't be mined anymore (it reverted, or its nonce was used by another
+///   transaction) or nothing was recorded is a new transfer signed, recorded, then broadcast.
+pub async fn send_once<M>(
+    store: &TransferLedger,
+    key: &str,
+    ctx: &ChainContext<M>,
+    wallet: &LocalWallet,
+    to: Address,
+    amount: U256,
+) -> Result<TransactionReceipt>
+where
+    M: Middleware + Clone + 'static,
+    M::Error: 'static,
+{
+    if let Some(entry) = store.get(key)? {
+        if let Some(receipt) = resume(ctx, key, &entry).await? {
+            return Ok(receipt);
+        }
+        warn!(key, tx_hash = ?entry.tx_hash, "recorded transfer was dropped, sending a new one");
+    }
+
+    let entry = sign_transfer(ctx, wallet, to, amount).await?;
+    store.record(key, entry.clone())?; // Recorded before the node can see it
+    broadcast(ctx, key, &entry).await
+}
+
+// The receipt of a recorded transfer, awaiting or rebroadcasting it as needed; `None` if it was dropped
+async fn resume<M: Middleware + 'static>(
+    ctx: &ChainContext<M>,
+    key: &str,
+    entry: &LedgerEntry,
+) -> Result<Option<TransactionReceipt>>
+where
+    M::Error: 'static,
+{
+    if let Some(receipt) = ctx.get_transaction_receipt(entry.tx_hash).await? {
+        if receipt.status == Some(1u64.into()) {
+            info!(key, tx_hash = ?entry.tx_hash, "already paid");
+            return Ok(Some(receipt));
+        }
+        return Ok(None); // Mined but reverted: nothing was paid
+    }
+    if ctx.get_transaction(entry.tx_hash).await?.is_some() {
+        info!(key, tx_hash = ?entry.tx_hash, "awaiting the recorded transfer");
+        return wait_for(ctx, entry.tx_hash).await.map(Some);
+    }
+    // Unknown to the node: replayable unless its nonce went to another transaction
+    let mined_nonce = ctx.get_transaction_count(entry.from, None).await?;
+    if mined_nonce > entry.nonce {
+        // The nonce may have gone to the recorded transfer itself, mined since the first lookup
+        return match ctx.get_transaction_receipt(entry.tx_hash).await? {
+            Some(receipt) if receipt.status == Some(1u64.into()) => {
+                info!(key, tx_hash = ?entry.tx_hash, "already paid");
+                Ok(Some(receipt))
+            }
+            _ => Ok(None),
+        };
+    }
+    broadcast(ctx, key, entry).await.map(Some)
+}
+
+async fn sign_transfer<M>(
+    ctx: &ChainContext<M>,
+    wallet: &LocalWallet,
+    to: Address,
+    amount: U256,
+) -> Result<LedgerEntry>
+where
+    M: Middleware + Clone + 'static,
+    M::Error: 'static,
+{
+    let wallet = bind_to_chain(ctx, wallet.clone()).await?;
+    let from = wallet.address();
+    let client = SignerMiddleware::new(ctx.clone(), wallet.clone());
+
+    // Let the client fill the nonce, gas and fees, then sign without sending
+    let mut tx = TxBuilder::new()
+        .from(from)
+        .to(to)
+        .value(amount)
+        .build(&client)
+        .await?;
+    client.fill_transaction(&mut tx, None).await?;
+    let signature = wallet.sign_transaction(&tx).await?;
+    let raw = tx.rlp_signed(&signature);
+    Ok(LedgerEntry {
+        tx_hash: H256(keccak256(&raw)),
+        from,
+        nonce: *tx.nonce().context("The nonce wasn't filled")?,
+        to,
+        value: amount,
+        raw,
+    })
+}
+
+async fn broadcast<M: Middleware + 'static>(
+    ctx: &ChainContext<M>,
+    key: &str,
+    entry: &LedgerEntry,
+) -> Result<TransactionReceipt>
+where
+    M::Error: 'static,
+{
+    let pending = ctx.send_raw_transaction(entry.raw.clone()).await?;
+    if *pending != entry.tx_hash {
+        return Err(eyre!(
+            "The node reported {:?} for the recorded {:?}",
+            *pending,
+            entry.tx_hash
+        ));
+    }
+    info!(key, tx_hash = ?entry.tx_hash, nonce = %entry.nonce, "transfer broadcast");
+    wait_for(ctx, entry.tx_hash).await
+}
+
+async fn wait_for<M: Middleware + 'static>(
+    ctx: &ChainContext<M>,
+    tx_hash: H256,
+) -> Result<TransactionReceipt> {
+    OpTimeouts::default()
+        .receipt(
+            "idempotent transfer receipt",
+            PendingTransaction::new(tx_hash, ctx.provider()).await_smart(ctx),
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mined_receipt, spawn_mock_node};
+    use ethers::prelude::Provider;
+    use ethers::utils::Ganache;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    fn temp_ledger(name: &str) -> TransferLedger {
+        let path = std::env::temp_dir().join(format!("{name}-{}.json", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        TransferLedger::open(path)
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_retried_payout_is_paid_once() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let ledger = temp_ledger("ledger-retry");
+        let (to, amount) = (Address::from_low_u64_be(0xb0b), U256::from(1000u64));
+
+        let first = send_once(&ledger, "payout-1", &ctx, &wallet, to, amount).await?;
+        // The script is retried: the recorded payout is found and nothing is sent
+        let retried = send_once(&ledger, "payout-1", &ctx, &wallet, to, amount).await?;
+        assert_eq!(retried.transaction_hash, first.transaction_hash);
+        assert_eq!(provider.get_balance(to, None).await?, amount);
+
+        // Another key is another payout
+        send_once(&ledger, "payout-2", &ctx, &wallet, to, amount).await?;
+        assert_eq!(provider.get_balance(to, None).await?, amount * 2);
+
+        std::fs::remove_file(ledger.path())?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_crash_before_broadcast_resends_the_same_nonce() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let ledger = temp_ledger("ledger-crash");
+        let (to, amount) = (Address::from_low_u64_be(0xca11), U256::from(2500u64));
+
+        // The first run recorded its transfer, then crashed before broadcasting it
+        let recorded = sign_transfer(&ctx, &wallet, to, amount).await?;
+        ledger.record("payout", recorded.clone())?;
+        assert!(provider.get_transaction(recorded.tx_hash).await?.is_none());
+
+        // The restarted run sends exactly the recorded transaction
+        let receipt = send_once(&ledger, "payout", &ctx, &wallet, to, amount).await?;
+        assert_eq!(receipt.transaction_hash, recorded.tx_hash);
+        let mined = provider
+            .get_transaction(recorded.tx_hash)
+            .await?
+            .context("Missing transaction")?;
+        assert_eq!(mined.nonce, recorded.nonce);
+
+        send_once(&ledger, "payout", &ctx, &wallet, to, amount).await?;
+        assert_eq!(provider.get_balance(to, None).await?, amount);
+        assert_eq!(
+            provider
+                .get_transaction_count(wallet.address(), None)
+                .await?,
+            U256::one()
+        );
+
+        std::fs::remove_file(ledger.path())?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transfer_mined_during_resume_is_not_paid_again() -> Result<()> {
+        let entry = LedgerEntry {
+            tx_hash: H256::from_low_u64_be(0x5e),
+            from: Address::from_low_u64_be(0xa11ce),
+            nonce: U256::from(4u64),
+            to: Address::from_low_u64_be(0xb0b),
+            value: U256::from(1000u64),
+            raw: Bytes::from(vec![0xf8]),
+        };
+        let receipt_lookups = Arc::new(AtomicUsize::new(0));
+        let methods = Arc::new(Mutex::new(Vec::new()));
+
+        // The recorded transfer is mined right after its first receipt lookup, so the nonce
+        // read next has already moved past it
+        let url = spawn_mock_node({
+            let (receipt_lookups, methods) = (receipt_lookups.clone(), methods.clone());
+            let tx_hash = entry.tx_hash;
+            move |method, _| {
+                methods.lock().unwrap().push(method.to_string());
+                match method {
+                    "eth_getTransactionReceipt" => {
+                        if receipt_lookups.fetch_add(1, Ordering::SeqCst) == 0 {
+                            json!(null)
+                        } else {
+                            json!(mined_receipt(tx_hash))
+                        }
+                    }
+                    "eth_getTransactionByHash" => json!(null),
+                    "eth_getTransactionCount" => json!("0x5"),
+                    _ => json!({ "error": { "code": -32601, "message": "method not found" } }),
+                }
+            }
+        })
+        .await?;
+        let ctx = ChainContext::new(Provider::try_from(url)?);
+
+        let receipt = resume(&ctx, "payout", &entry)
+            .await?
+            .context("The mined transfer was taken for dropped")?;
+        assert_eq!(receipt.transaction_hash, entry.tx_hash);
+        // Nothing was signed or sent again
+        let methods = methods.lock().unwrap();
+        assert!(!methods.iter().any(|method| method.starts_with("eth_send")));
+        Ok(())
+    }
+}
@@ -0,0 +1,65 @@
+//! Helpers shared by the `deploy` and `transact` binaries.
+
+pub mod abi_diff;
+pub mod accounts;
+pub mod amount;
+pub mod artifacts;
+pub mod bytecode;
+pub mod calldata;
+pub mod chain_context;
+pub mod chain_guard;
+pub mod config;
+pub mod confirm;
+pub mod contract_repl;
+pub mod create2;
+pub mod deploy;
+pub mod dry_run;
+pub mod ens;
+pub mod erc20;
+pub mod errors;
+pub mod events;
+pub mod explorer;
+pub mod export;
+pub mod faucet;
+pub mod fees;
+pub mod flatten;
+pub mod funds;
+pub mod gas_report;
+pub mod hash;
+pub mod history;
+pub mod ledger;
+pub mod logging;
+pub mod mempool;
+pub mod mnemonic;
+pub mod monitor;
+pub mod multicall;
+pub mod node;
+pub mod notify;
+pub mod offline;
+pub mod output;
+pub mod payment_uri;
+pub mod plan;
+pub mod polling;
+pub mod project;
+pub mod provider;
+pub mod proxy;
+pub mod receipt;
+pub mod reorg;
+pub mod repl;
+pub mod report;
+pub mod safe;
+pub mod simulate;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod state;
+pub mod storage;
+pub mod timeouts;
+pub mod trace;
+pub mod traffic;
+pub mod transfer;
+pub mod tx_builder;
+pub mod verify;
+pub mod wallet;
+
+#[cfg(test)]
+mod test_support;
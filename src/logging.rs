@@ -0,0 +1,29 @@
+//! Subscriber setup for the binaries. Library code only emits `tracing` events and never
+//! installs a subscriber itself.
+
+use clap::ValueEnum;
+use tracing_subscriber::EnvFilter;
+
+/// Output format of the log lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    #[default]
+    Pretty,
+    /// One JSON object per event.
+    Json,
+}
+
+/// Installs the global subscriber, filtered by `RUST_LOG` (defaults to `info`).
+///
+/// Logs go to stderr so stdout only carries the command's result.
+pub fn init_tracing(format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr);
+    match format {
+        LogFormat::Pretty => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+}
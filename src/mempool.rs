@@ -0,0 +1,184 @@
+//! Inspection of the transactions a node holds but hasn't mined yet.
+
+use crate::errors::is_method_unavailable;
+use ethers::{
+    prelude::{Address, BlockNumber, JsonRpcClient, Middleware, Provider, Transaction, H256, U256},
+    providers::RpcError,
+};
+use eyre::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+use tracing::debug;
+
+/// A transaction waiting in the pool.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingTx {
+    pub hash: H256,
+    pub nonce: U256,
+    /// `None` when the node reports no gas price for the transaction.
+    pub gas_price: Option<U256>,
+    pub to: Option<Address>,
+    pub value: U256,
+}
+
+impl From<&Transaction> for PendingTx {
+    fn from(tx: &Transaction) -> Self {
+        Self {
+            hash: tx.hash,
+            nonce: tx.nonce,
+            // EIP-1559 transactions carry their fee cap instead of a gas price
+            gas_price: tx.gas_price.or(tx.max_fee_per_gas),
+            to: tx.to,
+            value: tx.value,
+        }
+    }
+}
+
+/// The pool's pending transactions, per sender, each list sorted by nonce.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct PoolSnapshot {
+    pub senders: BTreeMap<Address, Vec<PendingTx>>,
+}
+
+impl PoolSnapshot {
+    fn from_transactions<'a>(transactions: impl IntoIterator<Item = &'a Transaction>) -> Self {
+        let mut senders: BTreeMap<Address, Vec<PendingTx>> = BTreeMap::new();
+        for tx in transactions {
+            senders.entry(tx.from).or_default().push(tx.into());
+        }
+        for pending in senders.values_mut() {
+            pending.sort_by_key(|tx| tx.nonce);
+        }
+        Self { senders }
+    }
+
+    /// Pending transactions of `sender`, sorted by nonce.
+    pub fn for_sender(&self, sender: Address) -> &[PendingTx] {
+        self.senders.get(&sender).map_or(&[], Vec::as_slice)
+    }
+
+    /// Nonces missing between the first and last pending transaction of `sender`.
+    ///
+    /// A transaction behind a gap can't be mined until the missing nonces are sent.
+    pub fn nonce_gaps(&self, sender: Address) -> Vec<U256> {
+        self.for_sender(sender)
+            .windows(2)
+            .flat_map(|pair| {
+                let mut missing = Vec::new();
+                let mut nonce = pair[0].nonce + 1;
+                while nonce < pair[1].nonce {
+                    missing.push(nonce);
+                    nonce += U256::one();
+                }
+                missing
+            })
+            .collect()
+    }
+}
+
+/// Lists the transactions waiting in the node's pool.
+///
+/// Uses `txpool_content` (Geth, Anvil), pending and queued transactions together. Nodes without
+/// it fall back to the `pending` block, minus the transactions already in the latest block.
+pub async fn pending_pool<T: JsonRpcClient>(provider: &Provider<T>) -> Result<PoolSnapshot> {
+    match provider.txpool_content().await {
+        Ok(content) => {
+            let transactions = content
+                .pending
+                .values()
+                .chain(content.queued.values())
+                .flat_map(|by_nonce| by_nonce.values());
+            Ok(PoolSnapshot::from_transactions(transactions))
+        }
+        Err(err) => match err.as_error_response() {
+            Some(response) if is_method_unavailable(response) => {
+                debug!(message = %response.message, "txpool_content unavailable, using the pending block");
+                pending_block_pool(provider).await
+            }
+            _ => Err(err.into()),
+        },
+    }
+}
+
+/// Pending transactions sent by `address`, sorted by nonce.
+pub async fn pending_for<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    address: Address,
+) -> Result<Vec<PendingTx>> {
+    let mut pool = pending_pool(provider).await?;
+    Ok(pool.senders.remove(&address).unwrap_or_default())
+}
+
+async fn pending_block_pool<T: JsonRpcClient>(provider: &Provider<T>) -> Result<PoolSnapshot> {
+    let pending = provider.get_block_with_txs(BlockNumber::Pending).await?;
+    let latest = provider.get_block(BlockNumber::Latest).await?;
+    // Nodes that don't build a pending block answer with the latest one
+    let mined: HashSet<H256> = latest
+        .map(|block| block.transactions)
+        .unwrap_or_default()
+        .into_iter()
+        .collect();
+    let transactions = pending.map(|block| block.transactions).unwrap_or_default();
+    Ok(PoolSnapshot::from_transactions(
+        transactions.iter().filter(|tx| !mined.contains(&tx.hash)),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::Render;
+    use ethers::prelude::TransactionRequest;
+    use ethers::utils::Ganache;
+
+    fn pending_tx(nonce: u64) -> PendingTx {
+        PendingTx {
+            hash: H256::from_low_u64_be(nonce),
+            nonce: nonce.into(),
+            gas_price: Some(U256::from(1_000_000_000u64)),
+            to: Some(Address::repeat_byte(0xbb)),
+            value: 1.into(),
+        }
+    }
+
+    #[test]
+    fn test_nonce_gaps_are_flagged() {
+        let sender = Address::repeat_byte(0xaa);
+        let snapshot = PoolSnapshot {
+            senders: BTreeMap::from([(sender, vec![pending_tx(3), pending_tx(4), pending_tx(7)])]),
+        };
+
+        assert_eq!(
+            snapshot.nonce_gaps(sender),
+            vec![U256::from(5), U256::from(6)]
+        );
+        let rendered = snapshot.human();
+        assert!(rendered.contains("nonce gap: 5, 6"), "{rendered}");
+        assert!(snapshot.nonce_gaps(Address::zero()).is_empty());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_pending_transfers_are_listed_before_mining() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        // Mine every 10 seconds so the transfers stay in the pool while it is queried
+        let ganache = Ganache::new().mnemonic(mnemonic).block_time(10u64).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let (from, to) = (ganache.addresses()[0], ganache.addresses()[1]);
+
+        let mut hashes = Vec::new();
+        for nonce in 0u64..3 {
+            let tx = TransactionRequest::pay(to, 1000u64).from(from).nonce(nonce);
+            hashes.push(*provider.send_transaction(tx, None).await?);
+        }
+
+        let pending = pending_for(&provider, from).await?;
+        let nonces: Vec<U256> = pending.iter().map(|tx| tx.nonce).collect();
+        assert_eq!(nonces, vec![U256::from(0), U256::from(1), U256::from(2)]);
+        assert_eq!(pending.iter().map(|tx| tx.hash).collect::<Vec<_>>(), hashes);
+        assert!(pending_for(&provider, to).await?.is_empty());
+
+        Ok(())
+    }
+}
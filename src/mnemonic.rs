@@ -0,0 +1,120 @@
+//! BIP-39 mnemonic validation and wallet derivation.
+
+use crate::errors::Error;
+use ethers::signers::{
+    coins_bip39::{English, Mnemonic, Wordlist},
+    LocalWallet, MnemonicBuilder,
+};
+use eyre::Result;
+
+/// Word counts allowed by BIP-39.
+const VALID_WORD_COUNTS: [usize; 5] = [12, 15, 18, 21, 24];
+
+/// A mnemonic phrase that passed [`validate_mnemonic`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ValidatedMnemonic(String);
+
+impl ValidatedMnemonic {
+    /// The normalized phrase (lowercase, single-space separated).
+    pub fn phrase(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Checks the word count, English wordlist membership and checksum of a BIP-39 phrase.
+pub fn validate_mnemonic(phrase: &str) -> Result<ValidatedMnemonic> {
+    // Normalize whitespace and case so the checksum comparison is on the canonical phrase
+    let words: Vec<String> = phrase.split_whitespace().map(str::to_lowercase).collect();
+
+    if !VALID_WORD_COUNTS.contains(&words.len()) {
+        return Err(Error::InvalidMnemonicWordCount { count: words.len() }.into());
+    }
+
+    // Report the first word that is not part of the English wordlist
+    for (index, word) in words.iter().enumerate() {
+        if English::get_index(word).is_err() {
+            return Err(Error::UnknownMnemonicWord {
+                index,
+                word: word.clone(),
+            }
+            .into());
+        }
+    }
+
+    // All words are known, so any remaining failure comes from the checksum bits
+    let normalized = words.join(" ");
+    Mnemonic::<English>::new_from_phrase(&normalized)
+        .map_err(|_| Error::InvalidMnemonicChecksum)?;
+
+    Ok(ValidatedMnemonic(normalized))
+}
+
+/// Derives the wallet at `m/44'/60'/0'/0/{index}`, the same path Ganache uses for its accounts.
+pub fn derive_wallet(mnemonic: &ValidatedMnemonic, index: u32) -> Result<LocalWallet> {
+    let wallet = MnemonicBuilder::<English>::default()
+        .phrase(mnemonic.phrase())
+        .index(index)?
+        .build()?;
+    Ok(wallet)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::signers::Signer;
+
+    const MNEMONIC: &str =
+        "brisk usual burst upper buddy female library dial rifle mercy globe nurse";
+
+    fn error_of(phrase: &str) -> Error {
+        validate_mnemonic(phrase)
+            .expect_err("Phrase should be rejected")
+            .downcast::<Error>()
+            .expect("Error should be typed")
+    }
+
+    #[test]
+    fn test_valid_mnemonic_is_accepted() -> Result<()> {
+        let mnemonic = validate_mnemonic(&format!("  {}  ", MNEMONIC.to_uppercase()))?;
+        assert_eq!(mnemonic.phrase(), MNEMONIC);
+
+        // The first derived account is the one Ganache funds for this mnemonic
+        let wallet = derive_wallet(&mnemonic, 0)?;
+        assert!(!wallet.address().is_zero());
+        Ok(())
+    }
+
+    #[test]
+    fn test_misspelled_word_reports_index() {
+        let phrase = MNEMONIC.replace("upper", "uper");
+        match error_of(&phrase) {
+            Error::UnknownMnemonicWord { index, word } => {
+                assert_eq!(index, 3);
+                assert_eq!(word, "uper");
+            }
+            other => panic!("Expected UnknownMnemonicWord, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_wrong_word_count_is_rejected() {
+        let phrase = MNEMONIC.rsplit_once(' ').unwrap().0;
+        assert!(matches!(
+            error_of(phrase),
+            Error::InvalidMnemonicWordCount { count: 11 }
+        ));
+    }
+
+    #[test]
+    fn test_swapped_words_fail_the_checksum() {
+        // Every word is still valid, only their order changed
+        let phrase = MNEMONIC.replacen("brisk usual", "usual brisk", 1);
+        assert!(matches!(error_of(&phrase), Error::InvalidMnemonicChecksum));
+    }
+
+    #[test]
+    fn test_bad_checksum_is_rejected() {
+        let phrase = ["abandon"; 12].join(" ");
+        assert!(matches!(error_of(&phrase), Error::InvalidMnemonicChecksum));
+    }
+}
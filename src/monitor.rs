@@ -0,0 +1,330 @@
+//! Live view of the blocks a chain produces.
+
+use crate::errors::Error;
+use ethers::prelude::{
+    Address, BlockNumber, Http, JsonRpcClient, Middleware, Provider, Ws, H256, U256,
+};
+use eyre::{eyre, ContextCompat, Result};
+use futures::{
+    channel::mpsc::{channel, Sender},
+    SinkExt, Stream, StreamExt,
+};
+use serde::Serialize;
+use std::{pin::Pin, time::Duration};
+use tracing::{debug, info, warn};
+
+/// One mined block.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockSummary {
+    pub number: u64,
+    pub hash: H256,
+    /// Unix timestamp, in seconds.
+    pub timestamp: u64,
+    pub tx_count: usize,
+    pub gas_used: U256,
+    /// `None` on chains without EIP-1559.
+    pub base_fee: Option<U256>,
+    /// Sender of each transaction, in block order.
+    pub senders: Vec<Address>,
+}
+
+impl BlockSummary {
+    /// Whether `address` sent any of the block's transactions.
+    pub fn has_sender(&self, address: Address) -> bool {
+        self.senders.contains(&address)
+    }
+}
+
+/// Longest wait between two reconnection attempts of a `ws://` watcher.
+pub const MAX_RECONNECT_BACKOFF: Duration = Duration::from_secs(5);
+
+// Wait before the second connection attempt, doubled for each further one
+const FIRST_RECONNECT_DELAY: Duration = Duration::from_millis(100);
+
+/// Blocks reported by [`watch_blocks`], in order and without gaps.
+pub type BlockStream = Pin<Box<dyn Stream<Item = Result<BlockSummary>> + Send>>;
+
+/// Streams every block mined after block `after` (after the current head when `None`).
+///
+/// `ws://` and `wss://` endpoints are followed with an `eth_subscribe` subscription; other
+/// endpoints are polled with `eth_blockNumber` every `poll_interval`. A dropped subscription is
+/// reconnected with [`reconnecting_ws`] and resumes after the last reported block. When the node
+/// stays away, the stream yields a final [`Error::MonitorDisconnected`] holding the last
+/// reported block, which can be passed back as `after` to resume without missing any. Dropping
+/// the stream stops the watcher.
+pub async fn watch_blocks(
+    endpoint: &str,
+    poll_interval: Duration,
+    after: Option<u64>,
+) -> Result<BlockStream> {
+    // Buffer a few blocks so a slow consumer doesn't stall the subscription
+    let (mut sender, receiver) = channel(16);
+    if endpoint.starts_with("ws://") || endpoint.starts_with("wss://") {
+        let endpoint = endpoint.to_string();
+        let mut provider = Provider::<Ws>::connect(&endpoint).await?;
+        let mut last = start_block(&provider, after).await?;
+        tokio::spawn(async move {
+            loop {
+                let message = match provider.subscribe_blocks().await {
+                    Ok(heads) => {
+                        info!(from = last, "following new heads");
+                        let heads = heads
+                            .filter_map(|block| async move { block.number })
+                            .map(|number| Ok(number.as_u64()));
+                        match follow(&provider, heads, last, &mut sender).await {
+                            Stop::Dropped => return,
+                            Stop::Disconnected {
+                                last_block,
+                                message,
+                            } => {
+                                last = last_block;
+                                message
+                            }
+                        }
+                    }
+                    Err(err) => err.to_string(),
+                };
+                warn!(last_block = last, %message, "subscription lost, reconnecting");
+                provider = match reconnecting_ws(&endpoint, MAX_RECONNECT_BACKOFF).await {
+                    Ok(provider) => provider,
+                    Err(err) => {
+                        let message = format!("{message}; reconnecting failed: {err:#}");
+                        return report_disconnect(sender, last, message).await;
+                    }
+                };
+            }
+        });
+    } else {
+        let provider = Provider::<Http>::try_from(endpoint)?;
+        let last = start_block(&provider, after).await?;
+        tokio::spawn(async move {
+            info!(from = last, ?poll_interval, "polling for new blocks");
+            let heads = futures::stream::unfold(provider.clone(), move |provider| async move {
+                tokio::time::sleep(poll_interval).await;
+                let head = provider
+                    .get_block_number()
+                    .await
+                    .map(|number| number.as_u64())
+                    .map_err(|err| err.to_string());
+                Some((head, provider))
+            });
+            if let Stop::Disconnected {
+                last_block,
+                message,
+            } = follow(&provider, heads, last, &mut sender).await
+            {
+                report_disconnect(sender, last_block, message).await;
+            }
+        });
+    }
+    Ok(receiver.boxed())
+}
+
+/// Connects to the `ws://` endpoint `url`, retrying with exponential backoff while it refuses.
+///
+/// The wait between attempts starts at 100 ms and doubles; once it would exceed `max_backoff`,
+/// the last connection error is returned.
+pub async fn reconnecting_ws(url: &str, max_backoff: Duration) -> Result<Provider<Ws>> {
+    let mut delay = FIRST_RECONNECT_DELAY;
+    loop {
+        match Provider::<Ws>::connect(url).await {
+            Ok(provider) => return Ok(provider),
+            Err(err) if delay > max_backoff => {
+                return Err(eyre!(err).wrap_err(format!("Failed to reconnect to {url}")))
+            }
+            Err(err) => debug!(%url, ?delay, error = %err, "connection refused, retrying"),
+        }
+        tokio::time::sleep(delay).await;
+        delay *= 2;
+    }
+}
+
+async fn start_block<T: JsonRpcClient>(provider: &Provider<T>, after: Option<u64>) -> Result<u64> {
+    match after {
+        Some(after) => Ok(after),
+        None => Ok(provider.get_block_number().await?.as_u64()),
+    }
+}
+
+// Why `follow` returned
+enum Stop {
+    /// The consumer dropped the stream.
+    Dropped,
+    /// The node went away after `last_block` was reported.
+    Disconnected { last_block: u64, message: String },
+}
+
+// Sends a summary of every block between the last reported one and each new head
+async fn follow<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    heads: impl Stream<Item = Result<u64, String>>,
+    mut last: u64,
+    sender: &mut Sender<Result<BlockSummary>>,
+) -> Stop {
+    let disconnected = |last_block, message| Stop::Disconnected {
+        last_block,
+        message,
+    };
+    futures::pin_mut!(heads);
+    while let Some(head) = heads.next().await {
+        let head = match head {
+            Ok(head) => head,
+            Err(message) => return disconnected(last, message),
+        };
+        while last < head {
+            let summary = match summarize_block(provider, last + 1).await {
+                Ok(summary) => summary,
+                Err(err) => return disconnected(last, format!("{err:#}")),
+            };
+            if sender.send(Ok(summary)).await.is_err() {
+                debug!("block stream dropped, stopping the watcher");
+                return Stop::Dropped;
+            }
+            last += 1;
+        }
+    }
+    // A subscription only ends when its connection does
+    disconnected(last, "subscription closed".to_string())
+}
+
+async fn report_disconnect(
+    mut sender: Sender<Result<BlockSummary>>,
+    last_block: u64,
+    message: String,
+) {
+    let _ = sender
+        .send(Err(Error::MonitorDisconnected {
+            last_block,
+            message,
+        }
+        .into()))
+        .await;
+}
+
+/// Fetches block `number` with its transactions and summarizes it.
+pub async fn summarize_block<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    number: u64,
+) -> Result<BlockSummary> {
+    let block = provider
+        .get_block_with_txs(BlockNumber::Number(number.into()))
+        .await?
+        .with_context(|| format!("Block {number} not found"))?;
+    Ok(BlockSummary {
+        number,
+        hash: block
+            .hash
+            .ok_or_else(|| eyre!("Block {number} has no hash"))?,
+        timestamp: block.timestamp.as_u64(),
+        tx_count: block.transactions.len(),
+        gas_used: block.gas_used,
+        base_fee: block.base_fee_per_gas,
+        senders: block.transactions.iter().map(|tx| tx.from).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::transfer::{send_transfer, TransferOptions};
+    use ethers::utils::Ganache;
+
+    async fn count_transactions(mut blocks: BlockStream, from: Address) -> Result<usize> {
+        let mut tx_count = 0;
+        while tx_count < 2 {
+            let block = blocks.next().await.context("The stream ended")??;
+            assert!(block.tx_count == 0 || block.has_sender(from), "{block:?}");
+            tx_count += block.tx_count;
+        }
+        Ok(tx_count)
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_watchers_report_two_transfers() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let (from, to) = (ganache.addresses()[0], ganache.addresses()[1]);
+
+        let polled = watch_blocks(&ganache.endpoint(), Duration::from_millis(20), None).await?;
+        let subscribed =
+            watch_blocks(&ganache.ws_endpoint(), Duration::from_millis(20), None).await?;
+        for value in [1u64, 2] {
+            send_transfer(&ctx, from, to, value.into(), &TransferOptions::default()).await?;
+        }
+
+        let timeout = Duration::from_secs(10);
+        let polled = tokio::time::timeout(timeout, count_transactions(polled, from)).await??;
+        let subscribed =
+            tokio::time::timeout(timeout, count_transactions(subscribed, from)).await??;
+        assert_eq!(polled, 2);
+        assert_eq!(subscribed, 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_disconnect_is_resumable() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let endpoint = ganache.endpoint();
+
+        let mut blocks = watch_blocks(&endpoint, Duration::from_millis(20), None).await?;
+        drop(ganache);
+
+        let err = tokio::time::timeout(Duration::from_secs(10), blocks.next())
+            .await?
+            .context("The stream ended without an error")?
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::MonitorDisconnected { last_block: 0, .. })
+        ));
+        assert!(blocks.next().await.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_subscription_survives_a_restart() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let port = 8571u16;
+        let ganache = Ganache::new().mnemonic(mnemonic).port(port).spawn();
+        let (from, to) = (ganache.addresses()[0], ganache.addresses()[1]);
+        let transfer = |endpoint: String| async move {
+            let ctx = ChainContext::new(Provider::try_from(endpoint)?);
+            send_transfer(&ctx, from, to, 1u64.into(), &TransferOptions::default()).await
+        };
+
+        let mut blocks =
+            watch_blocks(&ganache.ws_endpoint(), Duration::from_millis(20), None).await?;
+        transfer(ganache.endpoint()).await?;
+        let timeout = Duration::from_secs(10);
+        let first = tokio::time::timeout(timeout, blocks.next())
+            .await?
+            .context("The stream ended")??;
+        assert!(first.has_sender(from));
+
+        // Restart the node on the same port: the fresh chain starts over at block 0
+        drop(ganache);
+        let ganache = Ganache::new().mnemonic(mnemonic).port(port).spawn();
+        for _ in 0..=first.number {
+            transfer(ganache.endpoint()).await?;
+        }
+
+        // The watcher reconnects and resumes after the last block it reported
+        let resumed = tokio::time::timeout(Duration::from_secs(20), blocks.next())
+            .await?
+            .context("The stream ended")??;
+        assert_eq!(resumed.number, first.number + 1);
+        assert!(resumed.has_sender(from));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,325 @@
+//! Batched read-only contract calls through a Multicall3 contract.
+
+use crate::project::{compile, contract_parts};
+use ethers::{
+    abi::{parse_abi, Abi, Function, Token, Tokenize},
+    contract::{Contract, ContractFactory, MULTICALL_ADDRESS, MULTICALL_SUPPORTED_CHAIN_IDS},
+    prelude::{Address, Middleware, H256},
+    providers::RpcError,
+    types::Bytes,
+};
+use ethers_solc::Artifact;
+use eyre::{eyre, Context, ContextCompat, Result};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, OnceLock},
+};
+use tracing::{debug, info};
+
+// Folder holding the Multicall3 source deployed on chains that lack it
+const MULTICALL_SOURCES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/contracts/");
+
+// Addresses of the Multicall3 instances deployed by this process, keyed by chain id and genesis
+// hash so two Ganache instances sharing a chain id don't share an entry
+static DEPLOYED: OnceLock<Mutex<HashMap<(u64, H256), Address>>> = OnceLock::new();
+
+/// Outcome of one call in a batch.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CallResult {
+    /// Whether the call succeeded; always true unless the batch allows failures.
+    pub success: bool,
+    /// Decoded return values, empty for a failed call.
+    pub tokens: Vec<Token>,
+    /// Raw return data (the revert data for a failed call).
+    pub return_data: Bytes,
+}
+
+/// Read-only calls collected for one `tryAggregate` round trip.
+pub struct MulticallBatch<M> {
+    multicall: Contract<M>,
+    calls: Vec<(Address, Function, Bytes)>,
+    allow_failure: bool,
+}
+
+impl<M: Middleware + 'static> MulticallBatch<M> {
+    /// Starts an empty batch, attaching to Multicall3 on `client`'s chain or deploying it
+    /// once per chain when it isn't there.
+    pub async fn new(client: Arc<M>) -> Result<Self> {
+        let address = multicall_address(client.clone()).await?;
+        Ok(Self {
+            multicall: Contract::new(address, multicall_abi()?, client),
+            calls: Vec::new(),
+            allow_failure: false,
+        })
+    }
+
+    /// Address of the Multicall3 contract the batch is sent to.
+    pub fn address(&self) -> Address {
+        self.multicall.address()
+    }
+
+    /// When set, failed calls are reported in their [`CallResult`] instead of failing the batch.
+    pub fn allow_failure(mut self, allow_failure: bool) -> Self {
+        self.allow_failure = allow_failure;
+        self
+    }
+
+    /// Queues a call of `fn_name` with `args` on `contract`.
+    pub fn add_call<N, A>(
+        &mut self,
+        contract: &Contract<N>,
+        fn_name: &str,
+        args: A,
+    ) -> Result<&mut Self>
+    where
+        A: Tokenize,
+    {
+        let function = contract
+            .abi()
+            .function(fn_name)
+            .wrap_err_with(|| format!("{fn_name} is not in the contract's ABI"))?
+            .clone();
+        let data = function.encode_input(&args.into_tokens())?;
+        self.calls.push((contract.address(), function, data.into()));
+        Ok(self)
+    }
+
+    /// Sends every queued call in one `eth_call` and returns their results in order.
+    pub async fn execute(&self) -> Result<Vec<CallResult>> {
+        let calls: Vec<(Address, Bytes)> = self
+            .calls
+            .iter()
+            .map(|(target, _, data)| (*target, data.clone()))
+            .collect();
+
+        // `tryAggregate(requireSuccess, calls)` reverts on the first failure only if asked to
+        let results: Vec<(bool, Bytes)> = self
+            .multicall
+            .method("tryAggregate", (!self.allow_failure, calls))?
+            .call()
+            .await
+            .wrap_err("Multicall failed")?;
+
+        self.calls
+            .iter()
+            .zip(results)
+            .map(|((_, function, _), (success, return_data))| {
+                let tokens = if success {
+                    function
+                        .decode_output(&return_data)
+                        .wrap_err_with(|| format!("Failed to decode {} output", function.name))?
+                } else {
+                    Vec::new()
+                };
+                Ok(CallResult {
+                    success,
+                    tokens,
+                    return_data,
+                })
+            })
+            .collect()
+    }
+}
+
+fn multicall_abi() -> Result<Abi> {
+    Ok(parse_abi(&[
+        "struct Call { address target; bytes callData; }",
+        "struct Result { bool success; bytes returnData; }",
+        "function tryAggregate(bool requireSuccess, Call[] calls) payable returns (Result[] returnData)",
+    ])?)
+}
+
+/// Finds Multicall3 on `client`'s chain: the canonical deployment where one exists, else the
+/// instance this process deployed earlier, else a fresh deployment.
+pub async fn multicall_address<M: Middleware + 'static>(client: Arc<M>) -> Result<Address> {
+    let chain_id = client.get_chainid().await.map_err(|e| eyre!(e))?.as_u64();
+    // Supported chains have it, and so do local chains after `ensure_multicall`
+    let code = client
+        .get_code(MULTICALL_ADDRESS, None)
+        .await
+        .map_err(|e| eyre!(e))?;
+    if !code.is_empty() {
+        return Ok(MULTICALL_ADDRESS);
+    }
+    if MULTICALL_SUPPORTED_CHAIN_IDS.contains(&chain_id) {
+        debug!(
+            chain_id,
+            "no multicall3 at the canonical address of a supported chain"
+        );
+    }
+
+    let genesis = client
+        .get_block(0u64)
+        .await
+        .map_err(|e| eyre!(e))?
+        .and_then(|block| block.hash)
+        .context("Failed to get the genesis block hash")?;
+    let key = (chain_id, genesis);
+    let cache = DEPLOYED.get_or_init(Default::default);
+    if let Some(address) = cache.lock().unwrap().get(&key) {
+        return Ok(*address);
+    }
+
+    // Not available yet: compile and deploy our copy
+    let project = compile(MULTICALL_SOURCES).await?.output;
+    let (abi, bytecode) = contract_parts(&project, MULTICALL_SOURCES, "Multicall3")?;
+    let contract = ContractFactory::new(abi, bytecode, client)
+        .deploy(())?
+        .send()
+        .await?;
+    let address = contract.address();
+    info!(address = ?address, chain_id, "multicall3 deployed");
+
+    cache.lock().unwrap().insert(key, address);
+    Ok(address)
+}
+
+/// Makes Multicall3 available at its canonical address ([`MULTICALL_ADDRESS`]) on a local chain,
+/// returning that address.
+///
+/// Nothing is done when code is already there. Otherwise the runtime code of
+/// `contracts/Multicall3.sol` is installed with `evm_setAccountCode` (Ganache) or
+/// `anvil_setCode` (Anvil, Hardhat): the canonical deployment is a presigned transaction from a
+/// dedicated key, which a fresh chain can't replay for its own chain id. Public chains refuse
+/// both methods; use [`multicall_address`] there.
+pub async fn ensure_multicall<M: Middleware>(client: &M) -> Result<Address>
+where
+    M::Error: 'static,
+{
+    if !client.get_code(MULTICALL_ADDRESS, None).await?.is_empty() {
+        return Ok(MULTICALL_ADDRESS);
+    }
+
+    let project = compile(MULTICALL_SOURCES).await?.output;
+    let runtime = project
+        .find_first("Multicall3")
+        .and_then(|artifact| artifact.get_deployed_bytecode_bytes())
+        .map(|bytes| bytes.into_owned())
+        .context("Multicall3 has no runtime bytecode")?;
+
+    let provider = client.provider();
+    let params = (MULTICALL_ADDRESS, runtime);
+    match provider
+        .request::<_, serde_json::Value>("evm_setAccountCode", &params)
+        .await
+    {
+        Ok(_) => {}
+        // Not Ganache: try the Anvil/Hardhat flavour
+        Err(err) if err.as_error_response().is_some() => {
+            provider
+                .request::<_, serde_json::Value>("anvil_setCode", &params)
+                .await
+                .wrap_err("The node can't set account code, deploy Multicall3 another way")?;
+        }
+        Err(err) => return Err(err.into()),
+    }
+    if client.get_code(MULTICALL_ADDRESS, None).await?.is_empty() {
+        return Err(eyre!(
+            "Multicall3 code wasn't installed at {MULTICALL_ADDRESS:?}"
+        ));
+    }
+    info!(address = ?MULTICALL_ADDRESS, "multicall3 installed at its canonical address");
+    Ok(MULTICALL_ADDRESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::deploy::{deploy_contract, DeployOptions};
+    use ethers::prelude::{LocalWallet, Provider, Signer, U256};
+    use ethers::utils::Ganache;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_batched_balances_with_a_failing_call() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let owner = wallet.address();
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let (busd, _) = deploy_contract(
+            &ctx,
+            wallet,
+            "BUSDImplementation",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+
+        // Mint to the owner so one balance is non-zero
+        let supply = U256::from(1_000_000u64);
+        busd.method::<_, ()>("unpause", ())?.send().await?.await?;
+        busd.method::<_, bool>("increaseSupply", supply)?
+            .send()
+            .await?
+            .await?;
+
+        let mut batch = MulticallBatch::new(busd.client())
+            .await?
+            .allow_failure(true);
+        let holders: Vec<Address> = std::iter::once(owner)
+            .chain((1..5).map(Address::from_low_u64_be))
+            .collect();
+        for holder in &holders {
+            batch.add_call(&busd, "balanceOf", *holder)?;
+        }
+        // The Multicall contract holds no BUSD, so transferring from it reverts
+        batch.add_call(&busd, "transfer", (owner, U256::one()))?;
+
+        let results = batch.execute().await?;
+        assert_eq!(results.len(), 6);
+        assert_eq!(results[0].tokens, vec![Token::Uint(supply)]);
+        for result in &results[1..5] {
+            assert!(result.success);
+            assert_eq!(result.tokens, vec![Token::Uint(U256::zero())]);
+        }
+        assert!(!results[5].success, "The transfer should fail");
+        assert!(results[5].tokens.is_empty());
+
+        // Without failure tolerance the failing call fails the batch
+        let strict = MulticallBatch {
+            allow_failure: false,
+            ..batch
+        };
+        assert!(strict.execute().await.is_err());
+
+        // The deployed instance is reused for the rest of the session
+        let again = MulticallBatch::new(busd.client()).await?;
+        assert_eq!(again.address(), strict.address());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_ensure_multicall_installs_once() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        assert!(provider.get_code(MULTICALL_ADDRESS, None).await?.is_empty());
+
+        let address = ensure_multicall(&provider).await?;
+        assert_eq!(address, MULTICALL_ADDRESS);
+        let code = provider.get_code(address, None).await?;
+        assert!(!code.is_empty());
+
+        // The second call finds it and changes nothing
+        let block = provider.get_block_number().await?;
+        assert_eq!(ensure_multicall(&provider).await?, address);
+        assert_eq!(provider.get_code(address, None).await?, code);
+        assert_eq!(provider.get_block_number().await?, block);
+
+        // Batches use the installed instance instead of deploying their own
+        let batch = MulticallBatch::new(Arc::new(provider)).await?;
+        assert_eq!(batch.address(), MULTICALL_ADDRESS);
+
+        Ok(())
+    }
+}
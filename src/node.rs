@@ -0,0 +1,118 @@
+//! Local Ganache node launcher.
+
+use crate::mnemonic::ValidatedMnemonic;
+use ethers::prelude::{JsonRpcClient, Middleware, Provider};
+use ethers::utils::{Ganache, GanacheInstance};
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+
+/// Options of the spawned Ganache node; unset fields keep Ganache's defaults.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GanacheConfig {
+    /// Port to listen on (a free one is picked otherwise).
+    pub port: Option<u16>,
+    /// Seconds between blocks; blocks are mined on every transaction otherwise.
+    pub block_time: Option<u64>,
+    /// RPC URL of a chain to fork instead of starting from an empty one.
+    pub fork: Option<String>,
+    /// Whether transactions get mined without being asked to. When false, they stay pending
+    /// until [`mine_block`] is called (or the next `block_time` tick, if set).
+    pub auto_mine: bool,
+}
+
+impl Default for GanacheConfig {
+    fn default() -> Self {
+        Self {
+            port: None,
+            block_time: None,
+            fork: None,
+            auto_mine: true,
+        }
+    }
+}
+
+impl GanacheConfig {
+    /// Launches Ganache with these options and accounts derived from `mnemonic`.
+    ///
+    /// Ganache has no startup flag for `auto_mine`: call [`GanacheConfig::apply_mining`] once
+    /// connected.
+    pub fn spawn(&self, mnemonic: &ValidatedMnemonic) -> GanacheInstance {
+        let mut ganache = Ganache::new().mnemonic(mnemonic.phrase());
+        if let Some(port) = self.port {
+            ganache = ganache.port(port);
+        }
+        if let Some(block_time) = self.block_time {
+            ganache = ganache.block_time(block_time);
+        }
+        if let Some(fork) = &self.fork {
+            ganache = ganache.fork(fork);
+        }
+        ganache.spawn()
+    }
+
+    /// Stops the node's miner (`miner_stop`) when `auto_mine` is off; does nothing otherwise.
+    pub async fn apply_mining<T: JsonRpcClient>(&self, provider: &Provider<T>) -> Result<()> {
+        if !self.auto_mine {
+            provider.request::<_, bool>("miner_stop", ()).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Mines one block with the pending transactions (`evm_mine`), returning its number.
+pub async fn mine_block<T: JsonRpcClient>(provider: &Provider<T>) -> Result<u64> {
+    provider
+        .request::<_, serde_json::Value>("evm_mine", ())
+        .await?;
+    Ok(provider.get_block_number().await?.as_u64())
+}
+
+/// Launches a local Ganache instance whose accounts are derived from `mnemonic`.
+pub fn spawn_ganache(mnemonic: &ValidatedMnemonic) -> GanacheInstance {
+    GanacheConfig::default().spawn(mnemonic)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mnemonic::validate_mnemonic;
+    use crate::receipt::ReceiptSummary;
+    use ethers::prelude::TransactionRequest;
+    use eyre::ContextCompat;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_transactions_wait_for_mine_block_without_auto_mining() -> Result<()> {
+        let mnemonic = validate_mnemonic(
+            "gas monster ski craft below illegal discover limit dog bundle bus artefact",
+        )?;
+        let config = GanacheConfig {
+            auto_mine: false,
+            ..Default::default()
+        };
+        let ganache = config.spawn(&mnemonic);
+        let provider = Provider::try_from(ganache.endpoint())?;
+        config.apply_mining(&provider).await?;
+        let (from, to) = (ganache.addresses()[0], ganache.addresses()[1]);
+        let start = provider.get_block_number().await?.as_u64();
+
+        let tx = TransactionRequest::pay(to, 1000u64).from(from);
+        let hash = *provider.send_transaction(tx, None).await?;
+
+        // Nothing is mined on its own, even after a while
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+        assert!(provider.get_transaction_receipt(hash).await?.is_none());
+        assert_eq!(provider.get_block_number().await?.as_u64(), start);
+
+        let mined = mine_block(&provider).await?;
+        assert_eq!(mined, start + 1);
+        let receipt = provider
+            .get_transaction_receipt(hash)
+            .await?
+            .context("Missing receipt after mining")?;
+        assert_eq!(ReceiptSummary::from(receipt).block_number, mined);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,412 @@
+//! Notifications of finished deployments and transfers, e.g. to a Slack incoming webhook.
+//!
+//! Delivery is best effort: a failed notification is logged and never fails the operation.
+
+use crate::amount::format_eth_decimal;
+use ethers::{
+    prelude::{Address, TxHash, U256},
+    utils::parse_ether,
+};
+use eyre::{eyre, Result};
+use futures::future::BoxFuture;
+use reqwest::{header::CONTENT_TYPE, Client};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{fmt, sync::Arc, time::Duration};
+use tracing::{debug, warn};
+
+/// Upper bound for a whole delivery, retries included.
+pub const NOTIFY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Attempts per notification, and the pause before the second one (doubled for each further one)
+const ATTEMPTS: u32 = 3;
+const RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// What a notification reports on.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationEvent {
+    Deployment,
+    Transfer,
+}
+
+/// The outcome of a deployment or transfer, as posted to a webhook.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    pub event: NotificationEvent,
+    pub chain_id: Option<u64>,
+    /// Name of the deployed contract.
+    pub contract: Option<String>,
+    /// Recipient of the transfer.
+    pub recipient: Option<Address>,
+    /// Address of the deployed contract.
+    pub address: Option<Address>,
+    pub tx_hash: Option<TxHash>,
+    /// Value of the transfer in ETH.
+    pub value_eth: Option<String>,
+    /// Gas used times the effective gas price, in ETH without the unit, as in
+    /// `0.000021000000000000`.
+    pub gas_cost_eth: Option<String>,
+    pub success: bool,
+    pub error: Option<String>,
+    /// Value of the transfer in Wei, for thresholds.
+    #[serde(skip)]
+    pub value: Option<U256>,
+}
+
+impl Notification {
+    /// A notification of `event` with only the success flag set.
+    pub fn new(event: NotificationEvent, success: bool) -> Self {
+        Self {
+            event,
+            chain_id: None,
+            contract: None,
+            recipient: None,
+            address: None,
+            tx_hash: None,
+            value_eth: None,
+            gas_cost_eth: None,
+            success,
+            error: None,
+            value: None,
+        }
+    }
+
+    /// Sets the transfer value, in Wei.
+    pub fn value(mut self, value: U256) -> Self {
+        self.value = Some(value);
+        self.value_eth = Some(format_eth_decimal(value));
+        self
+    }
+
+    /// Sets the gas cost from the gas used and the effective gas price.
+    pub fn gas_cost(mut self, gas_used: Option<U256>, gas_price: Option<U256>) -> Self {
+        if let (Some(gas_used), Some(gas_price)) = (gas_used, gas_price) {
+            self.gas_cost_eth = Some(format_eth_decimal(gas_used * gas_price));
+        }
+        self
+    }
+
+    // Contract name or recipient, whichever the event has
+    fn subject(&self) -> String {
+        match (&self.contract, self.recipient) {
+            (Some(contract), _) => contract.clone(),
+            (None, Some(recipient)) => format!("{recipient:?}"),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+/// Delivers notifications somewhere, such as a chat webhook.
+pub trait Notifier: fmt::Debug + Send + Sync {
+    /// Sends `notification`, returning the delivery error if it couldn't be sent.
+    fn notify<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<()>>;
+}
+
+/// Sends `notification` through `notifier`, if any, logging a warning instead of failing.
+pub async fn deliver(notifier: Option<&Arc<dyn Notifier>>, notification: Notification) {
+    let Some(notifier) = notifier else {
+        return;
+    };
+    if let Err(err) = notifier.notify(&notification).await {
+        warn!(event = ?notification.event, error = %format!("{err:#}"), "notification not delivered");
+    }
+}
+
+/// The `[notify]` section of the config file.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// URL the notifications are POSTed to.
+    pub webhook: Option<String>,
+    /// Text posted as `{"text": ...}` instead of the JSON notification, with `{event}`,
+    /// `{chain_id}`, `{subject}`, `{address}`, `{tx_hash}`, `{value_eth}`, `{gas_cost_eth}` and
+    /// `{status}` replaced.
+    pub template: Option<String>,
+    /// Transfers of less ETH than this aren't notified.
+    pub min_value_eth: Option<f64>,
+}
+
+impl NotifyConfig {
+    /// The webhook notifier, when a URL is configured.
+    pub fn notifier(&self) -> Result<Option<Arc<dyn Notifier>>> {
+        let Some(url) = &self.webhook else {
+            return Ok(None);
+        };
+        let mut notifier = WebhookNotifier::new(url)?;
+        notifier.template = self.template.clone();
+        if let Some(min_value_eth) = self.min_value_eth {
+            notifier.min_value = parse_ether(min_value_eth)?;
+        }
+        Ok(Some(Arc::new(notifier)))
+    }
+}
+
+/// POSTs notifications as JSON to a webhook URL, retrying server errors briefly.
+#[derive(Clone, Debug)]
+pub struct WebhookNotifier {
+    pub url: String,
+    /// See [`NotifyConfig::template`].
+    pub template: Option<String>,
+    /// Transfers below this many Wei are skipped.
+    pub min_value: U256,
+    client: Client,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: &str) -> Result<Self> {
+        Ok(Self {
+            url: url.to_string(),
+            template: None,
+            min_value: U256::zero(),
+            client: Client::builder().timeout(NOTIFY_TIMEOUT).build()?,
+        })
+    }
+
+    /// The body posted for `notification`.
+    pub fn payload(&self, notification: &Notification) -> Result<String> {
+        let Some(template) = &self.template else {
+            return Ok(serde_json::to_string(notification)?);
+        };
+        let or_empty = |value: Option<String>| value.unwrap_or_default();
+        let text = template
+            .replace(
+                "{event}",
+                &format!("{:?}", notification.event).to_lowercase(),
+            )
+            .replace(
+                "{chain_id}",
+                &or_empty(notification.chain_id.map(|id| id.to_string())),
+            )
+            .replace("{subject}", &notification.subject())
+            .replace(
+                "{address}",
+                &or_empty(notification.address.map(|a| format!("{a:?}"))),
+            )
+            .replace(
+                "{tx_hash}",
+                &or_empty(notification.tx_hash.map(|h| format!("{h:?}"))),
+            )
+            .replace("{value_eth}", &or_empty(notification.value_eth.clone()))
+            .replace(
+                "{gas_cost_eth}",
+                &or_empty(notification.gas_cost_eth.clone()),
+            )
+            .replace(
+                "{status}",
+                if notification.success {
+                    "succeeded"
+                } else {
+                    "failed"
+                },
+            );
+        Ok(json!({ "text": text }).to_string())
+    }
+
+    async fn post(&self, body: String) -> Result<()> {
+        let mut delay = RETRY_DELAY;
+        for attempt in 1..=ATTEMPTS {
+            let sent = self
+                .client
+                .post(&self.url)
+                .header(CONTENT_TYPE, "application/json")
+                .body(body.clone())
+                .send()
+                .await;
+            let retryable = match sent {
+                Ok(response) if response.status().is_success() => return Ok(()),
+                Ok(response) if response.status().is_client_error() => {
+                    return Err(eyre!(
+                        "webhook rejected the notification: {}",
+                        response.status()
+                    ))
+                }
+                Ok(response) => eyre!("webhook answered {}", response.status()),
+                Err(err) => eyre!(err),
+            };
+            if attempt == ATTEMPTS {
+                return Err(retryable.wrap_err(format!("gave up after {ATTEMPTS} attempts")));
+            }
+            debug!(attempt, error = %retryable, "retrying notification");
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+        unreachable!("the last attempt returns")
+    }
+}
+
+impl Notifier for WebhookNotifier {
+    fn notify<'a>(&'a self, notification: &'a Notification) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async move {
+            let below_threshold = notification.event == NotificationEvent::Transfer
+                && notification.value.unwrap_or_default() < self.min_value;
+            if below_threshold {
+                return Ok(());
+            }
+            let body = self.payload(notification)?;
+            tokio::time::timeout(NOTIFY_TIMEOUT, self.post(body))
+                .await
+                .map_err(|_| eyre!("webhook timed out after {NOTIFY_TIMEOUT:?}"))?
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::deploy::{compile_and_deploy, DeployOptions};
+    use crate::receipt::ReceiptSummary;
+    use crate::test_support::spawn_mock_http;
+    use crate::transfer::{send_transfer, TransferOptions};
+    use ethers::prelude::{LocalWallet, Middleware, Provider};
+    use ethers::utils::Ganache;
+    use serde_json::Value;
+    use std::sync::Mutex;
+
+    // A webhook answering `status` to everything, and the bodies it received
+    async fn webhook(status: u16) -> Result<(String, Arc<Mutex<Vec<Value>>>)> {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let seen = received.clone();
+        let url = spawn_mock_http(move |body| {
+            let body = serde_json::from_slice(body).unwrap_or_default();
+            seen.lock().unwrap().push(body);
+            (status, "{}".to_string())
+        })
+        .await?;
+        Ok((url, received))
+    }
+
+    fn deployment() -> Notification {
+        Notification {
+            chain_id: Some(1337),
+            contract: Some("Migrations".to_string()),
+            address: Some(Address::repeat_byte(0x11)),
+            tx_hash: Some(TxHash::repeat_byte(0x22)),
+            ..Notification::new(NotificationEvent::Deployment, true)
+        }
+        .gas_cost(Some(21_000.into()), Some(1_000_000_000u64.into()))
+    }
+
+    #[tokio::test]
+    async fn test_webhook_payload() -> Result<()> {
+        let (url, received) = webhook(200).await?;
+        let notifier = WebhookNotifier::new(&url)?;
+        notifier.notify(&deployment()).await?;
+
+        let received = received.lock().unwrap().clone();
+        assert_eq!(received.len(), 1);
+        let payload = &received[0];
+        assert_eq!(payload["event"], "deployment");
+        assert_eq!(payload["chainId"], 1337);
+        assert_eq!(payload["contract"], "Migrations");
+        assert_eq!(
+            payload["address"],
+            format!("{:?}", Address::repeat_byte(0x11))
+        );
+        assert_eq!(
+            payload["txHash"],
+            format!("{:?}", TxHash::repeat_byte(0x22))
+        );
+        assert_eq!(payload["gasCostEth"], "0.000021000000000000");
+        assert_eq!(payload["success"], true);
+        assert!(payload.get("value").is_none());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_template_and_threshold() -> Result<()> {
+        let (url, received) = webhook(200).await?;
+        let config = NotifyConfig {
+            webhook: Some(url),
+            template: Some("{event} of {subject} on {chain_id} {status}".to_string()),
+            min_value_eth: Some(1.0),
+        };
+        let notifier = config.notifier()?.expect("a webhook is configured");
+
+        let transfer = |wei: U256| Notification {
+            chain_id: Some(1337),
+            recipient: Some(Address::repeat_byte(0xb0)),
+            ..Notification::new(NotificationEvent::Transfer, true).value(wei)
+        };
+        notifier.notify(&transfer(U256::exp10(17))).await?; // 0.1 ETH, below the threshold
+        notifier.notify(&transfer(U256::exp10(18) * 2)).await?;
+        notifier.notify(&deployment()).await?; // Deployments are always notified
+
+        let received = received.lock().unwrap().clone();
+        assert_eq!(
+            received,
+            vec![
+                json!({ "text": format!("transfer of {:?} on 1337 succeeded", Address::repeat_byte(0xb0)) }),
+                json!({ "text": "deployment of Migrations on 1337 succeeded" }),
+            ]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_errors_are_retried_then_only_logged() -> Result<()> {
+        let (url, received) = webhook(500).await?;
+        let notifier: Arc<dyn Notifier> = Arc::new(WebhookNotifier::new(&url)?);
+
+        let err = notifier.notify(&deployment()).await.unwrap_err();
+        assert!(format!("{err:#}").contains("500"), "{err:#}");
+        assert_eq!(received.lock().unwrap().len(), ATTEMPTS as usize);
+
+        // Through `deliver` the failure is only a warning
+        deliver(Some(&notifier), deployment()).await;
+        assert_eq!(received.lock().unwrap().len(), 2 * ATTEMPTS as usize);
+
+        // Client errors aren't retried
+        let (url, received) = webhook(404).await?;
+        assert!(WebhookNotifier::new(&url)?
+            .notify(&deployment())
+            .await
+            .is_err());
+        assert_eq!(received.lock().unwrap().len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_failing_webhook_does_not_fail_the_operations() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+
+        let (url, received) = webhook(500).await?;
+        let notifier: Arc<dyn Notifier> = Arc::new(WebhookNotifier::new(&url)?);
+        let options = DeployOptions {
+            notifier: Some(notifier.clone()),
+            ..Default::default()
+        };
+        let (_, result) =
+            compile_and_deploy(&ctx, wallet, "examples/", "Migrations", (), &options).await?;
+        assert!(!provider.get_code(result.address, None).await?.is_empty());
+
+        let (from, to) = (ganache.addresses()[0], ganache.addresses()[1]);
+        let options = TransferOptions {
+            notifier: Some(notifier),
+            ..Default::default()
+        };
+        let receipt = send_transfer(&ctx, from, to, U256::from(1000u64), &options).await?;
+        assert!(ReceiptSummary::from(&receipt).success);
+
+        let received = received.lock().unwrap().clone();
+        assert_eq!(received.len(), 2 * ATTEMPTS as usize);
+        let (deployed, transferred) = (&received[0], &received[ATTEMPTS as usize]);
+        assert_eq!(deployed["contract"], "Migrations");
+        assert_eq!(deployed["address"], format!("{:?}", result.address));
+        assert_eq!(transferred["event"], "transfer");
+        assert_eq!(transferred["recipient"], format!("{to:?}"));
+        assert_eq!(
+            transferred["txHash"],
+            format!("{:?}", receipt.transaction_hash)
+        );
+        assert_eq!(transferred["success"], true);
+        Ok(())
+    }
+}
@@ -0,0 +1,157 @@
+//! Cold-wallet workflow: sign a complete transaction without a node, then broadcast the raw
+//! bytes from a connected machine.
+
+use crate::errors::Error;
+use ethers::prelude::{LocalWallet, Middleware, Signer, TxHash};
+use ethers::types::{transaction::eip2718::TypedTransaction, Bytes};
+use eyre::{eyre, Result};
+use tracing::info;
+
+/// Signs `tx` for `chain_id` with `wallet` and returns the raw signed RLP.
+///
+/// Nothing is filled in from a node, so the nonce, gas limit and fees must be set, or this
+/// fails with [`Error::IncompleteTransaction`].
+pub fn sign_tx_offline(
+    wallet: &LocalWallet,
+    tx: &TypedTransaction,
+    chain_id: u64,
+) -> Result<Bytes> {
+    let mut missing = Vec::new();
+    if tx.nonce().is_none() {
+        missing.push("nonce");
+    }
+    if tx.gas().is_none() {
+        missing.push("gas limit");
+    }
+    match tx {
+        TypedTransaction::Eip1559(inner) => {
+            if inner.max_fee_per_gas.is_none() {
+                missing.push("max fee per gas");
+            }
+            if inner.max_priority_fee_per_gas.is_none() {
+                missing.push("max priority fee per gas");
+            }
+        }
+        _ if tx.gas_price().is_none() => missing.push("gas price"),
+        _ => {}
+    }
+    if !missing.is_empty() {
+        return Err(Error::IncompleteTransaction {
+            fields: missing.into_iter().map(String::from).collect(),
+        }
+        .into());
+    }
+    if let Some(from) = tx.from() {
+        if *from != wallet.address() {
+            return Err(eyre!(
+                "The transaction is from {from:?} but the wallet is {:?}",
+                wallet.address()
+            ));
+        }
+    }
+
+    let mut tx = tx.clone();
+    tx.set_chain_id(chain_id);
+    tx.set_from(wallet.address());
+    let signature = wallet
+        .clone()
+        .with_chain_id(chain_id)
+        .sign_transaction_sync(&tx)?;
+    Ok(tx.rlp_signed(&signature))
+}
+
+/// Broadcasts a transaction signed with [`sign_tx_offline`] and returns its hash without
+/// waiting for it to be mined.
+pub async fn broadcast_raw<M: Middleware>(provider: &M, raw: Bytes) -> Result<TxHash>
+where
+    M::Error: 'static,
+{
+    let pending = provider
+        .send_raw_transaction(raw)
+        .await
+        .map_err(|e| eyre!(e))?;
+    let tx_hash = pending.tx_hash();
+    info!(?tx_hash, "raw transaction broadcast");
+    Ok(tx_hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::prelude::{Provider, Transaction, TransactionRequest};
+    use ethers::types::{Eip1559TransactionRequest, U256};
+    use ethers::utils::{keccak256, parse_ether, parse_units, Ganache};
+
+    const MNEMONIC: &str =
+        "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+
+    fn cold_wallet() -> LocalWallet {
+        "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_signed_bytes_recover_the_sender() -> Result<()> {
+        let wallet = cold_wallet();
+        let tx: TypedTransaction = Eip1559TransactionRequest::new()
+            .to(ethers::types::Address::repeat_byte(0x11))
+            .value(1_000)
+            .nonce(7)
+            .gas(21_000)
+            .max_fee_per_gas(30_000_000_000u64)
+            .max_priority_fee_per_gas(1_000_000_000u64)
+            .into();
+
+        let raw = sign_tx_offline(&wallet, &tx, 5)?;
+        let decoded: Transaction = ethers::utils::rlp::decode(&raw)?;
+        assert_eq!(decoded.recover_from()?, wallet.address());
+        assert_eq!(decoded.chain_id, Some(U256::from(5)));
+        assert_eq!(decoded.nonce, U256::from(7));
+        assert_eq!(decoded.hash, keccak256(&raw).into());
+        Ok(())
+    }
+
+    #[test]
+    fn test_incomplete_transaction_is_refused() {
+        let tx: TypedTransaction = TransactionRequest::pay(ethers::types::Address::zero(), 1)
+            .gas(21_000)
+            .into();
+        let err = sign_tx_offline(&cold_wallet(), &tx, 1).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::IncompleteTransaction { fields }) if fields == &["nonce", "gas price"]
+        ));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_offline_transfer_is_broadcast_and_mined() -> Result<()> {
+        let ganache = Ganache::new().mnemonic(MNEMONIC).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let recipient = ganache.addresses()[1];
+        let before = provider.get_balance(recipient, None).await?;
+        let chain_id = 1337; // Ganache's default, known without asking the node
+
+        // Everything a node would fill in is written down, as on a cold machine
+        let value = parse_ether("1.5")?;
+        let tx: TypedTransaction = TransactionRequest::pay(recipient, value)
+            .nonce(0)
+            .gas(21_000)
+            .gas_price(parse_units(20, "gwei")?)
+            .into();
+        let raw = sign_tx_offline(&wallet, &tx, chain_id)?;
+
+        let tx_hash = broadcast_raw(&provider, raw).await?;
+        let receipt = provider
+            .get_transaction_receipt(tx_hash)
+            .await?
+            .expect("Ganache mines instantly");
+        assert_eq!(receipt.status, Some(1.into()));
+        assert_eq!(receipt.from, wallet.address());
+        assert_eq!(provider.get_balance(recipient, None).await?, before + value);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,393 @@
+//! Final result output of the binaries, either human-readable or as a single JSON object.
+
+use crate::abi_diff::AbiDiff;
+use crate::amount::format_eth;
+use crate::deploy::DeploymentResult;
+use crate::dry_run::{DryRunReport, DryRunSimulation};
+use crate::explorer::BlockReport;
+use crate::gas_report::GasReport;
+use crate::mempool::PoolSnapshot;
+use crate::project::ContractList;
+use crate::safe::SafeTxExport;
+#[cfg(feature = "sqlite")]
+use crate::sqlite::RunHistory;
+use crate::state::ChainState;
+use crate::traffic::TrafficSummary;
+use crate::transfer::TransferResult;
+use eyre::Result;
+use serde::Serialize;
+
+/// A command result that can be printed for a person or for a script.
+pub trait Render: Serialize {
+    /// Human-readable form of the result.
+    fn human(&self) -> String;
+}
+
+/// Renders `result` as a single-line JSON object when `json` is set, or in its human-readable form.
+pub fn render<R: Render>(result: &R, json: bool) -> Result<String> {
+    if json {
+        Ok(serde_json::to_string(result)?)
+    } else {
+        Ok(result.human())
+    }
+}
+
+impl Render for DeploymentResult {
+    fn human(&self) -> String {
+        let mut out = format!(
+            "Deployed at {:?} in block {} (tx {:?}, gas used {})",
+            self.address, self.block_number, self.tx_hash, self.gas_used
+        );
+        if let (Some(tx_hash), Some(gas_used)) = (self.init_tx_hash, self.init_gas_used) {
+            out.push_str(&format!(
+                "\nInitialized in tx {:?} (gas used {})",
+                tx_hash, gas_used
+            ));
+        }
+        out
+    }
+}
+
+impl Render for TransferResult {
+    fn human(&self) -> String {
+        format!(
+            "Transferred {} from {:?} to {:?} in block {} (tx {:?}, gas used {})",
+            format_eth(self.value),
+            self.from,
+            self.to,
+            self.block_number,
+            self.tx_hash,
+            self.gas_used
+        )
+    }
+}
+
+impl Render for ContractList {
+    fn human(&self) -> String {
+        self.contracts.join("\n")
+    }
+}
+
+impl Render for GasReport {
+    fn human(&self) -> String {
+        let mut out = format!(
+            "{:<24} {:<24} {:>6} {:>10} {:>10} {:>10}",
+            "Contract", "Function", "Calls", "Min", "Avg", "Max"
+        );
+        for row in &self.functions {
+            out.push_str(&format!(
+                "\n{:<24} {:<24} {:>6} {:>10} {:>10} {:>10}",
+                row.contract, row.function, row.calls, row.min, row.avg, row.max
+            ));
+            if row.failures > 0 {
+                out.push_str(&format!(" ({} failed)", row.failures));
+            }
+        }
+        if !self.deployments.is_empty() {
+            out.push_str("\n\nDeployments");
+            for deployment in &self.deployments {
+                out.push_str(&format!(
+                    "\n{:<24} {:>10}",
+                    deployment.contract, deployment.gas_used
+                ));
+            }
+        }
+        out
+    }
+}
+
+impl Render for DryRunReport {
+    fn human(&self) -> String {
+        let to = self
+            .to
+            .map_or("contract creation".to_string(), |to| format!("{to:?}"));
+        let mut out = format!(
+            "Dry run: would send {} from {:?} to {} (nonce {}, {} bytes of calldata)",
+            format_eth(self.value),
+            self.from,
+            to,
+            self.nonce,
+            self.calldata_size
+        );
+        out.push_str(&format!(
+            "\nFee mode {:?}, {} wei per gas",
+            self.fee_mode, self.fee_per_gas
+        ));
+        if let Some(priority_fee) = self.priority_fee_per_gas {
+            out.push_str(&format!(" (priority fee {priority_fee} wei)"));
+        }
+        match &self.simulation {
+            DryRunSimulation::Succeeded { .. } => out.push_str("\nSimulation succeeded"),
+            DryRunSimulation::Reverted { reason } => out.push_str(&format!(
+                "\nSimulation reverted: {}",
+                reason.as_deref().unwrap_or("no reason given")
+            )),
+        }
+        if let (Some(gas), Some(cost)) = (self.gas_estimate, self.estimated_cost) {
+            out.push_str(&format!(
+                "\nEstimated gas {gas}, costing at most {}",
+                format_eth(cost)
+            ));
+        }
+        for note in &self.notes {
+            out.push_str(&format!("\n- {note}"));
+        }
+        out
+    }
+}
+
+impl Render for AbiDiff {
+    fn human(&self) -> String {
+        if self.is_empty() {
+            return "No ABI changes".to_string();
+        }
+        let mut lines = Vec::new();
+        lines.extend(self.added.iter().map(|signature| format!("+ {signature}")));
+        lines.extend(
+            self.removed
+                .iter()
+                .map(|signature| format!("- {signature}")),
+        );
+        for change in &self.changed {
+            lines.push(format!(
+                "~ {} -> {}",
+                change.old.join(" | "),
+                change.new.join(" | ")
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+impl Render for PoolSnapshot {
+    fn human(&self) -> String {
+        if self.senders.is_empty() {
+            return "No pending transactions".to_string();
+        }
+        let mut lines = Vec::new();
+        for (sender, pending) in &self.senders {
+            lines.push(format!("{sender:?} ({} pending)", pending.len()));
+            let gaps = self.nonce_gaps(*sender);
+            if !gaps.is_empty() {
+                let gaps: Vec<String> = gaps.iter().map(|nonce| nonce.to_string()).collect();
+                lines.push(format!("  nonce gap: {}", gaps.join(", ")));
+            }
+            for tx in pending {
+                let to = tx.to.map_or("<create>".to_string(), |to| format!("{to:?}"));
+                let gas_price = tx
+                    .gas_price
+                    .map_or("-".to_string(), |price| price.to_string());
+                lines.push(format!(
+                    "  nonce {} {:?} -> {} value {} gas price {}",
+                    tx.nonce,
+                    tx.hash,
+                    to,
+                    format_eth(tx.value),
+                    gas_price
+                ));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+impl Render for SafeTxExport {
+    fn human(&self) -> String {
+        let mut out = format!(
+            "Safe transaction for {} on chain {} (nonce {}): call {} with {} wei\nsafeTxHash {}",
+            self.safe,
+            self.chain_id,
+            self.nonce,
+            self.to,
+            self.value,
+            self.contract_transaction_hash
+        );
+        if let Some(address) = &self.deployed_address {
+            out.push_str(&format!("\nDeploys the contract at {address}"));
+        }
+        out
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl Render for RunHistory {
+    fn human(&self) -> String {
+        let mut lines = vec![format!("{} deployments", self.deployments.len())];
+        lines.extend(self.deployments.iter().map(|row| {
+            format!(
+                "  {} at {:?} in block {} (tx {:?}, gas used {})",
+                row.name, row.address, row.block, row.tx_hash, row.gas_used
+            )
+        }));
+        lines.push(format!("{} transactions", self.transactions.len()));
+        lines.extend(self.transactions.iter().map(|row| {
+            let to = row
+                .to
+                .map_or("contract creation".to_string(), |to| format!("{to:?}"));
+            let status = if row.status { "ok" } else { "reverted" };
+            format!(
+                "  {:?}: {} from {:?} to {to}, fee {} ({status})",
+                row.hash,
+                format_eth(row.value),
+                row.from,
+                format_eth(row.fee)
+            )
+        }));
+        lines.join("\n")
+    }
+}
+
+impl Render for BlockReport {
+    fn human(&self) -> String {
+        let base_fee = self
+            .base_fee
+            .map_or("none".to_string(), |fee| format!("{fee} wei"));
+        let mut lines = vec![
+            format!("Block {} ({:?})", self.number, self.hash),
+            format!("  timestamp {}, miner {:?}", self.timestamp, self.miner),
+            format!(
+                "  gas used {} of {} ({:.1}%), base fee {base_fee}",
+                self.gas_used,
+                self.gas_limit,
+                self.gas_used_percent()
+            ),
+            format!("{} transactions", self.transactions.len()),
+        ];
+        for tx in &self.transactions {
+            let status = if tx.success { "ok" } else { "reverted" };
+            let target = match (tx.to, tx.created) {
+                (Some(to), _) => format!("{to:?}"),
+                (None, Some(created)) => format!("new contract {created:?}"),
+                (None, None) => "contract creation".to_string(),
+            };
+            let name = tx
+                .contract
+                .as_ref()
+                .map_or(String::new(), |name| format!(" ({name})"));
+            lines.push(format!(
+                "  {:?}: {:?} -> {target}{name}, {}, gas used {} ({status})",
+                tx.hash,
+                tx.from,
+                format_eth(tx.value),
+                tx.gas_used
+            ));
+            if let Some(function) = &tx.function {
+                let args: Vec<_> = tx
+                    .args
+                    .iter()
+                    .map(|(name, value)| format!("{name} = {value}"))
+                    .collect();
+                lines.push(format!("    {function}({})", args.join(", ")));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+impl Render for TrafficSummary {
+    fn human(&self) -> String {
+        format!(
+            "{} submitted, {} mined, {} failed in {:.1} s ({:.1} mined/s, seed {})\n\
+             Latency p50 {} ms, p90 {} ms, p99 {} ms, max {} ms",
+            self.submitted,
+            self.mined,
+            self.failed,
+            self.elapsed_ms as f64 / 1000.0,
+            self.mined_per_sec,
+            self.seed,
+            self.latency_p50_ms,
+            self.latency_p90_ms,
+            self.latency_p99_ms,
+            self.latency_max_ms
+        )
+    }
+}
+
+impl Render for ChainState {
+    fn human(&self) -> String {
+        self.accounts
+            .iter()
+            .map(|account| {
+                let kind = if account.has_code {
+                    "contract"
+                } else {
+                    "account"
+                };
+                format!(
+                    "{:?} {kind} balance {} nonce {}",
+                    account.address,
+                    format_eth(account.balance),
+                    account.nonce
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mined_receipt, mock_provider, receipt_reply};
+    use ethers::prelude::Middleware;
+    use ethers::types::{Address, TxHash, U256};
+    use ethers::utils::parse_ether;
+    use eyre::ContextCompat;
+
+    #[test]
+    fn test_render_deployment_as_json_or_text() -> Result<()> {
+        let result = DeploymentResult {
+            address: Address::repeat_byte(0x11),
+            tx_hash: TxHash::repeat_byte(0x22),
+            block_number: 7,
+            gas_used: U256::from(21_000u64),
+            init_tx_hash: None,
+            init_gas_used: None,
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&render(&result, true)?)?;
+        assert_eq!(
+            json["address"],
+            "0x1111111111111111111111111111111111111111"
+        );
+        assert_eq!(
+            json["txHash"],
+            "0x2222222222222222222222222222222222222222222222222222222222222222"
+        );
+        assert_eq!(json["gasUsed"], "0x5208");
+        assert_eq!(json["blockNumber"], 7);
+
+        let text = render(&result, false)?;
+        assert!(text.contains("0x1111111111111111111111111111111111111111"));
+        assert!(text.contains("block 7"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_render_transfer_from_a_mocked_receipt() -> Result<()> {
+        let hash = TxHash::repeat_byte(0x33);
+        let mut receipt = mined_receipt(hash);
+        receipt.from = Address::repeat_byte(0xaa);
+        receipt.to = Some(Address::repeat_byte(0xbb));
+        let (provider, _) = mock_provider([receipt_reply(&receipt)]);
+
+        let mut receipt = provider
+            .get_transaction_receipt(hash)
+            .await?
+            .context("The mock has the receipt")?;
+        let result = TransferResult::from_receipt(&receipt, parse_ether("1.5")?)?;
+        assert_eq!(
+            render(&result, false)?,
+            format!(
+                "Transferred 1.500000000000000000 ETH from {:?} to {:?} in block 7 (tx {hash:?}, gas used 21000)",
+                receipt.from,
+                Address::repeat_byte(0xbb)
+            )
+        );
+
+        // A contract creation has no recipient to report
+        receipt.to = None;
+        assert!(TransferResult::from_receipt(&receipt, U256::one()).is_err());
+        Ok(())
+    }
+}
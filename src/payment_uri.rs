@@ -0,0 +1,394 @@
+//! EIP-681 payment request URIs, e.g. `ethereum:0x…@1?value=2.014e18`.
+
+use crate::{ens::Recipient, errors::Error};
+use ethers::prelude::{Address, JsonRpcClient, Middleware, Provider, U256};
+use eyre::Result;
+
+/// A decoded EIP-681 URI: a plain payment, or a call such as an ERC-20 `transfer`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaymentRequest {
+    /// The payee for plain payments, the contract for function calls.
+    pub to: Recipient,
+    pub chain_id: Option<u64>,
+    /// Wei to send, from the `value` parameter.
+    pub value: Option<U256>,
+    pub function: Option<String>,
+    /// Every other parameter, in URI order (`gas`, `gasPrice`, or typed call arguments).
+    pub params: Vec<(String, String)>,
+}
+
+impl PaymentRequest {
+    /// Fails with [`Error::ChainIdMismatch`] when the URI names another chain than the provider's.
+    pub async fn ensure_chain<T: JsonRpcClient>(&self, provider: &Provider<T>) -> Result<()> {
+        let Some(expected) = self.chain_id else {
+            return Ok(());
+        };
+        let actual = provider.get_chainid().await?.as_u64();
+        if actual != expected {
+            return Err(Error::ChainIdMismatch {
+                endpoint: None,
+                expected,
+                actual,
+            }
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// Parses an EIP-681 URI: `ethereum:[pay-]<address|name>[@chain_id][/function][?key=value&…]`.
+///
+/// As in the EIP's own examples, hex addresses aren't required to carry an EIP-55 checksum.
+/// Numbers accept the EIP's exponent notation (`2.014e18`) but must come out as whole Wei.
+pub fn parse_payment_uri(uri: &str) -> Result<PaymentRequest> {
+    let invalid = |reason: String| Error::InvalidPaymentUri {
+        uri: uri.to_string(),
+        reason,
+    };
+    let (scheme, rest) = uri
+        .split_once(':')
+        .ok_or_else(|| invalid("missing the `ethereum:` scheme".to_string()))?;
+    if !scheme.eq_ignore_ascii_case("ethereum") {
+        return Err(Error::UnsupportedUriScheme {
+            scheme: scheme.to_string(),
+        }
+        .into());
+    }
+    let rest = rest.strip_prefix("pay-").unwrap_or(rest);
+    let (path, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let (target, function) = match path.split_once('/') {
+        Some((target, function)) => (target, Some(function)),
+        None => (path, None),
+    };
+    let (target, chain_id) = match target.split_once('@') {
+        Some((target, chain_id)) => {
+            let chain_id = chain_id
+                .parse()
+                .map_err(|_| invalid(format!("chain id {chain_id:?} is not a number")))?;
+            (target, Some(chain_id))
+        }
+        None => (target, None),
+    };
+    let function = match function {
+        Some(name) if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') => {
+            return Err(invalid(format!("{name:?} is not a function name")).into())
+        }
+        function => function.map(str::to_string),
+    };
+
+    let mut value = None;
+    let mut params = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, raw) = pair
+            .split_once('=')
+            .ok_or_else(|| invalid(format!("parameter {pair:?} has no value")))?;
+        match key {
+            "value" => {
+                let amount = parse_amount(raw)?;
+                if value.replace(amount).is_some() {
+                    return Err(invalid("`value` given twice".to_string()).into());
+                }
+                continue;
+            }
+            "gas" | "gasLimit" | "gasPrice" => {
+                parse_amount(raw)?;
+            }
+            "address" => {
+                parse_address(raw)?;
+            }
+            key if key.starts_with("uint") || key.starts_with("int") => {
+                parse_amount(raw)?;
+            }
+            _ => {}
+        }
+        params.push((key.to_string(), raw.to_string()));
+    }
+
+    Ok(PaymentRequest {
+        to: parse_target(target)?,
+        chain_id,
+        value,
+        function,
+        params,
+    })
+}
+
+/// Parses a decimal amount, with optional fraction and exponent, into a whole number of Wei.
+pub fn parse_amount(input: &str) -> Result<U256, Error> {
+    let invalid = |reason: &str| Error::InvalidAmount {
+        input: input.to_string(),
+        reason: reason.to_string(),
+    };
+    let unsigned = input.strip_prefix('+').unwrap_or(input);
+    if unsigned.starts_with('-') {
+        return Err(invalid("amounts can't be negative"));
+    }
+    let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((_, "")) => return Err(invalid("expected digits after the exponent")),
+        Some((mantissa, exponent)) if exponent.chars().all(|c| c.is_ascii_digit()) => (
+            mantissa,
+            exponent
+                .parse::<usize>()
+                .map_err(|_| invalid("exponent too large"))?,
+        ),
+        Some(_) => return Err(invalid("the exponent must be digits")),
+        None => (unsigned, 0),
+    };
+    let (integer, fraction) = match mantissa.split_once('.') {
+        Some((_, "")) => return Err(invalid("expected digits after the decimal point")),
+        Some((integer, fraction)) => (integer, fraction),
+        None => (mantissa, ""),
+    };
+    let digits = format!("{integer}{fraction}");
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(invalid("expected a decimal number"));
+    }
+
+    let (digits, zeros) = if exponent >= fraction.len() {
+        (digits.as_str(), exponent - fraction.len())
+    } else {
+        let dropped = fraction.len() - exponent;
+        let (kept, rest) = digits.split_at(digits.len() - dropped);
+        if rest.chars().any(|c| c != '0') {
+            return Err(invalid("not a whole number of wei"));
+        }
+        (kept, 0)
+    };
+    let base = U256::from_dec_str(if digits.is_empty() { "0" } else { digits })
+        .map_err(|_| invalid("too large"))?;
+    if base.is_zero() {
+        return Ok(base);
+    }
+    if zeros > 77 {
+        return Err(invalid("too large"));
+    }
+    base.checked_mul(U256::exp10(zeros))
+        .ok_or_else(|| invalid("too large"))
+}
+
+fn parse_target(target: &str) -> Result<Recipient> {
+    if target.to_lowercase().ends_with(".eth") {
+        return target.parse();
+    }
+    parse_address(target).map(Recipient::Address)
+}
+
+fn parse_address(input: &str) -> Result<Address> {
+    let hex = input.strip_prefix("0x").unwrap_or_default();
+    if hex.len() != 40 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::InvalidAddress {
+            input: input.to_string(),
+            reason: "expected 0x followed by 40 hex characters, or an ENS name".to_string(),
+        }
+        .into());
+    }
+    Ok(input.parse()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::spawn_mock_node;
+    use ethers::utils::format_units;
+    use proptest::prelude::*;
+    use serde_json::json;
+
+    fn error_of(uri: &str) -> Error {
+        let report = parse_payment_uri(uri).unwrap_err();
+        report.downcast::<Error>().expect("a crate error")
+    }
+
+    #[test]
+    fn test_eip_681_examples() -> Result<()> {
+        let payment = parse_payment_uri(
+            "ethereum:0xfb6916095ca1df60bb79Ce92ce3ea74c37c5d359?value=2.014e18",
+        )?;
+        assert_eq!(
+            payment.to,
+            Recipient::Address("0xfb6916095ca1df60bb79ce92ce3ea74c37c5d359".parse()?)
+        );
+        assert_eq!(
+            payment.value,
+            Some(U256::from(2_014_000_000_000_000_000u64))
+        );
+        assert_eq!(payment.chain_id, None);
+        assert_eq!(payment.function, None);
+
+        let transfer = parse_payment_uri(
+            "ethereum:0x89205a3a3b2a69de6dbf7f01ed13b2108b2c43e7/transfer?address=0x8e23ee67d1332ad560396262c48ffbb01f93d052&uint256=1",
+        )?;
+        assert_eq!(transfer.function.as_deref(), Some("transfer"));
+        assert_eq!(transfer.value, None);
+        assert_eq!(
+            transfer.params,
+            vec![
+                (
+                    "address".to_string(),
+                    "0x8e23ee67d1332ad560396262c48ffbb01f93d052".to_string()
+                ),
+                ("uint256".to_string(), "1".to_string()),
+            ]
+        );
+
+        let on_chain = parse_payment_uri(
+            "ethereum:pay-0xb794F5eA0ba39494cE839613fffBA74279579268@1337?value=1e3&gasPrice=2.5e9",
+        )?;
+        assert_eq!(on_chain.chain_id, Some(1337));
+        assert_eq!(on_chain.value, Some(U256::from(1000)));
+        assert_eq!(
+            on_chain.params,
+            vec![("gasPrice".to_string(), "2.5e9".to_string())]
+        );
+
+        let named = parse_payment_uri("ethereum:vitalik.eth?value=1")?;
+        assert_eq!(named.to, Recipient::Name("vitalik.eth".to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_amount_notation() -> Result<()> {
+        assert_eq!(parse_amount("42")?, U256::from(42));
+        assert_eq!(parse_amount("+1.5e1")?, U256::from(15));
+        assert_eq!(parse_amount("2.50E1")?, U256::from(25));
+        assert_eq!(parse_amount("0.0e5")?, U256::zero());
+        assert_eq!(parse_amount("1e18")?, U256::exp10(18));
+        assert!(parse_amount("1.5").is_err());
+        assert!(parse_amount("-1").is_err());
+        assert!(matches!(
+            parse_amount("1e"),
+            Err(Error::InvalidAmount { reason, .. }) if reason == "expected digits after the exponent"
+        ));
+        assert!(parse_amount("1.").is_err());
+        assert!(parse_amount("0x10").is_err());
+        assert!(parse_amount("1e78").is_err());
+        Ok(())
+    }
+
+    // Units as the power of ten `parse_amount`'s exponent scales by: wei, gwei and ether
+    const UNIT_DECIMALS: [usize; 3] = [0, 9, 18];
+
+    fn any_wei() -> impl Strategy<Value = U256> {
+        prop_oneof![
+            any::<u64>().prop_map(U256::from),
+            any::<u128>().prop_map(U256::from),
+            any::<[u8; 32]>().prop_map(|bytes| U256::from_big_endian(&bytes)),
+        ]
+    }
+
+    // A decimal amount in a unit: integer digits, at most `decimals` fraction digits, and the
+    // exponent that scales it to wei
+    fn decimal_in_unit() -> impl Strategy<Value = (String, usize)> {
+        (
+            "[0-9]{1,30}",
+            "[0-9]{0,18}",
+            prop::sample::select(UNIT_DECIMALS.to_vec()),
+        )
+            .prop_map(|(integer, mut fraction, decimals)| {
+                fraction.truncate(decimals);
+                let amount = if fraction.is_empty() {
+                    format!("{integer}e{decimals}")
+                } else {
+                    format!("{integer}.{fraction}e{decimals}")
+                };
+                (amount, decimals)
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn test_formatted_amounts_parse_back(wei in any_wei(), index in 0..3usize) {
+            let decimals = UNIT_DECIMALS[index];
+            let formatted = format_units(wei, decimals as u32).unwrap();
+            prop_assert_eq!(parse_amount(&format!("{formatted}e{decimals}")).unwrap(), wei);
+        }
+
+        #[test]
+        fn test_decimal_amounts_are_stable((amount, decimals) in decimal_in_unit()) {
+            let wei = parse_amount(&amount).unwrap();
+            let formatted = format_units(wei, decimals as u32).unwrap();
+            prop_assert_eq!(parse_amount(&format!("{formatted}e{decimals}")).unwrap(), wei);
+        }
+
+        #[test]
+        fn test_sub_wei_amounts_are_rejected(
+            integer in "[0-9]{1,10}",
+            fraction in "[0-9]{18,30}[1-9]",
+        ) {
+            // More decimals than ether has, ending in a non-zero digit
+            let err = parse_amount(&format!("{integer}.{fraction}e18")).unwrap_err();
+            prop_assert!(err.to_string().contains("not a whole number of wei"), "{}", err);
+        }
+
+        #[test]
+        fn test_amounts_past_u256_max_overflow(excess in 1u64..1_000_000) {
+            prop_assert_eq!(parse_amount(&U256::MAX.to_string()).unwrap(), U256::MAX);
+            // Scaling anything above MAX / 10 by ten overflows, through digits or the exponent
+            let above = U256::MAX / 10 + excess;
+            let err = parse_amount(&format!("{above}0")).unwrap_err();
+            prop_assert!(err.to_string().contains("too large"), "{}", err);
+            let err = parse_amount(&format!("{above}e1")).unwrap_err();
+            prop_assert!(err.to_string().contains("too large"), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_malformed_uris() {
+        assert!(matches!(
+            error_of("bitcoin:0xb794F5eA0ba39494cE839613fffBA74279579268"),
+            Error::UnsupportedUriScheme { scheme } if scheme == "bitcoin"
+        ));
+        assert!(matches!(
+            error_of("0xb794F5eA0ba39494cE839613fffBA74279579268"),
+            Error::InvalidPaymentUri { .. }
+        ));
+        assert!(matches!(
+            error_of("ethereum:0xb794F5eA0ba39494cE839613fffBA7427957926?value=1"),
+            Error::InvalidAddress { .. }
+        ));
+        assert!(matches!(
+            error_of("ethereum:0x89205a3a3b2a69de6dbf7f01ed13b2108b2c43e7/transfer?address=0x12&uint256=1"),
+            Error::InvalidAddress { input, .. } if input == "0x12"
+        ));
+        assert!(matches!(
+            error_of("ethereum:0xb794F5eA0ba39494cE839613fffBA74279579268@main"),
+            Error::InvalidPaymentUri { .. }
+        ));
+        assert!(matches!(
+            error_of("ethereum:0xb794F5eA0ba39494cE839613fffBA74279579268?value=0.5"),
+            Error::InvalidAmount { .. }
+        ));
+        assert!(matches!(
+            error_of("ethereum:0xb794F5eA0ba39494cE839613fffBA74279579268?value"),
+            Error::InvalidPaymentUri { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_chain_id_cross_check() -> Result<()> {
+        let url = spawn_mock_node(|method, _| match method {
+            "eth_chainId" => json!("0x539"),
+            _ => json!(null),
+        })
+        .await?;
+        let provider = Provider::try_from(url)?;
+
+        let uri = "ethereum:0xb794F5eA0ba39494cE839613fffBA74279579268";
+        parse_payment_uri(&format!("{uri}@1337"))?
+            .ensure_chain(&provider)
+            .await?;
+        parse_payment_uri(uri)?.ensure_chain(&provider).await?;
+        let err = parse_payment_uri(&format!("{uri}@1"))?
+            .ensure_chain(&provider)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::ChainIdMismatch {
+                expected: 1,
+                actual: 1337,
+                ..
+            })
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+//! Multi-contract deployments where later constructors take the addresses of earlier contracts.
+
+use crate::errors::Error;
+use crate::project::artifact_parts;
+use ethers::{
+    abi::{Token, Tokenizable},
+    contract::ContractFactory,
+    prelude::{Address, Middleware, TransactionReceipt},
+};
+use ethers_solc::ProjectCompileOutput;
+use eyre::{Context, ContextCompat, Result};
+use std::{collections::HashMap, sync::Arc};
+use tracing::info;
+
+/// A constructor argument of a plan step.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Arg {
+    /// A literal value.
+    Value(Token),
+    /// The address of the contract deployed by an earlier step.
+    Ref(String),
+    /// A dynamic array whose elements may hold references.
+    Array(Vec<Arg>),
+    /// A struct (tuple) whose fields may hold references.
+    Tuple(Vec<Arg>),
+}
+
+impl Arg {
+    /// A reference to the step named `step`, replaced by its address when the plan runs.
+    pub fn reference(step: &str) -> Self {
+        Self::Ref(step.to_string())
+    }
+
+    fn references<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            Self::Value(_) => {}
+            Self::Ref(step) => out.push(step),
+            Self::Array(args) | Self::Tuple(args) => {
+                args.iter().for_each(|arg| arg.references(out))
+            }
+        }
+    }
+
+    fn resolve(&self, addresses: &HashMap<String, Address>) -> Token {
+        match self {
+            Self::Value(token) => token.clone(),
+            // Validated when the step was added
+            Self::Ref(step) => Token::Address(addresses[step]),
+            Self::Array(args) => Token::Array(args.iter().map(|a| a.resolve(addresses)).collect()),
+            Self::Tuple(args) => Token::Tuple(args.iter().map(|a| a.resolve(addresses)).collect()),
+        }
+    }
+}
+
+impl<T: Tokenizable> From<T> for Arg {
+    fn from(value: T) -> Self {
+        Self::Value(value.into_token())
+    }
+}
+
+/// A reference to the step named `step`; see [`Arg::reference`].
+pub fn reference(step: &str) -> Arg {
+    Arg::reference(step)
+}
+
+/// Builds the constructor arguments of a plan step, converting each value into an [`Arg`].
+///
+/// ```ignore
+/// plan.add("Vault", args![reference("TokenA"), U256::from(10)])?;
+/// ```
+#[macro_export]
+macro_rules! args {
+    ($($arg:expr),* $(,)?) => {
+        vec![$($crate::plan::Arg::from($arg)),*]
+    };
+}
+
+/// Contracts to deploy in order, each step named after the contract it deploys.
+#[derive(Clone, Debug, Default)]
+pub struct DeployPlan {
+    steps: Vec<(String, Vec<Arg>)>,
+}
+
+/// What a plan deployed.
+#[derive(Clone, Debug)]
+pub struct PlanOutcome {
+    /// Deployed address of every step.
+    pub addresses: HashMap<String, Address>,
+    /// Creation receipt of every step, in execution order.
+    pub receipts: Vec<(String, TransactionReceipt)>,
+}
+
+impl DeployPlan {
+    /// An empty plan.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Appends the deployment of contract `name` with `args`.
+    ///
+    /// References may only point at steps added before this one, which also rules out cycles;
+    /// anything else is rejected with [`Error::UnresolvedPlanReference`].
+    pub fn add(&mut self, name: &str, args: Vec<Arg>) -> Result<&mut Self> {
+        if self.steps.iter().any(|(step, _)| step == name) {
+            return Err(Error::DuplicatePlanStep {
+                step: name.to_string(),
+            }
+            .into());
+        }
+        let mut references = Vec::new();
+        args.iter().for_each(|arg| arg.references(&mut references));
+        if let Some(reference) = references
+            .into_iter()
+            .find(|reference| !self.steps.iter().any(|(step, _)| step == reference))
+        {
+            return Err(Error::UnresolvedPlanReference {
+                step: name.to_string(),
+                reference: reference.to_string(),
+            }
+            .into());
+        }
+
+        self.steps.push((name.to_string(), args));
+        Ok(self)
+    }
+
+    /// Deploys every step from `project`'s artifacts, in order, substituting references with
+    /// the addresses deployed so far.
+    pub async fn execute<M: Middleware + 'static>(
+        &self,
+        project: &ProjectCompileOutput,
+        client: Arc<M>,
+    ) -> Result<PlanOutcome> {
+        let mut addresses = HashMap::new();
+        let mut receipts = Vec::new();
+        for (name, args) in &self.steps {
+            let artifact = project
+                .find_first(name)
+                .with_context(|| format!("{name} not found in the compiled project"))?;
+            let (abi, bytecode) = artifact_parts(name, artifact)?;
+
+            let tokens = args.iter().map(|arg| arg.resolve(&addresses)).collect();
+            let (contract, receipt) = ContractFactory::new(abi, bytecode, client.clone())
+                .deploy_tokens(tokens)?
+                .send_with_receipt()
+                .await
+                .wrap_err_with(|| format!("Failed to deploy plan step {name}"))?;
+            info!(step = %name, address = ?contract.address(), "plan step deployed");
+
+            addresses.insert(name.clone(), contract.address());
+            receipts.push((name.clone(), receipt));
+        }
+        Ok(PlanOutcome {
+            addresses,
+            receipts,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::compile;
+    use ethers::abi::parse_abi;
+    use ethers::contract::Contract;
+    use ethers::prelude::{LocalWallet, Provider, Signer, SignerMiddleware, U256};
+    use ethers::utils::Ganache;
+
+    #[test]
+    fn test_references_must_point_backwards() -> Result<()> {
+        let mut plan = DeployPlan::new();
+        plan.add("TokenA", args!["TKA".to_string()])?;
+
+        // Unknown step, nested inside an array
+        let err = plan
+            .add(
+                "Vault",
+                args![reference("TokenA"), Arg::Array(vec![reference("TokenB")])],
+            )
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::UnresolvedPlanReference { step, reference })
+                if step == "Vault" && reference == "TokenB"
+        ));
+
+        // A step can't depend on itself
+        let err = plan.add("Vault", args![reference("Vault")]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::UnresolvedPlanReference { .. })
+        ));
+
+        let err = plan.add("TokenA", args!["TKB".to_string()]).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::DuplicatePlanStep { .. })
+        ));
+
+        // Only the valid step was kept
+        assert_eq!(plan.steps.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_references_resolve_inside_arrays_and_tuples() {
+        let token = Address::from_low_u64_be(0xa);
+        let addresses = HashMap::from([("TokenA".to_string(), token)]);
+        let arg = Arg::Tuple(vec![
+            Arg::from(U256::from(7)),
+            Arg::Array(vec![reference("TokenA"), Arg::from(Address::zero())]),
+        ]);
+        assert_eq!(
+            arg.resolve(&addresses),
+            Token::Tuple(vec![
+                Token::Uint(U256::from(7)),
+                Token::Array(vec![Token::Address(token), Token::Address(Address::zero())]),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_vault_receives_token_address() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let client = Arc::new(SignerMiddleware::new(
+            provider,
+            wallet.with_chain_id(chain_id),
+        ));
+
+        let project = compile("tests/fixtures/plan/").await?.output;
+        let mut plan = DeployPlan::new();
+        plan.add("TokenA", args!["TKA".to_string()])?.add(
+            "Vault",
+            args![reference("TokenA"), Arg::Array(vec![reference("TokenA")])],
+        )?;
+        let outcome = plan.execute(&project, client.clone()).await?;
+
+        assert_eq!(outcome.receipts.len(), 2);
+        assert_eq!(outcome.receipts[0].0, "TokenA");
+        let token = outcome.addresses["TokenA"];
+        let vault_abi = parse_abi(&[
+            "function token() view returns (address)",
+            "function guardians(uint256) view returns (address)",
+        ])?;
+        let vault = Contract::new(outcome.addresses["Vault"], vault_abi, client);
+        let stored: Address = vault.method("token", ())?.call().await?;
+        assert_eq!(stored, token);
+        let guardian: Address = vault.method("guardians", U256::zero())?.call().await?;
+        assert_eq!(guardian, token);
+
+        Ok(())
+    }
+}
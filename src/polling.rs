@@ -0,0 +1,194 @@
+//! Receipt waiting that follows the chain instead of polling at a fixed interval.
+
+use crate::chain_context::ChainContext;
+use ethers::prelude::{Middleware, PendingTransaction, TransactionReceipt, TxHash};
+use eyre::Result;
+use std::future::Future;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// First delay between receipt polls over HTTP; it doubles from here up to the block time.
+pub const MIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Adaptive waiting for a sent transaction.
+pub trait PendingTxExt {
+    /// Waits for the transaction's receipt through `ctx`.
+    ///
+    /// When `ctx` follows new heads (see [`ChainContext::watch_heads`]) the receipt is only asked
+    /// for when a block arrives. Otherwise polls back off from [`MIN_POLL_INTERVAL`] up to the
+    /// chain's block time; every wait starts over at the minimum. Failed lookups are logged and
+    /// retried, so neither an RPC error nor a transaction dropped from the mempool ends the wait:
+    /// bound it with the caller's timeout, e.g. [`OpTimeouts::receipt`].
+    ///
+    /// [`OpTimeouts::receipt`]: crate::timeouts::OpTimeouts::receipt
+    fn await_smart<M>(
+        self,
+        ctx: &ChainContext<M>,
+    ) -> impl Future<Output = Result<TransactionReceipt>> + '_
+    where
+        M: Middleware,
+        M::Error: 'static;
+}
+
+impl<P> PendingTxExt for PendingTransaction<'_, P> {
+    fn await_smart<M>(
+        self,
+        ctx: &ChainContext<M>,
+    ) -> impl Future<Output = Result<TransactionReceipt>> + '_
+    where
+        M: Middleware,
+        M::Error: 'static,
+    {
+        wait_for_receipt(*self, ctx)
+    }
+}
+
+async fn wait_for_receipt<M>(tx_hash: TxHash, ctx: &ChainContext<M>) -> Result<TransactionReceipt>
+where
+    M: Middleware,
+    M::Error: 'static,
+{
+    let node = ctx.inner();
+    if let Some(mut heads) = ctx.heads() {
+        loop {
+            heads.borrow_and_update(); // A block arriving during the lookup wakes the next wait
+            match node.get_transaction_receipt(tx_hash).await {
+                Ok(Some(receipt)) => return Ok(receipt),
+                Ok(None) => {}
+                Err(err) => {
+                    warn!(?tx_hash, %err, "receipt lookup failed, retrying on the next block")
+                }
+            }
+            if heads.changed().await.is_err() {
+                break; // The subscription ended
+            }
+        }
+    }
+
+    let block_time = ctx.block_time().await.unwrap_or_else(|err| {
+        debug!(%err, "block time unknown, polling at the minimum interval");
+        Duration::ZERO
+    });
+    let ceiling = block_time.max(MIN_POLL_INTERVAL);
+    let mut delay = MIN_POLL_INTERVAL;
+    loop {
+        match node.get_transaction_receipt(tx_hash).await {
+            Ok(Some(receipt)) => return Ok(receipt),
+            Ok(None) => {}
+            Err(err) => warn!(?tx_hash, %err, ?delay, "receipt lookup failed, retrying"),
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(ceiling);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{mined_receipt, spawn_mock_node};
+    use ethers::prelude::{Provider, Transaction, H256};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn test_backoff_polls_an_order_of_magnitude_less() -> eyre::Result<()> {
+        const MINED_AFTER: Duration = Duration::from_secs(2);
+        let tx_hash = H256::from_low_u64_be(0x7a);
+        let polls = Arc::new(AtomicUsize::new(0));
+        let sent = Arc::new(Mutex::new(Instant::now()));
+
+        // A node with 2 s blocks that mines the transaction 2 s after it is "sent"
+        let url = spawn_mock_node({
+            let (polls, sent) = (polls.clone(), sent.clone());
+            move |method, params| {
+                let mined = sent.lock().unwrap().elapsed() >= MINED_AFTER;
+                match method {
+                    // Both count: ethers polls the transaction until it has a block, then the receipt
+                    "eth_getTransactionByHash" => {
+                        polls.fetch_add(1, Ordering::SeqCst);
+                        json!(Transaction {
+                            hash: tx_hash,
+                            block_number: mined.then(|| 7u64.into()),
+                            ..Default::default()
+                        })
+                    }
+                    "eth_getTransactionReceipt" => {
+                        polls.fetch_add(1, Ordering::SeqCst);
+                        if mined {
+                            json!(mined_receipt(tx_hash))
+                        } else {
+                            json!(null)
+                        }
+                    }
+                    "eth_getBlockByNumber" => {
+                        let number = if params[0] == "latest" { 100 } else { 90 };
+                        json!({
+                            "number": format!("{number:#x}"),
+                            "hash": format!("{:?}", H256::from_low_u64_be(number)),
+                            "timestamp": format!("{:#x}", 1_000 + 2 * number),
+                            "gasLimit": "0x1c9c380",
+                            "gasUsed": "0x0",
+                            "transactions": [],
+                        })
+                    }
+                    "eth_blockNumber" => json!("0x64"),
+                    _ => json!({ "error": { "code": -32601, "message": "method not found" } }),
+                }
+            }
+        })
+        .await?;
+        let provider = Provider::try_from(url)?;
+
+        // The fixed 10 ms interval the binaries used to set
+        *sent.lock().unwrap() = Instant::now();
+        PendingTransaction::new(tx_hash, &provider)
+            .interval(Duration::from_millis(10))
+            .await?;
+        let fixed = polls.swap(0, Ordering::SeqCst);
+
+        let ctx = ChainContext::new(provider.clone());
+        assert_eq!(ctx.block_time().await?, MINED_AFTER);
+        *sent.lock().unwrap() = Instant::now();
+        let receipt = PendingTransaction::new(tx_hash, &provider)
+            .await_smart(&ctx)
+            .await?;
+        assert_eq!(receipt.transaction_hash, tx_hash);
+        let adaptive = polls.load(Ordering::SeqCst);
+
+        assert!(
+            adaptive * 10 <= fixed,
+            "{adaptive} adaptive polls vs {fixed} fixed ones"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rpc_errors_keep_polling() -> eyre::Result<()> {
+        let tx_hash = H256::from_low_u64_be(0x7b);
+        let polls = Arc::new(AtomicUsize::new(0));
+
+        // A flaky node whose first three receipt lookups fail
+        let url = spawn_mock_node({
+            let polls = polls.clone();
+            move |method, _| match method {
+                "eth_getTransactionReceipt" if polls.fetch_add(1, Ordering::SeqCst) < 3 => {
+                    json!({ "error": { "code": -32000, "message": "upstream unavailable" } })
+                }
+                "eth_getTransactionReceipt" => json!(mined_receipt(tx_hash)),
+                _ => json!({ "error": { "code": -32601, "message": "method not found" } }),
+            }
+        })
+        .await?;
+        let provider = Provider::try_from(url)?;
+        let ctx = ChainContext::new(provider.clone());
+
+        let receipt = PendingTransaction::new(tx_hash, &provider)
+            .await_smart(&ctx)
+            .await?;
+        assert_eq!(receipt.transaction_hash, tx_hash);
+        assert_eq!(polls.load(Ordering::SeqCst), 4);
+        Ok(())
+    }
+}
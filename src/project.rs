@@ -0,0 +1,414 @@
+//! Solidity project compilation and inspection helpers.
+
+use crate::report::project_report; // Structured summary of the compiled contracts
+use ethers::abi::Abi; // Contract ABI type returned from the compiled artifacts
+use ethers::types::Bytes; // Raw bytecode type used for deployments
+use ethers_solc::artifacts::output_selection::ContractOutputSelection; // Extra solc outputs (NatSpec)
+use ethers_solc::{Artifact, Project, ProjectPathsConfig, SolcConfig}; // Import for Solidity project and artifact management
+use ethers_solc::{ConfigurableArtifacts, ConfigurableContractArtifact, ProjectCompileOutput}; // Solidity compilation outputs and configuration
+use eyre::{eyre, ContextCompat, Result}; // For error handling and contextual errors
+use futures::future::join_all; // Waits for every root of a multi-project compilation
+use serde::Serialize; // Contract listings are reported as JSON
+use std::path::{Path, PathBuf}; // Used for file system path management
+use std::sync::Arc; // Shares the worker permits between the compilations
+use tokio::sync::Semaphore; // Bounds the concurrent compilations
+use tokio::task::JoinHandle; // Compilations run on blocking workers
+use tracing::{info, info_span, warn}; // Structured progress output
+
+/// Result of a successful compilation together with its non-fatal diagnostics.
+#[derive(Clone, Debug)]
+pub struct CompileReport {
+    /// The compiled project.
+    pub output: ProjectCompileOutput<ConfigurableArtifacts>,
+    /// Warnings emitted by solc (deprecations, shadowing, unused variables, ...).
+    pub warnings: Vec<String>,
+}
+
+// Function to build the Solidity project rooted at the given folder path, without compiling it
+pub fn build_project(root: &str) -> Result<Project> {
+    let root = PathBuf::from(root); // Convert the root folder path to a PathBuf object
+    if !root.exists() {
+        return Err(eyre!("Project root {root:?} does not exist!")); // Error handling for non-existent project root
+    }
+
+    // Define the paths to be used for the Solidity project
+    let paths = ProjectPathsConfig::builder()
+        .root(&root)
+        .sources(&root)
+        .build()?; // Build the project path configuration
+
+    // Keep the NatSpec comments (devdoc/userdoc) in the artifacts, for the generated docs
+    let artifacts = ConfigurableArtifacts::new(
+        [
+            ContractOutputSelection::DevDoc,
+            ContractOutputSelection::UserDoc,
+        ],
+        [],
+    );
+    let solc_config = SolcConfig::builder().settings(artifacts.settings()).build(); // Ask solc for the same outputs
+
+    // Build the project object, enabling auto-detection of the Solidity compiler
+    let project = Project::builder()
+        .paths(paths)
+        .artifacts(artifacts)
+        .solc_config(solc_config)
+        .set_auto_detect(true) // Automatically detect Solidity compiler
+        .no_artifacts() // Avoid writing artifacts to disk
+        .build()?;
+    Ok(project)
+}
+
+// Function to compile a Solidity project from the given root folder path, on a blocking worker
+// thread since solc runs synchronously
+pub async fn compile(root: &str) -> Result<CompileReport> {
+    spawn_compile(root).await?
+}
+
+/// Compiles each of `roots` concurrently, on at most as many blocking workers as there are
+/// cores. A root listed twice is compiled once. The reports come back in the order of `roots`.
+pub async fn compile_many(roots: &[&str]) -> Vec<Result<CompileReport>> {
+    let mut unique: Vec<&str> = roots.to_vec();
+    unique.sort_unstable();
+    unique.dedup();
+
+    let workers = std::thread::available_parallelism().map_or(1, |cores| cores.get());
+    let permits = Arc::new(Semaphore::new(workers));
+    let compiled = join_all(unique.iter().map(|root| {
+        let permits = permits.clone();
+        async move {
+            let _permit = permits.acquire_owned().await?;
+            spawn_compile(root).await?
+        }
+    }))
+    .await;
+
+    let warnings: usize = compiled
+        .iter()
+        .flatten()
+        .map(|report| report.warnings.len())
+        .sum();
+    let failed = compiled.iter().filter(|result| result.is_err()).count();
+    info!(roots = unique.len(), failed, warnings, "compiled projects");
+
+    // eyre reports can't be cloned, so a repeated root that failed gets the message again
+    roots
+        .iter()
+        .map(
+            |root| match &compiled[unique.binary_search(root).expect("deduplicated from roots")] {
+                Ok(report) => Ok(report.clone()),
+                Err(err) => Err(eyre!("{err:#}")),
+            },
+        )
+        .collect()
+}
+
+// Runs the compilation of `root` on a blocking worker, inside a span of the caller's
+fn spawn_compile(root: &str) -> JoinHandle<Result<CompileReport>> {
+    let span = info_span!("compile", root = %root); // Group the compilation events
+    let root = root.to_string();
+    tokio::task::spawn_blocking(move || span.in_scope(|| compile_blocking(&root)))
+}
+
+fn compile_blocking(root: &str) -> Result<CompileReport> {
+    let project = build_project(root)?;
+
+    // Compile the Solidity project
+    let output = project.compile()?;
+
+    // Check if there were any compiler errors
+    if output.has_compiler_errors() {
+        Err(eyre!(
+            "Compiling solidity project failed: {:?}",
+            output.output().errors // Print compilation errors
+        ))
+    } else {
+        // Collect the non-fatal diagnostics so callers can surface them
+        let warnings: Vec<String> = output
+            .clone()
+            .output()
+            .errors
+            .iter()
+            .filter(|diagnostic| diagnostic.severity.is_warning())
+            .map(|diagnostic| {
+                diagnostic
+                    .formatted_message
+                    .clone()
+                    .unwrap_or_else(|| diagnostic.message.clone())
+            })
+            .collect();
+        for warning in &warnings {
+            warn!(%warning, "compiler warning");
+        }
+        info!(warnings = warnings.len(), "compiled");
+
+        Ok(CompileReport {
+            output, // Return the compiled output if successful
+            warnings,
+        })
+    }
+}
+
+// Function to report the details of the compiled contracts, rendered from the project report
+pub async fn print_project(project: &ProjectCompileOutput<ConfigurableArtifacts>) -> Result<()> {
+    let report = project_report(project); // Summarize the compiled artifacts (contracts)
+    for contract in &report.contracts {
+        let _span = info_span!("contract", contract = %contract.name).entered(); // Attach the contract name to its events
+
+        // If the contract has a constructor, report its arguments
+        if let Some(args) = &contract.constructor {
+            let args: Vec<String> = args
+                .iter()
+                .map(|arg| format!("{} {}", arg.kind, arg.name))
+                .collect();
+            info!(args = %args.join(", "), "constructor"); // Report the constructor arguments
+        }
+
+        // Report each function's signature and selector
+        for func in &contract.functions {
+            info!(function = %func.signature, selector = %func.selector, mutability = %func.state_mutability, "function");
+        }
+
+        // Report the events and custom errors
+        for event in &contract.events {
+            info!(event = %event.signature, topic = %event.topic, "event");
+        }
+        for error in &contract.errors {
+            info!(error = %error.signature, selector = %error.selector, "custom error");
+        }
+    }
+    Ok(())
+}
+
+/// Locates `<contracts_folder><contract_name>.sol` in the compiled project and
+/// returns its ABI and creation bytecode, ready to be handed to a `ContractFactory`.
+pub fn contract_parts(
+    project: &ProjectCompileOutput<ConfigurableArtifacts>,
+    contracts_folder: &str,
+    contract_name: &str,
+) -> Result<(Abi, Bytes)> {
+    let contract_path = format!("{contracts_folder}{contract_name}.sol"); // Construct the full contract path
+
+    // Convert the relative contract path to an absolute path on the filesystem
+    let contract_absolute_path = std::fs::canonicalize(Path::new(&contract_path))?;
+
+    // Convert the absolute path to a string for further use (required for the `find` function)
+    let contract_absolute_str = contract_absolute_path
+        .to_str()
+        .context("Failed to convert path to string")?; // Error handling for failed conversion
+
+    // Locate the compiled contract using the project object
+    let contract = project
+        .find(contract_absolute_str, contract_name) // Find the contract by its name and path
+        .with_context(|| {
+            // Name the contracts that do exist so a typo is easy to spot
+            let mut names: Vec<String> = project.artifact_ids().map(|(id, _)| id.name).collect();
+            names.sort();
+            names.dedup();
+            format!(
+                "Contract {contract_name} not found in {contract_path}, available: {}",
+                names.join(", ")
+            )
+        })?; // Handle the case where the contract is not found
+
+    // Extract ABI (Application Binary Interface) and bytecode from the compiled contract
+    artifact_parts(contract_name, contract)
+}
+
+/// Copies the ABI and creation bytecode out of a compiled artifact, leaving the rest of it
+/// (sources, runtime bytecode, metadata) borrowed in the project output.
+pub fn artifact_parts(
+    contract_name: &str,
+    artifact: &ConfigurableContractArtifact,
+) -> Result<(Abi, Bytes)> {
+    let abi = artifact
+        .get_abi()
+        .with_context(|| format!("Missing abi for {contract_name}"))?
+        .into_owned();
+    let bytecode = artifact
+        .get_bytecode_bytes()
+        .with_context(|| format!("Missing bytecode for {contract_name}"))?
+        .into_owned();
+    Ok((abi, bytecode))
+}
+
+/// Names of the contracts found in a folder.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ContractList {
+    pub contracts: Vec<String>,
+}
+
+// Function to compile the given folder and list the names of every contract found, sorted
+pub async fn list_contracts(root: &str) -> Result<Vec<String>> {
+    Ok(contract_names(&compile(root).await?.output))
+}
+
+// Function to list the names of every contract of an already compiled project, sorted
+pub fn contract_names(output: &ProjectCompileOutput<ConfigurableArtifacts>) -> Vec<String> {
+    let mut names: Vec<String> = output.artifact_ids().map(|(id, _)| id.name).collect();
+    names.sort();
+    names.dedup(); // The same name may be compiled from several files
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs solc")]
+    async fn test_compile_captures_warnings() -> Result<()> {
+        let report = compile("tests/fixtures/warnings/").await?;
+        assert!(
+            report
+                .warnings
+                .iter()
+                .any(|warning| warning.contains("Unused local variable")),
+            "Unused variable warning should be captured: {:?}",
+            report.warnings
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compile_many_reports_each_missing_root() {
+        let results = compile_many(&["missing/a/", "missing/b/", "missing/a/"]).await;
+        assert_eq!(results.len(), 3);
+        for (result, root) in results
+            .iter()
+            .zip(["missing/a/", "missing/b/", "missing/a/"])
+        {
+            let err = result.as_ref().unwrap_err().to_string();
+            assert!(err.contains(root.trim_end_matches('/')), "{err}");
+        }
+    }
+
+    // Copies a fixture into a fresh folder, so no solc cache from an earlier run speeds it up
+    fn fresh_copy(fixture: &str, run: &str) -> Result<String> {
+        let dir = std::env::temp_dir().join(format!(
+            "compile-many-{}-{run}-{fixture}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&dir)?;
+        for entry in std::fs::read_dir(Path::new("tests/fixtures").join(fixture))? {
+            let path = entry?.path();
+            std::fs::copy(
+                &path,
+                dir.join(path.file_name().context("Missing file name")?),
+            )?;
+        }
+        Ok(format!("{}/", dir.display()))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs solc")]
+    async fn test_compile_many_runs_roots_concurrently() -> Result<()> {
+        let fixtures = [
+            ("warnings", "UnusedVariable"),
+            ("plan", "Vault"),
+            ("contracts", "Auction"),
+        ];
+
+        // The slowest of the roots compiled on its own
+        let mut single = Duration::ZERO;
+        for (fixture, _) in fixtures {
+            let root = fresh_copy(fixture, "single")?;
+            let started = Instant::now();
+            compile(&root).await?;
+            single = single.max(started.elapsed());
+            std::fs::remove_dir_all(&root)?;
+        }
+
+        let roots = fixtures
+            .iter()
+            .map(|(fixture, _)| fresh_copy(fixture, "many"))
+            .collect::<Result<Vec<_>>>()?;
+        let mut requested: Vec<&str> = roots.iter().map(String::as_str).collect();
+        requested.push(requested[0]); // Compiled once, reported twice
+        let started = Instant::now();
+        let results = compile_many(&requested).await;
+        let elapsed = started.elapsed();
+        assert!(
+            elapsed < single * 2,
+            "{elapsed:?} for three roots vs {single:?} for one"
+        );
+
+        assert_eq!(results.len(), 4);
+        let expected = fixtures.iter().chain(&fixtures[..1]);
+        for (result, (root, (_, contract))) in
+            results.into_iter().zip(requested.iter().zip(expected))
+        {
+            let output = result?.output;
+            contract_parts(&output, root, contract)?;
+        }
+        for root in &roots {
+            std::fs::remove_dir_all(root)?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs solc")]
+    async fn test_list_contracts_in_examples() -> Result<()> {
+        let names = list_contracts("examples/").await?;
+        assert!(
+            names.iter().any(|name| name == "BUSDImplementation"),
+            "BUSDImplementation should be listed: {names:?}"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_report_borrows_a_large_output() -> Result<()> {
+        use crate::deploy::{deploy_contract, DeployOptions};
+        use ethers::prelude::{LocalWallet, Middleware, Provider};
+        use ethers::utils::Ganache;
+
+        const CONTRACTS: usize = 50;
+        let dir = std::env::temp_dir().join(format!("generated-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        for index in 0..CONTRACTS {
+            let source = format!(
+                "// SPDX-License-Identifier: MIT\npragma solidity ^0.8.0;\n\
+                 contract Generated{index} {{ uint256 public value = {index}; }}\n"
+            );
+            std::fs::write(dir.join(format!("Generated{index}.sol")), source)?;
+        }
+        let folder = format!("{}/", dir.display());
+        let output = compile(&folder).await?.output;
+
+        // Reporting only borrows the output, which stays usable for the lookup below
+        let report = project_report(&output);
+        assert_eq!(report.contracts.len(), CONTRACTS);
+        assert!(report
+            .contracts
+            .iter()
+            .all(|contract| contract.has_bytecode));
+        print_project(&output).await?;
+        let (abi, bytecode) = contract_parts(&output, &folder, "Generated7")?;
+        std::fs::remove_dir_all(&dir)?;
+
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let (contract, result) = deploy_contract(
+            &ctx,
+            wallet,
+            "Generated7",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        assert!(!provider.get_code(result.address, None).await?.is_empty());
+        let value: ethers::types::U256 = contract.method("value", ())?.call().await?;
+        assert_eq!(value, 7u64.into());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,80 @@
+//! Provider construction for endpoints other than the local Ganache instance.
+
+use ethers::providers::{Http, Provider};
+use eyre::{Context, Result};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    Client, Url,
+};
+use std::collections::HashMap;
+
+/// Connects to an HTTP JSON-RPC endpoint, attaching `headers` to every request.
+///
+/// Hosted RPC providers usually expect an API key header; for Ganache the plain
+/// `Provider::try_from(endpoint)` is enough.
+pub fn connect_with_headers(url: &str, headers: HashMap<String, String>) -> Result<Provider<Http>> {
+    let url = Url::parse(url).wrap_err_with(|| format!("Invalid RPC url {url}"))?;
+
+    // Convert the plain string pairs into typed header names and values
+    let mut header_map = HeaderMap::new();
+    for (name, value) in headers {
+        let header_name = HeaderName::from_bytes(name.as_bytes())
+            .wrap_err_with(|| format!("Invalid header name {name:?}"))?;
+        let header_value = HeaderValue::from_str(&value)
+            .wrap_err_with(|| format!("Invalid value for header {name:?}"))?;
+        header_map.insert(header_name, header_value);
+    }
+
+    // Every request sent by this client carries the default headers
+    let client = Client::builder().default_headers(header_map).build()?;
+    Ok(Provider::new(Http::new_with_client(url, client)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::prelude::Middleware;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    #[tokio::test]
+    async fn test_headers_are_attached_to_requests() -> Result<()> {
+        // A one-shot mock RPC server that records the raw request and answers eth_chainId
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await?;
+            let mut buf = vec![0u8; 4096];
+            let n = socket.read(&mut buf).await?;
+            let body = r#"{"jsonrpc":"2.0","id":1,"result":"0x539"}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await?;
+            eyre::Ok(String::from_utf8_lossy(&buf[..n]).to_lowercase())
+        });
+
+        let headers = HashMap::from([("X-Api-Key".to_string(), "secret-key".to_string())]);
+        let provider = connect_with_headers(&url, headers)?;
+        let chain_id = provider.get_chainid().await?;
+        assert_eq!(chain_id.as_u64(), 1337);
+
+        let request = server.await??;
+        assert!(
+            request.contains("x-api-key: secret-key"),
+            "Request should carry the custom header: {request}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_header_name_is_rejected() {
+        let headers = HashMap::from([("bad header".to_string(), "value".to_string())]);
+        assert!(connect_with_headers("http://127.0.0.1:8545", headers).is_err());
+    }
+}
@@ -0,0 +1,227 @@
+//! Upgradeable deployments: an implementation behind an ERC-1967 proxy.
+
+use crate::artifacts::Artifact;
+use crate::project::{compile, contract_parts};
+use crate::storage::read_slot;
+use ethers::{
+    abi::{parse_abi, Token, Tokenize},
+    contract::{Contract, ContractFactory},
+    prelude::{Address, JsonRpcClient, Middleware, Provider, TransactionReceipt, H256},
+};
+use ethers_solc::ProjectCompileOutput;
+use eyre::{eyre, Context, ContextCompat, Result};
+use std::sync::Arc;
+use tracing::info;
+
+// Folder holding the proxy source shipped with the crate
+const PROXY_SOURCES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/contracts/");
+
+/// `bytes32(uint256(keccak256("eip1967.proxy.implementation")) - 1)`
+pub const IMPLEMENTATION_SLOT: H256 = H256([
+    0x36, 0x08, 0x94, 0xa1, 0x3b, 0xa1, 0xa3, 0x21, 0x06, 0x67, 0xc8, 0x28, 0x49, 0x2d, 0xb9, 0x8d,
+    0xca, 0x3e, 0x20, 0x76, 0xcc, 0x37, 0x35, 0xa9, 0x20, 0xa3, 0xca, 0x50, 0x5d, 0x38, 0x2b, 0xbc,
+]);
+
+/// An implementation deployed behind a proxy.
+pub struct UpgradeableDeployment<M> {
+    /// Handle bound to the proxy address with the implementation's ABI.
+    pub contract: Contract<M>,
+    /// Address of the implementation the proxy points at.
+    pub implementation: Address,
+}
+
+/// Compiles the crate's `ERC1967Proxy`, whose constructor takes `(address implementation, bytes data)`
+/// and whose deployer becomes the admin allowed to call `upgradeTo(address)`.
+pub async fn erc1967_proxy_artifact() -> Result<Artifact> {
+    let project = compile(PROXY_SOURCES).await?.output;
+    let (abi, bytecode) = contract_parts(&project, PROXY_SOURCES, "ERC1967Proxy")?;
+    Ok(Artifact { abi, bytecode })
+}
+
+/// Deploys `impl_name` from `project`, then `proxy_artifact` pointing at it with the
+/// `init_fn(init_args)` calldata run by the proxy's constructor.
+///
+/// `proxy_artifact`'s constructor must take `(address implementation, bytes data)`, like
+/// [`erc1967_proxy_artifact`]'s does.
+pub async fn deploy_upgradeable<M, A>(
+    project: &ProjectCompileOutput,
+    impl_name: &str,
+    proxy_artifact: &Artifact,
+    init_fn: &str,
+    init_args: A,
+    client: Arc<M>,
+) -> Result<UpgradeableDeployment<M>>
+where
+    M: Middleware + 'static,
+    A: Tokenize,
+{
+    let (abi, bytecode) = project
+        .find_first(impl_name)
+        .and_then(|artifact| Some((artifact.abi.clone()?, artifact.bytecode.clone()?)))
+        .with_context(|| format!("{impl_name} not found in the compiled project"))?;
+    let bytecode = bytecode
+        .object
+        .into_bytes()
+        .with_context(|| format!("{impl_name} has unlinked bytecode"))?;
+    let abi = abi.abi;
+
+    // The implementation itself takes no constructor arguments; its state lives in the proxy
+    let implementation = ContractFactory::new(abi.clone(), bytecode, client.clone())
+        .deploy(())?
+        .send()
+        .await
+        .wrap_err_with(|| format!("Failed to deploy the {impl_name} implementation"))?;
+    let implementation = implementation.address();
+
+    let init_data = abi
+        .function(init_fn)
+        .wrap_err_with(|| format!("{init_fn} is not in {impl_name}'s ABI"))?
+        .encode_input(&init_args.into_tokens())?;
+    let proxy = ContractFactory::new(
+        proxy_artifact.abi.clone(),
+        proxy_artifact.bytecode.clone(),
+        client.clone(),
+    )
+    .deploy_tokens(vec![
+        Token::Address(implementation),
+        Token::Bytes(init_data),
+    ])?
+    .send()
+    .await
+    .wrap_err("Failed to deploy the proxy")?;
+    info!(proxy = ?proxy.address(), implementation = ?implementation, "upgradeable contract deployed");
+
+    Ok(UpgradeableDeployment {
+        contract: Contract::new(proxy.address(), abi, client),
+        implementation,
+    })
+}
+
+/// Points `proxy` at `new_implementation` through its admin-only `upgradeTo(address)`;
+/// `proxy`'s client must be the admin.
+pub async fn upgrade_to<M: Middleware + 'static>(
+    proxy: &Contract<M>,
+    new_implementation: Address,
+) -> Result<TransactionReceipt> {
+    let admin_abi = parse_abi(&["function upgradeTo(address implementation)"])?;
+    let admin = Contract::new(proxy.address(), admin_abi, proxy.client());
+    let receipt = admin
+        .method::<_, ()>("upgradeTo", new_implementation)?
+        .send()
+        .await?
+        .await?
+        .context("Missing upgrade receipt")?;
+    if receipt.status != Some(1.into()) {
+        return Err(eyre!(
+            "upgrade of {:?} reverted in tx {:?}",
+            proxy.address(),
+            receipt.transaction_hash
+        ));
+    }
+    info!(proxy = ?proxy.address(), implementation = ?new_implementation, "proxy upgraded");
+    Ok(receipt)
+}
+
+/// Reads the implementation address from `proxy`'s ERC-1967 implementation slot.
+pub async fn read_implementation_slot<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    proxy: Address,
+) -> Result<Address> {
+    let word = read_slot(provider, proxy, IMPLEMENTATION_SLOT).await?;
+    Ok(Address::from(word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::prelude::{LocalWallet, Signer, SignerMiddleware, U256};
+    use ethers::utils::{keccak256, Ganache};
+
+    #[test]
+    fn test_implementation_slot_constant() {
+        let hash = U256::from_big_endian(&keccak256("eip1967.proxy.implementation"));
+        assert_eq!(IMPLEMENTATION_SLOT, H256((hash - 1).into()));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs solc")]
+    async fn test_proxy_artifact_interface() -> Result<()> {
+        let proxy = erc1967_proxy_artifact().await?;
+        let constructor = proxy
+            .abi
+            .constructor()
+            .context("The proxy has a constructor")?;
+        let inputs: Vec<String> = constructor
+            .inputs
+            .iter()
+            .map(|input| input.kind.to_string())
+            .collect();
+        assert_eq!(inputs, ["address", "bytes"]);
+        assert!(proxy.abi.function("upgradeTo").is_ok());
+        assert!(proxy.abi.function("admin").is_ok());
+        assert!(!proxy.bytecode.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_busd_behind_proxy_upgrade() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let owner = wallet.address();
+        let client = Arc::new(SignerMiddleware::new(
+            provider.clone(),
+            wallet.with_chain_id(chain_id),
+        ));
+
+        let project = compile("examples/").await?.output;
+        let proxy_artifact = erc1967_proxy_artifact().await?;
+        let deployment = deploy_upgradeable(
+            &project,
+            "BUSDImplementation",
+            &proxy_artifact,
+            "initialize",
+            (),
+            client.clone(),
+        )
+        .await?;
+        let busd = &deployment.contract;
+        assert_eq!(
+            read_implementation_slot(&provider, busd.address()).await?,
+            deployment.implementation
+        );
+
+        // The initializer ran in the proxy's storage: the deployer owns the proxied token,
+        // which unlike the bare implementation starts unpaused
+        let proxied_owner: Address = busd.method("owner", ())?.call().await?;
+        assert_eq!(proxied_owner, owner);
+        let supply = U256::from(5_000u64);
+        busd.method::<_, bool>("increaseSupply", supply)?
+            .send()
+            .await?
+            .await?;
+        let balance: U256 = busd.method("balanceOf", owner)?.call().await?;
+        assert_eq!(balance, supply);
+
+        // Upgrade to a second implementation; the state stays in the proxy
+        let second = ContractFactory::new(
+            busd.abi().clone(),
+            contract_parts(&project, "examples/", "BUSDImplementation")?.1,
+            client.clone(),
+        )
+        .deploy(())?
+        .send()
+        .await?;
+        upgrade_to(busd, second.address()).await?;
+        let active = read_implementation_slot(&provider, busd.address()).await?;
+        assert_eq!(active, second.address());
+        assert_ne!(active, deployment.implementation);
+        let balance: U256 = busd.method("balanceOf", owner)?.call().await?;
+        assert_eq!(balance, supply);
+
+        Ok(())
+    }
+}
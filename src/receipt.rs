@@ -0,0 +1,100 @@
+//! A flat view of a transaction receipt, for assertions and reports.
+
+use ethers::types::{Address, TransactionReceipt, U256};
+use serde::Serialize;
+
+/// The receipt fields callers check most, without the `Option`s of the raw receipt.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptSummary {
+    /// The transaction executed without reverting (`status` is 1).
+    pub success: bool,
+    /// Block the transaction was mined in; 0 for a receipt without one.
+    pub block_number: u64,
+    /// Gas the transaction used; 0 when the node didn't report it.
+    pub gas_used: U256,
+    /// Address of the contract a deployment created.
+    pub contract_address: Option<Address>,
+    /// Number of logs the transaction emitted.
+    pub logs_count: usize,
+}
+
+impl From<TransactionReceipt> for ReceiptSummary {
+    fn from(receipt: TransactionReceipt) -> Self {
+        Self::from(&receipt)
+    }
+}
+
+impl From<&TransactionReceipt> for ReceiptSummary {
+    fn from(receipt: &TransactionReceipt) -> Self {
+        Self {
+            success: receipt.status == Some(1u64.into()),
+            block_number: receipt.block_number.unwrap_or_default().as_u64(),
+            gas_used: receipt.gas_used.unwrap_or_default(),
+            contract_address: receipt.contract_address,
+            logs_count: receipt.logs.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::mined_receipt;
+    use ethers::prelude::*;
+    use ethers::utils::Ganache;
+    use eyre::{ContextCompat, Result};
+
+    #[test]
+    fn test_summary_of_a_failed_receipt() {
+        let receipt = TransactionReceipt {
+            status: Some(0u64.into()),
+            logs: vec![Log::default()],
+            ..mined_receipt(H256::from_low_u64_be(1))
+        };
+        assert_eq!(
+            ReceiptSummary::from(receipt),
+            ReceiptSummary {
+                success: false,
+                block_number: 7,
+                gas_used: 21_000u64.into(),
+                contract_address: None,
+                logs_count: 1,
+            }
+        );
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_summary_matches_the_raw_receipt() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let tx =
+            TransactionRequest::pay(ganache.addresses()[1], 1000u64).from(ganache.addresses()[0]);
+        let receipt = provider
+            .send_transaction(tx, None)
+            .await?
+            .await?
+            .context("Missing receipt")?;
+
+        let summary = ReceiptSummary::from(&receipt);
+        assert_eq!(summary.success, receipt.status == Some(1u64.into()));
+        assert_eq!(Some(summary.block_number.into()), receipt.block_number);
+        assert_eq!(Some(summary.gas_used), receipt.gas_used);
+        assert_eq!(summary.contract_address, receipt.contract_address);
+        assert_eq!(summary.logs_count, receipt.logs.len());
+        assert_eq!(
+            summary,
+            ReceiptSummary {
+                success: true,
+                block_number: 1,
+                gas_used: 21_000u64.into(),
+                contract_address: None,
+                logs_count: 0,
+            }
+        );
+
+        Ok(())
+    }
+}
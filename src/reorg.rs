@@ -0,0 +1,191 @@
+//! Confirmation tracking that notices when a mined transaction is reorganized away.
+
+use crate::chain_context::ChainContext;
+use crate::errors::Error;
+use ethers::prelude::{BlockNumber, Middleware, TransactionReceipt, H256};
+use eyre::Result;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+/// Options for [`wait_confirmed`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ConfirmationOptions {
+    /// New blocks to wait for a transaction that left the chain to be mined again before
+    /// giving up with [`Error::DroppedByReorg`].
+    pub drop_after_blocks: u64,
+    /// Time between checks of the head and the receipt.
+    pub poll_interval: Duration,
+}
+
+impl Default for ConfirmationOptions {
+    fn default() -> Self {
+        Self {
+            drop_after_blocks: 12,
+            poll_interval: Duration::from_millis(100),
+        }
+    }
+}
+
+/// Waits until `tx_hash` is `confirmations` blocks deep, its block still on the canonical chain.
+///
+/// At each check the receipt's block hash is compared with the canonical block at that height.
+/// If the transaction is reorganized into another block, the count restarts from there. If it
+/// disappears entirely for [`ConfirmationOptions::drop_after_blocks`] blocks, the wait fails
+/// with [`Error::DroppedByReorg`]. A transaction that was never mined is waited for
+/// indefinitely; bound the wait with [`crate::timeouts::with_timeout`].
+pub async fn wait_confirmed<M: Middleware + 'static>(
+    ctx: &ChainContext<M>,
+    tx_hash: H256,
+    confirmations: u64,
+    options: &ConfirmationOptions,
+) -> Result<TransactionReceipt>
+where
+    M::Error: 'static,
+{
+    // Last block the transaction was seen in, and the head when it was found missing from it
+    let mut included_in: Option<H256> = None;
+    let mut missing_since: Option<u64> = None;
+    loop {
+        let head = ctx.get_block_number().await?.as_u64();
+        match canonical_receipt(ctx, tx_hash).await? {
+            Some(receipt) => {
+                let (number, hash) = block_of(&receipt)?;
+                if included_in.is_some_and(|previous| previous != hash) {
+                    warn!(
+                        ?tx_hash,
+                        block = number,
+                        "transaction reorganized, restarting the confirmation count"
+                    );
+                }
+                included_in = Some(hash);
+                missing_since = None;
+                let depth = head.saturating_sub(number) + 1;
+                debug!(?tx_hash, depth, confirmations, "waiting for confirmations");
+                if depth >= confirmations.max(1) {
+                    return Ok(receipt);
+                }
+            }
+            None => {
+                if let Some(block_hash) = included_in {
+                    let since = *missing_since.get_or_insert_with(|| {
+                        warn!(
+                            ?tx_hash,
+                            ?block_hash,
+                            "transaction no longer on the canonical chain"
+                        );
+                        head
+                    });
+                    if head.saturating_sub(since) >= options.drop_after_blocks {
+                        return Err(Error::DroppedByReorg {
+                            tx_hash,
+                            block_hash,
+                            blocks: head - since,
+                        }
+                        .into());
+                    }
+                }
+            }
+        }
+        tokio::time::sleep(options.poll_interval).await;
+    }
+}
+
+// The receipt, unless the node still serves it for a block that is no longer canonical
+async fn canonical_receipt<M: Middleware + 'static>(
+    ctx: &ChainContext<M>,
+    tx_hash: H256,
+) -> Result<Option<TransactionReceipt>>
+where
+    M::Error: 'static,
+{
+    let Some(receipt) = ctx.get_transaction_receipt(tx_hash).await? else {
+        return Ok(None);
+    };
+    let (number, hash) = block_of(&receipt)?;
+    // By number, which the context never serves from its cache
+    let canonical = ctx
+        .get_block(BlockNumber::Number(number.into()))
+        .await?
+        .and_then(|block| block.hash);
+    Ok((canonical == Some(hash)).then_some(receipt))
+}
+
+fn block_of(receipt: &TransactionReceipt) -> Result<(u64, H256)> {
+    match (receipt.block_number, receipt.block_hash) {
+        (Some(number), Some(hash)) => Ok((number.as_u64(), hash)),
+        _ => Err(eyre::eyre!(
+            "Receipt of {:?} has no block",
+            receipt.transaction_hash
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::prelude::{Provider, TransactionRequest, U256};
+    use ethers::utils::Anvil;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Anvil")]
+    async fn test_reverted_block_is_reported_as_dropped() -> Result<()> {
+        // Anvil's snapshots remove the mined block, and the transaction with it
+        let anvil = Anvil::new().spawn();
+        let provider = Provider::try_from(anvil.endpoint())?;
+        let (from, to) = (anvil.addresses()[0], anvil.addresses()[1]);
+
+        let snapshot: U256 = provider.request("evm_snapshot", ()).await?;
+        let tx = TransactionRequest::pay(to, 1000u64).from(from);
+        let hash = *provider.send_transaction(tx, None).await?;
+
+        let options = ConfirmationOptions {
+            drop_after_blocks: 2,
+            poll_interval: Duration::from_millis(20),
+        };
+        let waiter = {
+            let ctx = ChainContext::new(provider.clone());
+            tokio::spawn(async move { wait_confirmed(&ctx, hash, 5, &options).await })
+        };
+        // Let the waiter see the inclusion before the block vanishes
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(!waiter.is_finished());
+
+        let reverted: bool = provider.request("evm_revert", [snapshot]).await?;
+        assert!(reverted);
+        for _ in 0..6 {
+            provider
+                .request::<_, serde_json::Value>("evm_mine", ())
+                .await?;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+
+        // Six new blocks would have confirmed it, had it stayed
+        let err = waiter.await?.unwrap_err();
+        match err.downcast_ref::<Error>() {
+            Some(Error::DroppedByReorg { tx_hash, .. }) => assert_eq!(*tx_hash, hash),
+            other => panic!("Expected DroppedByReorg, got {other:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Anvil")]
+    async fn test_confirmed_once_deep_enough() -> Result<()> {
+        let anvil = Anvil::new().spawn();
+        let provider = Provider::try_from(anvil.endpoint())?;
+        let tx = TransactionRequest::pay(anvil.addresses()[1], 1000u64).from(anvil.addresses()[0]);
+        let hash = *provider.send_transaction(tx, None).await?;
+        for _ in 0..2 {
+            provider
+                .request::<_, serde_json::Value>("evm_mine", ())
+                .await?;
+        }
+
+        let ctx = ChainContext::new(provider);
+        let receipt = wait_confirmed(&ctx, hash, 3, &ConfirmationOptions::default()).await?;
+        assert_eq!(receipt.transaction_hash, hash);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,255 @@
+//! Line-oriented command sessions against a running chain, used by the `repl` subcommand.
+
+use crate::amount::format_eth;
+use crate::chain_context::ChainContext;
+use crate::deploy::{deploy_contract, DeployOptions};
+use crate::output::Render;
+use crate::project::{compile, contract_parts};
+use crate::timeouts::OpTimeouts;
+use crate::transfer::{send_transfer, TransferOptions, TransferResult};
+use async_trait::async_trait;
+use ethers::prelude::{Address, JsonRpcClient, LocalWallet, Middleware, Provider, Signer, U256};
+use ethers_solc::{ConfigurableArtifacts, ProjectCompileOutput};
+use eyre::{eyre, Context, Result};
+use std::io::Write;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+
+const HELP: &str = "\
+balance <address>        balance of an account
+transfer <address> <wei> send wei from the session account
+deploy <Contract>        compile the contracts folder and deploy a contract
+help                     this message
+exit                     leave the session";
+
+/// A parsed REPL line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReplCommand {
+    Balance(Address),
+    Transfer {
+        to: Address,
+        value: U256,
+    },
+    Deploy(String),
+    Help,
+    Exit,
+    /// A blank line.
+    Nothing,
+}
+
+impl ReplCommand {
+    /// Parses one input line; unknown commands and malformed arguments are errors.
+    pub fn parse(line: &str) -> Result<Self> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let command = match words.as_slice() {
+            [] => Self::Nothing,
+            ["balance", address] => Self::Balance(parse_address(address)?),
+            ["transfer", to, value] => Self::Transfer {
+                to: parse_address(to)?,
+                value: U256::from_dec_str(value)
+                    .map_err(|_| eyre!("Invalid wei amount {value:?}"))?,
+            },
+            ["deploy", name] => Self::Deploy(name.to_string()),
+            ["help"] => Self::Help,
+            ["exit"] | ["quit"] => Self::Exit,
+            [command, ..] => {
+                return Err(eyre!(
+                    "Unknown or malformed command {command:?}, type `help` for the list"
+                ))
+            }
+        };
+        Ok(command)
+    }
+}
+
+pub(crate) fn parse_address(address: &str) -> Result<Address> {
+    address
+        .parse()
+        .wrap_err_with(|| format!("Invalid address {address:?}"))
+}
+
+/// What the caller should do after a line was handled.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ReplOutcome {
+    /// Print this (possibly empty) text and read the next line.
+    Output(String),
+    /// Leave the loop.
+    Exit,
+}
+
+/// A session that handles one input line at a time.
+#[async_trait]
+pub trait LineHandler {
+    /// Parses and executes one line.
+    async fn handle_line(&mut self, line: &str) -> Result<ReplOutcome>;
+}
+
+/// Feeds `session` the lines of `input` until the input ends or the session exits, writing each
+/// output to `out`. A failing line is reported on stderr and the session goes on; the prompt goes
+/// to stderr too, so `out` only carries results.
+pub async fn run<S, R, W>(session: &mut S, input: R, out: &mut W) -> Result<()>
+where
+    S: LineHandler + ?Sized,
+    R: AsyncBufRead + Unpin,
+    W: Write,
+{
+    let mut lines = input.lines();
+    eprint!("> ");
+    while let Some(line) = lines.next_line().await? {
+        match session.handle_line(&line).await {
+            Ok(ReplOutcome::Exit) => break,
+            Ok(ReplOutcome::Output(text)) if !text.is_empty() => writeln!(out, "{text}")?,
+            Ok(ReplOutcome::Output(_)) => {}
+            Err(err) => eprintln!("error: {err:#}"), // A failed command doesn't end the session
+        }
+        eprint!("> ");
+    }
+    Ok(())
+}
+
+/// A persistent provider + wallet session; the contracts folder is compiled on the first `deploy`.
+pub struct ReplSession<T: JsonRpcClient> {
+    ctx: ChainContext<Provider<T>>,
+    wallet: LocalWallet,
+    contracts_folder: String,
+    project: Option<ProjectCompileOutput<ConfigurableArtifacts>>,
+    timeouts: OpTimeouts,
+}
+
+impl<T: JsonRpcClient + Clone + 'static> ReplSession<T> {
+    /// Starts a session sending from `wallet`'s account, which the node must have unlocked for transfers.
+    pub fn new(provider: Provider<T>, wallet: LocalWallet, contracts_folder: &str) -> Self {
+        Self {
+            ctx: ChainContext::new(provider), // Shared by every command of the session
+            wallet,
+            contracts_folder: contracts_folder.to_string(),
+            project: None,
+            timeouts: OpTimeouts::default(),
+        }
+    }
+}
+
+#[async_trait]
+impl<T: JsonRpcClient + Clone + 'static> LineHandler for ReplSession<T> {
+    async fn handle_line(&mut self, line: &str) -> Result<ReplOutcome> {
+        let output = match ReplCommand::parse(line)? {
+            ReplCommand::Nothing => String::new(),
+            ReplCommand::Help => HELP.to_string(),
+            ReplCommand::Exit => return Ok(ReplOutcome::Exit),
+            ReplCommand::Balance(address) => {
+                let balance = self
+                    .timeouts
+                    .rpc("balance", self.ctx.get_balance(address, None))
+                    .await?;
+                format_eth(balance)
+            }
+            ReplCommand::Transfer { to, value } => {
+                let options = TransferOptions {
+                    timeouts: self.timeouts,
+                    ..TransferOptions::default()
+                };
+                let receipt =
+                    send_transfer(&self.ctx, self.wallet.address(), to, value, &options).await?;
+                TransferResult::from_receipt(&receipt, value)?.human()
+            }
+            ReplCommand::Deploy(name) => {
+                if self.project.is_none() {
+                    self.project = Some(compile(&self.contracts_folder).await?.output);
+                }
+                let project = self.project.as_ref().expect("compiled above");
+                let (abi, bytecode) = contract_parts(project, &self.contracts_folder, &name)?;
+
+                let options = DeployOptions {
+                    timeouts: self.timeouts,
+                    ..DeployOptions::default()
+                };
+                let (_, result) = deploy_contract(
+                    &self.ctx,
+                    self.wallet.clone(),
+                    &name,
+                    abi,
+                    bytecode,
+                    (),
+                    &options,
+                )
+                .await?;
+                result.human()
+            }
+        };
+        Ok(ReplOutcome::Output(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::utils::Ganache;
+
+    #[test]
+    fn test_parse_commands() {
+        let address: Address = "0xB794F5eA0ba39494cE839613fffBA74279579268"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            ReplCommand::parse("  balance 0xB794F5eA0ba39494cE839613fffBA74279579268 ").unwrap(),
+            ReplCommand::Balance(address)
+        );
+        assert_eq!(
+            ReplCommand::parse("transfer 0xB794F5eA0ba39494cE839613fffBA74279579268 1000").unwrap(),
+            ReplCommand::Transfer {
+                to: address,
+                value: U256::from(1000u64)
+            }
+        );
+        assert_eq!(
+            ReplCommand::parse("deploy Migrations").unwrap(),
+            ReplCommand::Deploy("Migrations".to_string())
+        );
+        assert_eq!(ReplCommand::parse("").unwrap(), ReplCommand::Nothing);
+        assert_eq!(ReplCommand::parse("exit").unwrap(), ReplCommand::Exit);
+
+        assert!(ReplCommand::parse("balance nope").is_err());
+        assert!(ReplCommand::parse("transfer 0xB794F5eA0ba39494cE839613fffBA74279579268").is_err());
+        assert!(ReplCommand::parse("mint 5").is_err());
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_session_runs_piped_commands() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let mut session = ReplSession::new(provider, wallet, "examples/");
+
+        let recipient = "0xB794F5eA0ba39494cE839613fffBA74279579268";
+        let mut outputs = Vec::new();
+        for line in [
+            format!("balance {recipient}"),
+            format!("transfer {recipient} 1000"),
+            format!("balance {recipient}"),
+            "deploy Migrations".to_string(),
+            "exit".to_string(),
+        ] {
+            outputs.push(session.handle_line(&line).await?);
+        }
+
+        let text = |outcome: &ReplOutcome| match outcome {
+            ReplOutcome::Output(text) => text.clone(),
+            ReplOutcome::Exit => panic!("unexpected exit"),
+        };
+        assert_eq!(text(&outputs[0]), "0.000000000000000000 ETH");
+        assert!(text(&outputs[1]).starts_with("Transferred 0.000000000000001000 ETH"));
+        assert_eq!(text(&outputs[2]), "0.000000000000001000 ETH");
+        assert!(text(&outputs[3]).starts_with("Deployed at 0x"));
+        assert_eq!(outputs[4], ReplOutcome::Exit);
+
+        // A bad line is an error for that line only; the session keeps working
+        assert!(session.handle_line("transfer nowhere 1").await.is_err());
+        assert!(matches!(
+            session.handle_line("help").await?,
+            ReplOutcome::Output(_)
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,771 @@
+//! Machine-readable description of a compiled project's contracts.
+
+use crate::calldata::function_selector;
+use ethers::abi::{Abi, Param};
+use ethers_solc::artifacts::{DevDoc, UserDoc, UserDocNotice};
+use ethers_solc::{ConfigurableArtifacts, ProjectCompileOutput};
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::{self, Write as _};
+use std::path::Path;
+
+/// Every contract of a compiled project, sorted by source path and name.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProjectReport {
+    pub contracts: Vec<ContractReport>,
+}
+
+/// ABI summary of one compiled contract.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContractReport {
+    pub name: String,
+    /// Source file, relative to the working directory when possible.
+    pub source: String,
+    /// Constructor inputs, `None` when the contract has no explicit constructor.
+    pub constructor: Option<Vec<ParamReport>>,
+    pub functions: Vec<FunctionReport>,
+    pub events: Vec<EventReport>,
+    pub errors: Vec<ErrorReport>,
+    /// Whether the artifact carries (linked) creation bytecode, i.e. it can be deployed.
+    pub has_bytecode: bool,
+    /// NatSpec title, notice and details of the contract.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+    /// NatSpec notice and details of the constructor.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub constructor_doc: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParamReport {
+    pub name: String,
+    /// Canonical Solidity type, e.g. `uint256` or `(address,bool)`.
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FunctionReport {
+    pub name: String,
+    /// Canonical signature, e.g. `transfer(address,uint256)`.
+    pub signature: String,
+    /// 4-byte selector as lowercase hex without `0x`, e.g. `a9059cbb`.
+    pub selector: String,
+    pub inputs: Vec<ParamReport>,
+    pub outputs: Vec<ParamReport>,
+    pub state_mutability: String,
+    /// NatSpec notice and details.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventReport {
+    pub name: String,
+    pub signature: String,
+    /// `topic0` as lowercase hex without `0x`.
+    pub topic: String,
+    pub inputs: Vec<EventParamReport>,
+    pub anonymous: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EventParamReport {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub indexed: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ErrorReport {
+    pub name: String,
+    pub signature: String,
+    pub selector: String,
+    pub inputs: Vec<ParamReport>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub doc: Option<String>,
+}
+
+/// Functions of different signatures that share a 4-byte selector, so a call to one can reach
+/// the other, e.g. through a proxy whose own function shadows the implementation's.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct SelectorCollision {
+    /// Lowercase hex without `0x`.
+    pub selector: String,
+    /// Every function with the selector, sorted by contract and signature.
+    pub functions: Vec<CollidingFunction>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CollidingFunction {
+    pub contract: String,
+    pub signature: String,
+}
+
+impl fmt::Display for SelectorCollision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let functions: Vec<String> = self
+            .functions
+            .iter()
+            .map(|function| format!("{}.{}", function.contract, function.signature))
+            .collect();
+        write!(
+            f,
+            "0x{} is shared by {}",
+            self.selector,
+            functions.join(", ")
+        )
+    }
+}
+
+/// Builds the [`ProjectReport`] of a compiled project.
+pub fn project_report(project: &ProjectCompileOutput<ConfigurableArtifacts>) -> ProjectReport {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    let mut contracts: Vec<ContractReport> = project
+        .artifact_ids()
+        .map(|(id, artifact)| {
+            let source = id.source.strip_prefix(&cwd).unwrap_or(&id.source);
+            let has_bytecode = artifact
+                .bytecode
+                .as_ref()
+                .is_some_and(|bytecode| bytecode.object.is_non_empty_bytecode());
+            let mut report = match &artifact.abi {
+                Some(abi) => contract_report(&id.name, source, &abi.abi, has_bytecode),
+                None => ContractReport {
+                    name: id.name.clone(),
+                    source: source.display().to_string(),
+                    has_bytecode,
+                    ..Default::default()
+                },
+            };
+            apply_natspec(
+                &mut report,
+                artifact.userdoc.as_ref(),
+                artifact.devdoc.as_ref(),
+            );
+            report
+        })
+        .collect();
+    contracts.sort_by(|a, b| (&a.source, &a.name).cmp(&(&b.source, &b.name)));
+    ProjectReport { contracts }
+}
+
+/// Describes a single contract ABI.
+pub fn contract_report(name: &str, source: &Path, abi: &Abi, has_bytecode: bool) -> ContractReport {
+    let constructor = abi
+        .constructor()
+        .map(|constructor| params(&constructor.inputs));
+
+    let functions = abi
+        .functions()
+        .map(|function| FunctionReport {
+            name: function.name.clone(),
+            signature: signature(&function.name, &function.inputs),
+            selector: hex::encode(function.short_signature()),
+            inputs: params(&function.inputs),
+            outputs: params(&function.outputs),
+            state_mutability: format!("{:?}", function.state_mutability).to_lowercase(),
+            doc: None,
+        })
+        .collect();
+
+    let events = abi
+        .events()
+        .map(|event| EventReport {
+            name: event.name.clone(),
+            signature: format!(
+                "{}({})",
+                event.name,
+                event
+                    .inputs
+                    .iter()
+                    .map(|input| input.kind.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            topic: hex::encode(event.signature()),
+            inputs: event
+                .inputs
+                .iter()
+                .map(|input| EventParamReport {
+                    name: input.name.clone(),
+                    kind: input.kind.to_string(),
+                    indexed: input.indexed,
+                })
+                .collect(),
+            anonymous: event.anonymous,
+            doc: None,
+        })
+        .collect();
+
+    let errors = abi
+        .errors()
+        .map(|error| ErrorReport {
+            name: error.name.clone(),
+            signature: signature(&error.name, &error.inputs),
+            selector: hex::encode(&error.signature()[..4]),
+            inputs: params(&error.inputs),
+            doc: None,
+        })
+        .collect();
+
+    ContractReport {
+        name: name.to_string(),
+        source: source.display().to_string(),
+        constructor,
+        functions,
+        events,
+        errors,
+        has_bytecode,
+        doc: None,
+        constructor_doc: None,
+    }
+}
+
+/// The 4-byte selector of a canonical signature such as `transfer(address,uint256)`, as the
+/// report lists it (see [`function_selector`]).
+pub fn selector_of(signature: &str) -> [u8; 4] {
+    function_selector(signature)
+}
+
+/// Finds the selectors shared by functions of different signatures among all the contracts of
+/// `report`, sorted by selector. Pass a proxy and its implementation together to check the pair.
+pub fn detect_selector_collisions(report: &ProjectReport) -> Vec<SelectorCollision> {
+    let mut by_selector: BTreeMap<[u8; 4], Vec<CollidingFunction>> = BTreeMap::new();
+    for contract in &report.contracts {
+        for function in &contract.functions {
+            by_selector
+                .entry(selector_of(&function.signature))
+                .or_default()
+                .push(CollidingFunction {
+                    contract: contract.name.clone(),
+                    signature: function.signature.clone(),
+                });
+        }
+    }
+
+    by_selector
+        .into_iter()
+        .filter_map(|(selector, mut functions)| {
+            // The same function declared by several contracts (an interface and its
+            // implementation) is no collision
+            let first = &functions[0].signature;
+            if functions
+                .iter()
+                .all(|function| &function.signature == first)
+            {
+                return None;
+            }
+            functions.sort_by(|a, b| (&a.contract, &a.signature).cmp(&(&b.contract, &b.signature)));
+            functions.dedup();
+            Some(SelectorCollision {
+                selector: hex::encode(selector),
+                functions,
+            })
+        })
+        .collect()
+}
+
+/// Merges the NatSpec comments of a contract into its report, matching entries by signature.
+pub fn apply_natspec(
+    report: &mut ContractReport,
+    userdoc: Option<&UserDoc>,
+    devdoc: Option<&DevDoc>,
+) {
+    let user_method = |key: &str| userdoc.and_then(|doc| doc.methods.get(key)).map(notice);
+    let dev_method = |key: &str| {
+        devdoc
+            .and_then(|doc| doc.methods.get(key))
+            .and_then(|method| method.details.as_deref())
+    };
+
+    report.doc = join_doc(&[
+        devdoc.and_then(|doc| doc.title.as_deref()),
+        userdoc.and_then(|doc| doc.notice.as_deref()),
+        devdoc.and_then(|doc| doc.details.as_deref()),
+    ]);
+    report.constructor_doc = join_doc(&[user_method("constructor"), dev_method("constructor")]);
+    for function in &mut report.functions {
+        function.doc = join_doc(&[
+            user_method(&function.signature),
+            dev_method(&function.signature),
+        ]);
+    }
+    for event in &mut report.events {
+        event.doc = join_doc(&[
+            userdoc
+                .and_then(|doc| doc.events.get(&event.signature))
+                .map(notice),
+            devdoc
+                .and_then(|doc| doc.events.get(&event.signature))
+                .and_then(|event| event.details.as_deref()),
+        ]);
+    }
+    for error in &mut report.errors {
+        error.doc = join_doc(&[
+            userdoc
+                .and_then(|doc| doc.errors.get(&error.signature))
+                .and_then(|notices| notices.first())
+                .map(notice),
+            devdoc
+                .and_then(|doc| doc.errors.get(&error.signature))
+                .and_then(|docs| docs.first())
+                .and_then(|error| error.details.as_deref()),
+        ]);
+    }
+}
+
+/// Renders the report as Markdown: per contract, its constructor, functions, events and
+/// errors with their NatSpec. Contracts and entries are sorted so the output can be
+/// committed and diffed.
+pub fn abi_to_markdown(report: &ProjectReport) -> String {
+    let mut contracts: Vec<&ContractReport> = report.contracts.iter().collect();
+    contracts.sort_by(|a, b| (&a.source, &a.name).cmp(&(&b.source, &b.name)));
+
+    let mut out = String::from("# Contracts\n");
+    for contract in contracts {
+        let _ = write!(
+            out,
+            "\n## {}\n\nSource: `{}`\n",
+            contract.name, contract.source
+        );
+        if let Some(doc) = &contract.doc {
+            let _ = write!(out, "\n{doc}\n");
+        }
+
+        if let Some(inputs) = &contract.constructor {
+            out.push_str("\n### Constructor\n\n");
+            if inputs.is_empty() {
+                out.push_str("No parameters.\n");
+            } else {
+                out.push_str("| Parameter | Type |\n| --- | --- |\n");
+                for input in inputs {
+                    let _ = writeln!(out, "| {} | `{}` |", cell(&input.name), input.kind);
+                }
+            }
+            if let Some(doc) = &contract.constructor_doc {
+                let _ = write!(out, "\n{doc}\n");
+            }
+        }
+
+        let mut functions: Vec<&FunctionReport> = contract.functions.iter().collect();
+        functions.sort_by(|a, b| a.signature.cmp(&b.signature));
+        if !functions.is_empty() {
+            out.push_str("\n### Functions\n\n");
+            out.push_str("| Function | Inputs | Outputs | Mutability | Selector |\n");
+            out.push_str("| --- | --- | --- | --- | --- |\n");
+            for function in functions {
+                let _ = writeln!(
+                    out,
+                    "| `{}` | {} | {} | {} | `0x{}` |",
+                    function.name,
+                    param_list(&function.inputs),
+                    param_list(&function.outputs),
+                    function.state_mutability,
+                    function.selector
+                );
+                if let Some(doc) = &function.doc {
+                    let _ = writeln!(out, "| {} | | | | |", cell(doc));
+                }
+            }
+        }
+
+        let mut events: Vec<&EventReport> = contract.events.iter().collect();
+        events.sort_by(|a, b| a.signature.cmp(&b.signature));
+        if !events.is_empty() {
+            out.push_str("\n### Events\n\n| Event | Parameters | Topic |\n| --- | --- | --- |\n");
+            for event in events {
+                let inputs: Vec<String> = event
+                    .inputs
+                    .iter()
+                    .map(|input| {
+                        let indexed = if input.indexed { " indexed" } else { "" };
+                        param(&format!("{}{indexed}", input.kind), &input.name)
+                    })
+                    .collect();
+                let anonymous = if event.anonymous { " (anonymous)" } else { "" };
+                let _ = writeln!(
+                    out,
+                    "| `{}`{anonymous} | {} | `0x{}` |",
+                    event.name,
+                    inputs.join(", "),
+                    event.topic
+                );
+                if let Some(doc) = &event.doc {
+                    let _ = writeln!(out, "| {} | | |", cell(doc));
+                }
+            }
+        }
+
+        let mut errors: Vec<&ErrorReport> = contract.errors.iter().collect();
+        errors.sort_by(|a, b| a.signature.cmp(&b.signature));
+        if !errors.is_empty() {
+            out.push_str("\n### Errors\n\n| Error | Inputs | Selector |\n| --- | --- | --- |\n");
+            for error in errors {
+                let _ = writeln!(
+                    out,
+                    "| `{}` | {} | `0x{}` |",
+                    error.name,
+                    param_list(&error.inputs),
+                    error.selector
+                );
+                if let Some(doc) = &error.doc {
+                    let _ = writeln!(out, "| {} | | |", cell(doc));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Writes the report as pretty-printed JSON.
+pub fn write_report_json(report: &ProjectReport, path: impl AsRef<Path>) -> Result<()> {
+    let json = serde_json::to_string_pretty(report)?;
+    std::fs::write(path, json + "\n")?;
+    Ok(())
+}
+
+fn params(params: &[Param]) -> Vec<ParamReport> {
+    params
+        .iter()
+        .map(|param| ParamReport {
+            name: param.name.clone(),
+            kind: param.kind.to_string(),
+        })
+        .collect()
+}
+
+fn notice(notice: &UserDocNotice) -> &str {
+    match notice {
+        UserDocNotice::Constructor(notice) | UserDocNotice::Notice { notice } => notice,
+    }
+}
+
+/// Joins the non-empty comments on one line, `None` when there are none.
+fn join_doc(parts: &[Option<&str>]) -> Option<String> {
+    let words: Vec<&str> = parts
+        .iter()
+        .flatten()
+        .flat_map(|part| part.split_whitespace())
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
+/// Escapes text for a Markdown table cell.
+fn cell(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+fn param(kind: &str, name: &str) -> String {
+    if name.is_empty() {
+        format!("`{kind}`")
+    } else {
+        format!("`{kind} {}`", cell(name))
+    }
+}
+
+fn param_list(params: &[ParamReport]) -> String {
+    params
+        .iter()
+        .map(|p| param(&p.kind, &p.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn signature(name: &str, inputs: &[Param]) -> String {
+    let types: Vec<String> = inputs.iter().map(|input| input.kind.to_string()).collect();
+    format!("{name}({})", types.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::compile;
+    use std::path::PathBuf;
+
+    /// Compares `actual` with the checked-in snapshot. A missing snapshot fails the test;
+    /// run with `UPDATE_SNAPSHOTS` set to (re)write it.
+    fn assert_snapshot(name: &str, actual: &str) {
+        let path = PathBuf::from("tests/snapshots").join(name);
+        if std::env::var_os("UPDATE_SNAPSHOTS").is_some() {
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, actual).unwrap();
+        }
+        let expected = std::fs::read_to_string(&path).unwrap_or_else(|_| {
+            panic!("Snapshot {name} is missing; run with UPDATE_SNAPSHOTS=1 to write it")
+        });
+        assert_eq!(actual, expected, "Snapshot {name} changed");
+    }
+
+    /// The report as snapshot JSON, with the ABI entries sorted by signature: solc doesn't
+    /// promise an order, least of all between overloads, so a compiler patch release could
+    /// reshuffle them. The report carries no compiler version or metadata hash to mask.
+    fn snapshot_json(report: &ProjectReport) -> Result<String> {
+        let mut report = report.clone();
+        for contract in &mut report.contracts {
+            contract
+                .functions
+                .sort_by(|a, b| a.signature.cmp(&b.signature));
+            contract
+                .events
+                .sort_by(|a, b| a.signature.cmp(&b.signature));
+            contract
+                .errors
+                .sort_by(|a, b| a.signature.cmp(&b.signature));
+        }
+        Ok(serde_json::to_string_pretty(&report)? + "\n")
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs solc")]
+    async fn test_fixture_report_snapshot() -> Result<()> {
+        let project = compile("tests/fixtures/contracts/").await?.output;
+        let report = project_report(&project);
+
+        let names: Vec<_> = report.contracts.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Auction", "IAuction"]);
+        assert_snapshot("fixture_report.json", &snapshot_json(&report)?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs solc")]
+    async fn test_busd_report_json() -> Result<()> {
+        let project = compile("examples/").await?.output;
+        let report = project_report(&project);
+
+        let busd = report
+            .contracts
+            .iter()
+            .find(|contract| contract.name == "BUSDImplementation")
+            .expect("BUSDImplementation should be reported");
+        assert!(busd.has_bytecode);
+        assert!(busd
+            .functions
+            .iter()
+            .any(|f| f.signature == "transfer(address,uint256)" && f.selector == "a9059cbb"));
+        assert!(busd
+            .events
+            .iter()
+            .any(|e| e.name == "Transfer" && e.inputs.iter().filter(|i| i.indexed).count() == 2));
+
+        let json = snapshot_json(&report)?;
+        assert!(json.contains("a9059cbb"));
+        assert_snapshot("busd_report.json", &json);
+
+        // The written file parses back into the same report
+        let path = std::env::temp_dir().join("busd_report_roundtrip.json");
+        write_report_json(&report, &path)?;
+        let parsed: ProjectReport = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        assert_eq!(parsed, report);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_contract_report_from_abi() -> Result<()> {
+        let abi: Abi = serde_json::from_str(
+            r#"[
+                {"type":"function","name":"transfer","stateMutability":"nonpayable",
+                 "inputs":[{"name":"to","type":"address"},{"name":"value","type":"uint256"}],
+                 "outputs":[{"name":"","type":"bool"}]},
+                {"type":"event","name":"Transfer","anonymous":false,
+                 "inputs":[{"name":"from","type":"address","indexed":true},
+                           {"name":"to","type":"address","indexed":true},
+                           {"name":"value","type":"uint256","indexed":false}]},
+                {"type":"error","name":"Unauthorized","inputs":[{"name":"caller","type":"address"}]}
+            ]"#,
+        )?;
+        let report = contract_report("Token", Path::new("Token.sol"), &abi, true);
+
+        assert_eq!(report.constructor, None);
+        assert_eq!(report.functions[0].selector, "a9059cbb");
+        assert_eq!(report.functions[0].state_mutability, "nonpayable");
+        assert_eq!(
+            report.events[0].topic,
+            "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+        );
+        assert_eq!(report.errors[0].signature, "Unauthorized(address)");
+        assert_eq!(report.errors[0].selector, "8e4a23d6");
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs solc")]
+    async fn test_busd_markdown_snapshot() -> Result<()> {
+        let project = compile("examples/").await?.output;
+        let mut report = project_report(&project);
+        report
+            .contracts
+            .retain(|contract| contract.name == "BUSDImplementation");
+
+        let markdown = abi_to_markdown(&report);
+        assert!(markdown.contains("| `transfer` | `address _to`, `uint256 _value` | `bool` |"));
+        assert_snapshot("busd_docs.md", &markdown);
+
+        // Compiling again renders the same bytes
+        let again = project_report(&compile("examples/").await?.output);
+        let again = ProjectReport {
+            contracts: again
+                .contracts
+                .into_iter()
+                .filter(|contract| contract.name == "BUSDImplementation")
+                .collect(),
+        };
+        assert_eq!(abi_to_markdown(&again), markdown);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_markdown_merges_natspec_and_sorts() -> Result<()> {
+        let abi: Abi = serde_json::from_str(
+            r#"[
+                {"type":"constructor","stateMutability":"nonpayable",
+                 "inputs":[{"name":"supply","type":"uint256"}]},
+                {"type":"function","name":"transfer","stateMutability":"nonpayable",
+                 "inputs":[{"name":"to","type":"address"},{"name":"value","type":"uint256"}],
+                 "outputs":[{"name":"","type":"bool"}]},
+                {"type":"function","name":"balanceOf","stateMutability":"view",
+                 "inputs":[{"name":"owner","type":"address"}],
+                 "outputs":[{"name":"","type":"uint256"}]},
+                {"type":"event","name":"Transfer","anonymous":false,
+                 "inputs":[{"name":"from","type":"address","indexed":true},
+                           {"name":"value","type":"uint256","indexed":false}]},
+                {"type":"error","name":"Unauthorized","inputs":[]}
+            ]"#,
+        )?;
+        let userdoc: UserDoc = serde_json::from_str(
+            r#"{"notice":"A token","methods":{
+                "transfer(address,uint256)":{"notice":"Moves tokens"},
+                "constructor":{"notice":"Mints the supply"}}}"#,
+        )?;
+        let devdoc: DevDoc = serde_json::from_str(
+            r#"{"title":"Token","methods":{
+                "transfer(address,uint256)":{"details":"Reverts | when\n   paused"}},
+                "errors":{"Unauthorized()":[{"details":"Caller is not the owner"}]}}"#,
+        )?;
+        let mut contract = contract_report("Token", Path::new("Token.sol"), &abi, true);
+        apply_natspec(&mut contract, Some(&userdoc), Some(&devdoc));
+        assert_eq!(contract.doc.as_deref(), Some("Token A token"));
+
+        let markdown = abi_to_markdown(&ProjectReport {
+            contracts: vec![contract],
+        });
+        let expected = "# Contracts
+
+## Token
+
+Source: `Token.sol`
+
+Token A token
+
+### Constructor
+
+| Parameter | Type |
+| --- | --- |
+| supply | `uint256` |
+
+Mints the supply
+
+### Functions
+
+| Function | Inputs | Outputs | Mutability | Selector |
+| --- | --- | --- | --- | --- |
+| `balanceOf` | `address owner` | `uint256` | view | `0x70a08231` |
+| `transfer` | `address to`, `uint256 value` | `bool` | nonpayable | `0xa9059cbb` |
+| Moves tokens Reverts \\| when paused | | | | |
+
+### Events
+
+| Event | Parameters | Topic |
+| --- | --- | --- |
+| `Transfer` | `address indexed from`, `uint256 value` | `0x69ca02dd4edd7bf0a4abb9ed3b7af3f14778db5d61921c7dc7cd545266326de2` |
+
+### Errors
+
+| Error | Inputs | Selector |
+| --- | --- | --- |
+| `Unauthorized` |  | `0x82b42900` |
+| Caller is not the owner | | |
+";
+        assert_eq!(markdown, expected);
+
+        // Without NatSpec nothing is added to the JSON report
+        let plain = contract_report("Token", Path::new("Token.sol"), &abi, true);
+        assert!(!serde_json::to_string(&plain)?.contains("doc"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_detects_selector_collision_across_contracts() -> Result<()> {
+        assert_eq!(
+            selector_of("transfer(address,uint256)"),
+            [0xa9, 0x05, 0x9c, 0xbb]
+        );
+        // A documented collision: both hash to 0x42966c68
+        assert_eq!(
+            selector_of("burn(uint256)"),
+            selector_of("collate_propagate_storage(bytes16)")
+        );
+
+        let token: Abi = serde_json::from_str(
+            r#"[{"type":"function","name":"burn","stateMutability":"nonpayable",
+                 "inputs":[{"name":"amount","type":"uint256"}],"outputs":[]},
+                {"type":"function","name":"transfer","stateMutability":"nonpayable",
+                 "inputs":[{"name":"to","type":"address"},{"name":"value","type":"uint256"}],
+                 "outputs":[{"name":"","type":"bool"}]}]"#,
+        )?;
+        let proxy: Abi = serde_json::from_str(
+            r#"[{"type":"function","name":"collate_propagate_storage","stateMutability":"nonpayable",
+                 "inputs":[{"name":"","type":"bytes16"}],"outputs":[]},
+                {"type":"function","name":"transfer","stateMutability":"nonpayable",
+                 "inputs":[{"name":"to","type":"address"},{"name":"value","type":"uint256"}],
+                 "outputs":[{"name":"","type":"bool"}]}]"#,
+        )?;
+        let token = contract_report("Token", Path::new("Token.sol"), &token, true);
+        let proxy = contract_report("TokenProxy", Path::new("TokenProxy.sol"), &proxy, true);
+
+        // Alone, neither contract collides; the shared transfer is the same function
+        let report = ProjectReport {
+            contracts: vec![token.clone()],
+        };
+        assert!(detect_selector_collisions(&report).is_empty());
+
+        let report = ProjectReport {
+            contracts: vec![proxy, token],
+        };
+        let collisions = detect_selector_collisions(&report);
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(
+            collisions[0].to_string(),
+            "0x42966c68 is shared by Token.burn(uint256), TokenProxy.collate_propagate_storage(bytes16)"
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs solc")]
+    async fn test_fixture_selector_collision() -> Result<()> {
+        let report = project_report(&compile("tests/fixtures/collisions/").await?.output);
+
+        let collisions = detect_selector_collisions(&report);
+        assert_eq!(collisions.len(), 1);
+        assert_eq!(collisions[0].selector, "42966c68");
+        let signatures: Vec<_> = collisions[0]
+            .functions
+            .iter()
+            .map(|function| function.signature.as_str())
+            .collect();
+        assert_eq!(
+            signatures,
+            ["burn(uint256)", "collate_propagate_storage(bytes16)"]
+        );
+        Ok(())
+    }
+}
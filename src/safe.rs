@@ -0,0 +1,391 @@
+//! Safe transaction payloads, for deployers that are a Safe multisig rather than an EOA.
+//!
+//! Instead of sending, the binaries can write the transaction the Safe's owners must sign, in
+//! the Safe Transaction Service's proposal format, with the EIP-712 `safeTxHash` they sign.
+
+use crate::create2::compute_create2_address;
+use crate::errors::Error;
+use clap::Args;
+use ethers::{
+    abi::{encode, Token},
+    prelude::{
+        Address, Bytes, JsonRpcClient, Middleware, Provider, TransactionRequest, H256, U256,
+    },
+    utils::{keccak256, to_checksum},
+};
+use eyre::Result;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// The deterministic deployment proxy, deployed at the same address on most chains; it
+/// CREATE2-deploys the init code following a 32-byte salt in its calldata.
+pub const DETERMINISTIC_DEPLOYER: &str = "0x4e59b44847b379578588920cA78FbF26c0B4956C";
+
+const DOMAIN_TYPE: &str = "EIP712Domain(uint256 chainId,address verifyingContract)";
+const SAFE_TX_TYPE: &str = "SafeTx(address to,uint256 value,bytes data,uint8 operation,uint256 safeTxGas,uint256 baseGas,uint256 gasPrice,address gasToken,address refundReceiver,uint256 nonce)";
+
+/// How the Safe executes the transaction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SafeOperation {
+    #[default]
+    Call = 0,
+    DelegateCall = 1,
+}
+
+/// A Safe transaction. The gas and refund fields stay zero, so no relayer is paid and the
+/// executor supplies the gas.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SafeTx {
+    pub to: Address,
+    pub value: U256,
+    pub data: Bytes,
+    pub operation: SafeOperation,
+    pub safe_tx_gas: U256,
+    pub base_gas: U256,
+    pub gas_price: U256,
+    pub gas_token: Address,
+    pub refund_receiver: Address,
+    pub nonce: U256,
+}
+
+impl SafeTx {
+    /// A plain call of `to` with `value` Wei and `data`.
+    pub fn call(to: Address, value: U256, data: Bytes) -> Self {
+        Self {
+            to,
+            value,
+            data,
+            ..Default::default()
+        }
+    }
+
+    /// A deployment of `init_code` through [`DETERMINISTIC_DEPLOYER`], and the address it creates.
+    pub fn create(init_code: &Bytes, salt: [u8; 32]) -> Result<(Self, Address)> {
+        let deployer: Address = DETERMINISTIC_DEPLOYER.parse()?;
+        let data = [salt.as_slice(), init_code.as_ref()].concat();
+        let address = compute_create2_address(deployer, salt, init_code);
+        Ok((Self::call(deployer, U256::zero(), data.into()), address))
+    }
+
+    /// The EIP-712 struct hash of the transaction.
+    pub fn struct_hash(&self) -> H256 {
+        keccak256(encode(&[
+            Token::FixedBytes(keccak256(SAFE_TX_TYPE).to_vec()),
+            Token::Address(self.to),
+            Token::Uint(self.value),
+            Token::FixedBytes(keccak256(&self.data).to_vec()),
+            Token::Uint(U256::from(self.operation as u8)),
+            Token::Uint(self.safe_tx_gas),
+            Token::Uint(self.base_gas),
+            Token::Uint(self.gas_price),
+            Token::Address(self.gas_token),
+            Token::Address(self.refund_receiver),
+            Token::Uint(self.nonce),
+        ]))
+        .into()
+    }
+
+    /// The `safeTxHash` the owners of `safe` on `chain_id` sign.
+    pub fn safe_tx_hash(&self, chain_id: u64, safe: Address) -> H256 {
+        let message = [
+            [0x19, 0x01].as_slice(),
+            domain_separator(chain_id, safe).as_bytes(),
+            self.struct_hash().as_bytes(),
+        ]
+        .concat();
+        keccak256(message).into()
+    }
+
+    /// The payload to propose to the Safe Transaction Service for `safe` on `chain_id`.
+    pub fn export(&self, chain_id: u64, safe: Address) -> SafeTxExport {
+        SafeTxExport {
+            safe: to_checksum(&safe, None),
+            chain_id,
+            to: to_checksum(&self.to, None),
+            value: self.value.to_string(),
+            data: (!self.data.is_empty()).then(|| self.data.to_string()),
+            operation: self.operation as u8,
+            safe_tx_gas: self.safe_tx_gas.to_string(),
+            base_gas: self.base_gas.to_string(),
+            gas_price: self.gas_price.to_string(),
+            gas_token: to_checksum(&self.gas_token, None),
+            refund_receiver: to_checksum(&self.refund_receiver, None),
+            nonce: self.nonce.to_string(),
+            contract_transaction_hash: format!("{:?}", self.safe_tx_hash(chain_id, safe)),
+            deployed_address: None,
+        }
+    }
+}
+
+/// The EIP-712 domain separator of `safe` on `chain_id` (Safe 1.3.0 and later).
+pub fn domain_separator(chain_id: u64, safe: Address) -> H256 {
+    keccak256(encode(&[
+        Token::FixedBytes(keccak256(DOMAIN_TYPE).to_vec()),
+        Token::Uint(U256::from(chain_id)),
+        Token::Address(safe),
+    ]))
+    .into()
+}
+
+/// A Safe transaction in the Transaction Service's proposal format, with the Safe and chain it
+/// is for so signers can recompute `contractTransactionHash`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeTxExport {
+    pub safe: String,
+    pub chain_id: u64,
+    pub to: String,
+    pub value: String,
+    pub data: Option<String>,
+    pub operation: u8,
+    pub safe_tx_gas: String,
+    pub base_gas: String,
+    pub gas_price: String,
+    pub gas_token: String,
+    pub refund_receiver: String,
+    pub nonce: String,
+    pub contract_transaction_hash: String,
+    /// Address the contract will have once a deployment is executed.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deployed_address: Option<String>,
+}
+
+/// Command line flags writing a Safe transaction instead of sending.
+#[derive(Args, Clone, Debug, Default)]
+pub struct SafeTxArgs {
+    /// Write the transaction for this Safe to sign instead of sending it
+    #[arg(long, value_name = "SAFE_ADDRESS")]
+    pub as_safe_tx: Option<Address>,
+    /// Safe nonce to use (read from the Safe on the chain by default)
+    #[arg(long, requires = "as_safe_tx")]
+    pub safe_nonce: Option<u64>,
+    /// File the Safe transaction is written to
+    #[arg(long, default_value = "safe-tx.json")]
+    pub safe_tx_out: PathBuf,
+}
+
+impl SafeTxArgs {
+    /// Completes `tx` with the Safe's nonce and the provider's chain id, then writes it to
+    /// `--safe-tx-out`, along with the address a deployment will create.
+    pub async fn write<T: JsonRpcClient>(
+        &self,
+        provider: &Provider<T>,
+        safe: Address,
+        mut tx: SafeTx,
+        deployed_address: Option<Address>,
+    ) -> Result<SafeTxExport> {
+        tx.nonce = match self.safe_nonce {
+            Some(nonce) => nonce.into(),
+            None => safe_nonce(provider, safe).await?,
+        };
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let export = SafeTxExport {
+            deployed_address: deployed_address.map(|address| to_checksum(&address, None)),
+            ..tx.export(chain_id, safe)
+        };
+        write_safe_tx(&self.safe_tx_out, &export)?;
+        Ok(export)
+    }
+}
+
+/// Reads the nonce the Safe's next transaction must use.
+pub async fn safe_nonce<T: JsonRpcClient>(provider: &Provider<T>, safe: Address) -> Result<U256> {
+    if provider.get_code(safe, None).await?.is_empty() {
+        let chain_id = provider.get_chainid().await?.as_u64();
+        return Err(Error::SafeNotDeployed { safe, chain_id }.into());
+    }
+    let call = TransactionRequest::new()
+        .to(safe)
+        .data(keccak256("nonce()")[..4].to_vec());
+    let output = provider.call(&call.into(), None).await?;
+    Ok(U256::from_big_endian(&output))
+}
+
+/// Writes `export` as pretty-printed JSON.
+pub fn write_safe_tx(path: &Path, export: &SafeTxExport) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(export)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::spawn_mock_node;
+    use ethers::types::transaction::eip712::{Eip712, TypedData};
+    use serde_json::{json, Value};
+
+    fn safe() -> Address {
+        "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_type_hashes_match_the_safe_contracts() {
+        // DOMAIN_SEPARATOR_TYPEHASH and SAFE_TX_TYPEHASH from SafeL2.sol / Safe.sol 1.3.0+
+        assert_eq!(
+            hex::encode(keccak256(DOMAIN_TYPE)),
+            "47e79534a245952e8b16893a336b85a3d9ea9fa8c573f3d803afb92a79469218"
+        );
+        assert_eq!(
+            hex::encode(keccak256(SAFE_TX_TYPE)),
+            "bb8310d486368db6bd6f849402fdd73ad53d316b5a4b2644ad6efe0f941286d8"
+        );
+    }
+
+    #[test]
+    fn test_safe_tx_hash_matches_eip712_typed_data() -> Result<()> {
+        let tx = SafeTx {
+            nonce: 7.into(),
+            ..SafeTx::call(
+                "0xb794F5eA0ba39494cE839613fffBA74279579268".parse()?,
+                U256::exp10(15),
+                Bytes::from(vec![0xa9, 0x05, 0x9c, 0xbb]),
+            )
+        };
+        // The same transaction as a generic eth_signTypedData_v4 document
+        let typed: TypedData = serde_json::from_value(json!({
+            "types": {
+                "EIP712Domain": [
+                    {"name": "chainId", "type": "uint256"},
+                    {"name": "verifyingContract", "type": "address"}
+                ],
+                "SafeTx": [
+                    {"name": "to", "type": "address"},
+                    {"name": "value", "type": "uint256"},
+                    {"name": "data", "type": "bytes"},
+                    {"name": "operation", "type": "uint8"},
+                    {"name": "safeTxGas", "type": "uint256"},
+                    {"name": "baseGas", "type": "uint256"},
+                    {"name": "gasPrice", "type": "uint256"},
+                    {"name": "gasToken", "type": "address"},
+                    {"name": "refundReceiver", "type": "address"},
+                    {"name": "nonce", "type": "uint256"}
+                ]
+            },
+            "primaryType": "SafeTx",
+            "domain": {"chainId": 5, "verifyingContract": format!("{:?}", safe())},
+            "message": {
+                "to": "0xb794f5ea0ba39494ce839613fffba74279579268",
+                "value": "1000000000000000",
+                "data": "0xa9059cbb",
+                "operation": 0,
+                "safeTxGas": 0,
+                "baseGas": 0,
+                "gasPrice": 0,
+                "gasToken": format!("{:?}", Address::zero()),
+                "refundReceiver": format!("{:?}", Address::zero()),
+                "nonce": 7
+            }
+        }))?;
+        assert_eq!(
+            tx.safe_tx_hash(5, safe()),
+            H256(typed.encode_eip712()?),
+            "safeTxHash must equal the generic EIP-712 hash"
+        );
+        assert_ne!(tx.safe_tx_hash(1, safe()), tx.safe_tx_hash(5, safe()));
+        assert_ne!(
+            tx.safe_tx_hash(5, safe()),
+            tx.safe_tx_hash(5, Address::repeat_byte(1))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_export_structure() -> Result<()> {
+        let init_code = Bytes::from(vec![0x60, 0x80, 0x60, 0x40]);
+        let (tx, address) = SafeTx::create(&init_code, [0; 32])?;
+        assert_eq!(tx.to, DETERMINISTIC_DEPLOYER.parse()?);
+        assert_eq!(tx.data.len(), 32 + init_code.len());
+
+        let export = tx.export(1337, safe());
+        let value: Value = serde_json::to_value(&export)?;
+        let fields: Vec<&str> = value
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect();
+        for field in [
+            "safe",
+            "chainId",
+            "to",
+            "value",
+            "data",
+            "operation",
+            "safeTxGas",
+            "baseGas",
+            "gasPrice",
+            "gasToken",
+            "refundReceiver",
+            "nonce",
+            "contractTransactionHash",
+        ] {
+            assert!(fields.contains(&field), "missing {field}");
+        }
+        assert!(!fields.contains(&"deployedAddress"));
+        assert_eq!(value["to"], DETERMINISTIC_DEPLOYER);
+        assert_eq!(value["operation"], 0);
+        assert_eq!(value["safeTxGas"], "0");
+        assert_eq!(
+            value["data"],
+            format!("0x{}{}", "00".repeat(32), "60806040")
+        );
+        assert_eq!(
+            value["contractTransactionHash"],
+            format!("{:?}", tx.safe_tx_hash(1337, safe()))
+        );
+        assert_eq!(
+            address,
+            compute_create2_address(DETERMINISTIC_DEPLOYER.parse()?, [0; 32], &init_code)
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_nonce_is_read_from_the_safe() -> Result<()> {
+        let url = spawn_mock_node(|method, _| match method {
+            "eth_chainId" => json!("0x539"),
+            "eth_getCode" => json!("0x6080"),
+            "eth_call" => json!(format!("0x{:064x}", 3)),
+            _ => json!(null),
+        })
+        .await?;
+        let provider = Provider::try_from(url)?;
+        let path = std::env::temp_dir().join(format!("safe-tx-{}.json", std::process::id()));
+        let args = SafeTxArgs {
+            as_safe_tx: Some(safe()),
+            safe_nonce: None,
+            safe_tx_out: path.clone(),
+        };
+        let export = args
+            .write(
+                &provider,
+                safe(),
+                SafeTx::call(safe(), 1.into(), Bytes::new()),
+                None,
+            )
+            .await?;
+        assert_eq!(export.nonce, "3");
+        assert_eq!(export.chain_id, 1337);
+        assert_eq!(export.data, None);
+        let written: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+        assert_eq!(written, serde_json::to_value(&export)?);
+        std::fs::remove_file(path)?;
+
+        let empty = spawn_mock_node(|method, _| match method {
+            "eth_chainId" => json!("0x539"),
+            "eth_getCode" => json!("0x"),
+            _ => json!(null),
+        })
+        .await?;
+        let err = safe_nonce(&Provider::try_from(empty)?, safe())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::SafeNotDeployed { chain_id: 1337, .. })
+        ));
+        Ok(())
+    }
+}
@@ -1,83 +1,232 @@
-use std::time::Duration;
+use std::path::PathBuf;
 
-use ethers::{
-    prelude::{Address, LocalWallet, Middleware, Provider, Signer, TransactionRequest, U256},
-    utils::Ganache,
-};
+use clap::Parser;
+use ethers::prelude::{Bytes, Middleware, Provider, Signer, U256};
 use eyre::{ContextCompat, Result};
-use hex::ToHex;
+use rust_eth_yt::{
+    accounts::{account_infos, accounts_table},
+    chain_context::ChainContext,
+    config::ConnectionArgs,
+    dry_run::dry_run_transfer,
+    ens::Recipient,
+    errors::Error,
+    export::export_csv,
+    history::summarize_tx,
+    logging::{init_tracing, LogFormat},
+    mnemonic::validate_mnemonic,
+    output::render,
+    payment_uri::{parse_amount, parse_payment_uri},
+    safe::{SafeTx, SafeTxArgs},
+    timeouts::OpTimeouts,
+    transfer::{send_signed_transfer, send_transfer, TransferOptions, TransferResult},
+    wallet::{bind_to_chain, select_account},
+};
+use tracing::info;
+
+/// Sends a small transfer between two accounts on a local Ganache chain.
+#[derive(Parser)]
+struct Cli {
+    /// Format of the log output (filter it with `RUST_LOG`)
+    #[arg(long, value_enum, default_value_t = LogFormat::Pretty)]
+    log_format: LogFormat,
+    /// Print the result as a single JSON object on stdout
+    #[arg(long, global = true)]
+    json: bool,
+    /// Estimate and simulate the transfer, printing what would be sent, without sending it
+    #[arg(long)]
+    dry_run: bool,
+    /// Refuse to send unless the node serves this chain id
+    #[arg(long)]
+    expected_chain: Option<u64>,
+    /// Rows of the account table printed at startup (every Ganache account by default)
+    #[arg(long)]
+    limit: Option<usize>,
+    /// Let the node sign with its unlocked account instead of signing locally
+    #[arg(long)]
+    unlocked: bool,
+    /// Recipient: a hex address (checksummed if mixed-case) or an ENS name such as `vitalik.eth`
+    /// [default: 0xb794F5eA0ba39494cE839613fffBA74279579268]
+    #[arg(long)]
+    to: Option<Recipient>,
+    /// Amount to send in Wei, exponent notation allowed (`1e15`) [default: 1000]
+    #[arg(long, value_parser = parse_amount)]
+    amount: Option<U256>,
+    /// Gas limit of the transfer, for a recipient contract that needs more than 21000 (estimated by default)
+    #[arg(long)]
+    gas_limit: Option<U256>,
+    /// EIP-681 payment URI (`ethereum:0x…@1337?value=1e15`), instead of --to and --amount
+    #[arg(long)]
+    uri: Option<String>,
+    /// Append the mined transfer to this CSV file (created with a header if missing)
+    #[arg(long)]
+    csv: Option<PathBuf>,
+    #[command(flatten)]
+    connection: ConnectionArgs,
+    #[command(flatten)]
+    safe: SafeTxArgs,
+}
 
 /// Main asynchronous function which sets up a local blockchain using Ganache,
 /// queries balances, and sends a transaction from one account to another.
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Define a mnemonic for a wallet (used to derive private keys)
-    let mnemonic = "brisk usual burst upper buddy female library dial rifle mercy globe nurse";
+    let cli = Cli::parse(); // Parse the command line arguments
+    init_tracing(cli.log_format); // Install the log subscriber before anything is reported
 
-    // Create and launch a Ganache instance (local Ethereum test blockchain) with the mnemonic
-    let ganache = Ganache::new().mnemonic(mnemonic).spawn();
-    println!("HTTP Endpoint: {}", ganache.endpoint()); // Print the HTTP endpoint for Ganache
-
-    // Create a local wallet from the first generated key from Ganache
-    let wallet: LocalWallet = ganache.keys()[0].clone().into();
-    let first_address = wallet.address(); // Extract the first address from the wallet
-    println!(
-        "wallet first address: {}",
-        first_address.encode_hex::<String>() // Encode the address to a hexadecimal string
-    );
+    // Load the config file, if any, with the command line flags taking precedence
+    let config = cli.connection.resolve()?;
 
-    // Connect to the Ganache provider using the Ganache endpoint, set polling interval to 10ms
-    let provider = Provider::try_from(ganache.endpoint())?.interval(Duration::from_millis(10));
+    // Define a mnemonic for a wallet (used to derive private keys), failing fast if it is invalid
+    let mnemonic =
+        validate_mnemonic(config.wallet.mnemonic.as_deref().unwrap_or(
+            "brisk usual burst upper buddy female library dial rifle mercy globe nurse",
+        ))?;
 
-    // Query and print the balance of the wallet's first address
-    let first_balance = provider.get_balance(first_address, None).await?;
-    println!("wallet first address balance: {}", first_balance); // Display the balance
+    // Create and launch a Ganache instance (local Ethereum test blockchain) with the mnemonic and configured options
+    let ganache = config.ganache.spawn(&mnemonic);
+    info!(endpoint = %ganache.endpoint(), "ganache started"); // Report the HTTP endpoint for Ganache
 
-    // Query the balance of a random Ethereum address (external to this wallet)
-    let other_address_hex = "0xB794F5eA0ba39494cE839613fffBA74279579268"; // Random address in hex format
-    let other_address = other_address_hex.parse::<Address>()?; // Parse the hex string into an Address type
-    let other_balance = provider.get_balance(other_address, None).await?; // Get the balance of the random address
-    println!(
-        "Balance for address {}: {}",
-        other_address_hex,
-        other_balance // Display the balance
-    );
+    // Create a local wallet from the configured Ganache account (the first by default), failing past the last one
+    let account_index = config.wallet.account_index.unwrap_or(0) as usize;
+    let wallet = select_account(ganache.keys(), account_index)?;
+    let first_address = wallet.address(); // Extract the first address from the wallet
+    info!(address = ?first_address, "wallet first address");
+
+    // Connect to the Ganache provider using the Ganache endpoint
+    let provider = Provider::try_from(ganache.endpoint())?;
+    let context = ChainContext::new(provider.clone()); // Caches the chain id, latest block and fees for the transfer helpers
+    let wallet = bind_to_chain(&context, wallet).await?; // Sign for the chain the provider serves, never another
+    info!(chain_id = wallet.chain_id(), "wallet bound to chain");
+    config.ganache.apply_mining(&provider).await?; // Stop the miner when auto-mining is disabled
+    let accounts = account_infos(&provider, ganache.keys(), cli.limit).await?;
+    eprintln!("{}", accounts_table(&accounts)); // Table of every account on stderr, so stdout keeps only the result
+
+    // Query and report the balance of the wallet's first address
+    let timeouts = OpTimeouts::default(); // Bound every network await so a dead node can't hang the run
+    let first_balance = timeouts
+        .rpc("balance", provider.get_balance(first_address, None))
+        .await?;
+    info!(address = ?first_address, balance = %first_balance, "wallet balance"); // Display the balance
+
+    // Take the recipient and amount from the payment URI, or from --to and --amount
+    let (recipient, value) = match cli.uri.as_deref() {
+        Some(uri) => {
+            // The URI already says who to pay and how much, so explicit flags would be ambiguous
+            if let Some(flag) = [
+                ("--to", cli.to.is_some()),
+                ("--amount", cli.amount.is_some()),
+            ]
+            .into_iter()
+            .find_map(|(flag, given)| given.then_some(flag))
+            {
+                return Err(Error::ConflictingFlags {
+                    flag: flag.to_string(),
+                    with: "--uri".to_string(),
+                }
+                .into());
+            }
+            let request = parse_payment_uri(uri)?;
+            if let Some(function) = &request.function {
+                return Err(Error::InvalidPaymentUri {
+                    uri: uri.to_string(),
+                    reason: format!("the transfer command sends ETH and can't call `{function}`"),
+                }
+                .into());
+            }
+            request.ensure_chain(&provider).await?; // A URI for another chain must not be paid here
+            (request.to, request.value.unwrap_or(U256::from(1000u64)))
+        }
+        None => (
+            cli.to.unwrap_or(Recipient::Address(
+                "0xb794F5eA0ba39494cE839613fffBA74279579268".parse()?,
+            )),
+            cli.amount.unwrap_or(U256::from(1000u64)), // Amount to send, in Wei (smallest denomination of Ether)
+        ),
+    };
+
+    // Query the balance of the recipient (by default a random address external to this wallet)
+    let other_address_hex = recipient.to_string(); // The recipient as typed, or its checksummed hex
+    let other_address = recipient.resolve(&context).await?; // Look ENS names up on the chain
+    let other_balance = timeouts
+        .rpc("balance", provider.get_balance(other_address, None))
+        .await?; // Get the balance of the random address
+    info!(address = %other_address_hex, balance = %other_balance, "recipient balance");
+
+    let options = TransferOptions {
+        expected_chain: cli.expected_chain, // Checked against the node before sending
+        gas_limit: cli.gas_limit,           // Set explicitly instead of estimated when given
+        notifier: config.notify.notifier()?, // Posts the transfer to the configured webhook
+        #[cfg(feature = "sqlite")]
+        ledger: config.database.open()?, // Records the transfer when a database is configured
+        ..Default::default()
+    };
+
+    // In dry-run mode, report what would be sent and stop before anything reaches the chain
+    if cli.dry_run {
+        let report =
+            dry_run_transfer(&provider, first_address, other_address, value, &options).await?;
+        println!("{}", render(&report, cli.json)?);
+        return Ok(());
+    }
 
-    // Create a transaction request to send 1000 units of Wei (smallest denomination of Ether)
-    // from the wallet's first address to the random address
-    let tx = TransactionRequest::pay(other_address, U256::from(1000u64)).from(first_address);
+    // With --as-safe-tx, write the transfer for the Safe's owners to sign instead of sending it
+    if let Some(safe) = cli.safe.as_safe_tx {
+        let tx = SafeTx::call(other_address, value, Bytes::new());
+        let export = cli.safe.write(&provider, safe, tx, None).await?;
+        info!(path = %cli.safe.safe_tx_out.display(), "safe transaction written");
+        println!("{}", render(&export, cli.json)?);
+        return Ok(());
+    }
 
-    // Send the transaction and wait for it to be mined (with at least 1 confirmation)
-    let receipt = provider
-        .send_transaction(tx, None) // Send the transaction
-        .await? // Wait for the transaction to be processed
-        .log_msg("Pending transfer") // Log a message for the pending transaction
-        .confirmations(1) // Wait for 1 confirmation
-        .await? // Await for confirmation
-        .context("Missing receipt")?; // Ensure the receipt is not missing
+    // Send the Wei from the wallet's first address to the random address
+    // and wait for it to be mined (with at least 1 confirmation)
+    // The wallet signs locally unless --unlocked leaves the signing to the node's own account
+    let receipt = if cli.unlocked {
+        send_transfer(&context, first_address, other_address, value, &options).await?
+    } else {
+        send_signed_transfer(&context, wallet, other_address, value, &options).await?
+    };
+
+    // Report the block number in which the transaction was mined
+    let block = receipt.block_number.context("cannot get block number")?; // Handle potential error if block number is unavailable
+    info!(%block, "TX mined");
+
+    // With --csv, append the transfer as the sender saw it for spreadsheet use
+    if let Some(path) = &cli.csv {
+        let summary = summarize_tx(&provider, receipt.from, receipt.transaction_hash).await?;
+        export_csv(&[summary], path)?;
+        info!(path = %path.display(), "transfer exported");
+    }
 
-    // Print the block number in which the transaction was mined
-    println!(
-        "TX mined in block {}",
-        receipt.block_number.context("cannot get block number")? // Handle potential error if block number is unavailable
-    );
+    // Query and report the balance of the random address after the transaction
+    let new_balance = timeouts
+        .rpc("balance", provider.get_balance(other_address, None))
+        .await?; // Fetch the updated balance
+    info!(address = %other_address_hex, balance = %new_balance, "recipient balance after TX");
 
-    // Query and print the balance of the random address after the transaction
+    // Print the transfer summary as the command's only stdout output
     println!(
-        "Balance of {} after TX: {}",
-        other_address_hex,
-        provider.get_balance(other_address, None).await? // Fetch and display updated balance
+        "{}",
+        render(&TransferResult::from_receipt(&receipt, value)?, cli.json)?
     );
 
     Ok(()) // Return Ok if everything succeeds
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_comparison)]
 mod tests {
     use super::*;
-    use ethers::signers::LocalWallet;
+    use ethers::{
+        prelude::{Address, TransactionRequest, I256},
+        signers::LocalWallet,
+        utils::Ganache,
+    };
+    use hex::ToHex;
+    use rust_eth_yt::funds::BalanceTracker;
 
     #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
     async fn test_wallet_generation() -> Result<()> {
         let mnemonic = "brisk usual burst upper buddy female library dial rifle mercy globe nurse";
 
@@ -111,6 +260,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
     async fn test_get_balance() {
         let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
         let ganache = Ganache::new().mnemonic(mnemonic).spawn();
@@ -128,6 +278,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
     async fn test_send_transaction() -> Result<()> {
         // Change return type to Result
         let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
@@ -140,14 +291,14 @@ mod tests {
             .parse::<Address>()
             .unwrap();
 
-        let initial_balance = provider.get_balance(other_address, None).await.unwrap();
-
-        // Send transaction
+        // Send transaction, tracking the recipient's balance around it
         let tx = TransactionRequest::pay(other_address, U256::from(1000u64)).from(first_address);
-        let pending_tx = provider.send_transaction(tx, None).await.unwrap();
-
-        // Await the transaction receipt
-        let receipt = pending_tx.await?.context("Missing receipt")?; // This will now compile correctly
+        let (receipt, deltas) = BalanceTracker::new(&provider, other_address)
+            .measure(|| async {
+                let pending_tx = provider.send_transaction(tx, None).await?;
+                pending_tx.await?.context("Missing receipt")
+            })
+            .await?;
 
         // Check that the transaction was mined successfully
         assert!(
@@ -155,10 +306,10 @@ mod tests {
             "Transaction should have been mined"
         );
 
-        // Check that the balance of the recipient has increased
-        let new_balance = provider.get_balance(other_address, None).await.unwrap();
-        assert!(
-            new_balance > initial_balance,
+        // Check that the balance of the recipient has increased by the amount sent
+        assert_eq!(
+            deltas.delta(other_address),
+            Some(I256::from(1000)),
             "Recipient's balance should have increased"
         );
 
@@ -166,6 +317,7 @@ mod tests {
     }
 
     #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
     async fn test_get_balance_nonexistent_address() {
         let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
         let ganache = Ganache::new().mnemonic(mnemonic).spawn();
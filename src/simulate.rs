@@ -0,0 +1,394 @@
+//! `eth_call` simulation with state overrides (balance, nonce, code and storage per address).
+
+use crate::errors::Error;
+use ethers::{
+    abi::{decode, Detokenize, ParamType, Token},
+    contract::{decode_function_data, ContractCall},
+    prelude::{JsonRpcClient, Middleware, Provider, RawCall, U256},
+    providers::{JsonRpcError, RpcError},
+    types::{spoof, transaction::eip2718::TypedTransaction, Bytes},
+};
+use eyre::Result;
+
+/// Selector of `Error(string)`, used by `require` and `revert` with a message.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// Selector of `Panic(uint256)`, used by failed asserts, overflows and the like.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Result of a simulated call.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SimulationOutcome {
+    /// The call succeeded with this return data.
+    Success(Bytes),
+    /// The call reverted (or the node refused to execute it).
+    Reverted(Revert),
+}
+
+/// Why a simulated call failed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Revert {
+    /// Decoded `Error(string)`/`Panic(uint256)` reason, or the node's message when there's no revert data.
+    pub reason: Option<String>,
+    /// Raw revert data, empty when the node didn't return any.
+    pub data: Bytes,
+}
+
+/// Runs `tx` through `eth_call` with `overrides` applied on top of the latest state.
+///
+/// Nodes that don't accept the override parameter yield [`Error::StateOverridesUnsupported`].
+pub async fn simulate_call<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    tx: &TypedTransaction,
+    overrides: &spoof::State,
+) -> Result<SimulationOutcome> {
+    match provider.call_raw(tx).state(overrides).await {
+        Ok(data) => Ok(SimulationOutcome::Success(data)),
+        Err(err) => match err.as_error_response() {
+            Some(response) if rejects_overrides(response) => {
+                Err(Error::StateOverridesUnsupported {
+                    message: response.message.clone(),
+                }
+                .into())
+            }
+            Some(response) => Ok(SimulationOutcome::Reverted(revert_from_response(response))),
+            None => Err(err.into()),
+        },
+    }
+}
+
+/// Runs `tx` through `eth_call` and `eth_estimateGas` at once, returning the raw return data and
+/// the gas estimate; nothing is mined. A revert becomes [`Error::CallReverted`].
+pub async fn simulate_call_with_gas<M: Middleware>(
+    provider: &M,
+    tx: &TypedTransaction,
+) -> Result<(Bytes, U256)>
+where
+    M::Error: 'static,
+{
+    let (data, gas) = tokio::join!(provider.call(tx, None), provider.estimate_gas(tx, None));
+    Ok((data.map_err(reverted)?, gas.map_err(reverted)?))
+}
+
+/// Runs `tx` through `eth_call` with `overrides` applied and returns the raw return data, e.g.
+/// to ask "what if this account had more balance". A revert becomes [`Error::CallReverted`].
+pub async fn call_raw_with_overrides<P: JsonRpcClient>(
+    provider: &Provider<P>,
+    tx: &TypedTransaction,
+    overrides: &spoof::State,
+) -> Result<Bytes> {
+    match simulate_call(provider, tx, overrides).await? {
+        SimulationOutcome::Success(data) => Ok(data),
+        SimulationOutcome::Reverted(Revert { reason, data }) => {
+            Err(Error::CallReverted { reason, data }.into())
+        }
+    }
+}
+
+/// Simulates a contract call through `provider` with `overrides` and decodes its return value;
+/// a revert becomes [`Error::CallReverted`].
+pub async fn call_with_overrides<P, M, D>(
+    provider: &Provider<P>,
+    call: &ContractCall<M, D>,
+    overrides: &spoof::State,
+) -> Result<D>
+where
+    P: JsonRpcClient,
+    M: Middleware + 'static,
+    D: Detokenize,
+{
+    let data = call_raw_with_overrides(provider, &call.tx, overrides).await?;
+    Ok(decode_function_data(&call.function, data, false)?)
+}
+
+/// Decodes the standard `Error(string)` and `Panic(uint256)` revert payloads.
+pub fn decode_revert_reason(data: &[u8]) -> Option<String> {
+    let (selector, payload) = (data.get(..4)?, &data[4..]);
+    if selector == ERROR_SELECTOR {
+        match decode(&[ParamType::String], payload).ok()?.pop()? {
+            Token::String(reason) => Some(reason),
+            _ => None,
+        }
+    } else if selector == PANIC_SELECTOR {
+        match decode(&[ParamType::Uint(256)], payload).ok()?.pop()? {
+            Token::Uint(code) => Some(format!("panic code {code:#x}")),
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+// Old Ganache and other nodes without override support complain about the extra parameter
+fn rejects_overrides(response: &JsonRpcError) -> bool {
+    let message = response.message.to_lowercase();
+    response.code == -32602
+        || message.contains("incorrect number of arguments")
+        || message.contains("too many arguments")
+        || message.contains("state override")
+}
+
+// Generic over the middleware error so any client stack can report reverts
+fn reverted<E: ethers::providers::MiddlewareError + 'static>(err: E) -> eyre::Report {
+    match err.as_error_response() {
+        Some(response) => {
+            let Revert { reason, data } = revert_from_response(response);
+            Error::CallReverted { reason, data }.into()
+        }
+        None => err.into(),
+    }
+}
+
+pub(crate) fn revert_from_response(response: &JsonRpcError) -> Revert {
+    // Most nodes put the revert data in `data` as a hex string
+    let data: Bytes = response
+        .data
+        .as_ref()
+        .and_then(|data| data.as_str())
+        .and_then(|hex_data| hex_data.parse().ok())
+        .unwrap_or_default();
+    let reason = decode_revert_reason(&data).or_else(|| Some(response.message.clone()));
+    Revert { reason, data }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::deploy::{deploy_contract, DeployOptions};
+    use crate::project::{compile, contract_parts};
+    use crate::test_support::{mock_provider, reply, revert_reply};
+    use ethers::abi::encode;
+    use ethers::prelude::{Address, BlockNumber, LocalWallet, TransactionRequest};
+    use ethers::utils::{parse_ether, Anvil, Ganache};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    #[test]
+    fn test_decode_revert_reason() {
+        let mut data = ERROR_SELECTOR.to_vec();
+        data.extend(encode(&[Token::String(
+            "Ownable: caller is not the owner".to_string(),
+        )]));
+        assert_eq!(
+            decode_revert_reason(&data).as_deref(),
+            Some("Ownable: caller is not the owner")
+        );
+
+        let mut data = PANIC_SELECTOR.to_vec();
+        data.extend(encode(&[Token::Uint(U256::from(0x11))]));
+        assert_eq!(
+            decode_revert_reason(&data).as_deref(),
+            Some("panic code 0x11")
+        );
+
+        assert_eq!(decode_revert_reason(&[0xde, 0xad]), None);
+    }
+
+    #[tokio::test]
+    async fn test_call_with_gas_on_a_mock() -> Result<()> {
+        let tx: TypedTransaction = TransactionRequest::new()
+            .to(Address::from_low_u64_be(0xc0de))
+            .into();
+        let word = Bytes::from(encode(&[Token::Uint(42.into())]));
+        let (provider, _) = mock_provider([reply(&word), reply(U256::from(23_512u64))]);
+        let (data, gas) = simulate_call_with_gas(&provider, &tx).await?;
+        assert_eq!(data, word);
+        assert_eq!(gas, U256::from(23_512u64));
+
+        let mut revert = ERROR_SELECTOR.to_vec();
+        revert.extend(encode(&[Token::String("Pausable: paused".to_string())]));
+        let revert = Bytes::from(revert);
+        let (provider, _) = mock_provider([revert_reply(revert.clone()), revert_reply(revert)]);
+        let err = simulate_call_with_gas(&provider, &tx).await.unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<Error>(),
+                Some(Error::CallReverted { reason: Some(reason), .. }) if reason == "Pausable: paused"
+            ),
+            "{err:?}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_overrides_are_typed() -> Result<()> {
+        // A one-shot mock node answering like older Ganache versions
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await?;
+            let mut buf = vec![0u8; 4096];
+            let n = socket.read(&mut buf).await?;
+            let body = r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32602,"message":"Incorrect number of arguments. 'eth_call' requires between 1 and 2 arguments. 3 provided instead."}}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await?;
+            eyre::Ok(String::from_utf8_lossy(&buf[..n]).into_owned())
+        });
+
+        let provider = Provider::try_from(url)?;
+        let caller = Address::from_low_u64_be(0xdead);
+        let tx = TransactionRequest::pay(Address::zero(), 1u64)
+            .from(caller)
+            .into();
+        let err = simulate_call(&provider, &tx, &spoof::balance(caller, U256::zero()))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<Error>(),
+                Some(Error::StateOverridesUnsupported { .. })
+            ),
+            "{err}"
+        );
+
+        // The override object was sent as the third `eth_call` parameter
+        let request = server.await??;
+        assert!(request.contains("eth_call"), "{request}");
+        assert!(
+            request.contains("000000000000000000000000000000000000dead"),
+            "{request}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Anvil")]
+    async fn test_balance_override_on_anvil() -> Result<()> {
+        let anvil = Anvil::new().spawn();
+        let provider = Provider::try_from(anvil.endpoint())?;
+
+        // An address with no funds on the chain, sending 1 ETH
+        let caller = Address::from_low_u64_be(0xdead);
+        let tx = TransactionRequest::pay(Address::from_low_u64_be(0xbeef), parse_ether(1)?)
+            .from(caller)
+            .into();
+
+        let broke = spoof::balance(caller, U256::zero());
+        let outcome = simulate_call(&provider, &tx, &broke).await?;
+        assert!(
+            matches!(outcome, SimulationOutcome::Reverted(_)),
+            "{outcome:?}"
+        );
+
+        let rich = spoof::balance(caller, parse_ether(100)?);
+        let outcome = simulate_call(&provider, &tx, &rich).await?;
+        assert_eq!(outcome, SimulationOutcome::Success(Bytes::default()));
+
+        Ok(())
+    }
+
+    // Runtime code that returns 1 when its caller holds at least 50 ETH and reverts otherwise:
+    // `require(msg.sender.balance >= 50 ether); return 1;`
+    const RICH_ONLY: [u8; 32] = [
+        0x68, 0x02, 0xb5, 0xe3, 0xaf, 0x16, 0xb1, 0x88, 0x00, 0x00, // PUSH9 50 ether
+        0x33, 0x31, 0x10, // CALLER BALANCE LT
+        0x60, 0x1a, 0x57, // PUSH1 0x1a JUMPI
+        0x60, 0x01, 0x60, 0x00, 0x52, // MSTORE(0, 1)
+        0x60, 0x20, 0x60, 0x00, 0xf3, // RETURN(0, 32)
+        0x5b, 0x60, 0x00, 0x60, 0x00, 0xfd, // JUMPDEST REVERT(0, 0)
+    ];
+
+    #[tokio::test]
+    async fn test_raw_override_call_on_a_mock() -> Result<()> {
+        let caller = Address::from_low_u64_be(0xdead);
+        let tx: TypedTransaction = TransactionRequest::new()
+            .from(caller)
+            .to(Address::from_low_u64_be(0xc0de))
+            .into();
+        let mut overrides = spoof::balance(caller, parse_ether(100)?);
+        overrides
+            .account(Address::from_low_u64_be(0xc0de))
+            .code(Bytes::from(RICH_ONLY));
+
+        let word = Bytes::from(encode(&[Token::Uint(U256::one())]));
+        let (provider, mock) = mock_provider([reply(&word), revert_reply(Bytes::new())]);
+        assert_eq!(
+            call_raw_with_overrides(&provider, &tx, &overrides).await?,
+            word
+        );
+        mock.assert_request("eth_call", (&tx, BlockNumber::Latest, &overrides))?;
+
+        let err = call_raw_with_overrides(&provider, &tx, &overrides)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::CallReverted { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_balance_check_passes_with_a_richer_caller() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let checker = Address::from_low_u64_be(0xc0de);
+        let caller = Address::from_low_u64_be(0xdead);
+        let tx: TypedTransaction = TransactionRequest::new().from(caller).to(checker).into();
+
+        // The checker only exists in the overrides; the caller has nothing on chain
+        let mut poor = spoof::state();
+        poor.account(checker).code(Bytes::from(RICH_ONLY));
+        let err = call_raw_with_overrides(&provider, &tx, &poor)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::CallReverted { .. })
+        ));
+
+        let mut rich = poor.clone();
+        rich.account(caller).balance(parse_ether(50)?);
+        let data = call_raw_with_overrides(&provider, &tx, &rich).await?;
+        assert_eq!(U256::from_big_endian(&data), U256::one());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_balance_of_return_data_and_gas() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let owner = ganache.addresses()[0];
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let (busd, _) = deploy_contract(
+            &ctx,
+            wallet,
+            "BUSDImplementation",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        busd.method::<_, bool>("increaseSupply", U256::from(1_000u64))?
+            .send()
+            .await?
+            .await?;
+
+        let call = busd.method::<_, U256>("balanceOf", owner)?;
+        let (data, gas) = simulate_call_with_gas(&provider, &call.tx).await?;
+        let balance: U256 = decode_function_data(&call.function, data, false)?;
+        assert_eq!(balance, U256::from(1_000u64));
+        assert!(!gas.is_zero());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,442 @@
+//! SQLite record of what the binaries did: deployments, transactions and balance snapshots.
+//!
+//! Only built with the `sqlite` feature.
+
+use crate::deploy::DeploymentResult;
+use ethers::prelude::{Address, Middleware, TransactionReceipt, TxHash, U256};
+use eyre::{eyre, Result};
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::Serialize;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// Schema versions in order; `PRAGMA user_version` counts the ones already applied
+const MIGRATIONS: &[&str] = &["
+    CREATE TABLE deployments (
+        id INTEGER PRIMARY KEY,
+        chain_id INTEGER NOT NULL,
+        name TEXT NOT NULL,
+        address TEXT NOT NULL,
+        tx_hash TEXT NOT NULL,
+        block INTEGER NOT NULL,
+        gas_used TEXT NOT NULL,
+        timestamp INTEGER NOT NULL
+    );
+    CREATE INDEX deployments_chain ON deployments (chain_id);
+    CREATE TABLE transactions (
+        id INTEGER PRIMARY KEY,
+        chain_id INTEGER NOT NULL,
+        hash TEXT NOT NULL UNIQUE,
+        sender TEXT NOT NULL,
+        recipient TEXT,
+        value TEXT NOT NULL,
+        fee TEXT NOT NULL,
+        status INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL
+    );
+    CREATE TABLE balances (
+        id INTEGER PRIMARY KEY,
+        chain_id INTEGER NOT NULL,
+        address TEXT NOT NULL,
+        balance TEXT NOT NULL,
+        block INTEGER NOT NULL,
+        timestamp INTEGER NOT NULL
+    );
+    CREATE INDEX balances_address ON balances (chain_id, address);
+"];
+
+/// A recorded deployment.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentRow {
+    pub chain_id: u64,
+    pub name: String,
+    pub address: Address,
+    pub tx_hash: TxHash,
+    pub block: u64,
+    pub gas_used: U256,
+    /// Unix time the row was recorded at.
+    pub timestamp: u64,
+}
+
+/// A recorded transaction, with the fee it paid.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransactionRow {
+    pub chain_id: u64,
+    pub hash: TxHash,
+    pub from: Address,
+    /// `None` for contract creations.
+    pub to: Option<Address>,
+    pub value: U256,
+    pub fee: U256,
+    /// Whether the transaction succeeded.
+    pub status: bool,
+    pub timestamp: u64,
+}
+
+/// A balance seen at a block.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceRow {
+    pub chain_id: u64,
+    pub address: Address,
+    pub balance: U256,
+    pub block: u64,
+    pub timestamp: u64,
+}
+
+/// A SQLite database of runs, migrated to the current schema when opened.
+pub struct Ledger {
+    path: PathBuf,
+    conn: Mutex<Connection>,
+}
+
+impl fmt::Debug for Ledger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Ledger").field("path", &self.path).finish()
+    }
+}
+
+impl Ledger {
+    /// Opens (or creates) the database at `path` and applies the migrations it hasn't seen.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut conn = Connection::open(&path)?;
+        let applied: usize = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if applied > MIGRATIONS.len() {
+            return Err(eyre!(
+                "{} has schema version {applied}, newer than this build's {}",
+                path.display(),
+                MIGRATIONS.len()
+            ));
+        }
+        let tx = conn.transaction()?;
+        for (version, migration) in MIGRATIONS.iter().enumerate().skip(applied) {
+            tx.execute_batch(migration)?;
+            tx.pragma_update(None, "user_version", version + 1)?;
+        }
+        tx.commit()?;
+        Ok(Self {
+            path,
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Records a deployment of contract `name` on `chain_id`.
+    pub fn record_deployment(
+        &self,
+        chain_id: u64,
+        name: &str,
+        result: &DeploymentResult,
+    ) -> Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO deployments (chain_id, name, address, tx_hash, block, gas_used, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                chain_id,
+                name,
+                format!("{:?}", result.address),
+                format!("{:?}", result.tx_hash),
+                result.block_number,
+                result.gas_used.to_string(),
+                now(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Records a mined transaction that sent `value` Wei; recording it again replaces the row.
+    pub fn record_transaction(
+        &self,
+        chain_id: u64,
+        receipt: &TransactionReceipt,
+        value: U256,
+    ) -> Result<()> {
+        let fee =
+            receipt.gas_used.unwrap_or_default() * receipt.effective_gas_price.unwrap_or_default();
+        self.conn.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO transactions
+                (chain_id, hash, sender, recipient, value, fee, status, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                chain_id,
+                format!("{:?}", receipt.transaction_hash),
+                format!("{:?}", receipt.from),
+                receipt.to.map(|to| format!("{to:?}")),
+                value.to_string(),
+                fee.to_string(),
+                receipt.status == Some(1u64.into()),
+                now(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Records `address`'s balance at `block`.
+    pub fn record_balance(
+        &self,
+        chain_id: u64,
+        address: Address,
+        balance: U256,
+        block: u64,
+    ) -> Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO balances (chain_id, address, balance, block, timestamp)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                chain_id,
+                format!("{address:?}"),
+                balance.to_string(),
+                block,
+                now()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Records the current balance of each of `addresses`, all read at the same block.
+    pub async fn snapshot_balances<M: Middleware>(
+        &self,
+        client: &M,
+        addresses: &[Address],
+    ) -> Result<()>
+    where
+        M::Error: 'static,
+    {
+        let chain_id = client.get_chainid().await?.as_u64();
+        let block = client.get_block_number().await?;
+        for address in addresses {
+            let balance = client.get_balance(*address, Some(block.into())).await?;
+            self.record_balance(chain_id, *address, balance, block.as_u64())?;
+        }
+        Ok(())
+    }
+
+    /// Deployments recorded on `chain_id`, newest first.
+    pub fn query_deployments(&self, chain_id: u64) -> Result<Vec<DeploymentRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT chain_id, name, address, tx_hash, block, gas_used, timestamp
+             FROM deployments WHERE chain_id = ?1 ORDER BY id DESC",
+        )?;
+        let rows = statement.query_map([chain_id], |row| {
+            Ok(DeploymentRow {
+                chain_id: row.get(0)?,
+                name: row.get(1)?,
+                address: parse_column(row, 2)?,
+                tx_hash: parse_column(row, 3)?,
+                block: row.get(4)?,
+                gas_used: decimal_column(row, 5)?,
+                timestamp: row.get(6)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// The last `limit` transactions recorded on `chain_id`, newest first.
+    pub fn query_transactions(&self, chain_id: u64, limit: usize) -> Result<Vec<TransactionRow>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT chain_id, hash, sender, recipient, value, fee, status, timestamp
+             FROM transactions WHERE chain_id = ?1 ORDER BY id DESC LIMIT ?2",
+        )?;
+        let rows = statement.query_map(params![chain_id, limit], |row| {
+            Ok(TransactionRow {
+                chain_id: row.get(0)?,
+                hash: parse_column(row, 1)?,
+                from: parse_column(row, 2)?,
+                to: row
+                    .get::<_, Option<String>>(3)?
+                    .map(|to| to.parse().map_err(|err| conversion_error(3, err)))
+                    .transpose()?,
+                value: decimal_column(row, 4)?,
+                fee: decimal_column(row, 5)?,
+                status: row.get(6)?,
+                timestamp: row.get(7)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// The latest balance recorded for `address` on `chain_id`.
+    pub fn latest_balance(&self, chain_id: u64, address: Address) -> Result<Option<BalanceRow>> {
+        let conn = self.conn.lock().unwrap();
+        let row = conn
+            .query_row(
+                "SELECT chain_id, address, balance, block, timestamp FROM balances
+                 WHERE chain_id = ?1 AND address = ?2 ORDER BY id DESC LIMIT 1",
+                params![chain_id, format!("{address:?}")],
+                |row| {
+                    Ok(BalanceRow {
+                        chain_id: row.get(0)?,
+                        address: parse_column(row, 1)?,
+                        balance: decimal_column(row, 2)?,
+                        block: row.get(3)?,
+                        timestamp: row.get(4)?,
+                    })
+                },
+            )
+            .optional()?;
+        Ok(row)
+    }
+}
+
+/// The recent rows of a database, as printed by the `history` subcommand.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RunHistory {
+    pub deployments: Vec<DeploymentRow>,
+    pub transactions: Vec<TransactionRow>,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+fn parse_column<T>(row: &Row, index: usize) -> rusqlite::Result<T>
+where
+    T: std::str::FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    row.get::<_, String>(index)?
+        .parse()
+        .map_err(|err| conversion_error(index, err))
+}
+
+fn decimal_column(row: &Row, index: usize) -> rusqlite::Result<U256> {
+    U256::from_dec_str(&row.get::<_, String>(index)?).map_err(|err| conversion_error(index, err))
+}
+
+fn conversion_error(
+    index: usize,
+    err: impl std::error::Error + Send + Sync + 'static,
+) -> rusqlite::Error {
+    rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, Box::new(err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::deploy::{compile_and_deploy, DeployOptions};
+    use crate::transfer::{send_transfer, TransferOptions};
+    use ethers::prelude::{LocalWallet, Provider};
+    use ethers::utils::Ganache;
+    use std::sync::Arc;
+
+    fn temp_db(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("{name}-{}.sqlite", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_migrations_run_once_on_open() -> Result<()> {
+        let path = temp_db("ledger-migrations");
+        let result = DeploymentResult {
+            address: Address::repeat_byte(0x11),
+            tx_hash: TxHash::repeat_byte(0x22),
+            block_number: 3,
+            gas_used: U256::from(21_000u64),
+            init_tx_hash: None,
+            init_gas_used: None,
+        };
+        Ledger::open(&path)?.record_deployment(1337, "Migrations", &result)?;
+
+        // Reopening keeps the rows instead of recreating the tables
+        let ledger = Ledger::open(&path)?;
+        let version: usize =
+            ledger
+                .conn
+                .lock()
+                .unwrap()
+                .query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        assert_eq!(version, MIGRATIONS.len());
+        let rows = ledger.query_deployments(1337)?;
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].address, result.address);
+        assert_eq!(rows[0].tx_hash, result.tx_hash);
+        assert_eq!(rows[0].gas_used, result.gas_used);
+        assert!(ledger.query_deployments(1)?.is_empty());
+
+        drop(ledger);
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_deployments_and_transfers_are_recorded() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+
+        let path = temp_db("ledger-runs");
+        let ledger = Arc::new(Ledger::open(&path)?);
+        let deploy_options = DeployOptions {
+            ledger: Some(ledger.clone()),
+            ..Default::default()
+        };
+        let mut deployed = Vec::new();
+        for name in ["Migrations", "BUSDImplementation"] {
+            let (_, result) =
+                compile_and_deploy(&ctx, wallet.clone(), "examples/", name, (), &deploy_options)
+                    .await?;
+            deployed.push((name, result));
+        }
+
+        let (from, to) = (ganache.addresses()[0], ganache.addresses()[1]);
+        let transfer_options = TransferOptions {
+            ledger: Some(ledger.clone()),
+            ..Default::default()
+        };
+        let value = U256::from(1000u64);
+        let receipt = send_transfer(&ctx, from, to, value, &transfer_options).await?;
+
+        // Newest first
+        let rows = ledger.query_deployments(chain_id)?;
+        assert_eq!(rows.len(), 2);
+        for (row, (name, result)) in rows.iter().zip(deployed.iter().rev()) {
+            assert_eq!(row.name, *name);
+            assert_eq!(row.address, result.address);
+            assert_eq!(row.tx_hash, result.tx_hash);
+            assert_eq!(row.block, result.block_number);
+            assert_eq!(row.gas_used, result.gas_used);
+        }
+
+        let transactions = ledger.query_transactions(chain_id, 10)?;
+        assert_eq!(transactions.len(), 1);
+        let transfer = &transactions[0];
+        assert_eq!(transfer.hash, receipt.transaction_hash);
+        assert_eq!((transfer.from, transfer.to), (from, Some(to)));
+        assert_eq!(transfer.value, value);
+        assert_eq!(
+            transfer.fee,
+            receipt.gas_used.unwrap() * receipt.effective_gas_price.unwrap()
+        );
+        assert!(transfer.status);
+
+        // Both parties' balances were snapshotted after the transfer
+        for address in [from, to] {
+            let snapshot = ledger
+                .latest_balance(chain_id, address)?
+                .expect("a balance snapshot");
+            assert_eq!(snapshot.balance, provider.get_balance(address, None).await?);
+        }
+
+        drop(ledger);
+        std::fs::remove_file(path)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,76 @@
+//! Snapshot of account states, for debugging a chain.
+
+use ethers::prelude::{Address, Middleware, U256};
+use eyre::{eyre, Result};
+use futures::future::try_join_all;
+use serde::Serialize;
+
+/// Balance, nonce and code presence of one address.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountState {
+    pub address: Address,
+    /// Balance in Wei.
+    pub balance: U256,
+    /// Transactions sent so far (or contracts created, for a contract).
+    pub nonce: U256,
+    /// Whether code is deployed at the address.
+    pub has_code: bool,
+}
+
+/// The accounts of a [`dump_state`], as the `state` command prints them.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ChainState {
+    pub accounts: Vec<AccountState>,
+}
+
+/// Fetches the state of each of `addresses` at the latest block, concurrently and in order.
+pub async fn dump_state<M: Middleware>(
+    provider: &M,
+    addresses: &[Address],
+) -> Result<Vec<AccountState>>
+where
+    M::Error: 'static,
+{
+    try_join_all(addresses.iter().map(|&address| async move {
+        let (balance, nonce, code) = futures::try_join!(
+            provider.get_balance(address, None),
+            provider.get_transaction_count(address, None),
+            provider.get_code(address, None)
+        )
+        .map_err(|e| eyre!(e))?;
+        eyre::Ok(AccountState {
+            address,
+            balance,
+            nonce,
+            has_code: !code.is_empty(),
+        })
+    }))
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::prelude::Provider;
+    use ethers::utils::{parse_ether, Ganache};
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_dumps_the_default_accounts() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+
+        let state = dump_state(&provider, ganache.addresses()).await?;
+        assert_eq!(state.len(), ganache.addresses().len());
+        for (account, address) in state.iter().zip(ganache.addresses()) {
+            assert_eq!(account.address, *address);
+            assert!(account.nonce.is_zero());
+            assert_eq!(account.balance, parse_ether(1000)?); // Ganache's default funding
+            assert!(!account.has_code);
+        }
+
+        Ok(())
+    }
+}
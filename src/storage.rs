@@ -0,0 +1,256 @@
+//! Raw storage reads and Solidity storage slot computation.
+
+use ethers::{
+    abi::{encode, parse_abi, Token},
+    prelude::{Address, JsonRpcClient, Middleware, Provider, TransactionRequest, H256, U256},
+    utils::keccak256,
+};
+use eyre::{eyre, Result};
+use futures::future::try_join_all;
+
+/// How many leading slots [`find_balance_slot`] probes.
+const BALANCE_SLOT_PROBES: u64 = 30;
+/// Most `eth_getStorageAt` requests [`read_storage_range`] keeps in flight at once.
+const MAX_CONCURRENT_READS: usize = 64;
+
+/// Reads the raw 32-byte word stored at `slot` of `address`.
+pub async fn read_slot<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    address: Address,
+    slot: H256,
+) -> Result<H256> {
+    Ok(provider.get_storage_at(address, slot, None).await?)
+}
+
+/// Reads `count` consecutive slots of `contract` starting at `start_slot`.
+///
+/// ethers has no JSON-RPC batch support, so the reads are sent concurrently in chunks instead,
+/// which costs about one round trip per chunk rather than one per slot.
+pub async fn read_storage_range<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    contract: Address,
+    start_slot: U256,
+    count: usize,
+) -> Result<Vec<H256>> {
+    let slots: Vec<H256> = (0..count)
+        .map(|offset| {
+            start_slot
+                .checked_add(U256::from(offset))
+                .map(|slot| H256(slot.into()))
+                .ok_or_else(|| eyre!("slot range starting at {start_slot} overflows"))
+        })
+        .collect::<Result<_>>()?;
+
+    let mut words = Vec::with_capacity(count);
+    for chunk in slots.chunks(MAX_CONCURRENT_READS) {
+        let reads = chunk
+            .iter()
+            .map(|slot| read_slot(provider, contract, *slot));
+        words.extend(try_join_all(reads).await?);
+    }
+    Ok(words)
+}
+
+/// Slot of `mapping[key]` for a mapping declared at `base_slot`: `keccak256(key ++ base_slot)`.
+///
+/// Value-type keys are ABI-encoded (left-padded to 32 bytes); `string` and `bytes` keys are
+/// hashed unpadded, as solc does.
+pub fn mapping_slot(base_slot: H256, key: Token) -> H256 {
+    let mut preimage = match key {
+        Token::String(key) => key.into_bytes(),
+        Token::Bytes(key) => key,
+        key => encode(&[key]),
+    };
+    preimage.extend_from_slice(base_slot.as_bytes());
+    H256(keccak256(preimage))
+}
+
+/// Slot of element `index` of a dynamic array declared at `base_slot`: `keccak256(base_slot) + index`,
+/// for element types that fill a whole slot.
+pub fn array_slot(base_slot: H256, index: U256) -> H256 {
+    let start = U256::from_big_endian(&keccak256(base_slot));
+    let (slot, _) = start.overflowing_add(index);
+    H256(slot.into())
+}
+
+/// Reads `holder`'s entry of a `mapping(address => uint256)` balances mapping at `base_slot`.
+pub async fn read_erc20_balance_slot<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    token: Address,
+    holder: Address,
+    base_slot: H256,
+) -> Result<U256> {
+    let slot = mapping_slot(base_slot, Token::Address(holder));
+    let word = read_slot(provider, token, slot).await?;
+    Ok(U256::from_big_endian(word.as_bytes()))
+}
+
+/// Finds the base slot of `token`'s balances mapping by probing the first slots until one holds
+/// `holder`'s `balanceOf`. The holder needs a non-zero balance for the match to mean anything.
+pub async fn find_balance_slot<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    token: Address,
+    holder: Address,
+) -> Result<Option<H256>> {
+    let abi = parse_abi(&["function balanceOf(address) view returns (uint256)"])?;
+    let balance_of = abi.function("balanceOf")?;
+    let tx = TransactionRequest::new()
+        .to(token)
+        .data(balance_of.encode_input(&[Token::Address(holder)])?);
+    let output = provider.call(&tx.into(), None).await?;
+    let balance = match balance_of.decode_output(&output)?.pop() {
+        Some(Token::Uint(balance)) => balance,
+        _ => return Err(eyre!("balanceOf returned no uint256")),
+    };
+    if balance.is_zero() {
+        return Err(eyre!(
+            "{holder:?} holds no tokens, every empty slot would match"
+        ));
+    }
+
+    for slot in 0..BALANCE_SLOT_PROBES {
+        let base_slot = H256::from_low_u64_be(slot);
+        if read_erc20_balance_slot(provider, token, holder, base_slot).await? == balance {
+            return Ok(Some(base_slot));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::deploy::{deploy_contract, DeployOptions};
+    use crate::project::{compile, contract_parts};
+    use ethers::prelude::{LocalWallet, Signer};
+    use ethers::utils::Ganache;
+
+    #[test]
+    fn test_slot_computation_reference_values() {
+        // keccak256(uint256(0) ++ uint256(0)) and keccak256(uint256(0))
+        assert_eq!(
+            mapping_slot(H256::zero(), Token::Uint(U256::zero())),
+            "0xad3228b676f7d3cd4284a5443f17f1962b36e491b30a40b2405849e597ba5fb5"
+                .parse::<H256>()
+                .unwrap()
+        );
+        assert_eq!(
+            array_slot(H256::zero(), U256::zero()),
+            "0x290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e563"
+                .parse::<H256>()
+                .unwrap()
+        );
+        assert_eq!(
+            array_slot(H256::zero(), U256::from(2)),
+            "0x290decd9548b62a8d60345a988386fc84ba6bc95484008f6362f93160ef3e565"
+                .parse::<H256>()
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_busd_balances_read_from_storage() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let owner = wallet.address();
+        let holder = Address::from_low_u64_be(0xb0b);
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let (busd, _) = deploy_contract(
+            &ctx,
+            wallet,
+            "BUSDImplementation",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+
+        // Mint to the owner, then write the holder's balance with a transfer
+        busd.method::<_, ()>("unpause", ())?.send().await?.await?;
+        busd.method::<_, bool>("increaseSupply", U256::from(1_000_000u64))?
+            .send()
+            .await?
+            .await?;
+        let amount = U256::from(1234u64);
+        busd.method::<_, bool>("transfer", (holder, amount))?
+            .send()
+            .await?
+            .await?;
+
+        // `balances` is declared right after the `initialized` flag
+        let slot = find_balance_slot(&provider, busd.address(), holder).await?;
+        assert_eq!(slot, Some(H256::from_low_u64_be(1)));
+        let stored =
+            read_erc20_balance_slot(&provider, busd.address(), holder, slot.unwrap()).await?;
+        assert_eq!(stored, amount);
+
+        // Nested mappings chain the computation: allowed[owner][holder] lives under slot 3
+        busd.method::<_, bool>("approve", (holder, U256::from(77u64)))?
+            .send()
+            .await?
+            .await?;
+        let outer = mapping_slot(H256::from_low_u64_be(3), Token::Address(owner));
+        let allowance_slot = mapping_slot(outer, Token::Address(holder));
+        let word = read_slot(&provider, busd.address(), allowance_slot).await?;
+        assert_eq!(U256::from_big_endian(word.as_bytes()), U256::from(77u64));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_read_busd_state_variables_in_one_range() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let owner = wallet.address();
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let (busd, _) = deploy_contract(
+            &ctx,
+            wallet,
+            "BUSDImplementation",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        busd.method::<_, ()>("unpause", ())?.send().await?.await?;
+        let supply = U256::from(1_000_000u64);
+        busd.method::<_, bool>("increaseSupply", supply)?
+            .send()
+            .await?
+            .await?;
+
+        // initialized, balances, totalSupply_, allowed, owner
+        let words = read_storage_range(&provider, busd.address(), U256::zero(), 5).await?;
+        assert_eq!(words.len(), 5);
+        assert_eq!(words[0], H256::from_low_u64_be(1));
+        // Mapping base slots hold nothing themselves
+        assert_eq!(words[1], H256::zero());
+        assert_eq!(U256::from_big_endian(words[2].as_bytes()), supply);
+        assert_eq!(words[3], H256::zero());
+        assert_eq!(Address::from(words[4]), owner);
+
+        // A range can start anywhere
+        let tail = read_storage_range(&provider, busd.address(), U256::from(2), 3).await?;
+        assert_eq!(tail, words[2..]);
+        assert!(read_storage_range(&provider, busd.address(), U256::MAX, 2)
+            .await
+            .is_err());
+
+        Ok(())
+    }
+}
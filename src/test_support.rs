@@ -0,0 +1,322 @@
+//! Helpers shared by the unit tests.
+
+use async_trait::async_trait;
+use ethers::{
+    core::rand::thread_rng,
+    middleware::SignerMiddleware,
+    prelude::{Http, LocalWallet, Middleware, Provider, Signer},
+    providers::{JsonRpcClient, JsonRpcError, MockError, MockProvider, MockResponse},
+    types::{Block, Bytes, FeeHistory, TransactionReceipt, H256, U256},
+    utils::GanacheInstance,
+};
+use eyre::{ContextCompat, Result};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::json;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpListener,
+};
+
+/// Serves JSON-RPC over keep-alive HTTP connections, answering each request with
+/// `respond(method, params)`: a result, or an error object under an `"error"` key.
+pub(crate) async fn spawn_mock_node(
+    respond: impl Fn(&str, &serde_json::Value) -> serde_json::Value + Send + Sync + 'static,
+) -> Result<String> {
+    spawn_mock_http(move |body| {
+        let request: serde_json::Value = serde_json::from_slice(body).unwrap_or_default();
+        let answer = respond(
+            request["method"].as_str().unwrap_or_default(),
+            &request["params"],
+        );
+        let response = match answer.get("error") {
+            Some(error) => json!({ "jsonrpc": "2.0", "id": request["id"], "error": error }),
+            None => json!({ "jsonrpc": "2.0", "id": request["id"], "result": answer }),
+        };
+        (200, response.to_string())
+    })
+    .await
+}
+
+/// Serves HTTP over keep-alive connections, answering each request body with
+/// `respond(body)`'s status code and JSON body.
+pub(crate) async fn spawn_mock_http(
+    respond: impl Fn(&[u8]) -> (u16, String) + Send + Sync + 'static,
+) -> Result<String> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}", listener.local_addr()?);
+    let respond = Arc::new(respond);
+    tokio::spawn(async move {
+        while let Ok((socket, _)) = listener.accept().await {
+            let respond = respond.clone();
+            tokio::spawn(async move {
+                let mut socket = BufReader::new(socket);
+                loop {
+                    // Headers, then a body of content-length bytes
+                    let mut length = 0;
+                    loop {
+                        let mut line = String::new();
+                        if socket.read_line(&mut line).await? == 0 {
+                            return eyre::Ok(());
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                        if let Some((name, value)) = line.split_once(':') {
+                            if name.eq_ignore_ascii_case("content-length") {
+                                length = value.trim().parse()?;
+                            }
+                        }
+                    }
+                    let mut body = vec![0u8; length];
+                    socket.read_exact(&mut body).await?;
+
+                    let (status, response) = respond(&body);
+                    socket
+                        .write_all(
+                            format!(
+                                "HTTP/1.1 {status} {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                                if status < 400 { "OK" } else { "Error" },
+                                response.len(),
+                                response
+                            )
+                            .as_bytes(),
+                        )
+                        .await?;
+                }
+            });
+        }
+    });
+    Ok(url)
+}
+
+/// A provider answering each request with the next of `replies`, given in the order the
+/// requests are made, so helpers run without a node. The returned mock can check the requests
+/// afterwards with `assert_request`.
+pub(crate) fn mock_provider(
+    replies: impl IntoIterator<Item = MockResponse>,
+) -> (Provider<MockProvider>, MockProvider) {
+    let (provider, mock) = Provider::mocked();
+    // The mock answers from the back of its queue
+    let replies: Vec<_> = replies.into_iter().collect();
+    for reply in replies.into_iter().rev() {
+        mock.push_response(reply);
+    }
+    (provider, mock)
+}
+
+/// A [`MockProvider`] transport that also records the method of every request it carries, so
+/// tests can count the round trips a helper makes.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CountingClient {
+    mock: MockProvider,
+    methods: Arc<Mutex<Vec<String>>>,
+}
+
+impl CountingClient {
+    /// The methods requested so far, in order.
+    pub(crate) fn methods(&self) -> Vec<String> {
+        self.methods.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl JsonRpcClient for CountingClient {
+    type Error = MockError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, MockError>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned + Send,
+    {
+        self.methods.lock().unwrap().push(method.to_string());
+        self.mock.request(method, params).await
+    }
+}
+
+/// Like [`mock_provider`], over a [`CountingClient`] that reports the requests made.
+pub(crate) fn counting_provider(
+    replies: impl IntoIterator<Item = MockResponse>,
+) -> (Provider<CountingClient>, CountingClient) {
+    let client = CountingClient::default();
+    let replies: Vec<_> = replies.into_iter().collect();
+    for reply in replies.into_iter().rev() {
+        client.mock.push_response(reply);
+    }
+    (Provider::new(client.clone()), client)
+}
+
+/// A successful reply carrying `value`, such as a balance for `eth_getBalance`, a price for
+/// `eth_gasPrice` or a gas amount for `eth_estimateGas`.
+pub(crate) fn reply(value: impl Serialize) -> MockResponse {
+    MockResponse::Value(serde_json::to_value(value).expect("mock replies serialize"))
+}
+
+/// An `eth_getBlockByNumber` reply for a half-full block, with `base_fee` when the chain has
+/// EIP-1559.
+pub(crate) fn block_reply(number: u64, base_fee: Option<U256>) -> MockResponse {
+    reply(Block::<H256> {
+        number: Some(number.into()),
+        hash: Some(H256::from_low_u64_be(number)),
+        gas_limit: 30_000_000u64.into(),
+        gas_used: 15_000_000u64.into(),
+        base_fee_per_gas: base_fee,
+        ..Default::default()
+    })
+}
+
+/// An `eth_feeHistory` reply where every block paid `base_fee` and tipped `reward`.
+pub(crate) fn fee_history_reply(base_fee: U256, reward: U256) -> MockResponse {
+    reply(FeeHistory {
+        base_fee_per_gas: vec![base_fee; 11],
+        gas_used_ratio: vec![0.5; 10],
+        oldest_block: U256::one(),
+        reward: vec![vec![reward]; 10],
+    })
+}
+
+/// An `eth_getTransactionReceipt` reply for `receipt`.
+pub(crate) fn receipt_reply(receipt: &TransactionReceipt) -> MockResponse {
+    reply(receipt)
+}
+
+/// The error a node answers a reverting `eth_call` or `eth_estimateGas` with.
+pub(crate) fn revert_reply(data: Bytes) -> MockResponse {
+    MockResponse::Error(JsonRpcError {
+        code: 3,
+        message: "execution reverted".to_string(),
+        data: Some(json!(data)),
+    })
+}
+
+/// A successful receipt for transaction `hash`, mined in block 7 at 21000 gas and 1 gwei.
+pub(crate) fn mined_receipt(hash: H256) -> TransactionReceipt {
+    TransactionReceipt {
+        transaction_hash: hash,
+        block_hash: Some(H256::from_low_u64_be(7)),
+        block_number: Some(7u64.into()),
+        gas_used: Some(21_000u64.into()),
+        effective_gas_price: Some(1_000_000_000u64.into()),
+        status: Some(1u64.into()),
+        ..Default::default()
+    }
+}
+
+/// A wallet of [`TestActors`] wrapped in its own signing client.
+pub(crate) type ActorClient = SignerMiddleware<Provider<Http>, LocalWallet>;
+
+/// The named participants of a multi-actor test, in the order of the node's keys.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Actor {
+    Owner,
+    Alice,
+    Bob,
+    Attacker,
+}
+
+/// Pre-funded, named clients on a spawned Ganache, so tests switch signers by name instead of
+/// indexing `ganache.keys()`.
+pub(crate) struct TestActors {
+    provider: Provider<Http>,
+    chain_id: u64,
+    clients: [Arc<ActorClient>; 4],
+}
+
+impl TestActors {
+    /// Binds the first four keys of `ganache` to its chain id: owner, alice, bob and attacker.
+    pub(crate) async fn new(ganache: &GanacheInstance) -> Result<Self> {
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let client = |index: usize| -> Result<Arc<ActorClient>> {
+            let key = ganache
+                .keys()
+                .get(index)
+                .cloned()
+                .with_context(|| format!("Ganache has no key {index}"))?;
+            let wallet = LocalWallet::from(key).with_chain_id(chain_id);
+            Ok(Arc::new(SignerMiddleware::new(provider.clone(), wallet)))
+        };
+        Ok(Self {
+            clients: [client(0)?, client(1)?, client(2)?, client(3)?],
+            provider,
+            chain_id,
+        })
+    }
+
+    /// The plain provider, without a signer.
+    pub(crate) fn provider(&self) -> &Provider<Http> {
+        &self.provider
+    }
+
+    pub(crate) fn client_for(&self, actor: Actor) -> Arc<ActorClient> {
+        self.clients[actor as usize].clone()
+    }
+
+    pub(crate) fn owner(&self) -> Arc<ActorClient> {
+        self.client_for(Actor::Owner)
+    }
+
+    pub(crate) fn alice(&self) -> Arc<ActorClient> {
+        self.client_for(Actor::Alice)
+    }
+
+    pub(crate) fn bob(&self) -> Arc<ActorClient> {
+        self.client_for(Actor::Bob)
+    }
+
+    pub(crate) fn attacker(&self) -> Arc<ActorClient> {
+        self.client_for(Actor::Attacker)
+    }
+
+    /// A client for a random new wallet holding no ETH, for tests that expect a sender to be
+    /// unable to pay.
+    pub(crate) fn fresh_unfunded(&self) -> Arc<ActorClient> {
+        let wallet = LocalWallet::new(&mut thread_rng()).with_chain_id(self.chain_id);
+        Arc::new(SignerMiddleware::new(self.provider.clone(), wallet))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::prelude::TransactionRequest;
+    use ethers::utils::Ganache;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_actors_are_distinct_and_unfunded_cannot_pay() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let actors = TestActors::new(&ganache).await?;
+
+        let chain_id = actors.provider().get_chainid().await?.as_u64();
+        let actors_in_order = [
+            actors.owner(),
+            actors.alice(),
+            actors.bob(),
+            actors.attacker(),
+        ];
+        for (client, address) in actors_in_order.iter().zip(ganache.addresses()) {
+            assert_eq!(client.address(), *address);
+            assert_eq!(client.signer().chain_id(), chain_id);
+            assert!(!actors
+                .provider()
+                .get_balance(*address, None)
+                .await?
+                .is_zero());
+        }
+
+        let broke = actors.fresh_unfunded();
+        assert!(!ganache.addresses().contains(&broke.address()));
+        assert!(actors
+            .provider()
+            .get_balance(broke.address(), None)
+            .await?
+            .is_zero());
+        let tx = TransactionRequest::pay(actors.alice().address(), 1u64).gas(21_000u64);
+        assert!(broke.send_transaction(tx, None).await.is_err());
+
+        Ok(())
+    }
+}
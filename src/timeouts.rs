@@ -0,0 +1,237 @@
+//! Upper bounds for network awaits so a dead node can't hang a run forever.
+
+use crate::chain_context::ChainContext;
+use crate::errors::Error;
+use crate::polling::PendingTxExt;
+use ethers::prelude::{BlockNumber, PendingTransaction, ProviderError, TransactionReceipt, H256};
+use ethers::providers::{Middleware, MiddlewareError};
+use eyre::Result;
+use std::{future::Future, time::Duration};
+
+/// Blocks [`wait_for_receipt`] waits for by default, at the chain's average block time.
+const RECEIPT_WAIT_BLOCKS: u32 = 20;
+/// Shortest default receipt wait, for chains mining on every transaction.
+const MIN_RECEIPT_WAIT: Duration = Duration::from_secs(30);
+/// Blocks sampled for the default receipt wait and [`ChainContext::block_time`].
+///
+/// [`ChainContext::block_time`]: crate::chain_context::ChainContext::block_time
+pub const BLOCK_TIME_SAMPLE: usize = 10;
+
+/// Time limits applied to node interactions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpTimeouts {
+    /// Limit for a single JSON-RPC request (chain id, block, balance, estimates, sends).
+    pub rpc: Duration,
+    /// Limit for waiting until a submitted transaction is mined.
+    pub receipt: Duration,
+}
+
+impl Default for OpTimeouts {
+    fn default() -> Self {
+        Self {
+            rpc: Duration::from_secs(10),
+            receipt: Duration::from_secs(120),
+        }
+    }
+}
+
+impl OpTimeouts {
+    /// Runs a single RPC request under the [`OpTimeouts::rpc`] limit.
+    pub async fn rpc<F, T, E>(&self, what: &str, fut: F) -> Result<T>
+    where
+        F: Future<Output = Result<T, E>>,
+        E: Into<eyre::Report>,
+    {
+        with_timeout(self.rpc, what, fut).await
+    }
+
+    /// Waits for a transaction to be mined under the [`OpTimeouts::receipt`] limit.
+    pub async fn receipt<F, T, E>(&self, what: &str, fut: F) -> Result<T>
+    where
+        F: Future<Output = Result<T, E>>,
+        E: Into<eyre::Report>,
+    {
+        with_timeout(self.receipt, what, fut).await
+    }
+}
+
+/// Awaits `fut` for at most `after`, converting an elapsed timer into [`Error::OperationTimedOut`]
+/// naming `what` was being waited on.
+pub async fn with_timeout<F, T, E>(after: Duration, what: &str, fut: F) -> Result<T>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Into<eyre::Report>,
+{
+    match tokio::time::timeout(after, fut).await {
+        Ok(result) => result.map_err(Into::into),
+        Err(_) => Err(Error::OperationTimedOut {
+            operation: what.to_string(),
+            after,
+        }
+        .into()),
+    }
+}
+
+/// Average time between the last `sample` blocks, from their timestamps.
+///
+/// Uses every block on chains shorter than `sample`; zero with fewer than two blocks, which
+/// have no interval to measure. Timestamps have a one-second resolution, so sub-second
+/// cadences come out as a rough average.
+pub async fn average_block_time<M: Middleware>(
+    client: &M,
+    sample: usize,
+) -> Result<Duration, M::Error> {
+    let block = |number: BlockNumber| async move {
+        client.get_block(number).await?.ok_or_else(|| {
+            M::Error::from_provider_err(ProviderError::CustomError(format!(
+                "Block {number} not found"
+            )))
+        })
+    };
+    let latest = block(BlockNumber::Latest).await?;
+    let newest = latest.number.unwrap_or_default().as_u64();
+    let count = (sample as u64).min(newest + 1);
+    if count < 2 {
+        return Ok(Duration::ZERO);
+    }
+    let earlier = block((newest + 1 - count).into()).await?;
+    let oldest = earlier.number.unwrap_or_default().as_u64();
+    let elapsed = latest.timestamp.saturating_sub(earlier.timestamp).as_u64();
+    Ok(Duration::from_secs_f64(
+        elapsed as f64 / newest.saturating_sub(oldest).max(1) as f64,
+    ))
+}
+
+/// Waits until `tx_hash` is mined and returns its receipt, with
+/// [`PendingTxExt::await_smart`] under an [`OpTimeouts::receipt`] limit.
+///
+/// Without a `timeout`, the wait lasts 20 block times of the chain (at least 30 seconds), so
+/// slow chains aren't given up on too early and fast ones don't hang for minutes.
+pub async fn wait_for_receipt<M>(
+    ctx: &ChainContext<M>,
+    tx_hash: H256,
+    timeout: Option<Duration>,
+) -> Result<TransactionReceipt>
+where
+    M: Middleware + 'static,
+    M::Error: 'static,
+{
+    let receipt = match timeout {
+        Some(timeout) => timeout,
+        None => {
+            // An unmeasured chain gets the minimum wait
+            let block_time = ctx.block_time().await.unwrap_or_default();
+            (block_time * RECEIPT_WAIT_BLOCKS).max(MIN_RECEIPT_WAIT)
+        }
+    };
+    let timeouts = OpTimeouts {
+        receipt,
+        ..OpTimeouts::default()
+    };
+    timeouts
+        .receipt(
+            "receipt",
+            PendingTransaction::new(tx_hash, ctx.provider()).await_smart(ctx),
+        )
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::transfer::{send_transfer, TransferOptions};
+    use ethers::prelude::{Provider, U256};
+    use ethers::utils::Ganache;
+    use std::time::Instant;
+
+    #[tokio::test]
+    async fn test_with_timeout_names_the_operation() {
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            eyre::Ok(())
+        };
+        let err = with_timeout(Duration::from_millis(20), "slow thing", slow)
+            .await
+            .expect_err("Future should time out");
+        match err.downcast_ref::<Error>() {
+            Some(Error::OperationTimedOut { operation, after }) => {
+                assert_eq!(operation, "slow thing");
+                assert_eq!(*after, Duration::from_millis(20));
+            }
+            other => panic!("Expected OperationTimedOut, got {other:?}"),
+        }
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_transfer_terminates_when_node_dies() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        // Mine every 10 seconds so the transfer is still pending when the node goes away
+        let ganache = Ganache::new().mnemonic(mnemonic).block_time(10u64).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let from = ganache.addresses()[0];
+        let to = ganache.addresses()[1];
+
+        let options = TransferOptions {
+            timeouts: OpTimeouts {
+                rpc: Duration::from_secs(2),
+                receipt: Duration::from_secs(3),
+            },
+            ..Default::default()
+        };
+        let started = Instant::now();
+        let transfer = tokio::spawn(async move {
+            send_transfer(&ctx, from, to, U256::from(1000u64), &options).await
+        });
+
+        // Kill the Ganache child while the transfer waits for its receipt
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        drop(ganache);
+
+        let err = transfer
+            .await?
+            .expect_err("Transfer should fail once the node is gone");
+        assert!(
+            started.elapsed() < Duration::from_secs(6),
+            "Run should end within the configured timeouts"
+        );
+        assert!(
+            matches!(
+                err.downcast_ref::<Error>(),
+                Some(Error::OperationTimedOut { operation, .. }) if operation == "transfer receipt"
+            ),
+            "Unexpected error: {err:?}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_average_block_time_matches_ganache_block_time() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).block_time(2u64).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+
+        // Let a few blocks go by
+        while provider.get_block_number().await?.as_u64() < 4 {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        let average = average_block_time(&provider, 4).await?;
+        assert!(
+            (Duration::from_millis(1500)..=Duration::from_millis(2500)).contains(&average),
+            "average block time {average:?}"
+        );
+
+        // The default receipt wait covers a transfer mined on the next block
+        let (from, to) = (ganache.addresses()[0], ganache.addresses()[1]);
+        let tx = ethers::prelude::TransactionRequest::pay(to, 1000u64).from(from);
+        let hash = *provider.send_transaction(tx, None).await?;
+        let receipt = wait_for_receipt(&ChainContext::new(provider), hash, None).await?;
+        assert_eq!(receipt.transaction_hash, hash);
+
+        Ok(())
+    }
+}
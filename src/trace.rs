@@ -0,0 +1,396 @@
+//! Post-mortem execution traces through `debug_traceTransaction`.
+
+use crate::errors::{is_method_unavailable, Error};
+use ethers::{
+    prelude::{Address, JsonRpcClient, Provider, H256, U256},
+    providers::RpcError,
+    types::Bytes,
+};
+use eyre::Result;
+use serde::Deserialize;
+use serde_json::json;
+
+const TRACE_METHOD: &str = "debug_traceTransaction";
+
+/// Which tracer the node runs over the transaction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceKind {
+    /// The default struct logger: one entry per executed opcode.
+    #[default]
+    StructLogs,
+    /// `callTracer`: the tree of calls made by the transaction.
+    CallTracer,
+}
+
+/// A transaction trace, shaped by the [`TraceKind`] that produced it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Trace {
+    /// Executed opcodes, in order.
+    Steps(StructLogTrace),
+    /// The outermost call frame.
+    Calls(Box<CallFrame>),
+}
+
+/// Struct logger output.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StructLogTrace {
+    /// Whether execution ended in a revert or an exceptional halt.
+    pub failed: bool,
+    /// Gas used by the transaction.
+    pub gas: u64,
+    /// Executed opcodes, in order.
+    pub struct_logs: Vec<OpcodeStep>,
+}
+
+/// One executed opcode (a subset of what the struct logger reports).
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpcodeStep {
+    /// Call depth, starting at 1 for the transaction itself.
+    pub depth: u64,
+    /// Program counter.
+    pub pc: u64,
+    /// Opcode name, e.g. `SSTORE`.
+    pub op: String,
+    /// Gas left before the opcode ran.
+    pub gas: u64,
+    /// Gas charged for the opcode.
+    pub gas_cost: u64,
+}
+
+/// One call of a `callTracer` trace, with the calls it made nested inside.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CallFrame {
+    /// `CALL`, `STATICCALL`, `DELEGATECALL`, `CREATE`, ...
+    #[serde(rename = "type")]
+    pub call_type: String,
+    /// Caller.
+    pub from: Address,
+    /// Absent for failed creations.
+    #[serde(default)]
+    pub to: Option<Address>,
+    /// Wei sent along, absent for `STATICCALL`/`DELEGATECALL`.
+    #[serde(default)]
+    pub value: Option<U256>,
+    /// Calldata (the init code for creations).
+    pub input: Bytes,
+    /// Return or revert data.
+    #[serde(default)]
+    pub output: Option<Bytes>,
+    /// Gas used by this frame, sub-calls included.
+    pub gas_used: U256,
+    /// Why the frame failed, e.g. `execution reverted`; `None` for a successful frame.
+    #[serde(default)]
+    pub error: Option<String>,
+    /// Decoded `Error(string)` message, when the node provides one.
+    #[serde(default)]
+    pub revert_reason: Option<String>,
+    /// Calls made by this frame, in order.
+    #[serde(default)]
+    pub calls: Vec<CallFrame>,
+}
+
+impl CallFrame {
+    /// Whether the frame reverted or halted.
+    pub fn failed(&self) -> bool {
+        self.error.is_some()
+    }
+}
+
+/// Traces `tx_hash` with `tracer` through the raw `debug_traceTransaction` RPC.
+///
+/// Nodes without the `debug` namespace yield [`Error::DebugRpcUnsupported`].
+pub async fn trace_transaction<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    tx_hash: H256,
+    tracer: TraceKind,
+) -> Result<Trace> {
+    let options = match tracer {
+        TraceKind::StructLogs => json!({ "disableStorage": true, "disableMemory": true }),
+        TraceKind::CallTracer => json!({ "tracer": "callTracer" }),
+    };
+    let trace = request_trace(provider, tx_hash, options).await?;
+
+    Ok(match tracer {
+        TraceKind::StructLogs => Trace::Steps(serde_json::from_value(trace)?),
+        TraceKind::CallTracer => Trace::Calls(Box::new(serde_json::from_value(trace)?)),
+    })
+}
+
+/// Returns the node's default `debug_traceTransaction` output for `tx_hash` as is (for the
+/// struct logger: `gas`, `failed`, `returnValue` and `structLogs` with stack, memory and storage).
+///
+/// Nodes without the `debug` namespace yield [`Error::DebugRpcUnsupported`].
+pub async fn trace_tx<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    tx_hash: H256,
+) -> Result<serde_json::Value> {
+    request_trace(provider, tx_hash, json!({})).await
+}
+
+/// The opcode that ended the transaction with a `REVERT`, or `None` if it didn't revert.
+pub fn revert_location(trace: &StructLogTrace) -> Option<&OpcodeStep> {
+    // The outermost revert is the last one executed at the transaction's own depth
+    let top = trace.struct_logs.first()?.depth;
+    trace
+        .struct_logs
+        .iter()
+        .rev()
+        .find(|step| step.depth == top && step.op == "REVERT")
+}
+
+async fn request_trace<T: JsonRpcClient>(
+    provider: &Provider<T>,
+    tx_hash: H256,
+    options: serde_json::Value,
+) -> Result<serde_json::Value> {
+    match provider.request(TRACE_METHOD, (tx_hash, options)).await {
+        Ok(trace) => Ok(trace),
+        Err(err) => match err.as_error_response() {
+            Some(response) if is_method_unavailable(response) => Err(Error::DebugRpcUnsupported {
+                method: TRACE_METHOD.to_string(),
+                message: response.message.clone(),
+            }
+            .into()),
+            _ => Err(err.into()),
+        },
+    }
+}
+
+/// Renders `frame` and its sub-calls as an indented tree, one call per line.
+///
+/// Each line shows the call type, caller, callee, 4-byte selector and gas used; failed frames
+/// are flagged with `[REVERTED: <error>]`.
+pub fn print_call_tree(frame: &CallFrame) -> String {
+    let mut lines = Vec::new();
+    push_frame(frame, 0, &mut lines);
+    lines.join("\n")
+}
+
+fn push_frame(frame: &CallFrame, depth: usize, lines: &mut Vec<String>) {
+    let to = frame
+        .to
+        .map(|to| format!("{to:?}"))
+        .unwrap_or_else(|| "<none>".to_string());
+    let selector = match frame.input.get(..4) {
+        Some(selector) => format!("0x{}", hex::encode(selector)),
+        None => "-".to_string(),
+    };
+    let mut line = format!(
+        "{}{} {:?} -> {} {} (gas used {})",
+        "  ".repeat(depth),
+        frame.call_type,
+        frame.from,
+        to,
+        selector,
+        frame.gas_used
+    );
+    if let Some(error) = &frame.error {
+        let reason = frame.revert_reason.as_deref().unwrap_or(error);
+        line.push_str(&format!(" [REVERTED: {reason}]"));
+    }
+    lines.push(line);
+    for call in &frame.calls {
+        push_frame(call, depth + 1, lines);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chain_context::ChainContext;
+    use crate::deploy::{deploy_contract, DeployOptions};
+    use crate::project::{compile, contract_parts};
+    use crate::receipt::ReceiptSummary;
+    use ethers::abi::Token;
+    use ethers::prelude::{
+        ContractFactory, LocalWallet, Middleware, Signer, SignerMiddleware, TransactionRequest,
+    };
+    use ethers::utils::{Anvil, Ganache};
+    use std::sync::Arc;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    #[test]
+    fn test_call_tree_flags_reverted_frames() -> Result<()> {
+        let frame: CallFrame = serde_json::from_value(json!({
+            "type": "CALL",
+            "from": "0x00000000000000000000000000000000000000aa",
+            "to": "0x00000000000000000000000000000000000000bb",
+            "input": "0xa9059cbb",
+            "gasUsed": "0x5208",
+            "calls": [{
+                "type": "STATICCALL",
+                "from": "0x00000000000000000000000000000000000000bb",
+                "to": "0x00000000000000000000000000000000000000cc",
+                "input": "0x70a08231",
+                "gasUsed": "0x100",
+                "error": "execution reverted"
+            }]
+        }))?;
+
+        let tree = print_call_tree(&frame);
+        let lines: Vec<&str> = tree.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("CALL ") && lines[0].contains("0xa9059cbb"));
+        assert!(!lines[0].contains("REVERTED"));
+        assert!(lines[1].starts_with("  STATICCALL "));
+        assert!(lines[1].ends_with("[REVERTED: execution reverted]"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_revert_location_is_the_outermost_revert() -> Result<()> {
+        let trace: StructLogTrace = serde_json::from_value(json!({
+            "failed": true,
+            "gas": 30000,
+            "structLogs": [
+                { "depth": 1, "pc": 0, "op": "PUSH1", "gas": 100, "gasCost": 3 },
+                { "depth": 2, "pc": 7, "op": "REVERT", "gas": 50, "gasCost": 0 },
+                { "depth": 1, "pc": 41, "op": "REVERT", "gas": 40, "gasCost": 0 }
+            ]
+        }))?;
+        let step = revert_location(&trace).expect("the transaction reverted");
+        assert_eq!((step.depth, step.pc), (1, 41));
+
+        let mut succeeded = trace.clone();
+        succeeded.struct_logs.truncate(2);
+        assert_eq!(revert_location(&succeeded), None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_raw_trace_on_ganache() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "Migrations")?;
+        let (migrations, _) = deploy_contract(
+            &ctx,
+            wallet,
+            "Migrations",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        let receipt = migrations
+            .method::<_, ()>("setCompleted", U256::from(3))?
+            .send()
+            .await?
+            .await?
+            .expect("transaction mined");
+
+        let trace = trace_tx(&provider, receipt.transaction_hash).await?;
+        let steps = trace["structLogs"]
+            .as_array()
+            .expect("the struct logger returns structLogs");
+        assert!(!steps.is_empty());
+        assert_eq!(trace["failed"], json!(false));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_missing_debug_namespace_is_typed() -> Result<()> {
+        // A one-shot mock node answering like Ganache does for unknown methods
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await?;
+            let mut buf = vec![0u8; 4096];
+            let _ = socket.read(&mut buf).await?;
+            let body = r#"{"jsonrpc":"2.0","id":1,"error":{"code":-32601,"message":"The method debug_traceTransaction does not exist/is not available"}}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await?;
+            eyre::Ok(())
+        });
+
+        let provider = Provider::try_from(url)?;
+        let err = trace_transaction(&provider, H256::zero(), TraceKind::CallTracer)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<Error>(),
+                Some(Error::DebugRpcUnsupported { method, .. }) if method == TRACE_METHOD
+            ),
+            "{err}"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Anvil")]
+    async fn test_trace_reverting_busd_transfer_on_anvil() -> Result<()> {
+        let anvil = Anvil::new().spawn();
+        let provider = Provider::try_from(anvil.endpoint())?;
+        let wallet: LocalWallet = anvil.keys()[0].clone().into();
+        let client = Arc::new(SignerMiddleware::new(
+            provider.clone(),
+            wallet.with_chain_id(anvil.chain_id()),
+        ));
+
+        // BUSD is paused after construction, so any transfer reverts
+        let project = compile("examples/").await?.output;
+        let (abi, bytecode) = contract_parts(&project, "examples/", "BUSDImplementation")?;
+        let busd = ContractFactory::new(abi.clone(), bytecode, client.clone())
+            .deploy(())?
+            .send()
+            .await?;
+        let data = abi.function("transfer")?.encode_input(&[
+            Token::Address(Address::from_low_u64_be(0xb0b)),
+            Token::Uint(U256::one()),
+        ])?;
+        // A fixed gas limit skips the estimate, which would refuse the reverting call
+        let tx = TransactionRequest::new()
+            .to(busd.address())
+            .data(data)
+            .gas(200_000);
+        let receipt = client
+            .send_transaction(tx, None)
+            .await?
+            .await?
+            .expect("transaction mined");
+        assert!(!ReceiptSummary::from(&receipt).success);
+
+        let Trace::Calls(root) =
+            trace_transaction(&provider, receipt.transaction_hash, TraceKind::CallTracer).await?
+        else {
+            panic!("callTracer returns call frames");
+        };
+        assert!(root.failed());
+        assert_eq!(root.to, Some(busd.address()));
+        assert_eq!(root.input[..4], [0xa9, 0x05, 0x9c, 0xbb]);
+        assert!(print_call_tree(&root).contains("REVERTED"));
+
+        let Trace::Steps(steps) =
+            trace_transaction(&provider, receipt.transaction_hash, TraceKind::StructLogs).await?
+        else {
+            panic!("the struct logger returns opcode steps");
+        };
+        assert!(steps.failed);
+        assert_eq!(
+            steps.struct_logs.last().map(|step| step.op.as_str()),
+            Some("REVERT")
+        );
+
+        Ok(())
+    }
+}
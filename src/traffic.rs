@@ -0,0 +1,335 @@
+//! Sustained transfer traffic between a set of wallets, for load-testing whatever watches the
+//! chain.
+
+use crate::{
+    chain_context::ChainContext, polling::PendingTxExt, timeouts::OpTimeouts, wallet::bind_to_chain,
+};
+use ethers::{
+    core::rand::{rngs::StdRng, Rng, SeedableRng},
+    prelude::{
+        BlockNumber, JsonRpcClient, LocalWallet, Middleware, PendingTransaction, Provider, Signer,
+        TransactionRequest, U256,
+    },
+    types::transaction::eip2718::TypedTransaction,
+};
+use eyre::{eyre, Result};
+use serde::Serialize;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::{task::JoinSet, time::Instant};
+use tracing::{debug, info};
+
+/// Gas of a plain ETH transfer between accounts without code.
+const TRANSFER_GAS: u64 = 21_000;
+
+/// Knobs for [`generate`].
+#[derive(Clone, Debug)]
+pub struct TrafficOptions {
+    /// Seed of the recipient and amount sequence; a random one when `None`.
+    pub seed: Option<u64>,
+    /// Largest amount sent, in Wei; each transfer sends between 1 and this.
+    pub max_value: u64,
+    /// Limits for the RPC calls and the receipt waits.
+    pub timeouts: OpTimeouts,
+}
+
+impl Default for TrafficOptions {
+    fn default() -> Self {
+        Self {
+            seed: None,
+            max_value: 1_000,
+            timeouts: OpTimeouts::default(),
+        }
+    }
+}
+
+/// Counts and latencies of a [`generate`] run.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrafficSummary {
+    pub submitted: u64,
+    pub mined: u64,
+    /// Transfers the node refused, that reverted, or whose receipt never came.
+    pub failed: u64,
+    /// Seed the sequence was generated from, to replay it.
+    pub seed: u64,
+    /// Time from the first submission to the last receipt.
+    pub elapsed_ms: u64,
+    /// Mined transfers per second over `elapsed_ms`.
+    pub mined_per_sec: f64,
+    /// Submission-to-receipt latency percentiles of the mined transfers.
+    pub latency_p50_ms: u64,
+    pub latency_p90_ms: u64,
+    pub latency_p99_ms: u64,
+    pub latency_max_ms: u64,
+}
+
+/// One transfer of the sequence: indices into the wallets, and the amount in Wei.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlannedTransfer {
+    pub from: usize,
+    pub to: usize,
+    pub value: u64,
+}
+
+/// The transfers [`generate`] sends for `seed`: senders take turns, and each picks a seeded
+/// recipient among the other wallets and a seeded amount between 1 and `max_value` Wei.
+pub fn plan(seed: u64, wallets: usize, max_value: u64) -> impl Iterator<Item = PlannedTransfer> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    (0..).map(move |turn| {
+        let from = turn % wallets;
+        // Skip the sender itself by shifting the recipients above it
+        let to = (from + rng.gen_range(1..wallets)) % wallets;
+        PlannedTransfer {
+            from,
+            to,
+            value: rng.gen_range(1..=max_value.max(1)),
+        }
+    })
+}
+
+/// Limits sends to `rate` per second, allowing bursts of at most one second's worth.
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    /// A bucket refilled with `rate` tokens per second, starting with a single token so a run
+    /// ramps up instead of bursting.
+    pub fn new(rate: f64) -> Self {
+        Self {
+            rate,
+            capacity: rate.max(1.0),
+            tokens: 1.0,
+            last: Instant::now(),
+        }
+    }
+
+    /// Waits until a token is available and takes it.
+    pub async fn acquire(&mut self) {
+        loop {
+            let now = Instant::now();
+            let refill = now.duration_since(self.last).as_secs_f64() * self.rate;
+            self.tokens = (self.tokens + refill).min(self.capacity);
+            self.last = now;
+            if self.tokens >= 1.0 {
+                self.tokens -= 1.0;
+                return;
+            }
+            let wait = (1.0 - self.tokens) / self.rate;
+            tokio::time::sleep(Duration::from_secs_f64(wait)).await;
+        }
+    }
+}
+
+/// Sends small transfers round-robin between `wallets` through `ctx` at `rate_per_sec` for
+/// `duration`, then waits for the outstanding receipts and summarizes the run.
+///
+/// Each wallet's nonce is read once and then counted locally, and the transactions are signed
+/// locally with a gas price read up front, so a send costs a single `eth_sendRawTransaction`.
+/// Keep the wallets free of other traffic during the run, or the local nonces go stale.
+pub async fn generate<T: JsonRpcClient + Clone + 'static>(
+    ctx: &ChainContext<Provider<T>>,
+    wallets: Vec<LocalWallet>,
+    rate_per_sec: f64,
+    duration: Duration,
+    options: &TrafficOptions,
+) -> Result<TrafficSummary> {
+    if wallets.len() < 2 {
+        return Err(eyre!("Traffic needs at least two wallets"));
+    }
+    if rate_per_sec <= 0.0 {
+        return Err(eyre!("The rate must be positive, got {rate_per_sec}"));
+    }
+    let timeouts = options.timeouts;
+    let seed = options
+        .seed
+        .unwrap_or_else(|| ethers::core::rand::thread_rng().gen());
+
+    let mut signers = Vec::with_capacity(wallets.len());
+    let mut nonces = Vec::with_capacity(wallets.len());
+    for wallet in wallets {
+        let wallet = timeouts.rpc("chain id", bind_to_chain(ctx, wallet)).await?;
+        let nonce = timeouts
+            .rpc(
+                "nonce",
+                ctx.get_transaction_count(wallet.address(), Some(BlockNumber::Pending.into())),
+            )
+            .await?;
+        nonces.push(nonce);
+        signers.push(wallet);
+    }
+    let gas_price = timeouts.rpc("gas price", ctx.get_gas_price()).await?;
+    info!(
+        wallets = signers.len(),
+        rate_per_sec,
+        ?duration,
+        seed,
+        "generating traffic"
+    );
+
+    let latencies = Arc::new(Mutex::new(Vec::new()));
+    let mut summary = TrafficSummary {
+        seed,
+        ..Default::default()
+    };
+    let mut receipts = JoinSet::new();
+    let mut bucket = TokenBucket::new(rate_per_sec);
+    let start = Instant::now();
+    for transfer in plan(seed, signers.len(), options.max_value) {
+        bucket.acquire().await;
+        if start.elapsed() >= duration {
+            break;
+        }
+        let wallet = &signers[transfer.from];
+        let tx: TypedTransaction =
+            TransactionRequest::pay(signers[transfer.to].address(), transfer.value)
+                .from(wallet.address())
+                .nonce(nonces[transfer.from])
+                .gas(TRANSFER_GAS)
+                .gas_price(gas_price)
+                .chain_id(wallet.chain_id())
+                .into();
+        let signature = wallet.sign_transaction(&tx).await?;
+        let raw = tx.rlp_signed(&signature);
+
+        summary.submitted += 1;
+        let submitted_at = Instant::now();
+        let tx_hash = match timeouts.rpc("send", ctx.send_raw_transaction(raw)).await {
+            Ok(pending) => {
+                nonces[transfer.from] += U256::one();
+                pending.tx_hash()
+            }
+            Err(err) => {
+                // The nonce wasn't used, so the sender's next transfer reuses it
+                debug!(error = %format!("{err:#}"), "transfer refused");
+                summary.failed += 1;
+                continue;
+            }
+        };
+
+        let (ctx, latencies) = (ctx.clone(), latencies.clone());
+        receipts.spawn(async move {
+            let receipt = timeouts
+                .receipt(
+                    "receipt",
+                    PendingTransaction::new(tx_hash, ctx.provider()).await_smart(&ctx),
+                )
+                .await?;
+            if receipt.status != Some(1u64.into()) {
+                return Err(eyre!("Transfer {tx_hash:?} reverted"));
+            }
+            latencies
+                .lock()
+                .expect("latencies lock poisoned")
+                .push(submitted_at.elapsed());
+            Ok(())
+        });
+    }
+
+    while let Some(outcome) = receipts.join_next().await {
+        match outcome? {
+            Ok(()) => summary.mined += 1,
+            Err(err) => {
+                debug!(error = %format!("{err:#}"), "transfer failed");
+                summary.failed += 1;
+            }
+        }
+    }
+    let elapsed = start.elapsed();
+    let mut latencies = std::mem::take(&mut *latencies.lock().expect("latencies lock poisoned"));
+    latencies.sort();
+    summary.elapsed_ms = elapsed.as_millis() as u64;
+    summary.mined_per_sec = summary.mined as f64 / elapsed.as_secs_f64();
+    summary.latency_p50_ms = percentile_ms(&latencies, 50);
+    summary.latency_p90_ms = percentile_ms(&latencies, 90);
+    summary.latency_p99_ms = percentile_ms(&latencies, 99);
+    summary.latency_max_ms = latencies.last().map_or(0, |max| max.as_millis() as u64);
+    Ok(summary)
+}
+
+/// Nearest-rank percentile of `sorted`, in milliseconds; 0 when it's empty.
+pub fn percentile_ms(sorted: &[Duration], percent: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (percent * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1].as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethers::utils::Ganache;
+
+    #[test]
+    fn test_plan_is_reproducible_and_round_robin() {
+        let first: Vec<_> = plan(7, 4, 1_000).take(40).collect();
+        let again: Vec<_> = plan(7, 4, 1_000).take(40).collect();
+        assert_eq!(first, again);
+        assert_ne!(first, plan(8, 4, 1_000).take(40).collect::<Vec<_>>());
+        for (turn, transfer) in first.iter().enumerate() {
+            assert_eq!(transfer.from, turn % 4);
+            assert_ne!(transfer.to, transfer.from);
+            assert!(transfer.to < 4);
+            assert!((1..=1_000).contains(&transfer.value));
+        }
+    }
+
+    #[test]
+    fn test_percentiles() {
+        let sorted: Vec<_> = (1..=100).map(Duration::from_millis).collect();
+        assert_eq!(percentile_ms(&sorted, 50), 50);
+        assert_eq!(percentile_ms(&sorted, 90), 90);
+        assert_eq!(percentile_ms(&sorted, 99), 99);
+        assert_eq!(percentile_ms(&sorted[..1], 99), 1);
+        assert_eq!(percentile_ms(&[], 50), 0);
+    }
+
+    #[tokio::test]
+    async fn test_token_bucket_paces_sends() {
+        let mut bucket = TokenBucket::new(50.0);
+        let start = Instant::now();
+        for _ in 0..11 {
+            bucket.acquire().await;
+        }
+        // One token up front, then ten refilled at 50 per second
+        assert!(start.elapsed() >= Duration::from_millis(190));
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_two_seconds_at_twenty_per_second() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let ctx = ChainContext::new(Provider::try_from(ganache.endpoint())?);
+        let wallets: Vec<LocalWallet> = ganache.keys()[..5]
+            .iter()
+            .map(|key| key.clone().into())
+            .collect();
+
+        let options = TrafficOptions {
+            seed: Some(42),
+            ..Default::default()
+        };
+        let summary = generate(&ctx, wallets, 20.0, Duration::from_secs(2), &options).await?;
+
+        assert!((30..=45).contains(&summary.submitted), "{summary:?}");
+        assert_eq!(summary.mined, summary.submitted);
+        assert_eq!(summary.failed, 0);
+        assert_eq!(summary.seed, 42);
+        assert!(summary.latency_p50_ms <= summary.latency_p90_ms);
+        assert!(summary.latency_p90_ms <= summary.latency_p99_ms);
+        assert!(summary.latency_p99_ms <= summary.latency_max_ms);
+        let expected_rate = summary.mined as f64 * 1000.0 / summary.elapsed_ms as f64;
+        assert!((summary.mined_per_sec - expected_rate).abs() < 0.1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,632 @@
+//! Plain ETH transfer helpers.
+
+use crate::chain_context::ChainContext;
+use crate::chain_guard::ChainGuard;
+use crate::errors::Error;
+use crate::funds::ensure_funds;
+use crate::notify::{deliver, Notification, NotificationEvent, Notifier};
+use crate::polling::PendingTxExt;
+#[cfg(feature = "sqlite")]
+use crate::sqlite::Ledger;
+use crate::timeouts::OpTimeouts;
+use crate::tx_builder::TxBuilder;
+use crate::wallet::bind_to_chain;
+use ethers::prelude::{
+    Address, BlockNumber, LocalWallet, Middleware, Signer, SignerMiddleware, TransactionReceipt,
+    TransactionRequest, TxHash, U256,
+};
+use eyre::{ContextCompat, Result};
+use serde::Serialize;
+use std::sync::Arc;
+#[cfg(feature = "sqlite")]
+use tracing::warn;
+use tracing::{info, instrument};
+
+/// Summary of a mined transfer.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferResult {
+    pub tx_hash: TxHash,
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+    pub block_number: u64,
+    pub gas_used: U256,
+}
+
+impl TransferResult {
+    /// Builds the result from the transfer's receipt and the value that was sent.
+    pub fn from_receipt(receipt: &TransactionReceipt, value: U256) -> Result<Self> {
+        Ok(Self {
+            tx_hash: receipt.transaction_hash,
+            from: receipt.from,
+            to: receipt.to.context("Receipt has no recipient")?,
+            value,
+            block_number: receipt
+                .block_number
+                .context("Receipt has no block number")?
+                .as_u64(),
+            gas_used: receipt.gas_used.context("Receipt has no gas used")?,
+        })
+    }
+}
+
+/// Knobs for [`send_transfer`].
+#[derive(Clone, Debug)]
+pub struct TransferOptions {
+    /// Run [`ensure_funds`] before sending. Disable when the balance is expected to
+    /// change on-chain between the check and the send.
+    pub check_funds: bool,
+    /// Limits for the RPC calls and the receipt wait.
+    pub timeouts: OpTimeouts,
+    /// Chain id the provider must serve; checked with a [`ChainGuard`] before anything is sent.
+    pub expected_chain: Option<u64>,
+    /// Gas limit of the transfer instead of the node's estimate, e.g. for a recipient contract
+    /// whose `receive` needs more than 21000. Running out of it fails with [`Error::OutOfGas`],
+    /// any other revert with [`Error::TransactionReverted`].
+    pub gas_limit: Option<U256>,
+    /// Told about the transfer once it was mined or failed; its failures are only logged.
+    pub notifier: Option<Arc<dyn Notifier>>,
+    /// Database recording the transfer and both parties' balances after it; its failures are
+    /// only logged.
+    #[cfg(feature = "sqlite")]
+    pub ledger: Option<Arc<Ledger>>,
+}
+
+impl Default for TransferOptions {
+    fn default() -> Self {
+        Self {
+            check_funds: true,
+            timeouts: OpTimeouts::default(),
+            expected_chain: None,
+            gas_limit: None,
+            notifier: None,
+            #[cfg(feature = "sqlite")]
+            ledger: None,
+        }
+    }
+}
+
+/// Sends `value` Wei from the node-unlocked account `from` to `to` and waits for one confirmation.
+///
+/// The node signs the transaction, so this only works for accounts it unlocked (e.g. Ganache's);
+/// use [`send_signed_transfer`] with any other endpoint. Lookups and the receipt wait go
+/// through `ctx`.
+#[instrument(name = "transfer", skip(ctx, options), fields(from = ?from, to = ?to, value = %value))]
+pub async fn send_transfer<M: Middleware + 'static>(
+    ctx: &ChainContext<M>,
+    from: Address,
+    to: Address,
+    value: U256,
+    options: &TransferOptions,
+) -> Result<TransactionReceipt>
+where
+    M::Error: 'static,
+{
+    transfer_through(ctx, ctx, from, to, value, options).await
+}
+
+/// Sends `value` Wei from `wallet` to `to`, signed locally with EIP-155 replay protection for
+/// `ctx`'s chain, and waits for one confirmation.
+#[instrument(name = "transfer", skip(ctx, wallet, options), fields(from = ?wallet.address(), to = ?to, value = %value))]
+pub async fn send_signed_transfer<M: Middleware + Clone + 'static>(
+    ctx: &ChainContext<M>,
+    wallet: LocalWallet,
+    to: Address,
+    value: U256,
+    options: &TransferOptions,
+) -> Result<TransactionReceipt>
+where
+    M::Error: 'static,
+{
+    // Bind the wallet to the provider's chain id (required to sign for the correct chain)
+    let wallet = options
+        .timeouts
+        .rpc("chain id", bind_to_chain(ctx, wallet))
+        .await?;
+    let from = wallet.address();
+    let client = SignerMiddleware::new(ctx.clone(), wallet);
+    transfer_through(ctx, &client, from, to, value, options).await
+}
+
+// Shared by both signing paths: `client` decides who signs, on top of `ctx`
+async fn transfer_through<M: Middleware + 'static, C: Middleware>(
+    ctx: &ChainContext<M>,
+    client: &C,
+    from: Address,
+    to: Address,
+    value: U256,
+    options: &TransferOptions,
+) -> Result<TransactionReceipt>
+where
+    M::Error: 'static,
+    C::Error: 'static,
+{
+    let sent = send_through(ctx, client, from, to, value, options).await;
+    if let Some(notifier) = &options.notifier {
+        let chain_id = options.timeouts.rpc("chain id", ctx.chain_id()).await.ok();
+        let mut notification = Notification {
+            chain_id,
+            recipient: Some(to),
+            ..Notification::new(NotificationEvent::Transfer, sent.is_ok()).value(value)
+        };
+        match &sent {
+            Ok(receipt) => {
+                notification.tx_hash = Some(receipt.transaction_hash);
+                notification = notification.gas_cost(receipt.gas_used, receipt.effective_gas_price);
+            }
+            Err(err) => notification.error = Some(format!("{err:#}")),
+        }
+        deliver(Some(notifier), notification).await;
+    }
+    sent
+}
+
+async fn send_through<M: Middleware + 'static, C: Middleware>(
+    ctx: &ChainContext<M>,
+    client: &C,
+    from: Address,
+    to: Address,
+    value: U256,
+    options: &TransferOptions,
+) -> Result<TransactionReceipt>
+where
+    M::Error: 'static,
+    C::Error: 'static,
+{
+    // Create a transaction request to send `value` Wei from `from` to `to`
+    let mut tx = TxBuilder::new().from(from).to(to).value(value);
+    if let Some(gas_limit) = options.gas_limit {
+        tx = tx.gas_limit(gas_limit); // Otherwise the node estimates it
+    }
+
+    let timeouts = &options.timeouts;
+    // Refuse to go on if the endpoint isn't the chain the caller meant
+    if let Some(expected) = options.expected_chain {
+        timeouts
+            .rpc("chain check", ChainGuard::new(expected).check(ctx))
+            .await?;
+    }
+    if options.check_funds {
+        // Estimate with a zero value so the node doesn't reject the estimate itself for lack of funds
+        let gas = match options.gas_limit {
+            Some(gas_limit) => gas_limit,
+            None => {
+                let estimate_tx = tx.clone().value(0u64).build(ctx).await?;
+                timeouts
+                    .rpc("gas estimation", ctx.estimate_gas(&estimate_tx, None))
+                    .await?
+            }
+        };
+        timeouts
+            .rpc("funds check", ensure_funds(ctx, from, value, gas))
+            .await?;
+    }
+
+    // Send the transaction
+    let pending = timeouts
+        .rpc("transfer submission", tx.send(client))
+        .await?
+        .inspect(|tx_hash| info!(tx_hash = ?**tx_hash, "pending transfer")); // Log the pending transaction
+
+    // Wait for it to be mined, checking more often while it's fresh
+    let receipt = timeouts
+        .receipt("transfer receipt", pending.await_smart(ctx))
+        .await?;
+    info!(block = ?receipt.block_number, "transfer mined");
+    if receipt.status != Some(1u64.into()) {
+        // A revert that burned the whole explicit limit means the limit was too low
+        if let Some(gas_limit) = options
+            .gas_limit
+            .filter(|&limit| receipt.gas_used == Some(limit))
+        {
+            return Err(Error::OutOfGas {
+                tx_hash: receipt.transaction_hash,
+                gas_limit,
+            }
+            .into());
+        }
+        return Err(Error::TransactionReverted {
+            tx_hash: receipt.transaction_hash,
+        }
+        .into());
+    }
+
+    #[cfg(feature = "sqlite")]
+    if let Some(ledger) = &options.ledger {
+        // The transfer is mined whatever the database says, so a failed write is only logged
+        let recorded = async {
+            let chain_id = ctx.chain_id().await?;
+            ledger.record_transaction(chain_id, &receipt, value)?;
+            ledger.snapshot_balances(ctx, &[from, to]).await
+        }
+        .await;
+        if let Err(err) = recorded {
+            warn!(tx_hash = ?receipt.transaction_hash, error = %format!("{err:#}"), "transfer not recorded");
+        }
+    }
+
+    Ok(receipt)
+}
+
+/// Cancels the pending transaction at `nonce` from `client`'s sender by replacing it with a
+/// zero-value self-transfer at `gas_price`, which must beat the original's price for the node to
+/// accept the replacement. Returns the hash of the replacement without waiting for it.
+///
+/// Fails with [`Error::NoPendingTransaction`] when the nonce is already mined or was never used.
+pub async fn cancel_tx<M: Middleware>(client: &M, nonce: u64, gas_price: U256) -> Result<TxHash>
+where
+    M::Error: 'static,
+{
+    let from = client
+        .default_sender()
+        .context("The client has no sender to cancel transactions for")?;
+
+    // A nonce is pending when it's past the mined count but below the pending one
+    let mined = client
+        .get_transaction_count(from, Some(BlockNumber::Latest.into()))
+        .await?;
+    let pending = client
+        .get_transaction_count(from, Some(BlockNumber::Pending.into()))
+        .await?;
+    if !(mined..pending).contains(&U256::from(nonce)) {
+        return Err(Error::NoPendingTransaction { from, nonce }.into());
+    }
+
+    let tx = TransactionRequest::pay(from, 0u64)
+        .from(from)
+        .nonce(nonce)
+        .gas(21_000u64)
+        .gas_price(gas_price);
+    let replacement = client.send_transaction(tx, None).await?;
+    info!(tx_hash = ?*replacement, nonce, %gas_price, "cancellation sent");
+    Ok(*replacement)
+}
+
+/// Sends the whole balance of the sender of `ctx`'s client to `to`, minus the gas the transfer
+/// costs, and waits for one confirmation within `timeouts`. The sender is left with at most the
+/// gas it didn't use.
+///
+/// The transfer is priced as legacy so its cost is known up front; fails with
+/// [`Error::InsufficientFunds`] when the balance doesn't cover that cost.
+#[instrument(skip(ctx), fields(to = ?to))]
+pub async fn sweep<M: Middleware + 'static>(
+    ctx: &ChainContext<M>,
+    to: Address,
+    timeouts: &OpTimeouts,
+) -> Result<TransactionReceipt>
+where
+    M::Error: 'static,
+{
+    let from = ctx
+        .default_sender()
+        .context("The client has no sender to sweep")?;
+    let balance = ctx.get_balance(from, None).await?;
+
+    // Estimate with a zero value so the node doesn't reject the estimate for lack of funds
+    let estimate_tx = TransactionRequest::pay(to, 0u64).from(from);
+    let gas = ctx.estimate_gas(&estimate_tx.into(), None).await?;
+    let gas_price = ctx.get_gas_price().await?;
+    let cost = gas * gas_price;
+    if balance < cost {
+        return Err(Error::InsufficientFunds {
+            have: balance,
+            need: cost,
+            shortfall: cost - balance,
+        }
+        .into());
+    }
+
+    let value = balance - cost;
+    let tx = TransactionRequest::pay(to, value)
+        .from(from)
+        .gas(gas)
+        .gas_price(gas_price);
+    let pending = timeouts
+        .rpc("sweep submission", ctx.send_transaction(tx, None))
+        .await?;
+    let receipt = timeouts
+        .receipt("sweep receipt", pending.await_smart(ctx))
+        .await?;
+    info!(%value, block = ?receipt.block_number, "balance swept");
+    Ok(receipt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::deploy::{deploy_contract, DeployOptions};
+    use crate::project::{compile, contract_parts};
+    use crate::receipt::ReceiptSummary;
+    use crate::test_support::{spawn_mock_node, TestActors};
+    use ethers::prelude::{PendingTransaction, Provider, H256};
+    use ethers::types::transaction::eip2718::TypedTransaction;
+    use ethers::utils::rlp::Rlp;
+    use ethers::utils::{parse_units, Ganache};
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_cancel_stuck_transfer() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        // Blocks every 2 seconds so the transfer stays pending long enough to replace
+        let ganache = Ganache::new().mnemonic(mnemonic).block_time(2u64).spawn();
+        let from = ganache.addresses()[0];
+        let provider = Provider::try_from(ganache.endpoint())?
+            .interval(Duration::from_millis(100))
+            .with_sender(from);
+        let recipient = Address::from_low_u64_be(0xb0b);
+
+        // Nothing is pending yet
+        let err = cancel_tx(&provider, 0, parse_units(10, "gwei")?.into())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::NoPendingTransaction { nonce: 0, .. })
+        ));
+
+        let stuck = TransactionRequest::pay(recipient, 1000u64)
+            .from(from)
+            .nonce(0u64)
+            .gas(21_000u64)
+            .gas_price(U256::from(parse_units(1, "gwei")?));
+        let original = *provider.send_transaction(stuck, None).await?;
+
+        let cancellation = cancel_tx(&provider, 0, parse_units(10, "gwei")?.into()).await?;
+        assert_ne!(cancellation, original);
+        let receipt = PendingTransaction::new(cancellation, provider.provider())
+            .interval(Duration::from_millis(100))
+            .await?
+            .context("Missing receipt")?;
+        assert!(ReceiptSummary::from(receipt).success);
+
+        // The original was dropped: no receipt, and the recipient got nothing
+        assert!(provider.get_transaction_receipt(original).await?.is_none());
+        assert!(provider.get_balance(recipient, None).await?.is_zero());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_signed_transfer_carries_chain_id() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let to = Address::from_low_u64_be(0xb0b);
+
+        // A wallet left on the default chain id is rebound to the provider's
+        let receipt = send_signed_transfer(
+            &ctx,
+            wallet.clone(),
+            to,
+            U256::from(1000u64),
+            &TransferOptions::default(),
+        )
+        .await?;
+        assert_eq!(receipt.from, wallet.address());
+
+        // Decode the raw signed transaction and recover its signer
+        let tx = provider
+            .get_transaction(receipt.transaction_hash)
+            .await?
+            .context("Missing transaction")?;
+        let raw = tx.rlp();
+        let (decoded, signature) = TypedTransaction::decode_signed(&Rlp::new(&raw))?;
+        assert_eq!(signature.recover(decoded.sighash())?, wallet.address());
+        // EIP-155: v = chain_id * 2 + 35 or 36
+        assert!(
+            signature.v == chain_id * 2 + 35 || signature.v == chain_id * 2 + 36,
+            "v = {}",
+            signature.v
+        );
+        assert_eq!(decoded.chain_id(), Some(chain_id.into()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_sweep_empties_the_account() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let actors = TestActors::new(&ganache).await?;
+        let (alice, bob) = (actors.alice(), actors.bob());
+        let ctx = ChainContext::new(alice.clone());
+        let before = actors.provider().get_balance(bob.address(), None).await?;
+
+        let receipt = sweep(&ctx, bob.address(), &OpTimeouts::default()).await?;
+        assert!(ReceiptSummary::from(&receipt).success);
+        let gas_price = receipt.effective_gas_price.context("Missing gas price")?;
+        let remaining = actors.provider().get_balance(alice.address(), None).await?;
+        assert!(remaining < U256::from(21_000u64) * gas_price, "{remaining}");
+        let after = actors.provider().get_balance(bob.address(), None).await?;
+        let tx = actors
+            .provider()
+            .get_transaction(receipt.transaction_hash)
+            .await?
+            .context("Missing transaction")?;
+        assert_eq!(after - before, tx.value);
+
+        // Nothing left to pay for another sweep
+        let err = sweep(&ctx, bob.address(), &OpTimeouts::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::InsufficientFunds { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sweep_gives_up_on_a_missing_receipt() -> Result<()> {
+        let from = Address::from_low_u64_be(0xa11ce);
+        // A node that accepts the sweep but never mines it
+        let url = spawn_mock_node(|method, _| match method {
+            "eth_getBalance" => json!("0xde0b6b3a7640000"),
+            "eth_estimateGas" => json!("0x5208"),
+            "eth_gasPrice" => json!("0x3b9aca00"),
+            "eth_sendTransaction" => json!(H256::from_low_u64_be(0x5eeb)),
+            "eth_getTransactionReceipt" => json!(null),
+            _ => json!({ "error": { "code": -32601, "message": "method not found" } }),
+        })
+        .await?;
+        let ctx = ChainContext::new(Provider::try_from(url)?.with_sender(from));
+
+        let timeouts = OpTimeouts {
+            rpc: Duration::from_secs(2),
+            receipt: Duration::from_millis(300),
+        };
+        let err = sweep(&ctx, Address::from_low_u64_be(0xb0b), &timeouts)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<Error>(),
+                Some(Error::OperationTimedOut { operation, .. }) if operation == "sweep receipt"
+            ),
+            "Unexpected error: {err:?}"
+        );
+        Ok(())
+    }
+
+    const COUNTER_SOURCE: &str = r#"// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+contract PaymentCounter {
+    uint256 public payments;
+
+    receive() external payable {
+        payments += 1;
+    }
+}
+"#;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_transfer_to_a_contract_with_an_explicit_gas_limit() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let from = ganache.addresses()[0];
+
+        let dir = std::env::temp_dir().join(format!("payment-counter-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("PaymentCounter.sol"), COUNTER_SOURCE)?;
+        let folder = format!("{}/", dir.display());
+        let project = compile(&folder).await?.output;
+        let (abi, bytecode) = contract_parts(&project, &folder, "PaymentCounter")?;
+        std::fs::remove_dir_all(&dir)?;
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let (counter, _) = deploy_contract(
+            &ctx,
+            wallet,
+            "PaymentCounter",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+        let value = U256::from(1000u64);
+
+        // 21000 only covers a plain transfer, not the storage write in `receive`
+        let options = TransferOptions {
+            gas_limit: Some(21_000u64.into()),
+            ..Default::default()
+        };
+        let err = send_transfer(&ctx, from, counter.address(), value, &options)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<Error>(),
+            Some(Error::OutOfGas { gas_limit, .. }) if *gas_limit == U256::from(21_000u64)
+        ));
+
+        let options = TransferOptions {
+            gas_limit: Some(100_000u64.into()),
+            ..Default::default()
+        };
+        let receipt = send_transfer(&ctx, from, counter.address(), value, &options).await?;
+        assert!(ReceiptSummary::from(&receipt).success);
+        let tx = provider
+            .get_transaction(receipt.transaction_hash)
+            .await?
+            .context("Missing transaction")?;
+        assert_eq!(tx.gas, U256::from(100_000u64));
+        let payments: U256 = counter.method("payments", ())?.call().await?;
+        assert_eq!(payments, U256::one());
+        assert_eq!(provider.get_balance(counter.address(), None).await?, value);
+
+        Ok(())
+    }
+
+    const REFUSER_SOURCE: &str = r#"// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+contract Refuser {
+    receive() external payable {
+        revert("no payments");
+    }
+}
+"#;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_transfer_reverted_by_receive_fails() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let ctx = ChainContext::new(provider.clone());
+        let from = ganache.addresses()[0];
+
+        let dir = std::env::temp_dir().join(format!("refuser-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("Refuser.sol"), REFUSER_SOURCE)?;
+        let folder = format!("{}/", dir.display());
+        let project = compile(&folder).await?.output;
+        let (abi, bytecode) = contract_parts(&project, &folder, "Refuser")?;
+        std::fs::remove_dir_all(&dir)?;
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let (refuser, _) = deploy_contract(
+            &ctx,
+            wallet,
+            "Refuser",
+            abi,
+            bytecode,
+            (),
+            &DeployOptions::default(),
+        )
+        .await?;
+
+        // The node can't estimate a reverting transfer, so the limit is explicit and never reached
+        let options = TransferOptions {
+            gas_limit: Some(100_000u64.into()),
+            ..Default::default()
+        };
+        let err = send_transfer(&ctx, from, refuser.address(), U256::from(1000u64), &options)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(
+                err.downcast_ref::<Error>(),
+                Some(Error::TransactionReverted { .. })
+            ),
+            "Unexpected error: {err:?}"
+        );
+        assert!(provider
+            .get_balance(refuser.address(), None)
+            .await?
+            .is_zero());
+
+        Ok(())
+    }
+}
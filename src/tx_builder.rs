@@ -0,0 +1,248 @@
+//! Fluent construction of legacy, EIP-2930 and EIP-1559 transactions.
+
+use crate::fees::{resolve_fee_mode, FeeMode};
+use ethers::prelude::{
+    Address, Bytes, Eip1559TransactionRequest, Eip2930TransactionRequest, Middleware,
+    PendingTransaction, TransactionRequest, U256,
+};
+use ethers::types::transaction::{eip2718::TypedTransaction, eip2930::AccessList};
+use eyre::Result;
+
+/// Builds a transaction step by step and sends it through any [`Middleware`].
+#[derive(Clone, Debug, Default)]
+pub struct TxBuilder {
+    from: Option<Address>,
+    to: Option<Address>,
+    value: Option<U256>,
+    data: Option<Bytes>,
+    gas_limit: Option<U256>,
+    fee_mode: FeeMode,
+    access_list: Option<AccessList>,
+}
+
+impl TxBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sender of the transaction; defaults to the middleware's signer or the node's first account.
+    pub fn from(mut self, from: Address) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    /// Recipient of the transaction; leave unset for contract creations.
+    pub fn to(mut self, to: Address) -> Self {
+        self.to = Some(to);
+        self
+    }
+
+    /// Amount of Wei to send.
+    pub fn value(mut self, wei: impl Into<U256>) -> Self {
+        self.value = Some(wei.into());
+        self
+    }
+
+    /// Calldata (or init code for contract creations).
+    pub fn data(mut self, data: impl Into<Bytes>) -> Self {
+        self.data = Some(data.into());
+        self
+    }
+
+    /// Explicit gas limit; estimated by the middleware when unset.
+    pub fn gas_limit(mut self, gas: impl Into<U256>) -> Self {
+        self.gas_limit = Some(gas.into());
+        self
+    }
+
+    /// How the transaction fees are priced. [`FeeMode::Auto`] is resolved against the chain when building.
+    pub fn fee_mode(mut self, mode: FeeMode) -> Self {
+        self.fee_mode = mode;
+        self
+    }
+
+    /// Addresses and storage slots to pre-warm (EIP-2930), e.g. from [`create_access_list`].
+    ///
+    /// Legacy-priced transactions with an access list are sent as EIP-2930 transactions.
+    pub fn access_list(mut self, access_list: AccessList) -> Self {
+        self.access_list = Some(access_list);
+        self
+    }
+
+    /// Produces the typed transaction, resolving [`FeeMode::Auto`] against `client`'s chain.
+    pub async fn build<M: Middleware>(&self, client: &M) -> Result<TypedTransaction>
+    where
+        M::Error: 'static,
+    {
+        let tx = match resolve_fee_mode(client, self.fee_mode).await? {
+            FeeMode::Eip1559 => {
+                let mut tx = Eip1559TransactionRequest::new();
+                tx.from = self.from;
+                tx.to = self.to.map(Into::into);
+                tx.value = self.value;
+                tx.data = self.data.clone();
+                tx.gas = self.gas_limit;
+                if let Some(access_list) = &self.access_list {
+                    tx.access_list = access_list.clone();
+                }
+                TypedTransaction::Eip1559(tx)
+            }
+            _ => {
+                let mut tx = TransactionRequest::new();
+                tx.from = self.from;
+                tx.to = self.to.map(Into::into);
+                tx.value = self.value;
+                tx.data = self.data.clone();
+                tx.gas = self.gas_limit;
+                match &self.access_list {
+                    Some(access_list) => TypedTransaction::Eip2930(Eip2930TransactionRequest::new(
+                        tx,
+                        access_list.clone(),
+                    )),
+                    None => TypedTransaction::Legacy(tx),
+                }
+            }
+        };
+        Ok(tx)
+    }
+
+    /// Builds the transaction and submits it, returning the pending transaction.
+    pub async fn send<'a, M: Middleware>(
+        &self,
+        client: &'a M,
+    ) -> Result<PendingTransaction<'a, M::Provider>>
+    where
+        M::Error: 'static,
+    {
+        let tx = self.build(client).await?;
+        Ok(client.send_transaction(tx, None).await?)
+    }
+}
+
+/// Asks the node (`eth_createAccessList`) which addresses and storage slots `tx` touches.
+///
+/// The sender, the recipient and precompiles are left out, as they are warm anyway.
+pub async fn create_access_list<M: Middleware>(
+    provider: &M,
+    tx: &TypedTransaction,
+) -> Result<AccessList>
+where
+    M::Error: 'static,
+{
+    Ok(provider.create_access_list(tx, None).await?.access_list)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::compile;
+    use crate::proxy::{deploy_upgradeable, erc1967_proxy_artifact};
+    use crate::receipt::ReceiptSummary;
+    use ethers::prelude::{LocalWallet, Provider, Signer, SignerMiddleware};
+    use ethers::utils::{Anvil, Ganache};
+    use eyre::ContextCompat;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache")]
+    async fn test_builder_sets_all_fields_and_sends() -> Result<()> {
+        let mnemonic = "gas monster ski craft below illegal discover limit dog bundle bus artefact";
+        let ganache = Ganache::new().mnemonic(mnemonic).spawn();
+        let provider = Provider::try_from(ganache.endpoint())?;
+        let chain_id = provider.get_chainid().await?.as_u64();
+        let wallet: LocalWallet = ganache.keys()[0].clone().into();
+        let client = SignerMiddleware::new(provider.clone(), wallet.with_chain_id(chain_id));
+
+        let recipient = ganache.addresses()[1];
+        let before = provider.get_balance(recipient, None).await?;
+
+        let builder = TxBuilder::new()
+            .from(ganache.addresses()[0])
+            .to(recipient)
+            .value(1000u64)
+            .data(vec![0xde, 0xad, 0xbe, 0xef])
+            .gas_limit(60_000u64)
+            .fee_mode(FeeMode::Legacy);
+
+        // The built request carries every configured field
+        let tx = builder.build(&client).await?;
+        assert_eq!(tx.to_addr(), Some(&recipient));
+        assert_eq!(tx.value(), Some(&U256::from(1000u64)));
+        assert_eq!(tx.data(), Some(&Bytes::from(vec![0xde, 0xad, 0xbe, 0xef])));
+        assert_eq!(tx.gas(), Some(&U256::from(60_000u64)));
+        assert!(matches!(tx, TypedTransaction::Legacy(_)));
+
+        let receipt = builder
+            .send(&client)
+            .await?
+            .await?
+            .context("Missing receipt")?;
+        assert!(ReceiptSummary::from(&receipt).success);
+        assert_eq!(receipt.from, ganache.addresses()[0]);
+
+        let after = provider.get_balance(recipient, None).await?;
+        assert_eq!(after - before, U256::from(1000u64));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Anvil")]
+    async fn test_access_list_covers_the_called_contract() -> Result<()> {
+        // Ganache doesn't implement eth_createAccessList
+        let anvil = Anvil::new().spawn();
+        let provider = Provider::try_from(anvil.endpoint())?;
+        let wallet: LocalWallet = anvil.keys()[0].clone().into();
+        let owner = wallet.address();
+        let client = Arc::new(SignerMiddleware::new(
+            provider.clone(),
+            wallet.with_chain_id(anvil.chain_id()),
+        ));
+
+        // Calling the proxy delegates to the implementation, which the access list must name
+        let project = compile("examples/").await?.output;
+        let deployment = deploy_upgradeable(
+            &project,
+            "BUSDImplementation",
+            &erc1967_proxy_artifact().await?,
+            "initialize",
+            (),
+            client.clone(),
+        )
+        .await?;
+        let data = deployment
+            .contract
+            .method::<_, U256>("balanceOf", owner)?
+            .calldata()
+            .context("Missing calldata")?;
+        let builder = TxBuilder::new()
+            .from(owner)
+            .to(deployment.contract.address())
+            .data(data)
+            .fee_mode(FeeMode::Legacy);
+
+        let access_list = create_access_list(&provider, &builder.build(&client).await?).await?;
+        assert!(
+            access_list
+                .0
+                .iter()
+                .any(|item| item.address == deployment.implementation),
+            "{access_list:?}"
+        );
+
+        // With the list attached, the legacy-priced call goes out as an EIP-2930 transaction
+        let builder = builder.access_list(access_list.clone());
+        let tx = builder.build(&client).await?;
+        assert_eq!(tx.access_list(), Some(&access_list));
+        assert!(matches!(tx, TypedTransaction::Eip2930(_)));
+        let receipt = builder
+            .send(client.as_ref())
+            .await?
+            .await?
+            .context("Missing receipt")?;
+        assert!(ReceiptSummary::from(&receipt).success);
+        assert_eq!(receipt.transaction_type, Some(1u64.into()));
+
+        Ok(())
+    }
+}
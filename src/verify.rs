@@ -0,0 +1,473 @@
+//! Source verification of deployed contracts on Etherscan (and its per-chain explorers).
+
+use crate::errors::Error;
+use ethers::types::{Address, Bytes, Chain};
+use ethers_solc::{Project, Solc};
+use eyre::{eyre, Context, ContextCompat, Result};
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::Deserialize;
+use std::{sync::Arc, time::Duration};
+use tracing::{info, instrument, warn};
+
+/// Outcome of a verification request.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VerificationStatus {
+    /// Etherscan accepted the source.
+    Verified,
+    /// The contract was verified before; treated as success.
+    AlreadyVerified,
+    /// Etherscan rejected the source, with its reason.
+    Failed(String),
+}
+
+impl VerificationStatus {
+    /// Whether the contract's source is verified now.
+    pub fn is_success(&self) -> bool {
+        matches!(self, Self::Verified | Self::AlreadyVerified)
+    }
+}
+
+/// Knobs for [`verify_on_etherscan`].
+#[derive(Clone, Debug)]
+pub struct VerifyOptions {
+    /// Overrides the chain's Etherscan API url (an explorer clone, or a mock server in tests).
+    pub api_url: Option<String>,
+    /// Delay between two status checks while the request is queued.
+    pub poll_interval: Duration,
+    /// Status checks before giving up on a queued request.
+    pub max_polls: u32,
+    /// Delay after the first rate-limited response, doubled on every retry.
+    pub backoff: Duration,
+    /// Rate-limited responses tolerated per call before giving up.
+    pub max_retries: u32,
+}
+
+impl Default for VerifyOptions {
+    fn default() -> Self {
+        Self {
+            api_url: None,
+            poll_interval: Duration::from_secs(5),
+            max_polls: 20,
+            backoff: Duration::from_secs(1),
+            max_retries: 5,
+        }
+    }
+}
+
+/// The fields Etherscan needs to verify one contract.
+#[derive(Clone, Debug)]
+pub struct VerificationRequest {
+    pub address: Address,
+    /// Standard-json-input of the contract's source file and its imports.
+    pub source: String,
+    /// Fully qualified name, `<source path>:<ContractName>`.
+    pub contract_name: String,
+    /// Compiler version in Etherscan's `v0.8.19+commit.7dd6d404` form.
+    pub compiler_version: String,
+    /// ABI-encoded constructor arguments (empty when there are none).
+    pub constructor_args: Bytes,
+}
+
+// Every Etherscan API answer has this shape; `result` is a guid, a status or an error message
+#[derive(Debug, Deserialize)]
+struct EtherscanResponse {
+    status: String,
+    result: String,
+}
+
+/// Submits the source of `contract_name` from `project`, deployed at `address`, for verification
+/// and polls Etherscan until it is verified or rejected.
+pub async fn verify_on_etherscan(
+    api_key: &str,
+    chain: Chain,
+    address: Address,
+    project: Arc<Project>,
+    contract_name: &str,
+    constructor_args: Bytes,
+    options: &VerifyOptions,
+) -> Result<VerificationStatus> {
+    let api_url = match &options.api_url {
+        Some(url) => url.clone(),
+        None => chain
+            .etherscan_urls()
+            .map(|(api_url, _)| api_url.to_string())
+            .with_context(|| format!("No Etherscan API known for chain {chain}"))?,
+    };
+    let request = verification_request(project, address, contract_name, constructor_args).await?;
+    submit_verification(api_key, &api_url, &request, options).await
+}
+
+/// Builds the verification fields of `contract_name` from the project's own compile settings.
+///
+/// The project is compiled again, on a blocking worker, to find the contract's source file and
+/// solc version.
+pub async fn verification_request(
+    project: Arc<Project>,
+    address: Address,
+    contract_name: &str,
+    constructor_args: Bytes,
+) -> Result<VerificationRequest> {
+    // Compile again so the source file and solc version are exactly the ones the project uses
+    let (id, input) = tokio::task::spawn_blocking({
+        let (project, contract_name) = (project.clone(), contract_name.to_string());
+        move || -> Result<_> {
+            let output = project.compile()?;
+            if output.has_compiler_errors() {
+                return Err(eyre!(
+                    "Compiling solidity project failed: {:?}",
+                    output.output().errors
+                ));
+            }
+            let id = output
+                .artifact_ids()
+                .map(|(id, _)| id)
+                .find(|id| id.name == contract_name)
+                .with_context(|| format!("Contract {contract_name} not found in the project"))?;
+            let input = project.standard_json_input(&id.source)?;
+            Ok((id, input))
+        }
+    })
+    .await??;
+
+    let source_path = id.source.strip_prefix(project.root()).unwrap_or(&id.source);
+
+    // The artifact only records the short version; the installed solc also reports the commit.
+    // Asking it runs `solc --version`, so off the executor too
+    let short_version = id.version.to_string();
+    let version = tokio::task::spawn_blocking(move || -> Result<String> {
+        Ok(match Solc::find_svm_installed_version(&short_version)? {
+            Some(solc) => solc.version()?.to_string(),
+            None => short_version,
+        })
+    })
+    .await??;
+
+    Ok(VerificationRequest {
+        address,
+        source: serde_json::to_string(&input)?,
+        contract_name: format!("{}:{contract_name}", source_path.to_string_lossy()),
+        compiler_version: etherscan_compiler_version(&version),
+        constructor_args,
+    })
+}
+
+/// Posts `request` to the Etherscan API at `api_url` and polls the returned guid until the
+/// verification settles. Rate-limited calls are retried with exponential backoff.
+#[instrument(name = "verify", skip_all, fields(contract = %request.contract_name, address = ?request.address))]
+pub async fn submit_verification(
+    api_key: &str,
+    api_url: &str,
+    request: &VerificationRequest,
+    options: &VerifyOptions,
+) -> Result<VerificationStatus> {
+    let client = Client::new();
+    let address = format!("{:?}", request.address);
+    let constructor_args = hex::encode(&request.constructor_args);
+    let form = [
+        ("apikey", api_key),
+        ("module", "contract"),
+        ("action", "verifysourcecode"),
+        ("contractaddress", &address),
+        ("sourceCode", &request.source),
+        ("codeformat", "solidity-standard-json-input"),
+        ("contractname", &request.contract_name),
+        ("compilerversion", &request.compiler_version),
+        ("constructorArguements", &constructor_args), // Etherscan's own spelling
+    ];
+
+    let submitted = call_with_backoff(options, "verification submission", || {
+        client.post(api_url).form(&form)
+    })
+    .await?;
+    if submitted.status != "1" {
+        if is_already_verified(&submitted.result) {
+            info!("already verified");
+            return Ok(VerificationStatus::AlreadyVerified);
+        }
+        return Err(eyre!(
+            "Etherscan rejected the verification request: {}",
+            submitted.result
+        ));
+    }
+    let guid = submitted.result;
+    info!(%guid, "verification submitted");
+
+    for _ in 0..options.max_polls {
+        tokio::time::sleep(options.poll_interval).await;
+        let query = [
+            ("apikey", api_key),
+            ("module", "contract"),
+            ("action", "checkverifystatus"),
+            ("guid", &guid),
+        ];
+        let checked = call_with_backoff(options, "verification status", || {
+            client.get(api_url).query(&query)
+        })
+        .await?;
+
+        let result = checked.result;
+        if result.contains("Pending in queue") {
+            continue;
+        }
+        let status = if is_already_verified(&result) {
+            VerificationStatus::AlreadyVerified
+        } else if checked.status == "1" {
+            VerificationStatus::Verified
+        } else {
+            VerificationStatus::Failed(result)
+        };
+        info!(?status, "verification settled");
+        return Ok(status);
+    }
+
+    Err(Error::OperationTimedOut {
+        operation: "etherscan verification".to_string(),
+        after: options.poll_interval * options.max_polls,
+    }
+    .into())
+}
+
+// Sends the request built by `make`, sleeping and retrying while Etherscan reports its rate limit
+async fn call_with_backoff(
+    options: &VerifyOptions,
+    what: &str,
+    make: impl Fn() -> RequestBuilder,
+) -> Result<EtherscanResponse> {
+    let mut delay = options.backoff;
+    let mut retries = 0;
+    loop {
+        let response = make()
+            .send()
+            .await
+            .wrap_err_with(|| format!("Etherscan {what} failed"))?;
+        let http_status = response.status();
+        let body = response.text().await?;
+
+        let rate_limited = http_status == StatusCode::TOO_MANY_REQUESTS
+            || body.to_lowercase().contains("rate limit");
+        if rate_limited && retries < options.max_retries {
+            warn!(%what, ?delay, "etherscan rate limit reached, backing off");
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+            retries += 1;
+            continue;
+        }
+
+        return serde_json::from_str(&body)
+            .wrap_err_with(|| format!("Unexpected Etherscan {what} response: {body}"));
+    }
+}
+
+fn is_already_verified(result: &str) -> bool {
+    result.to_lowercase().contains("already verified")
+}
+
+/// Turns a solc version (`0.8.19+commit.7dd6d404.Linux.g++`) into the `v0.8.19+commit.7dd6d404`
+/// form Etherscan expects, dropping the platform suffix.
+fn etherscan_compiler_version(version: &str) -> String {
+    let version = version.trim_start_matches('v');
+    match version.split_once('+') {
+        Some((release, build)) => {
+            let commit: Vec<&str> = build.split('.').take(2).collect();
+            format!("v{release}+{}", commit.join("."))
+        }
+        None => format!("v{version}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Url;
+    use std::collections::HashMap;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+        task::JoinHandle,
+    };
+
+    // A mock Etherscan that answers each connection with the next canned body and records the raw requests
+    async fn mock_etherscan(
+        bodies: Vec<&'static str>,
+    ) -> Result<(String, JoinHandle<Result<Vec<String>>>)> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("http://{}/api", listener.local_addr()?);
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for body in bodies {
+                let (mut socket, _) = listener.accept().await?;
+
+                // Read the headers, then as much body as they announce
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                let header_end = loop {
+                    let n = socket.read(&mut chunk).await?;
+                    buf.extend_from_slice(&chunk[..n]);
+                    if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break end + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&buf[..header_end]).to_lowercase();
+                let length: usize = head
+                    .lines()
+                    .find_map(|line| line.strip_prefix("content-length: "))
+                    .map_or(Ok(0), str::parse)?;
+                while buf.len() < header_end + length {
+                    let n = socket.read(&mut chunk).await?;
+                    buf.extend_from_slice(&chunk[..n]);
+                }
+                requests.push(String::from_utf8_lossy(&buf).into_owned());
+
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/json\r\nconnection: close\r\ncontent-length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await?;
+            }
+            Ok(requests)
+        });
+        Ok((url, server))
+    }
+
+    // Decodes the url-encoded pairs of a form body or a query string
+    fn decode_pairs(encoded: &str) -> Result<HashMap<String, String>> {
+        let url = Url::parse(&format!("http://mock/?{encoded}"))?;
+        Ok(url.query_pairs().into_owned().collect())
+    }
+
+    fn fast_options(api_url: String) -> VerifyOptions {
+        VerifyOptions {
+            api_url: Some(api_url),
+            poll_interval: Duration::from_millis(1),
+            max_polls: 5,
+            backoff: Duration::from_millis(1),
+            max_retries: 3,
+        }
+    }
+
+    fn sample_request() -> VerificationRequest {
+        VerificationRequest {
+            address: "0xcd234a471b72ba2f1ccf0a70fcaba648a5eecd8d"
+                .parse()
+                .unwrap(),
+            source: r#"{"language":"Solidity","sources":{}}"#.to_string(),
+            contract_name: "contracts/Migrations.sol:Migrations".to_string(),
+            compiler_version: "v0.4.24+commit.e67f0147".to_string(),
+            constructor_args: Bytes::from(vec![0x00, 0x2a]),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_submission_fields_backoff_and_polling() -> Result<()> {
+        let (url, server) = mock_etherscan(vec![
+            r#"{"status":"0","message":"NOTOK","result":"Max rate limit reached"}"#,
+            r#"{"status":"1","message":"OK","result":"guid-123"}"#,
+            r#"{"status":"0","message":"NOTOK","result":"Pending in queue"}"#,
+            r#"{"status":"1","message":"OK","result":"Pass - Verified"}"#,
+        ])
+        .await?;
+
+        let options = fast_options(url);
+        let status = submit_verification(
+            "secret-key",
+            options.api_url.as_deref().unwrap(),
+            &sample_request(),
+            &options,
+        )
+        .await?;
+        assert_eq!(status, VerificationStatus::Verified);
+
+        let requests = server.await??;
+        assert_eq!(requests.len(), 4);
+
+        // The retried submission carries exactly the standard Etherscan fields
+        assert!(requests[1].starts_with("POST /api "));
+        let (_, body) = requests[1].split_once("\r\n\r\n").unwrap();
+        let fields = decode_pairs(body)?;
+        let expected: HashMap<String, String> = [
+            ("apikey", "secret-key"),
+            ("module", "contract"),
+            ("action", "verifysourcecode"),
+            (
+                "contractaddress",
+                "0xcd234a471b72ba2f1ccf0a70fcaba648a5eecd8d",
+            ),
+            ("sourceCode", r#"{"language":"Solidity","sources":{}}"#),
+            ("codeformat", "solidity-standard-json-input"),
+            ("contractname", "contracts/Migrations.sol:Migrations"),
+            ("compilerversion", "v0.4.24+commit.e67f0147"),
+            ("constructorArguements", "002a"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        assert_eq!(fields, expected);
+
+        // The status checks poll the returned guid
+        let request_line = requests[3].lines().next().unwrap();
+        let query = request_line
+            .split(' ')
+            .nth(1)
+            .and_then(|target| target.split_once('?'))
+            .map(|(_, query)| query)
+            .unwrap();
+        let query = decode_pairs(query)?;
+        assert_eq!(query["action"], "checkverifystatus");
+        assert_eq!(query["guid"], "guid-123");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_already_verified_is_success() -> Result<()> {
+        let (url, server) = mock_etherscan(vec![
+            r#"{"status":"0","message":"NOTOK","result":"Contract source code already verified"}"#,
+        ])
+        .await?;
+
+        let options = fast_options(url.clone());
+        let status = submit_verification("secret-key", &url, &sample_request(), &options).await?;
+        assert_eq!(status, VerificationStatus::AlreadyVerified);
+        assert!(status.is_success());
+        assert_eq!(server.await??.len(), 1, "No status polling is needed");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_etherscan_compiler_version() {
+        assert_eq!(
+            etherscan_compiler_version("0.8.19+commit.7dd6d404.Linux.g++"),
+            "v0.8.19+commit.7dd6d404"
+        );
+        assert_eq!(
+            etherscan_compiler_version("0.4.24+commit.e67f0147"),
+            "v0.4.24+commit.e67f0147"
+        );
+        assert_eq!(etherscan_compiler_version("0.8.19"), "v0.8.19");
+    }
+
+    // Needs ETHERSCAN_API_KEY and the address of a `Migrations` deployed from examples/ on Sepolia
+    #[tokio::test]
+    #[ignore]
+    async fn test_live_sepolia_verification() -> Result<()> {
+        let api_key = std::env::var("ETHERSCAN_API_KEY")?;
+        let address: Address = std::env::var("ETHERSCAN_VERIFY_ADDRESS")?.parse()?;
+        let project = Arc::new(crate::project::build_project("examples/")?);
+
+        let status = verify_on_etherscan(
+            &api_key,
+            Chain::Sepolia,
+            address,
+            project,
+            "Migrations",
+            Bytes::default(),
+            &VerifyOptions::default(),
+        )
+        .await?;
+        assert!(status.is_success(), "Verification failed: {status:?}");
+
+        Ok(())
+    }
+}
@@ -0,0 +1,187 @@
+//! Deployer wallet selection: an explicit private key from the environment, or the local Ganache key.
+//! Wallets are bound to the provider's chain with [`bind_to_chain`] before signing.
+
+use crate::errors::Error;
+use crate::mnemonic::{derive_wallet, ValidatedMnemonic};
+use ethers::core::k256::SecretKey;
+use ethers::prelude::Middleware;
+use ethers::signers::{LocalWallet, Signer};
+use ethers::utils::{keccak256, GanacheInstance};
+use eyre::Result;
+use std::env::VarError;
+use std::str::FromStr;
+use tracing::info;
+
+/// Environment variable holding a hex private key for the deployer.
+pub const PRIVATE_KEY_VAR: &str = "PRIVATE_KEY";
+
+/// Loads the wallet from `PRIVATE_KEY` when it is set, or derives the Ganache account at
+/// `index` from `mnemonic` otherwise.
+pub fn load_wallet(mnemonic: &ValidatedMnemonic, index: u32) -> Result<LocalWallet> {
+    load_wallet_from(mnemonic, index, env_lookup)
+}
+
+// `load_wallet` with the variable read through `lookup`
+fn load_wallet_from(
+    mnemonic: &ValidatedMnemonic,
+    index: u32,
+    lookup: impl Fn(&str) -> Result<String, VarError>,
+) -> Result<LocalWallet> {
+    match wallet_from(lookup)? {
+        Some(wallet) => Ok(wallet),
+        None => derive_wallet(mnemonic, index),
+    }
+}
+
+/// Loads the wallet from `PRIVATE_KEY` when it is set, or selects `ganache`'s account at `index`
+/// otherwise, failing with [`Error::AccountIndexOutOfRange`] past its last account.
+pub fn load_ganache_wallet(ganache: &GanacheInstance, index: usize) -> Result<LocalWallet> {
+    match wallet_from(env_lookup)? {
+        Some(wallet) => Ok(wallet),
+        None => select_account(ganache.keys(), index),
+    }
+}
+
+/// The wallet of account `index` among `keys`, such as [`GanacheInstance::keys`].
+pub fn select_account(keys: &[SecretKey], index: usize) -> Result<LocalWallet> {
+    let key = keys.get(index).ok_or(Error::AccountIndexOutOfRange {
+        index,
+        accounts: keys.len(),
+    })?;
+    Ok(key.clone().into())
+}
+
+fn env_lookup(name: &str) -> Result<String, VarError> {
+    std::env::var(name)
+}
+
+// The wallet of the `PRIVATE_KEY` that `lookup` finds, if any. Tests pass their own lookup
+// rather than changing the process environment under the other tests
+fn wallet_from(lookup: impl Fn(&str) -> Result<String, VarError>) -> Result<Option<LocalWallet>> {
+    match lookup(PRIVATE_KEY_VAR) {
+        Ok(key) => {
+            let wallet = parse_private_key(&key)?;
+            info!(var = PRIVATE_KEY_VAR, "wallet loaded from the environment");
+            Ok(Some(wallet))
+        }
+        Err(VarError::NotPresent) => Ok(None),
+        Err(VarError::NotUnicode(_)) => Err(Error::InvalidPrivateKey {
+            reason: "not valid UTF-8".to_string(),
+        }
+        .into()),
+    }
+}
+
+/// Sets `wallet`'s chain id to the one `provider` serves, so its signatures are only valid there.
+///
+/// Signing clients are built from wallets bound here, so the two ids can't diverge.
+pub async fn bind_to_chain<M: Middleware>(provider: &M, wallet: LocalWallet) -> Result<LocalWallet>
+where
+    M::Error: 'static,
+{
+    let chain_id = provider.get_chainid().await?.as_u64();
+    Ok(wallet.with_chain_id(chain_id))
+}
+
+/// Parses a 32-byte hex private key, with or without `0x`. Errors never echo the key itself.
+pub fn parse_private_key(key: &str) -> Result<LocalWallet> {
+    let hex_key = key.trim();
+    let hex_key = hex_key.strip_prefix("0x").unwrap_or(hex_key);
+
+    let invalid = |reason: String| Error::InvalidPrivateKey { reason };
+    if hex_key.len() != 64 {
+        return Err(invalid(format!("expected 64 hex characters, got {}", hex_key.len())).into());
+    }
+    if !hex_key.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(invalid("contains non-hex characters".to_string()).into());
+    }
+
+    // Still fails for keys outside the secp256k1 range, such as all zeroes
+    LocalWallet::from_str(hex_key)
+        .map_err(|_| invalid("not a valid secp256k1 key".to_string()).into())
+}
+
+/// A wallet derived from `seed` alone, bound to `chain_id`: the key is the keccak256 hash of the
+/// seed's big-endian bytes. Only for tests, since anyone can derive the same key.
+pub fn test_wallet(seed: u64, chain_id: u64) -> LocalWallet {
+    let mut key = keccak256(seed.to_be_bytes());
+    // Hash again in the astronomically unlikely case the hash isn't a valid secp256k1 key
+    loop {
+        if let Ok(wallet) = LocalWallet::from_bytes(&key) {
+            return wallet.with_chain_id(chain_id);
+        }
+        key = keccak256(key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mnemonic::validate_mnemonic;
+    use ethers::signers::Signer;
+    use ethers::types::Address;
+
+    // Well-known development key (account 0 of the "test test ... junk" mnemonic)
+    const KEY: &str = "0xac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80";
+    const ADDRESS: &str = "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266";
+
+    #[test]
+    fn test_wallet_from_env_and_fallback() -> Result<()> {
+        let mnemonic = validate_mnemonic(
+            "brisk usual burst upper buddy female library dial rifle mercy globe nurse",
+        )?;
+
+        let set = |name: &str| {
+            assert_eq!(name, PRIVATE_KEY_VAR);
+            Ok(KEY.to_string())
+        };
+        let from_env = load_wallet_from(&mnemonic, 0, set)?;
+        assert_eq!(from_env.address(), ADDRESS.parse::<Address>()?);
+
+        let fallback = load_wallet_from(&mnemonic, 0, |_| Err(VarError::NotPresent))?;
+        assert_eq!(fallback.address(), derive_wallet(&mnemonic, 0)?.address());
+
+        let not_unicode = wallet_from(|_| Err(VarError::NotUnicode("\u{fffd}".into())));
+        assert!(matches!(
+            not_unicode.unwrap_err().downcast_ref::<Error>(),
+            Some(Error::InvalidPrivateKey { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_seeded_wallets_are_deterministic() {
+        assert_eq!(
+            test_wallet(1, 1337).address(),
+            test_wallet(1, 1337).address()
+        );
+        assert_ne!(
+            test_wallet(1, 1337).address(),
+            test_wallet(2, 1337).address()
+        );
+        assert_eq!(test_wallet(7, 5).chain_id(), 5);
+        // The chain id doesn't change the key
+        assert_eq!(test_wallet(7, 5).address(), test_wallet(7, 1).address());
+    }
+
+    #[test]
+    fn test_malformed_keys_are_rejected() {
+        for key in [
+            "0x1234",
+            &KEY.replace('a', "z"),
+            "0x0000000000000000000000000000000000000000000000000000000000000000",
+        ] {
+            let err = parse_private_key(key).unwrap_err();
+            assert!(
+                matches!(
+                    err.downcast_ref::<Error>(),
+                    Some(Error::InvalidPrivateKey { .. })
+                ),
+                "{key}: {err}"
+            );
+            assert!(!err.to_string().contains(key.trim_start_matches("0x")));
+        }
+        assert!(parse_private_key(KEY.trim_start_matches("0x")).is_ok());
+    }
+}
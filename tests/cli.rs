@@ -0,0 +1,23 @@
+use std::process::Command;
+
+#[test]
+#[cfg_attr(not(feature = "ganache-tests"), ignore = "needs Ganache and solc")]
+fn test_deploy_prints_json_result() {
+    let output = Command::new(env!("CARGO_BIN_EXE_deploy"))
+        .arg("--json")
+        .output()
+        .expect("failed to run the deploy binary");
+    assert!(
+        output.status.success(),
+        "deploy failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Logs go to stderr, so stdout must be exactly one JSON object
+    let result: serde_json::Value =
+        serde_json::from_slice(&output.stdout).expect("stdout should be a JSON object");
+    for key in ["address", "txHash", "gasUsed"] {
+        assert!(result.get(key).is_some(), "missing `{key}` in {result}");
+    }
+    assert_eq!(result["address"].as_str().map(str::len), Some(42));
+}
@@ -0,0 +1,12 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.4;
+
+// Implementation behind the proxy; burn(uint256) shares its selector 0x42966c68 with the
+// proxy's collate_propagate_storage(bytes16), a documented collision
+contract Token {
+    mapping(address => uint256) public balanceOf;
+
+    function burn(uint256 amount) external {
+        balanceOf[msg.sender] -= amount;
+    }
+}
@@ -0,0 +1,19 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.4;
+
+// A call to burn(uint256) meant for the implementation never reaches the fallback: the proxy
+// answers it with its own function
+contract TokenProxy {
+    address public implementation;
+
+    constructor(address implementation_) {
+        implementation = implementation_;
+    }
+
+    function collate_propagate_storage(bytes16) external {}
+
+    fallback() external payable {
+        (bool ok, ) = implementation.delegatecall(msg.data);
+        require(ok);
+    }
+}
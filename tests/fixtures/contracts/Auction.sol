@@ -0,0 +1,38 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.4;
+
+// Exercises every field of the project report: events, a custom error, an overloaded function
+// and a payable constructor
+contract Auction {
+    address public seller;
+    address public highestBidder;
+    uint256 public highestBid;
+
+    event BidPlaced(address indexed bidder, uint256 amount);
+    event Settled(address indexed winner, uint256 amount);
+
+    error BidTooLow(uint256 bid, uint256 highest);
+
+    constructor(uint256 reserve) payable {
+        seller = msg.sender;
+        highestBid = reserve;
+    }
+
+    function bid() external payable {
+        bid(msg.sender);
+    }
+
+    function bid(address bidder) public payable {
+        if (msg.value <= highestBid) {
+            revert BidTooLow(msg.value, highestBid);
+        }
+        highestBidder = bidder;
+        highestBid = msg.value;
+        emit BidPlaced(bidder, msg.value);
+    }
+
+    function settle() external returns (address winner, uint256 amount) {
+        emit Settled(highestBidder, highestBid);
+        return (highestBidder, highestBid);
+    }
+}
@@ -0,0 +1,11 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.4;
+
+// An interface has no bytecode, so it is reported as not deployable
+interface IAuction {
+    event BidPlaced(address indexed bidder, uint256 amount);
+
+    function bid(address bidder) external payable;
+
+    function highestBid() external view returns (uint256);
+}
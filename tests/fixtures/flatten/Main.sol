@@ -0,0 +1,10 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+import "./Left.sol";
+import "./Right.sol";
+
+contract Main {
+    Left public left;
+    Right public right;
+}
@@ -0,0 +1,10 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+import "./Common.sol";
+
+contract Right is Common {
+    function right() public pure returns (uint256) {
+        return 2;
+    }
+}
@@ -0,0 +1,10 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+contract TokenA {
+    string public symbol;
+
+    constructor(string memory _symbol) {
+        symbol = _symbol;
+    }
+}
@@ -0,0 +1,12 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+contract Vault {
+    address public token;
+    address[] public guardians;
+
+    constructor(address _token, address[] memory _guardians) {
+        token = _token;
+        guardians = _guardians;
+    }
+}
@@ -0,0 +1,9 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+contract UnusedVariable {
+    function answer() public pure returns (uint256) {
+        uint256 unused = 1;
+        return 42;
+    }
+}